@@ -0,0 +1,95 @@
+use crate::events::{Event, EventKind};
+use crate::notify;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Firing,
+    Acknowledged,
+    Resolved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlertGroup {
+    Latency,
+    Dns,
+}
+
+fn classify(kind: EventKind) -> Option<(AlertGroup, bool)> {
+    match kind {
+        EventKind::InternetDegraded | EventKind::LocalDegraded => {
+            Some((AlertGroup::Latency, true))
+        }
+        EventKind::Recovered => Some((AlertGroup::Latency, false)),
+        EventKind::DnsHijack => Some((AlertGroup::Dns, true)),
+        EventKind::DnsRestored => Some((AlertGroup::Dns, false)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    group: AlertGroup,
+    pub kind: EventKind,
+    pub message: String,
+    pub state: AlertState,
+    pub fired_at: String,
+    pub repeats: u32,
+}
+
+#[derive(Default)]
+pub struct Alerts {
+    list: Vec<Alert>,
+    pub notify: bool,
+}
+
+impl Alerts {
+    // Returns false when the event only repeats an alert that is still open,
+    // so callers can keep it in the log without notifying again.
+    pub fn observe(&mut self, event: &Event) -> bool {
+        let Some((group, firing)) = classify(event.kind) else {
+            return true;
+        };
+        let open = self.list.iter_mut().find(|a| a.group == group && a.state != AlertState::Resolved);
+
+        match (open, firing) {
+            (Some(alert), true) => {
+                alert.kind = event.kind;
+                alert.message = event.message.clone();
+                alert.repeats += 1;
+                false
+            }
+            (None, true) => {
+                if self.notify {
+                    notify::send(&format!("Vasili: {}", event.kind.label()), &event.message);
+                }
+                self.list.push(Alert {
+                    group,
+                    kind: event.kind,
+                    message: event.message.clone(),
+                    state: AlertState::Firing,
+                    fired_at: event.timestamp.clone(),
+                    repeats: 0,
+                });
+                true
+            }
+            (Some(alert), false) => {
+                alert.state = AlertState::Resolved;
+                true
+            }
+            (None, false) => true,
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        for alert in self.list.iter_mut().filter(|a| a.state == AlertState::Firing) {
+            alert.state = AlertState::Acknowledged;
+        }
+    }
+
+    pub fn active(&self) -> Option<&Alert> {
+        let open = || self.list.iter().rev().filter(|a| a.state != AlertState::Resolved);
+        open().find(|a| a.state == AlertState::Firing).or_else(|| open().next())
+    }
+}
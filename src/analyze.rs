@@ -0,0 +1,189 @@
+use crate::app::HostStats;
+use crate::args::{AnalyzeArgs, format_duration};
+use crate::locale;
+use crate::scope::Scope;
+use crate::session;
+use crate::validate;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{net::IpAddr, path::Path, time::Duration};
+
+#[derive(Serialize)]
+pub struct Outage {
+    pub lost: u64,
+    pub secs: f64,
+    pub started: String,
+}
+
+#[derive(Serialize)]
+pub struct HostAnalysis {
+    pub target_type: String,
+    pub host: String,
+    pub samples: u64,
+    pub lost: u64,
+    pub loss_percent: f64,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub spikes_minor: u64,
+    pub spikes_major: u64,
+    pub anomalies: u64,
+    pub longest_outage: Option<Outage>,
+    pub grade: String,
+    // Fewer samples than --min-samples: the grade is not settled yet.
+    pub warming_up: bool,
+    // First to last sample, less the gaps without any.
+    pub measured_secs: f64,
+    pub gaps: u64,
+}
+
+impl HostAnalysis {
+    pub fn grade_label(&self) -> String {
+        if self.warming_up { format!("warming up (n={})", self.samples) } else { self.grade.clone() }
+    }
+}
+
+pub type Sample = (f64, Option<f64>, DateTime<Local>);
+pub type HostSamples = ((String, String), Vec<Sample>);
+
+// A run of timeouts lasts until the next reply, or until the end of the
+// session or a gap when none came.
+fn longest_outage(samples: &[Sample], configured_ms: f64) -> Option<Outage> {
+    let mut longest: Option<(u64, f64, DateTime<Local>)> = None;
+    let mut run: Option<(u64, f64, DateTime<Local>)> = None;
+    let mut keep = |lost: u64, secs: f64, started: DateTime<Local>| {
+        if longest.is_none_or(|(l, _, _)| lost > l) {
+            longest = Some((lost, secs, started));
+        }
+    };
+
+    let mut previous = None;
+    for &(t, latency, at) in samples {
+        if let Some(before) = previous
+            && validate::is_gap((t - before) * 1000.0, configured_ms)
+            && let Some((lost, start, started)) = run.take()
+        {
+            keep(lost, before - start, started);
+        }
+        previous = Some(t);
+        match latency {
+            None => {
+                let (lost, _, _) = run.get_or_insert((0, t, at));
+                *lost += 1;
+            }
+            Some(_) => {
+                if let Some((lost, start, started)) = run.take() {
+                    keep(lost, t - start, started);
+                }
+            }
+        }
+    }
+    if let (Some((lost, start, started)), Some(&(end, _, _))) = (run, samples.last()) {
+        keep(lost, end - start, started);
+    }
+
+    longest.map(|(lost, secs, started)| Outage { lost, secs, started: started.format("%Y-%m-%d %H:%M:%S").to_string() })
+}
+
+pub fn analyze_host(target_type: &str, host: &str, samples: &[Sample]) -> HostAnalysis {
+    let lan = target_type == "Gateway" || host.parse::<IpAddr>().is_ok_and(|ip| Scope::of(ip).is_local());
+    let stats = HostStats::from_samples(host.to_string(), lan, samples);
+
+    let latencies: Vec<f64> = samples.iter().filter_map(|(_, latency, _)| *latency).collect();
+    let min = latencies.iter().copied().reduce(f64::min).unwrap_or(0.0);
+    let avg = if latencies.is_empty() { 0.0 } else { latencies.iter().sum::<f64>() / latencies.len() as f64 };
+
+    HostAnalysis {
+        target_type: target_type.to_string(),
+        host: host.to_string(),
+        samples: stats.total_count,
+        lost: stats.loss_count,
+        loss_percent: stats.loss_percent(),
+        min,
+        avg,
+        max: stats.max_latency,
+        p25: stats.p25,
+        p50: stats.p50,
+        p95: stats.p95,
+        p99: stats.p99,
+        spikes_minor: stats.spikes_minor,
+        spikes_major: stats.spikes_major,
+        anomalies: stats.anomalies,
+        longest_outage: longest_outage(samples, stats.configured_ms),
+        grade: stats.calculate_grade().to_string(),
+        warming_up: stats.warming_up(),
+        measured_secs: stats.measured_secs(),
+        gaps: stats.gaps,
+    }
+}
+
+// Samples per (type, host) in the order the log first mentions them, with
+// the time in seconds since the first record. A rotated session is read
+// from all of its segments.
+pub fn load_hosts(path: &Path) -> Result<Vec<HostSamples>> {
+    let records = session::load_session(path)?;
+
+    let mut hosts: Vec<HostSamples> = Vec::new();
+    let mut start = None;
+    for record in records.iter().filter(|r| !r.is_marker()) {
+        let Some(at) = session::parse_timestamp(&record.timestamp) else {
+            bail!("Invalid timestamp '{}' in {}", record.timestamp, path.display());
+        };
+        let start = *start.get_or_insert(at);
+        let t = (at - start).num_milliseconds() as f64 / 1000.0;
+
+        let key = (record.target_type.clone(), record.target_ip.clone());
+        match hosts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, samples)) => samples.push((t, record.latency_ms, at)),
+            None => hosts.push((key, vec![(t, record.latency_ms, at)])),
+        }
+    }
+    if hosts.is_empty() {
+        bail!("{} contains no probe results", path.display());
+    }
+    Ok(hosts)
+}
+
+pub fn run(args: AnalyzeArgs) -> Result<()> {
+    let hosts = load_hosts(&args.file)?;
+
+    let analyses: Vec<HostAnalysis> =
+        hosts.iter().map(|((target_type, host), samples)| analyze_host(target_type, host, samples)).collect();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&analyses)?);
+        return Ok(());
+    }
+
+    let duration = hosts.iter().filter_map(|(_, s)| s.last().map(|(t, _, _)| *t)).fold(0.0, f64::max);
+    let segments = match session::segments(&args.file).len() {
+        1 => String::new(),
+        n => format!(", {} rotated segments", n),
+    };
+    println!("Session {} ({}{}):", args.file.display(), format_duration(Duration::from_secs(duration as u64)), segments);
+    for a in &analyses {
+        println!();
+        println!("{} {}", a.target_type, a.host);
+        let ms = |value: f64| locale::ms(value, 1);
+        println!("  Samples:        {} ({} lost, {} loss)", a.samples, a.lost, locale::percent(a.loss_percent, 2));
+        if a.gaps > 0 {
+            let secs = |secs: f64| format_duration(Duration::from_secs(secs as u64));
+            println!("  Measured:       {} of {} ({} gaps without samples left out)", secs(a.measured_secs), secs(duration), a.gaps);
+        }
+        println!("  Latency:        min {} / avg {} / max {}", ms(a.min), ms(a.avg), ms(a.max));
+        println!("  Percentiles:    P25 {} / P50 {} / P95 {} / P99 {}", ms(a.p25), ms(a.p50), ms(a.p95), ms(a.p99));
+        println!("  Spikes:         {} >30ms, {} >100ms (incl. timeouts)", a.spikes_minor, a.spikes_major);
+        println!("  Anomalies:      {} far above the usual latency", a.anomalies);
+        match &a.longest_outage {
+            Some(o) => println!("  Longest outage: {} lost in a row over {}s, from {}", o.lost, locale::num(o.secs, 1), locale::timestamp(&o.started)),
+            None => println!("  Longest outage: none"),
+        }
+        println!("  Grade:          {}", a.grade_label());
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// Learns the usual latency of a host while the session runs and flags replies
+// far above it. The center is an EWMA of the latency, the spread an EWMA of
+// the absolute deviation from it (a running stand-in for the MAD), so a link
+// that is always jittery gets a wide band and a steady one a narrow band,
+// unlike the fixed >30ms / >100ms spike thresholds.
+
+const ALPHA: f64 = 0.05;
+// How many deviations above the center a reply must be to count.
+const BAND: f64 = 4.0;
+// Keeps a very steady link (LAN, loopback) from flagging 1ms of noise.
+const MIN_MARGIN_MS: f64 = 5.0;
+const WARMUP: u64 = 30;
+
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyDetector {
+    center: f64,
+    deviation: f64,
+    seen: u64,
+}
+
+impl AnomalyDetector {
+    fn upper(&self) -> f64 {
+        self.center + (BAND * self.deviation).max(MIN_MARGIN_MS)
+    }
+
+    // (usual latency, upper edge of the band) once enough replies were seen.
+    pub fn band(&self) -> Option<(f64, f64)> {
+        (self.seen >= WARMUP).then(|| (self.center, self.upper()))
+    }
+
+    pub fn observe(&mut self, latency: f64) -> bool {
+        if self.seen == 0 {
+            self.center = latency;
+        }
+        let anomaly = self.seen >= WARMUP && latency > self.upper();
+
+        // An anomaly moves the baseline only as far as the band edge, so one
+        // lag spike does not teach it that lag is normal, while a lasting
+        // step (a new route) is learned within a few dozen replies.
+        let value = if anomaly { self.upper() } else { latency };
+        let diff = value - self.center;
+        self.center += ALPHA * diff;
+        self.deviation += ALPHA * (diff.abs() - self.deviation);
+        self.seen += 1;
+        anomaly
+    }
+}
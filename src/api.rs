@@ -0,0 +1,235 @@
+use crate::app::App;
+use crate::diag;
+use crate::sinks::Pipeline;
+use crate::status;
+use anyhow::Result;
+use serde_json::json;
+use std::{net::SocketAddr, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+
+const MAX_HEADER_BYTES: usize = 8 * 1024;
+const MAX_BODY_BYTES: usize = 4 * 1024;
+// A client that opens a connection and goes quiet must not hold its task forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum Action {
+    Status,
+    Pause,
+    Resume,
+    Marker(String),
+    Rotate,
+    Stop,
+}
+
+pub struct Reply {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(status: u16, value: serde_json::Value) -> Self {
+        Self { status, body: value.to_string().into_bytes() }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "ok": false, "error": message }))
+    }
+}
+
+pub struct Request {
+    pub action: Action,
+    pub reply: oneshot::Sender<Reply>,
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    origin: Option<String>,
+    body: Vec<u8>,
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<HttpRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        let len = stream.read(&mut chunk).await.ok()?;
+        if len == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..len]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return None;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.split('?').next()?.to_string();
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    let mut origin = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.trim().parse().ok()?,
+            "authorization" => authorization = Some(value.trim().to_string()),
+            "origin" => origin = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return None;
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let len = stream.read(&mut chunk).await.ok()?;
+        if len == 0 {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..len]);
+    }
+    body.truncate(content_length);
+
+    Some(HttpRequest { method, path, authorization, origin, body })
+}
+
+// Accepts {"label": "..."} as well as a plain-text body, so a one-line
+// curl or a home-automation REST command both work.
+fn marker_label(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    let label = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) => value.get("label").and_then(|l| l.as_str()).unwrap_or_default().to_string(),
+        Err(_) => text.trim().to_string(),
+    };
+    if label.is_empty() { "Marker".to_string() } else { label }
+}
+
+fn route(request: &HttpRequest) -> Result<Action, Reply> {
+    match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("GET", "/status") => Ok(Action::Status),
+        ("POST", "/pause") => Ok(Action::Pause),
+        ("POST", "/resume") => Ok(Action::Resume),
+        ("POST", "/marker") => Ok(Action::Marker(marker_label(&request.body))),
+        ("POST", "/rotate") => Ok(Action::Rotate),
+        ("POST", "/stop") => Ok(Action::Stop),
+        (_, "/status" | "/pause" | "/resume" | "/marker" | "/rotate" | "/stop") => {
+            Err(Reply::error(405, "method not allowed"))
+        }
+        _ => Err(Reply::error(404, "not found")),
+    }
+}
+
+async fn handle(mut stream: TcpStream, token: Option<String>, tx: mpsc::Sender<Request>) {
+    let Ok(Some(request)) = tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await else {
+        return;
+    };
+
+    let authorized = match &token {
+        Some(token) => request.authorization.as_deref() == Some(format!("Bearer {}", token).as_str()),
+        None => true,
+    };
+
+    // Browsers send an Origin with every cross-site POST and can reach a
+    // listener on localhost from any page. Without a token nothing else
+    // tells such a request apart from curl, so it is refused outright; a
+    // browser cannot add the bearer header without a preflight we never answer.
+    let reply = if !authorized {
+        Reply::error(401, "missing or wrong bearer token")
+    } else if token.is_none() && request.origin.is_some() {
+        Reply::error(403, "requests from web pages need --api-token")
+    } else {
+        match route(&request) {
+            Ok(action) => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                if tx.send(Request { action, reply: reply_tx }).await.is_err() {
+                    Reply::error(503, "session is shutting down")
+                } else {
+                    reply_rx.await.unwrap_or_else(|_| Reply::error(503, "session is shutting down"))
+                }
+            }
+            Err(reply) => reply,
+        }
+    };
+
+    let reason = match reply.status {
+        200 => "OK",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.status,
+        reason,
+        reply.body.len()
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&reply.body).await;
+    let _ = stream.shutdown().await;
+}
+
+pub async fn start(addr: SocketAddr, token: Option<String>) -> Result<mpsc::Receiver<Request>> {
+    let listener = TcpListener::bind(addr).await?;
+    let (tx, rx) = mpsc::channel(8);
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle(stream, token.clone(), tx.clone()));
+                }
+                Err(e) => diag::warn(format!("API accept failed: {}", e)),
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+pub fn apply(action: Action, app: &mut App, pipeline: &mut Pipeline) -> Reply {
+    match action {
+        Action::Status => match status::render(app) {
+            Ok(body) => Reply { status: 200, body },
+            Err(e) => Reply::error(500, &e.to_string()),
+        },
+        Action::Pause => {
+            app.is_paused = true;
+            Reply::json(200, json!({ "ok": true, "paused": true }))
+        }
+        Action::Resume => {
+            app.is_paused = false;
+            Reply::json(200, json!({ "ok": true, "paused": false }))
+        }
+        Action::Marker(label) => {
+            let record = app.add_marker(label.clone());
+            pipeline.write_record(&record);
+            Reply::json(200, json!({ "ok": true, "marker": label }))
+        }
+        Action::Rotate => {
+            let closed: Vec<String> = pipeline.rotate().iter().map(|p| p.display().to_string()).collect();
+            Reply::json(200, json!({ "ok": true, "closed": closed }))
+        }
+        Action::Stop => {
+            app.should_quit = true;
+            Reply::json(200, json!({ "ok": true, "stopping": true }))
+        }
+    }
+}
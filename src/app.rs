@@ -1,83 +1,370 @@
-use crate::pinger::SourceType;
+use crate::alerts::Alerts;
+use crate::args::format_duration;
+use crate::anomaly::AnomalyDetector;
+use crate::baseline::{Access, Comparison};
+use crate::clock::Clock;
+use crate::bell::Bell;
+use crate::config::{LayoutMode, TargetConfig, UiState};
+use crate::detector::DivergenceDetector;
+use crate::digest::Digest;
+use crate::discord::Discord;
+use crate::diag;
+use crate::events::{Event, EventKind, SystemEvent};
+use crate::chartexport::{self, ChartFormat};
+use crate::gifexport;
+use crate::grading::{self, GradeInput, GradeProvider};
+use crate::history::{self, Series};
+use crate::locale::{self, PanelUnits};
+use crate::geo::GeoEstimate;
+use crate::nic::NicWatch;
+use crate::overlay::BaselineOverlay;
+use crate::readiness::{ScheduledEvent, Verdict};
+use crate::redact;
+use crate::pinger::{PingUpdate, ProbeError, SourceType};
+use crate::rules::Rules;
+use crate::scope::Scope;
+use crate::search::EventSearch;
+use crate::trace::Hop;
+use crate::theme::Theme;
+use crate::validate;
 use chrono::{DateTime, Local};
 use crossterm::event::KeyCode;
-use serde::Serialize;
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Serialize, Clone)]
+// How long a notice stays in the footer.
+const NOTICE_DURATION: Duration = Duration::from_secs(5);
+pub const RECENT_SAMPLES: usize = 60;
+// A run of timeouts counts as an outage once it is both this long and this
+// many probes deep, so a single slow second at a fast interval does not.
+const OUTAGE_MIN_SECS: f64 = 2.0;
+const OUTAGE_MIN_TIMEOUTS: u64 = 3;
+// Below this many samples, P99 is little more than the maximum and a single
+// lost probe decides the grade, so neither is shown as settled yet.
+pub const DEFAULT_MIN_SAMPLES: u64 = 100;
+pub const MARKER_STATUS: &str = "MARKER";
+// Rows of the round-robin table; longer target lists scroll so the chart
+// keeps its room.
+pub const CYCLE_TABLE_ROWS: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PingRecord {
     pub timestamp: String,
     pub target_type: String,
     pub target_ip: String,
     pub latency_ms: Option<f64>,
     pub status: String,
+    #[serde(default)]
+    pub ttl: Option<u8>,
+}
+
+impl PingRecord {
+    // Markers share the record layout but are annotations, not probes.
+    pub fn is_marker(&self) -> bool {
+        self.status == MARKER_STATUS
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionSummary {
+    pub target_type: String,
+    pub target_ip: String,
+    pub started: String,
+    pub duration_secs: f64,
+    pub samples: u64,
+    pub lost: u64,
+    pub loss_percent: f64,
+    pub p25: f64,
+    pub p75: f64,
+    pub p99: f64,
+    #[serde(default)]
+    pub p999: f64,
+    #[serde(default)]
+    pub p999_at: Option<String>,
+    #[serde(default)]
+    pub max: f64,
+    #[serde(default)]
+    pub max_at: Option<String>,
+    pub spikes_minor: u64,
+    pub spikes_major: u64,
+    #[serde(default)]
+    pub deadline_misses: u64,
+    pub grade: String,
+    #[serde(default)]
+    pub protocol: String,
+    #[serde(default)]
+    pub aborted_by_user: bool,
+    #[serde(default)]
+    pub anomalies: u64,
+    #[serde(default)]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub dscp: u8,
+    // Of duration_secs, the time the host was measured, without its gaps.
+    #[serde(default)]
+    pub measured_secs: f64,
+    #[serde(default)]
+    pub gaps: u64,
+    #[serde(default)]
+    pub min: f64,
+    #[serde(default)]
+    pub mean: f64,
+    #[serde(default)]
+    pub stddev: f64,
+    // Fewer samples than --min-samples: the grade is not settled yet.
+    #[serde(default)]
+    pub warming_up: bool,
+    // Stopped by SIGTERM (a service manager, `kill`) before the planned end.
+    #[serde(default)]
+    pub terminated: bool,
+}
+
+// How a session ended, for the reports.
+fn ending(aborted_by_user: bool, terminated: bool) -> &'static str {
+    if terminated {
+        "terminated (SIGTERM)"
+    } else if aborted_by_user {
+        "stopped by user"
+    } else {
+        "finished"
+    }
+}
+
+impl SessionSummary {
+    // The gateway, or a target or hop with a LAN address, for `--units auto`.
+    pub fn lan(&self) -> bool {
+        self.target_type == "Gateway" || self.target_ip.parse().is_ok_and(|ip| Scope::of(ip).is_local())
+    }
+
+    // How much of the session the host was measured, once gaps left some out.
+    pub fn measured(&self) -> Option<String> {
+        let secs = |secs: f64| format_duration(Duration::from_secs(secs as u64));
+        let plural = if self.gaps == 1 { "gap" } else { "gaps" };
+        (self.gaps > 0).then(|| format!("{} of {} ({} {})", secs(self.measured_secs), secs(self.duration_secs), self.gaps, plural))
+    }
+
+    pub fn ending(&self) -> &'static str {
+        ending(self.aborted_by_user, self.terminated)
+    }
+
+    // The grade, or that there were too few samples for one, for people.
+    pub fn grade_label(&self) -> String {
+        if self.warming_up { format!("warming up (n={})", self.samples) } else { self.grade.clone() }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{:<8} {:<16} {} samples, {} loss, Mean {} ±{}, P25 {}, P99 {}, Max {}, Grade {}{}{}",
+            self.target_type,
+            self.target_ip,
+            self.samples,
+            locale::percent(self.loss_percent, 1),
+            locale::host_ms(self.mean, 1, self.lan()),
+            locale::host_ms(self.stddev, 1, self.lan()),
+            locale::host_ms(self.p25, 1, self.lan()),
+            locale::host_ms(self.p99, 1, self.lan()),
+            locale::host_ms(self.max, 1, self.lan()),
+            self.grade_label(),
+            if self.deadline_misses > 0 { format!(" ({} late replies)", self.deadline_misses) } else { String::new() },
+            self.measured().map(|m| format!(", measured {}", m)).unwrap_or_default()
+        )
+    }
+}
+
+// Lost probes, latency sum, replies and maximum of one minute.
+#[derive(Clone, Copy, Default)]
+pub struct Minute {
+    lost: u64,
+    sum: f64,
+    replies: u64,
+    max: f64,
+}
+
+impl Minute {
+    fn average(&self) -> f64 {
+        if self.replies > 0 { self.sum / self.replies as f64 } else { 0.0 }
+    }
+}
+
+// How `a` compares with `b` as the worst minute.
+fn worse(a: &Minute, b: &Minute) -> std::cmp::Ordering {
+    a.lost.cmp(&b.lost).then(a.average().total_cmp(&b.average()))
 }
 
 pub struct HostStats {
     pub display_name: String,
-    pub points: Vec<(f64, f64)>,
-    pub jitter_points: Vec<(f64, f64)>,
-    pub loss_points: Vec<(f64, f64)>,
-    pub all_latencies: Vec<f64>,
+    pub points: Series,
+    pub jitter_points: Series,
+    pub loss_points: Series,
+    // Losses with an error other than a timeout.
+    pub error_points: Vec<(f64, ProbeError)>,
+    // Replies that came after the next probe was due, with their latency.
+    pub late_points: Series,
+    // Spans without any result (a pause, a suspended laptop), from the last
+    // sample before to the first one after, and their total length. Rates
+    // leave them out.
+    pub gaps: u64,
+    pub gap_secs: f64,
+    // Kept up to date with every sample rather than searched for in the
+    // chart data, which may be on disk, see outages() and worst_minute().
+    pub outages: Vec<(f64, f64, u64)>,
+    pub minute: Option<(u64, Minute)>,
+    pub worst: Option<(u64, Minute)>,
+    pub latencies: Digest,
+    pub recent: VecDeque<Option<f64>>,
+    pub lan: bool,
+    pub configured_ms: f64,
+    pub first_seen: Option<f64>,
+    pub last_seen: f64,
+    pub note: Option<String>,
+    pub expected_ms: Option<f64>,
+    pub min_samples: u64,
+    pub grading: Arc<dyn GradeProvider>,
 
     pub last_latency: f64,
     pub current_jitter: f64,
+    pub jitter_sum: f64,
     pub total_count: u64,
     pub loss_count: u64,
     pub spikes_minor: u64,
     pub spikes_major: u64,
+    pub deadline_misses: u64,
+    pub anomaly: AnomalyDetector,
+    pub anomalies: u64,
+    pub anomaly_run: u64,
+    pub loss_burst: u64,
+    pub loss_since: Option<f64>,
 
     pub p25: f64,
+    pub p50: f64,
     pub p75: f64,
+    pub p95: f64,
     pub p99: f64,
+    pub p999: f64,
+    pub p999_at: Option<DateTime<Local>>,
+    pub max_latency: f64,
+    pub max_at: Option<DateTime<Local>>,
+    // Over every reply of the session, kept up to date with each one
+    // (Welford), unlike the percentiles which wait for the next recalc.
+    pub min_latency: f64,
+    pub mean_latency: f64,
+    pub latency_m2: f64,
 
-    pub last_recalc: Instant,
+    pub last_recalc: Option<DateTime<Local>>,
 }
 
 impl HostStats {
-    fn new(display_name: String) -> Self {
+    pub fn new(display_name: String) -> Self {
         Self {
             display_name,
-            points: Vec::new(),
-            jitter_points: Vec::new(),
-            loss_points: Vec::new(),
-            all_latencies: Vec::new(),
+            points: Series::new(),
+            jitter_points: Series::new(),
+            loss_points: Series::new(),
+            error_points: Vec::new(),
+            late_points: Series::new(),
+            gaps: 0,
+            gap_secs: 0.0,
+            outages: Vec::new(),
+            minute: None,
+            worst: None,
+            latencies: Digest::default(),
+            recent: VecDeque::with_capacity(RECENT_SAMPLES),
+            lan: false,
+            configured_ms: 0.0,
+            first_seen: None,
+            last_seen: 0.0,
+            note: None,
+            expected_ms: None,
+            min_samples: DEFAULT_MIN_SAMPLES,
+            grading: grading::general(),
 
             last_latency: 0.0,
             current_jitter: 0.0,
+            jitter_sum: 0.0,
             total_count: 0,
             loss_count: 0,
             spikes_minor: 0,
             spikes_major: 0,
+            deadline_misses: 0,
+            anomaly: AnomalyDetector::default(),
+            anomalies: 0,
+            anomaly_run: 0,
+            loss_burst: 0,
+            loss_since: None,
 
             p25: 0.0,
+            p50: 0.0,
             p75: 0.0,
+            p95: 0.0,
             p99: 0.0,
+            p999: 0.0,
+            p999_at: None,
+            max_latency: 0.0,
+            max_at: None,
+            min_latency: 0.0,
+            mean_latency: 0.0,
+            latency_m2: 0.0,
 
-            last_recalc: Instant::now(),
+            last_recalc: None,
         }
     }
 
-    fn update(&mut self, latency_opt: Option<f64>, time_val: f64) -> PingRecord {
+    fn update(&mut self, latency_opt: Option<f64>, error: Option<ProbeError>, late: bool, time_val: f64, now: DateTime<Local>) -> PingRecord {
+        if self.first_seen.is_some() && validate::is_gap((time_val - self.last_seen) * 1000.0, self.configured_ms) {
+            self.gaps += 1;
+            self.gap_secs += time_val - self.last_seen;
+            // Timeouts on both sides of a gap are not one outage, the one
+            // before ends with its last timeout.
+            self.end_outage(self.last_seen);
+            self.loss_burst = 0;
+            self.loss_since = None;
+        }
         self.total_count += 1;
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S.%3f").to_string();
+        self.first_seen.get_or_insert(time_val);
+        self.last_seen = time_val;
+        self.count_minute(time_val, latency_opt);
+        // Timeouts are late by definition and already counted as loss.
+        if let Some(latency) = latency_opt.filter(|_| late) {
+            self.deadline_misses += 1;
+            self.late_points.push((time_val, latency));
+        }
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency_opt);
+        let timestamp = now.format("%Y-%m-%d %H:%M:%S.%3f").to_string();
         
         match latency_opt {
             None => {
                 self.loss_count += 1;
                 self.spikes_major += 1;
-                self.loss_points.push((time_val, 100.0));
+                self.loss_burst += 1;
+                self.loss_since.get_or_insert(time_val);
+                self.loss_points.push((time_val, self.loss_burst as f64));
+                let error = error.unwrap_or(ProbeError::Timeout);
+                if error != ProbeError::Timeout {
+                    self.error_points.push((time_val, error));
+                }
                 
-                return PingRecord {
+                PingRecord {
                     timestamp,
                     target_type: "Unknown".to_string(),
                     target_ip: self.display_name.clone(),
                     latency_ms: None,
-                    status: "TIMEOUT".to_string(),
-                };
+                    status: error.status().to_string(),
+                    ttl: None,
+                }
             }
 
             Some(latency) => {
+                self.end_outage(time_val);
+                self.loss_burst = 0;
+                self.loss_since = None;
                 let jitter = if self.last_latency == 0.0 { 
                     0.0 
                 } else { 
@@ -86,7 +373,31 @@ impl HostStats {
 
                 self.last_latency = latency;
                 self.current_jitter = jitter;
-                self.all_latencies.push(latency);
+                self.jitter_sum += jitter;
+                self.latencies.add(latency);
+
+                let replies = (self.total_count - self.loss_count) as f64;
+                if replies == 1.0 || latency < self.min_latency {
+                    self.min_latency = latency;
+                }
+                let delta = latency - self.mean_latency;
+                self.mean_latency += delta / replies;
+                self.latency_m2 += delta * (latency - self.mean_latency);
+
+                if latency > self.max_latency {
+                    self.max_latency = latency;
+                    self.max_at = Some(now);
+                }
+                if self.p999 > 0.0 && latency >= self.p999 {
+                    self.p999_at = Some(now);
+                }
+
+                if self.anomaly.observe(latency) {
+                    self.anomalies += 1;
+                    self.anomaly_run += 1;
+                } else {
+                    self.anomaly_run = 0;
+                }
 
                 if latency >= 100.0 {
                     self.spikes_major += 1;
@@ -97,12 +408,12 @@ impl HostStats {
                 self.points.push((time_val, latency));
                 self.jitter_points.push((time_val, jitter));
 
-                let should_recalc = self.all_latencies.len() < 50 
-                    || self.last_recalc.elapsed().as_secs_f64() >= 1.0;
+                let should_recalc = self.latencies.count() < 50 
+                    || self.last_recalc.is_none_or(|t| (now - t).num_milliseconds() >= 1000);
 
                 if should_recalc {
                     self.recalculate_percentiles();
-                    self.last_recalc = Instant::now();
+                    self.last_recalc = Some(now);
                 }
 
                 PingRecord {
@@ -111,52 +422,233 @@ impl HostStats {
                     target_ip: self.display_name.clone(),
                     latency_ms: Some(latency),
                     status: "OK".to_string(),
+                    ttl: None,
                 }
             }
         }
     }
 
     fn recalculate_percentiles(&mut self) {
-        let len = self.all_latencies.len();
-        if len > 10 {
-            let sample_limit = 100_000;
-            let start_index = if len > sample_limit {
-                len - sample_limit
-            } else {
-                0
-            };
+        if self.latencies.count() > 10 {
+            self.p25 = self.latencies.quantile(0.25);
+            self.p50 = self.latencies.quantile(0.5);
+            self.p75 = self.latencies.quantile(0.75);
+            self.p95 = self.latencies.quantile(0.95);
+            self.p99 = self.latencies.quantile(0.99);
+            self.p999 = self.latencies.quantile(0.999);
+        }
+    }
 
-            let mut sorted = self.all_latencies[start_index..].to_vec();
-            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    // How long the host has been unreachable at `now`, once that qualifies
+    // as an outage.
+    pub fn down_for(&self, now: f64) -> Option<f64> {
+        let since = self.loss_since?;
+        (self.loss_burst >= OUTAGE_MIN_TIMEOUTS && now - since >= OUTAGE_MIN_SECS).then_some(now - since)
+    }
 
-            let sorted_len = sorted.len() as f64;
-            let max_idx = sorted_len - 1.0;
+    // Records the current run of timeouts, if it qualifies as an outage.
+    fn end_outage(&mut self, end: f64) {
+        if let Some(start) = self.loss_since
+            && self.loss_burst >= OUTAGE_MIN_TIMEOUTS
+            && end - start >= OUTAGE_MIN_SECS
+        {
+            self.outages.push((start, end, self.loss_burst));
+        }
+    }
 
-            self.p25 = sorted[(max_idx * 0.25).round() as usize];
-            self.p75 = sorted[(max_idx * 0.75).round() as usize];
-            self.p99 = sorted[(max_idx * 0.99).round() as usize];
+    // Runs of timeouts that qualify as an outage, as (start, end, lost) in
+    // seconds since the start. A run ends with the next reply, or with the
+    // last sample when none came.
+    pub fn outages(&self) -> Vec<(f64, f64, u64)> {
+        let mut outages = self.outages.clone();
+        if let Some(start) = self.loss_since
+            && self.loss_burst >= OUTAGE_MIN_TIMEOUTS
+            && self.last_seen - start >= OUTAGE_MIN_SECS
+        {
+            outages.push((start, self.last_seen, self.loss_burst));
         }
+        outages
+    }
+
+    // Adds a sample to its minute. A minute that is over is compared with
+    // the worst one so far; ties go to the later one.
+    fn count_minute(&mut self, time_val: f64, latency: Option<f64>) {
+        let index = (time_val / 60.0) as u64;
+        if let Some(done) = self.minute.take_if(|(current, _)| *current != index)
+            && self.worst.is_none_or(|worst| worse(&done.1, &worst.1).is_ge())
+        {
+            self.worst = Some(done);
+        }
+        let (_, minute) = self.minute.get_or_insert((index, Minute::default()));
+        match latency {
+            None => minute.lost += 1,
+            Some(ms) => {
+                minute.sum += ms;
+                minute.replies += 1;
+                minute.max = minute.max.max(ms);
+            }
+        }
+    }
+
+    // The minute of the session with the most lost probes, ties going to the
+    // higher average latency, as (start in seconds, lost, average, max).
+    pub fn worst_minute(&self) -> Option<(f64, u64, f64, f64)> {
+        let (index, minute) = match (self.worst, self.minute) {
+            (Some(worst), Some(current)) if worse(&current.1, &worst.1).is_lt() => worst,
+            (worst, current) => current.or(worst)?,
+        };
+        Some((index as f64 * 60.0, minute.lost, minute.average(), minute.max))
+    }
+
+    // Replays the samples inside [start, end] into fresh stats, so the panel
+    // can describe exactly what the chart shows.
+    pub fn window(&self, (start, end): (f64, f64), session_start: DateTime<Local>) -> HostStats {
+        let inside = |t: f64| t >= start && t <= end;
+        let at = |t: f64| session_start + chrono::Duration::milliseconds((t * 1000.0) as i64);
+
+        let mut samples: Vec<(f64, Option<f64>)> = self.points.range((start, end)).iter()
+            .filter(|(t, _)| inside(*t))
+            .map(|(t, ms)| (*t, Some(*ms)))
+            .chain(self.loss_points.range((start, end)).iter().filter(|(t, _)| inside(*t)).map(|(t, _)| (*t, None)))
+            .collect();
+        samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut stats = HostStats {
+            lan: self.lan,
+            configured_ms: self.configured_ms,
+            note: self.note.clone(),
+            expected_ms: self.expected_ms,
+            min_samples: self.min_samples,
+            grading: self.grading.clone(),
+            ..HostStats::new(self.display_name.clone())
+        };
+        // Percentiles are computed once at the end rather than during the replay.
+        stats.last_recalc = samples.last().map(|(t, _)| at(*t));
+        for (t, latency) in samples {
+            stats.update(latency, None, false, t, at(t));
+        }
+        stats.recalculate_percentiles();
+        stats.p999_at = stats.points.iter().rev().find(|(_, ms)| stats.p999 > 0.0 && *ms >= stats.p999).map(|(t, _)| at(*t));
+        stats.deadline_misses = self.late_points.range((start, end)).iter().filter(|(t, _)| inside(*t)).count() as u64;
+        stats
+    }
+
+    // Rebuilds a host from recorded samples (seconds since the start, latency,
+    // wall-clock time), with the same math as a live session.
+    pub fn from_samples(display_name: String, lan: bool, samples: &[(f64, Option<f64>, DateTime<Local>)]) -> HostStats {
+        // Intervals are not stored in the log, the typical spacing of the
+        // samples stands in for it when looking for gaps.
+        let mut deltas: Vec<f64> = samples.windows(2).map(|w| (w[1].0 - w[0].0) * 1000.0).collect();
+        deltas.sort_by(f64::total_cmp);
+        let configured_ms = deltas.get(deltas.len() / 2).copied().unwrap_or(0.0);
+        let mut stats = HostStats { lan, configured_ms, ..HostStats::new(display_name) };
+        stats.last_recalc = samples.last().map(|(_, _, at)| *at);
+        for &(t, latency, at) in samples {
+            stats.update(latency, None, false, t, at);
+        }
+        stats.recalculate_percentiles();
+        stats.p999_at = samples.iter().rev()
+            .find(|(_, ms, _)| stats.p999 > 0.0 && ms.is_some_and(|ms| ms >= stats.p999))
+            .map(|(_, _, at)| *at);
+        stats
+    }
+
+    // Average spacing of the results actually received, to compare with the
+    // configured interval. Gaps are no slow probes.
+    pub fn achieved_interval_ms(&self) -> Option<f64> {
+        let spaces = self.total_count.saturating_sub(1 + self.gaps);
+        (self.first_seen.is_some() && spaces > 0).then(|| self.measured_secs() * 1000.0 / spaces as f64)
     }
 
-    pub fn calculate_grade(&self, is_gateway: bool) -> &'static str {
-        let loss_percent = if self.total_count > 0 {
+    // The time the host was actually measured: first to last result, less
+    // the gaps.
+    pub fn measured_secs(&self) -> f64 {
+        let Some(first) = self.first_seen else {
+            return 0.0;
+        };
+        (self.last_seen - first - self.gap_secs).max(0.0)
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        if self.total_count > 0 {
             (self.loss_count as f64 / self.total_count as f64) * 100.0
         } else {
             0.0
-        };
+        }
+    }
+
+    // Keeps `retention` of samples in memory, older ones go to files named
+    // after `prefix` in `dir`.
+    pub fn retain(&mut self, retention: Duration, dir: &std::path::Path, prefix: &str) {
+        let interval_ms = if self.configured_ms > 0.0 { self.configured_ms } else { 1000.0 };
+        let capacity = (retention.as_secs_f64() * 1000.0 / interval_ms).ceil() as usize;
+        let series = [(&mut self.points, "points"), (&mut self.jitter_points, "jitter"), (&mut self.loss_points, "loss"), (&mut self.late_points, "late")];
+        for (series, name) in series {
+            series.retain(capacity, dir.join(format!("{}-{}.bin", prefix, name)));
+        }
+    }
+
+    pub fn apply_config(&mut self, config: Option<&TargetConfig>) -> bool {
+        let note = config.and_then(|c| c.note.clone());
+        let expected_ms = config.and_then(|c| c.expected_ms);
+        let changed = note != self.note || expected_ms != self.expected_ms;
+        self.note = note;
+        self.expected_ms = expected_ms;
+        changed
+    }
+
+    // The average difference between consecutive replies.
+    pub fn mean_jitter(&self) -> f64 {
+        let replies = self.total_count - self.loss_count;
+        if replies > 1 { self.jitter_sum / (replies - 1) as f64 } else { 0.0 }
+    }
+
+    // The sample standard deviation of the replies.
+    pub fn stddev(&self) -> f64 {
+        let replies = self.total_count - self.loss_count;
+        if replies > 1 { (self.latency_m2 / (replies - 1) as f64).sqrt() } else { 0.0 }
+    }
+
+    fn grade_input(&self) -> GradeInput {
+        GradeInput {
+            lan: self.lan,
+            samples: self.total_count,
+            loss_percent: self.loss_percent(),
+            p50: self.p50,
+            p99: self.p99,
+            jitter: self.mean_jitter(),
+            expected_ms: self.expected_ms,
+        }
+    }
 
-        if is_gateway {
-            if loss_percent >= 1.0 || self.p99 >= 50.0 { "F" }
-            else if loss_percent > 0.0 || self.p99 >= 25.0 { "C" }
-            else if self.p99 >= 10.0 { "B" }
-            else if self.p99 >= 5.0 { "A" }
-            else { "S" }
+    pub fn calculate_grade(&self) -> &'static str {
+        self.grading.grade(&self.display_name, &self.grade_input())
+    }
+
+    // For the end of the session: unlike calculate_grade, it waits for a
+    // --grade-command to answer.
+    pub fn final_grade(&self) -> &'static str {
+        self.grading.final_grade(&self.display_name, &self.grade_input())
+    }
+
+    // Which threshold decided the grade and how far each metric is from the
+    // next better and the next worse grade, for the grade popup.
+    pub fn explain_grade(&self) -> Vec<String> {
+        if self.warming_up() {
+            return vec![format!("No grade yet: {} of {} samples", self.total_count, self.min_samples)];
+        }
+        self.grading.explain(&self.display_name, &self.grade_input())
+    }
+
+    pub fn warming_up(&self) -> bool {
+        self.total_count < self.min_samples
+    }
+
+    pub fn grade_label(&self) -> String {
+        if self.warming_up() {
+            format!("warming up (n={})", self.total_count)
         } else {
-            if loss_percent >= 5.0 || self.p99 >= 150.0 { "F" } 
-            else if loss_percent >= 2.0 || self.p99 >= 100.0 { "C" } 
-            else if loss_percent >= 0.5 || self.p99 >= 70.0 { "B" } 
-            else if loss_percent > 0.0  || self.p99 >= 40.0 { "A" } 
-            else { "S" }
+            self.calculate_grade().to_string()
         }
     }
 }
@@ -164,8 +656,15 @@ impl HostStats {
 pub struct App {
     pub net_stats: HostStats,
     pub gw_stats: Option<HostStats>,
+    pub hops: Vec<(u8, HostStats)>,
+    pub cycle: Vec<HostStats>,
+    // First row of the round-robin table, moved with PageUp / PageDown.
+    pub cycle_scroll: usize,
+    pub passive_stats: Option<HostStats>,
 
     pub start_time: DateTime<Local>,
+    // The clock's monotonic reading at start_time; the time axis counts from it.
+    start_elapsed: std::time::Duration,
     pub recorded_duration: f64,
 
     pub configured_interval: u64,
@@ -176,7 +675,50 @@ pub struct App {
     pub is_paused: bool,
     pub should_quit: bool,
     pub is_finished: bool,
+    pub aborted_by_user: bool,
+    pub terminated: bool,
     pub max_duration: Option<std::time::Duration>,
+    // --count: the session finishes after this many Target probes.
+    pub max_probes: Option<u64>,
+
+    pub detector: DivergenceDetector,
+    nic: NicWatch,
+    pub events: Vec<Event>,
+    pub alerts: Alerts,
+    pub rules: Rules,
+    pub bell: Option<Bell>,
+    pub discord: Option<Discord>,
+    pub show_events: bool,
+    pub show_log: bool,
+    pub stats_window: bool,
+
+    pub show_jitter: bool,
+    pub show_loss: bool,
+    pub show_grade: bool,
+    pub search: EventSearch,
+    pub show_gateway: bool,
+    pub layout: LayoutMode,
+    pub theme: Theme,
+    pub units: PanelUnits,
+
+    pub geo: Option<GeoEstimate>,
+    pub baseline_overlay: Option<BaselineOverlay>,
+    pub scheduled_event: Option<ScheduledEvent>,
+    target_ttl: Option<u8>,
+    pub access: Option<Access>,
+    pub stats_page: usize,
+    pub probe: String,
+    pub gateway_probe: String,
+    pub timeout_ms: u64,
+    pub dscp: u8,
+    pub export_dir: PathBuf,
+    retention: Option<Duration>,
+    // A GIF being encoded in the background, see `collect_export`.
+    gif_export: Option<JoinHandle<anyhow::Result<PathBuf>>>,
+    // A short message for the footer and when it was set.
+    notice: Option<(String, Instant)>,
+    pub replay_speed: Option<f64>,
+    clock: Arc<dyn Clock>,
 }
 
 impl App {
@@ -186,12 +728,21 @@ impl App {
         interval_ms_float: f64,
         configured_interval: u64,
         max_duration: Option<std::time::Duration>,
+        divergence_hold_secs: f64,
+        clock: Arc<dyn Clock>,
     ) -> Self {
+        let has_gateway = gateway_host.is_some();
+
         Self {
-            net_stats: HostStats::new(target_host),
-            gw_stats: gateway_host.map(HostStats::new),
+            net_stats: HostStats { configured_ms: configured_interval as f64, ..HostStats::new(target_host) },
+            gw_stats: gateway_host.map(|host| HostStats { lan: true, configured_ms: configured_interval as f64 / 2.0, ..HostStats::new(host) }),
+            hops: Vec::new(),
+            cycle: Vec::new(),
+            cycle_scroll: 0,
+            passive_stats: None,
 
-            start_time: Local::now(),
+            start_time: clock.now(),
+            start_elapsed: clock.elapsed(),
             recorded_duration: 0.0,
 
             configured_interval,
@@ -206,76 +757,565 @@ impl App {
             is_paused: false,
             should_quit: false,
             is_finished: false,
+            aborted_by_user: false,
+            terminated: false,
             max_duration,
+            max_probes: None,
+
+            detector: DivergenceDetector::new(divergence_hold_secs, has_gateway),
+            nic: NicWatch::default(),
+            events: Vec::new(),
+            alerts: Alerts::default(),
+            rules: Rules::default(),
+            bell: None,
+            discord: None,
+            show_events: false,
+            show_log: false,
+            stats_window: false,
+
+            show_jitter: true,
+            show_loss: true,
+            show_grade: false,
+            search: EventSearch::default(),
+            show_gateway: true,
+            layout: LayoutMode::default(),
+            theme: Theme::default(),
+            units: PanelUnits::uniform(locale::units()),
+
+            geo: None,
+            baseline_overlay: None,
+            scheduled_event: None,
+            target_ttl: None,
+            access: None,
+            stats_page: 0,
+            probe: "ICMP echo".to_string(),
+            gateway_probe: "ICMP echo".to_string(),
+            timeout_ms: crate::pinger::DEFAULT_TIMEOUT.as_millis() as u64,
+            dscp: 0,
+            export_dir: PathBuf::from("."),
+            retention: None,
+            gif_export: None,
+            notice: None,
+            replay_speed: None,
+            clock,
+        }
+    }
+
+    pub fn set_route(&mut self, route: &[Hop]) {
+        self.hops = route.iter()
+            .map(|hop| {
+                let mut stats = HostStats::new(hop.addr().map(redact::ip).unwrap_or_else(|| "*".to_string()));
+                stats.lan = hop.addr().is_some_and(|a| Scope::of(a).is_local());
+                stats.configured_ms = self.configured_interval as f64;
+                (hop.ttl, stats)
+            })
+            .collect();
+    }
+
+    // Every member of the round robin is probed once per full rotation.
+    pub fn set_cycle(&mut self, targets: &[(String, IpAddr)], interval_ms: f64) {
+        self.cycle = targets.iter()
+            .map(|(host, ip)| HostStats {
+                lan: Scope::of(*ip).is_local(),
+                configured_ms: interval_ms * targets.len() as f64,
+                ..HostStats::new(redact::host(host))
+            })
+            .collect();
+    }
+
+    // The passive series has no single host, it stands for whatever TCP
+    // connections carried data during each poll.
+    pub fn enable_passive(&mut self, port: Option<u16>) {
+        let name = match port {
+            Some(port) => format!("TCP port {}", port),
+            None => "TCP connections".to_string(),
+        };
+        self.passive_stats = Some(HostStats { configured_ms: self.configured_interval as f64, ..HostStats::new(name) });
+    }
+
+    // Starts charting a gateway during the session, when this session took
+    // over probing it from another one.
+    pub fn add_gateway(&mut self, host: String, config: Option<&TargetConfig>) {
+        let mut stats = HostStats {
+            lan: true,
+            configured_ms: self.configured_interval as f64 / 2.0,
+            min_samples: self.net_stats.min_samples,
+            grading: self.net_stats.grading.clone(),
+            ..HostStats::new(host)
+        };
+        stats.apply_config(config);
+        if let Some(retention) = self.retention {
+            stats.retain(retention, &history::spill_dir(), "gateway");
+        }
+        self.gw_stats = Some(stats);
+        self.detector.enable_gateway();
+    }
+
+    pub fn set_min_samples(&mut self, min_samples: u64) {
+        let hosts = std::iter::once(&mut self.net_stats)
+            .chain(self.gw_stats.as_mut())
+            .chain(self.hops.iter_mut().map(|(_, stats)| stats))
+            .chain(self.cycle.iter_mut())
+            .chain(self.passive_stats.as_mut());
+        for stats in hosts {
+            stats.min_samples = min_samples;
+        }
+    }
+
+    // Caps the chart data of every host, see history.rs.
+    pub fn set_retention(&mut self, retention: Duration) {
+        self.retention = Some(retention);
+        history::remove_stale();
+        let dir = history::spill_dir();
+        let hosts = std::iter::once(&mut self.net_stats)
+            .chain(self.gw_stats.as_mut())
+            .chain(self.hops.iter_mut().map(|(_, stats)| stats))
+            .chain(self.cycle.iter_mut())
+            .chain(self.passive_stats.as_mut());
+        for (i, stats) in hosts.enumerate() {
+            stats.retain(retention, &dir, &i.to_string());
+        }
+    }
+
+    pub fn set_grading(&mut self, grading: Arc<dyn GradeProvider>) {
+        let hosts = std::iter::once(&mut self.net_stats)
+            .chain(self.gw_stats.as_mut())
+            .chain(self.hops.iter_mut().map(|(_, stats)| stats))
+            .chain(self.cycle.iter_mut())
+            .chain(self.passive_stats.as_mut());
+        for stats in hosts {
+            stats.grading = grading.clone();
+        }
+    }
+
+    pub fn apply_ui_state(&mut self, state: &UiState) {
+        if let Some(zoom) = state.zoom_window_seconds {
+            self.zoom_window_seconds = zoom.max(10.0);
+        }
+        self.show_jitter = state.show_jitter;
+        self.show_loss = state.show_loss;
+        self.show_gateway = state.show_gateway;
+        self.show_events = state.show_events;
+        self.show_log = state.show_log;
+        self.stats_window = state.stats_window;
+        self.layout = state.layout;
+        self.theme = state.theme;
+    }
+
+    pub fn ui_state(&self) -> UiState {
+        UiState {
+            zoom_window_seconds: Some(self.zoom_window_seconds),
+            show_jitter: self.show_jitter,
+            show_loss: self.show_loss,
+            show_gateway: self.show_gateway,
+            show_events: self.show_events,
+            show_log: self.show_log,
+            stats_window: self.stats_window,
+            layout: self.layout,
+            theme: self.theme,
+        }
+    }
+
+    // The time range the chart currently shows, in seconds since the start.
+    pub fn view(&self) -> (f64, f64) {
+        let end = (self.recorded_duration - self.scroll_offset_seconds).max(0.0);
+        ((end - self.zoom_window_seconds).max(0.0), end)
+    }
+
+    // The readiness line above the chart in the hour before an --event.
+    pub fn readiness_banner(&self) -> Option<(Verdict, String)> {
+        let now = self.clock.now();
+        let event = self.scheduled_event.as_ref().filter(|e| e.in_banner_lead(now))?;
+        let readiness = event.assess(&self.net_stats, self.start_time, self.elapsed_secs());
+        Some((readiness.verdict, readiness.describe(event, now)))
+    }
+
+    // Seconds since the start on the monotonic clock, the x of every sample.
+    fn elapsed_secs(&self) -> f64 {
+        self.clock.elapsed().saturating_sub(self.start_elapsed).as_secs_f64()
+    }
+
+    pub fn summaries(&self) -> Vec<SessionSummary> {
+        let summary = |stats: &HostStats, target_type: &str| SessionSummary {
+            target_type: target_type.to_string(),
+            target_ip: stats.display_name.clone(),
+            started: self.start_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+            duration_secs: self.elapsed_secs(),
+            samples: stats.total_count,
+            lost: stats.loss_count,
+            loss_percent: stats.loss_percent(),
+            p25: stats.p25,
+            p75: stats.p75,
+            p99: stats.p99,
+            p999: stats.p999,
+            p999_at: stats.p999_at.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+            max: stats.max_latency,
+            max_at: stats.max_at.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+            spikes_minor: stats.spikes_minor,
+            spikes_major: stats.spikes_major,
+            deadline_misses: stats.deadline_misses,
+            grade: stats.calculate_grade().to_string(),
+            protocol: match target_type {
+                "Target" => self.probe.clone(),
+                "Gateway" => self.gateway_probe.clone(),
+                "Passive" => "TCP RTT (passive)".to_string(),
+                _ => "ICMP echo".to_string(),
+            },
+            aborted_by_user: self.aborted_by_user,
+            anomalies: stats.anomalies,
+            timeout_ms: self.timeout_ms,
+            dscp: self.dscp,
+            measured_secs: stats.measured_secs(),
+            gaps: stats.gaps,
+            min: stats.min_latency,
+            mean: stats.mean_latency,
+            stddev: stats.stddev(),
+            warming_up: stats.warming_up(),
+            terminated: self.terminated,
+        };
+
+        self.hosts().into_iter().map(|(target_type, stats)| summary(stats, &target_type)).collect()
+    }
+
+    // Waits for a --grade-command to grade the final numbers of every host,
+    // so the summaries written after it carry its answers.
+    pub fn settle_grades(&self) {
+        for (_, stats) in self.hosts() {
+            stats.final_grade();
+        }
+    }
+
+    // Every host of the session with its target type, in summary order.
+    pub fn hosts(&self) -> Vec<(String, &HostStats)> {
+        let mut list = vec![("Target".to_string(), &self.net_stats)];
+        if let Some(gw) = &self.gw_stats {
+            list.push(("Gateway".to_string(), gw));
+        }
+        for (ttl, stats) in &self.hops {
+            list.push((format!("Hop {}", ttl), stats));
         }
+        for stats in &self.cycle {
+            list.push(("Cycle".to_string(), stats));
+        }
+        if let Some(passive) = &self.passive_stats {
+            list.push(("Passive".to_string(), passive));
+        }
+        list
     }
 
-    pub fn on_ping(&mut self, source: SourceType, latency: Option<f64>) -> Option<PingRecord> {
+    pub fn add_marker(&mut self, label: String) -> PingRecord {
+        let time_val = self.elapsed_secs();
+        self.push_event(Event::new(EventKind::Marker, time_val, label.clone()));
+
+        PingRecord {
+            timestamp: self.clock.now().format("%Y-%m-%d %H:%M:%S.%3f").to_string(),
+            target_type: "Marker".to_string(),
+            target_ip: label,
+            latency_ms: None,
+            status: MARKER_STATUS.to_string(),
+            ttl: None,
+        }
+    }
+
+    pub fn ending(&self) -> &'static str {
+        ending(self.aborted_by_user, self.terminated)
+    }
+
+    pub fn baseline_comparison(&self) -> Option<Comparison> {
+        self.access.filter(|_| !self.net_stats.lan).and_then(|access| Comparison::of(&self.net_stats, access))
+    }
+
+    fn push_event(&mut self, mut event: Event) {
+        event.message = redact::text(&event.message);
+        event.timestamp = self.clock.now().format("%Y-%m-%d %H:%M:%S").to_string();
+        event.suppressed = !self.alerts.observe(&event);
+        if let Some(discord) = &self.discord
+            && !event.suppressed
+        {
+            discord.post_event(&event, &self.net_stats);
+        }
+        self.events.push(event);
+    }
+
+    pub fn on_system_event(&mut self, event: SystemEvent) {
+        let time_val = self.elapsed_secs();
+        self.push_event(Event::new(event.kind, time_val, event.message));
+    }
+
+    pub fn probe_count_reached(&self) -> bool {
+        self.max_probes.is_some_and(|max| self.net_stats.total_count >= max)
+    }
+
+    pub fn on_ping(&mut self, update: PingUpdate) -> Option<PingRecord> {
+        let PingUpdate { source, latency, error, ttl, late } = update;
         if self.is_paused || self.is_finished {
             return None;
         }
 
-        let now = Local::now();
-        let duration_since_start = now.signed_duration_since(self.start_time);
-        
-        let time_val = duration_since_start.num_milliseconds() as f64 / 1000.0;
+        let now = self.clock.now();
+        let time_val = self.elapsed_secs();
         
         if time_val > self.recorded_duration {
             self.recorded_duration = time_val;
         }
         
-        if let Some(max) = self.max_duration {
-            if self.recorded_duration >= max.as_secs_f64() {
-                self.is_finished = true;
-                return None;
+        if let Some(max) = self.max_duration
+            && self.recorded_duration >= max.as_secs_f64()
+        {
+            self.is_finished = true;
+            return None;
+        }
+
+        if let Some(bell) = &mut self.bell {
+            bell.observe(&source, latency);
+        }
+
+        if let Some(mut event) = self.detector.observe(&source, latency, time_val) {
+            // The counters of this machine now say nothing about a replayed session.
+            let nic = match event.kind {
+                _ if self.replay_speed.is_some() => None,
+                EventKind::Recovered => self.nic.outage_ended(),
+                _ => self.nic.outage_started(),
+            };
+            if let Some(nic) = nic {
+                event.message.push_str(&format!(" [{}]", nic));
             }
+            self.push_event(event);
         }
-        
+        if source == SourceType::Target && self.replay_speed.is_none() && self.detector.target_healthy() {
+            self.nic.healthy(time_val);
+        }
+
         let record = match source {
             SourceType::Target => {
-                let mut r = self.net_stats.update(latency, time_val);
+                self.observe_target_ttl(ttl, time_val);
+                let mut r = self.net_stats.update(latency, error, late, time_val, now);
+                self.observe_scheduled_event(now, time_val);
                 r.target_type = "Target".to_string();
+                if self.probe_count_reached() {
+                    self.is_finished = true;
+                }
+                if self.net_stats.anomaly_run == 1
+                    && let (Some(latency), Some((usual, upper))) = (latency, self.net_stats.anomaly.band())
+                {
+                    let message = format!("Target at {}, usually ~{} (up to {})", locale::ms(latency, 0), locale::ms(usual, 0), locale::ms(upper, 0));
+                    self.push_event(Event::new(EventKind::Anomaly, time_val, message));
+                }
+                for event in self.rules.observe(&self.net_stats, latency, time_val) {
+                    self.push_event(event);
+                }
                 Some(r)
             },
             
             SourceType::Gateway => {
                 if let Some(gw) = &mut self.gw_stats {
-                    let mut r = gw.update(latency, time_val);
+                    let mut r = gw.update(latency, error, late, time_val, now);
                     r.target_type = "Gateway".to_string();
                     Some(r)
                 } else {
                     None
                 }
             }
+
+            SourceType::Hop(ttl) => {
+                let (_, stats) = self.hops.iter_mut().find(|(t, _)| *t == ttl)?;
+                let mut r = stats.update(latency, error, late, time_val, now);
+                r.target_type = format!("Hop {}", ttl);
+                Some(r)
+            }
+
+            SourceType::Cycle(idx) => {
+                let stats = self.cycle.get_mut(idx)?;
+                let mut r = stats.update(latency, error, late, time_val, now);
+                r.target_type = "Cycle".to_string();
+                Some(r)
+            }
+
+            SourceType::Passive => {
+                let stats = self.passive_stats.as_mut()?;
+                let mut r = stats.update(latency, error, late, time_val, now);
+                r.target_type = "Passive".to_string();
+                Some(r)
+            }
         };
+        record.map(|r| PingRecord { ttl, ..r })
+    }
 
-        record
+    // --event: the readiness verdict goes to the event log once, shortly
+    // before the event.
+    fn observe_scheduled_event(&mut self, now: DateTime<Local>, time_val: f64) {
+        let Some(event) = &mut self.scheduled_event else {
+            return;
+        };
+        event.roll_over(now);
+        if !event.announcement_due(now, time_val) {
+            return;
+        }
+        event.announced = true;
+        let message = event.assess(&self.net_stats, self.start_time, time_val).describe(event, now);
+        self.push_event(Event::new(EventKind::Readiness, time_val, message));
+    }
+
+    // A change of the Target's reply TTL means the replies now take a path
+    // with a different number of routers, usually a route change upstream.
+    fn observe_target_ttl(&mut self, ttl: Option<u8>, time_val: f64) {
+        let Some(ttl) = ttl else {
+            return;
+        };
+        if let Some(previous) = self.target_ttl.replace(ttl)
+            && previous != ttl
+        {
+            let (hops, direction) = if ttl < previous { (previous - ttl, "more") } else { (ttl - previous, "fewer") };
+            let message = format!(
+                "Target reply TTL {} -> {}: the replies cross {} {} {}",
+                previous,
+                ttl,
+                hops,
+                direction,
+                if hops == 1 { "router" } else { "routers" }
+            );
+            self.push_event(Event::new(EventKind::RouteChange, time_val, message));
+        }
+    }
+
+    fn set_notice(&mut self, message: impl Into<String>) {
+        self.notice = Some((message.into(), Instant::now()));
+    }
+
+    pub fn notice(&self) -> Option<&str> {
+        self.notice.as_ref().filter(|(_, since)| since.elapsed() < NOTICE_DURATION).map(|(message, _)| message.as_str())
+    }
+
+    // Reports a finished GIF export. With `wait`, e.g. when the session ends,
+    // a running one is finished first rather than cut off.
+    pub fn collect_export(&mut self, wait: bool) {
+        if !self.gif_export.as_ref().is_some_and(|job| wait || job.is_finished()) {
+            return;
+        }
+        let Some(job) = self.gif_export.take() else {
+            return;
+        };
+        match job.join() {
+            Ok(Ok(path)) => {
+                let message = format!("Chart window exported to {}", path.display());
+                self.set_notice(message.clone());
+                self.on_system_event(SystemEvent { kind: EventKind::ChartExport, message });
+            }
+            Ok(Err(e)) => {
+                self.set_notice(format!("GIF export failed: {}", e));
+                diag::error(format!("GIF export failed: {}", e));
+            }
+            Err(_) => diag::error("GIF export failed: the export thread panicked"),
+        }
     }
 
     pub fn on_key(&mut self, key: KeyCode) {
+        if self.search.editing {
+            self.on_search_key(key);
+        } else {
+            self.on_shortcut(key);
+        }
+    }
+
+    // Typing the '/' query of the event log.
+    fn on_search_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char(c) => {
+                self.search.query.push(c);
+                self.search.selected = None;
+            }
+            KeyCode::Backspace => {
+                self.search.query.pop();
+                self.search.selected = None;
+            }
+            KeyCode::Enter => {
+                self.search.editing = false;
+                self.jump_to_match(true);
+            }
+            KeyCode::Esc => self.search = EventSearch::default(),
+            _ => {}
+        }
+    }
+
+    // Scrolls the chart so the selected match sits in the middle of it.
+    fn jump_to_match(&mut self, forward: bool) {
+        let Some(index) = self.search.step(&self.events, forward) else {
+            return;
+        };
+        let time_val = self.events[index].time_val;
+        self.scroll_offset_seconds = (self.recorded_duration - time_val - self.zoom_window_seconds / 2.0).max(0.0);
+    }
+
+    fn on_shortcut(&mut self, key: KeyCode) {
         match key {
             KeyCode::Char('q') => self.should_quit = true,
             
-            KeyCode::Char(' ') => {
-                if !self.is_finished {
-                    self.is_paused = !self.is_paused;
-                }
+            KeyCode::Char(' ') if !self.is_finished => {
+                self.is_paused = !self.is_paused;
             }
 
-            KeyCode::Char('+') | KeyCode::Up => {
-                if self.zoom_window_seconds > 10.0 {
-                    self.zoom_window_seconds -= 10.0;
-                }
+            KeyCode::Char('e') => self.show_events = !self.show_events,
+            KeyCode::Char('a') => self.alerts.acknowledge(),
+            KeyCode::Char('l') => self.show_log = !self.show_log,
+            KeyCode::Char('j') => self.show_jitter = !self.show_jitter,
+            KeyCode::Char('x') => self.show_loss = !self.show_loss,
+            KeyCode::Char('h') => self.show_gateway = !self.show_gateway,
+            KeyCode::Char('v') => self.layout = self.layout.next(),
+            KeyCode::Char('t') => self.theme = self.theme.next(),
+            KeyCode::Char('u') => self.units.next_unit(),
+            KeyCode::Char('p') => self.units.next_precision(),
+            KeyCode::Char('s') => self.stats_page = self.stats_page.wrapping_add(1),
+            KeyCode::Char('w') => self.stats_window = !self.stats_window,
+
+            KeyCode::Char('g') => self.show_grade = !self.show_grade,
+            KeyCode::Esc => {
+                self.show_grade = false;
+                self.search = EventSearch::default();
+            }
+
+            KeyCode::Char('/') => {
+                self.show_events = true;
+                self.search = EventSearch { editing: true, ..EventSearch::default() };
+            }
+            KeyCode::Char('n') if self.search.is_active() => self.jump_to_match(true),
+            KeyCode::Char('N') if self.search.is_active() => self.jump_to_match(false),
+
+            KeyCode::Char('m') if self.gif_export.is_some() => self.set_notice("A GIF export is still running"),
+            KeyCode::Char('m') => {
+                // Encoding the frames takes a while, the TUI keeps drawing meanwhile.
+                let snapshot = gifexport::snapshot(self);
+                let directory = self.export_dir.clone();
+                self.gif_export = Some(std::thread::spawn(move || snapshot.write(&directory)));
+                self.set_notice("Exporting the chart window as GIF...");
+            }
+
+            KeyCode::Char('c') => match chartexport::export(self, &self.export_dir, ChartFormat::Png) {
+                Ok(path) => self.on_system_event(SystemEvent {
+                    kind: EventKind::ChartExport,
+                    message: format!("Chart window exported to {}", path.display()),
+                }),
+                Err(e) => diag::error(format!("PNG export failed: {}", e)),
+            },
+
+            KeyCode::Char('+') | KeyCode::Up if self.zoom_window_seconds > 10.0 => {
+                self.zoom_window_seconds -= 10.0;
             }
 
             KeyCode::Char('-') | KeyCode::Down => {
                 self.zoom_window_seconds += 10.0;
             }
 
-            KeyCode::Left => {
-                if self.scroll_offset_seconds < self.recorded_duration {
-                    self.scroll_offset_seconds += 10.0;
-                }
+            KeyCode::PageDown => {
+                let last_page = self.cycle.len().saturating_sub(CYCLE_TABLE_ROWS);
+                self.cycle_scroll = (self.cycle_scroll + CYCLE_TABLE_ROWS).min(last_page);
+            }
+
+            KeyCode::PageUp => {
+                self.cycle_scroll = self.cycle_scroll.saturating_sub(CYCLE_TABLE_ROWS);
+            }
+
+            KeyCode::Left if self.scroll_offset_seconds < self.recorded_duration => {
+                self.scroll_offset_seconds += 10.0;
             }
 
             KeyCode::Right => {
@@ -287,4 +1327,214 @@ impl App {
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use chrono::TimeZone;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn start() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 16, 21, 0, 0).unwrap()
+    }
+
+    fn at(ms: i64) -> DateTime<Local> {
+        start() + chrono::Duration::milliseconds(ms)
+    }
+
+    // A Target-only session on a clock that moves only when a ping says so.
+    fn session() -> (App, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new(start()));
+        let app = App::new("192.0.2.10".to_string(), None, 1000.0, 1000, None, 3.0, clock.clone());
+        (app, clock)
+    }
+
+    fn ping(app: &mut App, clock: &ManualClock, ms: i64, latency: Option<f64>) {
+        ping_from(app, clock, SourceType::Target, ms, latency);
+    }
+
+    fn ping_from(app: &mut App, clock: &ManualClock, source: SourceType, ms: i64, latency: Option<f64>) {
+        clock.set(at(ms));
+        app.on_ping(PingUpdate {
+            source,
+            latency,
+            error: latency.is_none().then_some(ProbeError::Timeout),
+            ttl: None,
+            late: false,
+        });
+    }
+
+    #[test]
+    fn percentiles_and_loss_follow_the_samples() {
+        let mut stats = HostStats::new("192.0.2.10".to_string());
+        for i in 1..=100 {
+            stats.update(Some(i as f64), None, false, i as f64, at(i * 1000));
+        }
+        for i in 101..=105 {
+            stats.update(None, Some(ProbeError::Timeout), false, i as f64, at(i * 1000));
+        }
+
+        // Nearest rank over 1..=100 while the digest has not merged yet.
+        assert_eq!((stats.p25, stats.p50, stats.p99), (26.0, 51.0, 99.0));
+        assert_eq!((stats.min_latency, stats.max_latency), (1.0, 100.0));
+        assert!((stats.mean_latency - 50.5).abs() < 1e-9);
+        assert_eq!((stats.total_count, stats.loss_count), (105, 5));
+        assert!((stats.loss_percent() - 5.0 / 105.0 * 100.0).abs() < 1e-9);
+        assert_eq!(stats.max_at, Some(at(100_000)));
+    }
+
+    #[test]
+    fn an_outage_is_reported_and_recovers() {
+        let clock = Arc::new(ManualClock::new(start()));
+        let mut app = App::new("192.0.2.10".to_string(), Some("192.0.2.1".to_string()), 1000.0, 1000, None, 3.0, clock.clone());
+        let mut ms = 0;
+        let mut next = |app: &mut App, latency| {
+            ms += 1000;
+            ping_from(app, &clock, SourceType::Gateway, ms, Some(1.0));
+            ping(app, &clock, ms, latency);
+        };
+        for _ in 0..10 {
+            next(&mut app, Some(20.0));
+        }
+        for _ in 0..6 {
+            next(&mut app, None);
+        }
+        for _ in 0..3 {
+            next(&mut app, Some(20.0));
+        }
+
+        let kinds: Vec<EventKind> = app.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [EventKind::InternetDegraded, EventKind::Recovered]);
+        // Degraded from the first timeout at 11s, reported once it held 3s.
+        assert_eq!(app.events[0].time_val, 14.0);
+        assert_eq!(app.events[1].time_val, 19.0);
+        assert_eq!(app.net_stats.outages(), [(11.0, 17.0, 6)]);
+    }
+
+    #[test]
+    fn outages_end_at_a_gap_or_stay_open() {
+        let mut stats = HostStats { configured_ms: 1000.0, ..HostStats::new("192.0.2.10".to_string()) };
+        let mut sample = |t: u32, latency: Option<f64>| stats.update(latency, None, false, t as f64, at(t as i64 * 1000));
+        for t in 0..5 {
+            sample(t, Some(20.0));
+        }
+        // Timeouts up to a 60s gap, then the run after it is a new one.
+        for t in 5..9 {
+            sample(t, None);
+        }
+        for t in 69..73 {
+            sample(t, None);
+        }
+        assert_eq!(stats.outages(), [(5.0, 8.0, 4), (69.0, 72.0, 4)]);
+        assert_eq!((stats.gaps, stats.gap_secs), (1, 61.0));
+
+        stats.update(Some(20.0), None, false, 73.0, at(73_000));
+        assert_eq!(stats.outages(), [(5.0, 8.0, 4), (69.0, 73.0, 4)]);
+    }
+
+    #[test]
+    fn the_worst_minute_is_tracked_as_samples_come_in() {
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut stats = HostStats::new("192.0.2.10".to_string());
+            let mut samples = Vec::new();
+            let mut t = 0.0;
+            for _ in 0..rng.gen_range(1..400) {
+                t += rng.gen_range(0.5..20.0);
+                // Few distinct values, so ties between minutes happen.
+                let latency = (!rng.gen_bool(0.1)).then(|| rng.gen_range(1..4) as f64 * 10.0);
+                stats.update(latency, None, false, t, at((t * 1000.0) as i64));
+                samples.push((t, latency));
+            }
+
+            // Every minute at once, ties going to the later one.
+            let mut minutes: std::collections::BTreeMap<u64, Minute> = std::collections::BTreeMap::new();
+            for (t, latency) in samples {
+                let minute = minutes.entry((t / 60.0) as u64).or_default();
+                match latency {
+                    None => minute.lost += 1,
+                    Some(ms) => {
+                        minute.sum += ms;
+                        minute.replies += 1;
+                        minute.max = minute.max.max(ms);
+                    }
+                }
+            }
+            let expected = minutes
+                .into_iter()
+                .max_by(|a, b| worse(&a.1, &b.1))
+                .map(|(index, m)| (index as f64 * 60.0, m.lost, m.average(), m.max));
+            assert_eq!(stats.worst_minute(), expected, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn grades_follow_loss_latency_and_profile() {
+        let (mut app, clock) = session();
+        for i in 0..200 {
+            ping(&mut app, &clock, i * 1000, Some(30.0));
+        }
+        assert_eq!(app.net_stats.calculate_grade(), "S");
+        app.net_stats.grading = grading::profile("gaming");
+        assert_eq!(app.net_stats.calculate_grade(), "A");
+
+        app.net_stats.grading = grading::general();
+        for i in 200..220 {
+            ping(&mut app, &clock, i * 1000, None);
+        }
+        assert_eq!(app.net_stats.calculate_grade(), "F");
+    }
+
+    // Any pattern of replies and timeouts at any spacing: the counters add
+    // up, loss% is lost over sent, outages only hold lost probes and the
+    // percentiles stay in order.
+    #[test]
+    fn counters_hold_for_random_timeout_patterns() {
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (mut app, clock) = session();
+            let (probes, loss_rate) = (rng.gen_range(1..500), rng.gen_range(0.0..1.0));
+            let (mut ms, mut lost) = (0, 0);
+            for _ in 0..probes {
+                ms += rng.gen_range(10..2000);
+                let latency = if rng.gen_bool(loss_rate) {
+                    lost += 1;
+                    None
+                } else {
+                    Some(rng.gen_range(1.0..300.0))
+                };
+                ping(&mut app, &clock, ms, latency);
+            }
+
+            let stats = &app.net_stats;
+            assert_eq!((stats.total_count, stats.loss_count), (probes, lost), "seed {}", seed);
+            assert_eq!(stats.loss_percent(), lost as f64 / probes as f64 * 100.0, "seed {}", seed);
+            assert!(stats.outages().iter().map(|(_, _, n)| n).sum::<u64>() <= lost, "seed {}", seed);
+            if probes - lost > 10 {
+                assert!(stats.min_latency <= stats.p25 && stats.p25 <= stats.p50 && stats.p50 <= stats.p99, "seed {}", seed);
+                assert!(stats.p99 <= stats.max_latency, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn the_round_robin_table_pages_within_its_targets() {
+        let (mut app, _) = session();
+        app.cycle = (0..20).map(|i| HostStats::new(format!("192.0.2.{}", i))).collect();
+
+        app.on_key(KeyCode::PageDown);
+        assert_eq!(app.cycle_scroll, CYCLE_TABLE_ROWS);
+        app.on_key(KeyCode::PageDown);
+        app.on_key(KeyCode::PageDown);
+        assert_eq!(app.cycle_scroll, 20 - CYCLE_TABLE_ROWS);
+        app.on_key(KeyCode::PageUp);
+        app.on_key(KeyCode::PageUp);
+        app.on_key(KeyCode::PageUp);
+        assert_eq!(app.cycle_scroll, 0);
+
+        app.cycle.truncate(5);
+        app.on_key(KeyCode::PageDown);
+        assert_eq!(app.cycle_scroll, 0);
+    }
+}
@@ -1,29 +1,447 @@
-use clap::{Parser, ValueEnum};
-use std::time::Duration;
+use crate::baseline::Access;
+use crate::bell::BellMode;
+use crate::sinks::OutputFormat;
+use crate::config::DEFAULT_PROFILE;
+use crate::locale::Unit;
+use crate::trace;
+use anyhow::{Result, anyhow, bail};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, parser::ValueSource};
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-pub struct Args {
-    #[arg(short, long)]
+#[command(author, version, about, long_about = None, subcommand_required = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[arg(long, global = true, env = "VASILI_LOCALE")]
+    pub locale: Option<String>,
+
+    #[arg(long, global = true, value_enum, env = "VASILI_UNITS")]
+    pub units: Option<Unit>,
+
+    #[arg(long, global = true, value_parser = clap::value_parser!(u8).range(0..=2), env = "VASILI_PRECISION")]
+    pub precision: Option<u8>,
+}
+
+impl Cli {
+    // `vasili [OPTIONS]` without a verb keeps working as `vasili run [OPTIONS]`.
+    pub fn argv() -> Vec<String> {
+        let mut argv: Vec<String> = env::args().collect();
+        let first = argv.get(1).map(String::as_str);
+        let is_verb = Cli::command().get_subcommands().any(|c| Some(c.get_name()) == first);
+        if !is_verb && !matches!(first, Some("-h" | "--help" | "-V" | "--version" | "help")) {
+            argv.insert(1, "run".to_string());
+        }
+        argv
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Monitor a target live in the TUI or as a daemon (the default)")]
+    Run(Box<RunArgs>),
+    #[command(about = "Print the route to a host with the round-trip time of every hop")]
+    Trace(TraceArgs),
+    #[command(about = "Print the summary of a recorded session")]
+    Report(ReportArgs),
+    #[command(about = "Compute loss, percentiles, spikes, outages and grade from a recorded session")]
+    Analyze(AnalyzeArgs),
+    #[command(about = "List the running sessions on this machine with their key stats")]
+    Status(StatusArgs),
+    #[command(about = "Watch the sessions on this machine and on others (via their HTTP API) in one live table")]
+    Top(TopArgs),
+    #[command(about = "Play a recorded session back in the TUI")]
+    Replay(ReplayArgs),
+    #[command(about = "Check a recorded session for schema errors, clock jumps, duplicates and gaps")]
+    Validate(ValidateArgs),
+    #[command(about = "Compare two recorded sessions side by side and tell which one was better")]
+    Compare(CompareArgs),
+    #[command(about = "Answer UDP probes of other vasili instances (`--protocol echo`), e.g. on a VPS")]
+    EchoServer(EchoServerArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    #[arg(short, long, env = "VASILI_TARGET")]
     pub target: Option<String>,
 
-    #[arg(short, long, value_enum, default_value_t = PingMode::Gaming)]
+    #[arg(short = '4', long, default_value_t = false, conflicts_with = "ipv6", env = "VASILI_IPV4")]
+    pub ipv4: bool,
+
+    #[arg(short = '6', long, default_value_t = false, env = "VASILI_IPV6")]
+    pub ipv6: bool,
+
+    #[arg(long, value_enum, default_value_t = Protocol::Icmp, env = "VASILI_PROTOCOL")]
+    pub protocol: Protocol,
+
+    #[arg(long, env = "VASILI_PORT")]
+    pub port: Option<u16>,
+
+    #[arg(long, conflicts_with = "target", env = "VASILI_DNS_SERVER")]
+    pub dns_server: Option<String>,
+
+    #[arg(long, env = "VASILI_FALLBACK")]
+    pub fallback: Option<String>,
+
+    #[arg(long, default_value = "example.com", env = "VASILI_DNS_NAME")]
+    pub dns_name: String,
+
+    #[arg(short, long, value_enum, default_value_t = PingMode::Gaming, env = "VASILI_MODE")]
     pub mode: PingMode,
 
-    #[arg(short, long)]
+    #[arg(short, long, env = "VASILI_DURATION")]
     pub duration: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..), env = "VASILI_COUNT")]
+    pub count: Option<u64>,
+
+    #[arg(long, default_value_t = false, conflicts_with_all = ["count", "duration", "daemon"], env = "VASILI_ONCE")]
+    pub once: bool,
+
+    #[arg(long, value_name = "BASELINE", num_args = 0..=1, default_missing_value = "60s", conflicts_with_all = ["count", "duration", "daemon", "once"], env = "VASILI_REBOOT_TEST")]
+    pub reboot_test: Option<String>,
+
+    #[arg(short, long, env = "VASILI_INTERVAL")]
     pub interval: Option<String>,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, env = "VASILI_TIMEOUT")]
+    pub timeout: Option<String>,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=255), env = "VASILI_TTL")]
+    pub ttl: Option<u32>,
+
+    #[arg(long, value_name = "CLASS", env = "VASILI_DSCP")]
+    pub dscp: Option<String>,
+
+    #[arg(long, value_delimiter = ',', env = "VASILI_CYCLE")]
+    pub cycle: Vec<String>,
+
+    #[arg(long, requires = "cycle", env = "VASILI_CYCLE_INTERVAL")]
+    pub cycle_interval: Option<String>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_NO_GATEWAY")]
     pub no_gateway: bool,
 
-    #[arg(long, default_value_t = false)]
+    #[arg(long, default_value_t = false, conflicts_with = "no_gateway", env = "VASILI_PROBE_SHARED_GATEWAY")]
+    pub probe_shared_gateway: bool,
+
+    #[arg(long, default_value_t = false, env = "VASILI_NO_ARP")]
+    pub no_arp: bool,
+
+    #[arg(long, default_value_t = false, env = "VASILI_NO_CSV")]
     pub no_csv: bool,
 
-    #[arg(short = 'D', long, default_value_t = false)]
+    #[arg(long, default_value_t = false, env = "VASILI_RECORD")]
+    pub record: bool,
+
+    #[arg(short, long = "format", value_enum, value_delimiter = ',', env = "VASILI_FORMAT")]
+    pub formats: Vec<OutputFormat>,
+
+    #[arg(long, env = "VASILI_OUTPUT_DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_SPLIT_OUTPUT")]
+    pub split_output: bool,
+
+    #[arg(long, value_name = "WHEN", env = "VASILI_ROTATE")]
+    pub rotate: Option<String>,
+
+    #[arg(long, value_name = "SIZE", env = "VASILI_ROTATE_SIZE")]
+    pub rotate_size: Option<String>,
+
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", env = "VASILI_COMPRESS")]
+    pub compress: Option<bool>,
+
+    #[arg(long, requires = "influx_bucket", env = "VASILI_INFLUX_URL")]
+    pub influx_url: Option<String>,
+
+    #[arg(long, requires = "influx_url", env = "VASILI_INFLUX_BUCKET")]
+    pub influx_bucket: Option<String>,
+
+    #[arg(long, requires = "influx_url", env = "VASILI_INFLUX_ORG")]
+    pub influx_org: Option<String>,
+
+    #[arg(long, requires = "influx_url", env = "VASILI_INFLUX_TOKEN", hide_env_values = true)]
+    pub influx_token: Option<String>,
+
+    #[arg(long, env = "VASILI_MQTT_URL", hide_env_values = true)]
+    pub mqtt_url: Option<String>,
+
+    #[arg(long, requires = "mqtt_url", env = "VASILI_MQTT_TOPIC")]
+    pub mqtt_topic: Option<String>,
+
+    #[arg(long, env = "VASILI_API")]
+    pub api: Option<SocketAddr>,
+
+    #[arg(long, requires = "api", env = "VASILI_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    #[arg(long, env = "VASILI_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[arg(long, env = "VASILI_PROFILE")]
+    pub profile: Option<String>,
+
+    #[arg(long, env = "VASILI_STATUS_FILE")]
+    pub status_file: Option<PathBuf>,
+
+    #[arg(long, env = "VASILI_HEARTBEAT_FILE")]
+    pub heartbeat_file: Option<PathBuf>,
+
+    #[arg(long, env = "VASILI_HEARTBEAT_URL", hide_env_values = true)]
+    pub heartbeat_url: Option<String>,
+
+    #[arg(long, env = "VASILI_HEARTBEAT_INTERVAL")]
+    pub heartbeat_interval: Option<String>,
+
+    #[arg(short = 'D', long, default_value_t = false, env = "VASILI_DAEMON")]
     pub daemon: bool,
+
+    #[arg(long, default_value_t = false)]
+    pub pool_rankings: bool,
+
+    #[arg(long, env = "VASILI_DIVERGENCE_AFTER")]
+    pub divergence_after: Option<String>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_NO_SUMMARY_SCREEN")]
+    pub no_summary_screen: bool,
+
+    #[arg(long, env = "VASILI_EXPORT_CHART")]
+    pub export_chart: Option<PathBuf>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_REDACT")]
+    pub redact: bool,
+
+    #[arg(long, env = "VASILI_MIN_SAMPLES")]
+    pub min_samples: Option<u64>,
+
+    #[arg(long, value_name = "DURATION", env = "VASILI_RETENTION")]
+    pub retention: Option<String>,
+
+    #[arg(long, value_name = "PROFILE", value_parser = ["general", "gaming", "voip"], ignore_case = true, env = "VASILI_GRADING")]
+    pub grading: Option<String>,
+
+    #[arg(long, value_name = "COMMAND", conflicts_with = "grading", env = "VASILI_GRADE_COMMAND")]
+    pub grade_command: Option<String>,
+
+    #[arg(long, value_parser = ["A", "B", "C", "F"], ignore_case = true, env = "VASILI_FAIL_ON_GRADE")]
+    pub fail_on_grade: Option<String>,
+
+    #[arg(long, env = "VASILI_FAIL_ON_LOSS")]
+    pub fail_on_loss: Option<String>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_HIGH_PRIORITY")]
+    pub high_priority: bool,
+
+    #[arg(long, env = "VASILI_BASELINE")]
+    pub baseline: Option<PathBuf>,
+
+    #[arg(long, value_name = "HH:MM", env = "VASILI_EVENT")]
+    pub event: Option<String>,
+
+    #[arg(long, env = "VASILI_TRACE_ON_LOSS")]
+    pub trace_on_loss: Option<u32>,
+
+    #[arg(long, num_args = 0..=1, env = "VASILI_DNS_CHECK")]
+    pub dns_check: Option<Option<IpAddr>>,
+
+    #[arg(long, num_args = 0..=1, env = "VASILI_PASSIVE_RTT")]
+    pub passive_rtt: Option<Option<u16>>,
+
+    #[arg(long, value_enum, env = "VASILI_ACCESS")]
+    pub access: Option<Access>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_NOTIFY")]
+    pub notify: bool,
+
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "all", env = "VASILI_BELL")]
+    pub bell: Option<BellMode>,
+
+    #[arg(long, env = "VASILI_WEBHOOK_URL", hide_env_values = true)]
+    pub webhook_url: Option<String>,
+
+    #[arg(long, env = "VASILI_DISCORD_WEBHOOK", hide_env_values = true)]
+    pub discord_webhook: Option<String>,
+
+    #[arg(long, env = "VASILI_ALERT_LOSS")]
+    pub alert_loss: Option<f64>,
+
+    #[arg(long, env = "VASILI_ALERT_WINDOW")]
+    pub alert_window: Option<String>,
+
+    #[arg(long, env = "VASILI_ALERT_TIMEOUTS")]
+    pub alert_timeouts: Option<u64>,
+
+    #[arg(long, env = "VASILI_ALERT_P95")]
+    pub alert_p95: Option<f64>,
+
+    #[arg(long, value_parser = ["S", "A", "B", "C"], ignore_case = true, env = "VASILI_ALERT_GRADE")]
+    pub alert_grade: Option<String>,
+
+    #[arg(long, default_value_t = false, env = "VASILI_GEO")]
+    pub geo: bool,
+
+    #[arg(long, env = "VASILI_LOCATION")]
+    pub location: Option<String>,
+}
+
+impl RunArgs {
+    pub fn profile_name(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    // A profile is a set of long options written as TOML keys. They are
+    // turned back into arguments and parsed again, so they go through the
+    // same validation as the command line. Options given on the command line
+    // or through a VASILI_* variable win over the profile.
+    pub fn with_profile(profile: &toml::Table) -> Result<Self> {
+        let argv = Cli::argv();
+        let cli_matches = Cli::command().get_matches_from(&argv);
+        let Some(matches) = cli_matches.subcommand_matches("run") else {
+            bail!("profiles only apply to 'vasili run'");
+        };
+
+        let mut profile_argv = Vec::new();
+        for (key, value) in profile {
+            let id = key.replace('-', "_");
+            if matches!(id.as_str(), "profile" | "config") {
+                bail!("'{}' cannot be set inside a profile", key);
+            }
+            if matches.try_contains_id(&id).is_ok()
+                && matches!(matches.value_source(&id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+            {
+                continue;
+            }
+
+            let flag = format!("--{}", key.replace('_', "-"));
+            let values = match value {
+                toml::Value::Array(items) => items.clone(),
+                value => vec![value.clone()],
+            };
+            for value in values {
+                match value {
+                    toml::Value::Boolean(true) => profile_argv.push(flag.clone()),
+                    toml::Value::Boolean(false) => {}
+                    toml::Value::String(s) => profile_argv.push(format!("{}={}", flag, s)),
+                    toml::Value::Integer(_) | toml::Value::Float(_) => profile_argv.push(format!("{}={}", flag, value)),
+                    _ => bail!("unsupported value for '{}'", key),
+                }
+            }
+        }
+
+        let argv = argv.iter().take(2).chain(&profile_argv).chain(argv.iter().skip(2));
+        // Only clap's first line applies here, its usage hints refer to the command line.
+        let matches = Cli::command().try_get_matches_from(argv).map_err(|e| {
+            anyhow!("{}", e.to_string().lines().next().unwrap_or_default().trim_start_matches("error: "))
+        })?;
+        match Cli::from_arg_matches(&matches)?.command {
+            Command::Run(args) => Ok(*args),
+            _ => unreachable!("profile arguments are only inserted after 'run'"),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TraceArgs {
+    pub target: String,
+
+    #[arg(short = '4', long, default_value_t = false, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    #[arg(short = '6', long, default_value_t = false)]
+    pub ipv6: bool,
+
+    #[arg(long, default_value_t = trace::DEFAULT_MAX_HOPS, value_parser = clap::value_parser!(u8).range(1..))]
+    pub max_hops: u8,
+
+    #[arg(short, long, default_value_t = trace::DEFAULT_QUERIES, value_parser = clap::value_parser!(u8).range(1..))]
+    pub queries: u8,
+
+    #[arg(long)]
+    pub timeout: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    pub record: bool,
+
+    #[arg(short, long = "format", value_enum, value_delimiter = ',')]
+    pub formats: Vec<OutputFormat>,
+
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+
+    #[arg(long, env = "VASILI_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ReportArgs {
+    pub file: PathBuf,
+
+    #[arg(long)]
+    pub chart: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeArgs {
+    pub file: PathBuf,
+
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ReplayArgs {
+    pub file: PathBuf,
+
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+
+    #[arg(long)]
+    pub from: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ValidateArgs {
+    pub file: PathBuf,
+
+    #[arg(long, default_value_t = false)]
+    pub print: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CompareArgs {
+    pub a: PathBuf,
+    pub b: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct EchoServerArgs {
+    #[arg(long, default_value = "0.0.0.0:7007", env = "VASILI_ECHO_LISTEN")]
+    pub listen: SocketAddr,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StatusArgs {
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TopArgs {
+    #[arg(long, value_name = "HOST:PORT", value_delimiter = ',', env = "VASILI_TOP_REMOTE")]
+    pub remote: Vec<String>,
+
+    #[arg(long, env = "VASILI_API_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    pub once: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -31,21 +449,141 @@ pub enum PingMode {
     Gaming,
     Standard,
     Monitor,
+    Path,
 }
 
-pub fn parse_duration_string(s: &str) -> Option<Duration> {
-    let digits: String = s.chars().take_while(|c| c.is_digit(10)).collect();
-    let unit: String = s.chars().skip(digits.len()).collect();
-    
-    if let Ok(val) = digits.parse::<u64>() {
-        match unit.trim() {
-            "ms" => Some(Duration::from_millis(val)),
-            "s" | "" => Some(Duration::from_secs(val)),
-            "m" => Some(Duration::from_secs(val * 60)),
-            "h" => Some(Duration::from_secs(val * 3600)),
-            _ => None,
-        }
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Protocol {
+    Icmp,
+    Tcp,
+    Udp,
+    Echo,
+    A2s,
+    Dns,
+}
+
+// Far beyond any sensible session, but still safe to add to an Instant.
+const MAX_DURATION: Duration = Duration::from_secs(10 * 365 * 24 * 3600);
+
+pub fn parse_duration_string(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let mut rest = s;
+    let mut total_ms = 0.0;
+    let mut parts = 0;
+
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let value: f64 = rest[..number_len].parse().map_err(|_| format!("expected a number at '{}'", rest))?;
+        rest = &rest[number_len..];
+
+        let unit_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        rest = rest[unit_len..].trim_start();
+
+        let factor = match unit {
+            "ms" => 1.0,
+            "s" => 1000.0,
+            "" if parts == 0 && rest.is_empty() => 1000.0,
+            "m" => 60_000.0,
+            "h" => 3_600_000.0,
+            "" => return Err("every number needs a unit (ms, s, m or h)".to_string()),
+            _ => return Err(format!("unknown unit '{}' (expected ms, s, m or h)", unit)),
+        };
+
+        total_ms += value * factor;
+        parts += 1;
+    }
+
+    if parts == 0 {
+        return Err("no duration given".to_string());
+    }
+    let duration = Duration::from_micros((total_ms * 1000.0).round().min(u64::MAX as f64) as u64);
+    if duration.is_zero() {
+        Err("must be longer than zero".to_string())
+    } else if duration > MAX_DURATION {
+        Err("must be at most 10 years".to_string())
     } else {
-        None
+        Ok(duration)
+    }
+}
+
+pub fn format_duration(d: Duration) -> String {
+    let total_ms = d.as_millis() as u64;
+    let (h, m, s, ms) = (total_ms / 3_600_000, total_ms / 60_000 % 60, total_ms / 1000 % 60, total_ms % 1000);
+
+    let mut parts = Vec::new();
+    if h > 0 {
+        parts.push(format!("{}h", h));
+    }
+    if m > 0 {
+        parts.push(format!("{}m", m));
     }
-}
\ No newline at end of file
+    if s > 0 {
+        parts.push(format!("{}s", s));
+    }
+    if ms > 0 || parts.is_empty() {
+        parts.push(format!("{}ms", ms));
+    }
+    parts.join(" ")
+}
+
+// "100MB", "2G", "512k" or plain bytes, in powers of 1024.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let number_len = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let value: f64 = s[..number_len].parse().ok()?;
+    let factor: u64 = match s[number_len..].trim().to_ascii_lowercase().trim_end_matches("ib").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        _ => return None,
+    };
+    Some((value * factor as f64).round() as u64).filter(|bytes| *bytes > 0)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 && b % (1 << 30) == 0 => format!("{}GB", b >> 30),
+        b if b >= 1 << 20 && b % (1 << 20) == 0 => format!("{}MB", b >> 20),
+        b if b >= 1 << 10 && b % (1 << 10) == 0 => format!("{}kB", b >> 10),
+        b => format!("{}B", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_accept_units_fractions_and_compounds() {
+        assert_eq!(parse_duration_string("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration_string("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration_string("45s"), Ok(Duration::from_secs(45)));
+        assert_eq!(parse_duration_string("90m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration_string("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration_string("2h15m30s"), Ok(Duration::from_secs(8130)));
+        assert_eq!(parse_duration_string(" 1h 30m "), Ok(Duration::from_secs(5400)));
+    }
+
+    #[test]
+    fn zero_durations_are_rejected() {
+        for zero in ["0", "0s", "0ms", "0h0m", "0.0001ms"] {
+            assert_eq!(parse_duration_string(zero), Err("must be longer than zero".to_string()), "{}", zero);
+        }
+    }
+
+    #[test]
+    fn huge_durations_are_rejected() {
+        assert!(parse_duration_string("87600h").is_ok());
+        assert!(parse_duration_string("87601h").is_err());
+        assert!(parse_duration_string(&format!("{}h", "9".repeat(400))).is_err());
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        for garbage in ["", "   ", "s", "-5s", "5x", "1.2.3s", "1h30", "ten seconds", "5s!"] {
+            assert!(parse_duration_string(garbage).is_err(), "{}", garbage);
+        }
+    }
+}
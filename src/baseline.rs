@@ -0,0 +1,157 @@
+use crate::app::HostStats;
+use crate::locale;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Fiber,
+    Cable,
+    Dsl,
+    Lte,
+    Satellite,
+}
+
+pub struct Baseline {
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub loss_percent: f64,
+}
+
+// Typical idle latency to a nearby server, rounded from public measurement
+// campaigns (FCC Measuring Broadband America, RIPE Atlas, Ookla reports).
+// Satellite means LEO constellations; geostationary links sit around 600ms.
+impl Access {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Access::Fiber => "fiber",
+            Access::Cable => "cable",
+            Access::Dsl => "DSL",
+            Access::Lte => "LTE",
+            Access::Satellite => "satellite",
+        }
+    }
+
+    pub fn baseline(&self) -> Baseline {
+        let (median_ms, p95_ms, loss_percent) = match self {
+            Access::Fiber => (10.0, 20.0, 0.1),
+            Access::Cable => (20.0, 45.0, 0.3),
+            Access::Dsl => (25.0, 50.0, 0.3),
+            Access::Lte => (45.0, 110.0, 1.0),
+            Access::Satellite => (45.0, 100.0, 1.5),
+        };
+        Baseline { median_ms, p95_ms, loss_percent }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Better,
+    Typical,
+    Worse,
+}
+
+impl Verdict {
+    fn of(value: f64, typical: f64) -> Self {
+        if value > typical * 1.2 {
+            Verdict::Worse
+        } else if value < typical * 0.8 {
+            Verdict::Better
+        } else {
+            Verdict::Typical
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verdict::Better => "better than",
+            Verdict::Typical => "typical for",
+            Verdict::Worse => "worse than typical",
+        }
+    }
+}
+
+pub struct Comparison {
+    pub access: Access,
+    pub metrics: Vec<(&'static str, String, Verdict)>,
+}
+
+impl Comparison {
+    pub fn of(stats: &HostStats, access: Access) -> Option<Self> {
+        if stats.total_count == stats.loss_count {
+            return None;
+        }
+
+        let baseline = access.baseline();
+        let loss = stats.loss_percent();
+        let loss_verdict = if loss == 0.0 {
+            Verdict::Better
+        } else if loss <= baseline.loss_percent {
+            Verdict::Typical
+        } else {
+            Verdict::of(loss, baseline.loss_percent)
+        };
+
+        Some(Self {
+            access,
+            metrics: vec![
+                ("median", locale::ms(stats.p50, 0), Verdict::of(stats.p50, baseline.median_ms)),
+                ("p95", locale::ms(stats.p95, 0), Verdict::of(stats.p95, baseline.p95_ms)),
+                ("loss", locale::percent(loss, 1), loss_verdict),
+            ],
+        })
+    }
+
+    pub fn overall(&self) -> Verdict {
+        let verdicts = self.metrics.iter().map(|(_, _, v)| *v);
+        if verdicts.clone().any(|v| v == Verdict::Worse) {
+            Verdict::Worse
+        } else if verdicts.clone().all(|v| v == Verdict::Better) {
+            Verdict::Better
+        } else {
+            Verdict::Typical
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let worse: Vec<String> = self
+            .metrics
+            .iter()
+            .filter(|(_, _, v)| *v == Verdict::Worse)
+            .map(|(name, value, _)| format!("your {} of {}", name, value))
+            .collect();
+
+        if worse.is_empty() {
+            format!("{} {} {}", self.summary_values(), self.overall().label(), self.access.label())
+        } else {
+            let verb = if worse.len() == 1 { "is" } else { "are" };
+            format!("{} {} worse than typical {}", worse.join(" and "), verb, self.access.label())
+        }
+    }
+
+    pub fn short(&self) -> String {
+        let worse: Vec<&str> = self.metrics.iter().filter(|(_, _, v)| *v == Verdict::Worse).map(|(name, _, _)| *name).collect();
+        match self.overall() {
+            Verdict::Worse => format!("{} worse", worse.join("/")),
+            Verdict::Better => "better".to_string(),
+            Verdict::Typical => "typical".to_string(),
+        }
+    }
+
+    pub fn details(&self) -> String {
+        let baseline = self.access.baseline();
+        format!(
+            "{} (typical {}: median {}, p95 {}, loss {})",
+            self.describe(),
+            self.access.label(),
+            locale::ms(baseline.median_ms, 0),
+            locale::ms(baseline.p95_ms, 0),
+            locale::percent(baseline.loss_percent, 1)
+        )
+    }
+
+    fn summary_values(&self) -> String {
+        self.metrics.iter().map(|(name, value, _)| format!("{} {}", name, value)).collect::<Vec<_>>().join(", ")
+    }
+}
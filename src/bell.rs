@@ -0,0 +1,54 @@
+use crate::pinger::SourceType;
+use clap::ValueEnum;
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+const SPIKE_MS: f64 = 100.0;
+const SPIKE_RUN: u32 = 3;
+const MIN_GAP: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BellMode {
+    Loss,
+    Spikes,
+    All,
+}
+
+pub struct Bell {
+    mode: BellMode,
+    spike_run: u32,
+    last_ring: Option<Instant>,
+}
+
+impl Bell {
+    pub fn new(mode: BellMode) -> Self {
+        Self { mode, spike_run: 0, last_ring: None }
+    }
+
+    // Timeouts ring for Target and Gateway. A single slow reply is noise, so
+    // a Target spike only rings once it lasts for SPIKE_RUN replies in a row.
+    pub fn observe(&mut self, source: &SourceType, latency: Option<f64>) {
+        let trigger = match (source, latency) {
+            (SourceType::Hop(_) | SourceType::Cycle(_) | SourceType::Passive, _) => false,
+            (_, None) => self.mode != BellMode::Spikes,
+            (SourceType::Gateway, Some(_)) => false,
+            (SourceType::Target, Some(ms)) if ms >= SPIKE_MS => {
+                self.spike_run += 1;
+                self.mode != BellMode::Loss && self.spike_run == SPIKE_RUN
+            }
+            (SourceType::Target, Some(_)) => {
+                self.spike_run = 0;
+                false
+            }
+        };
+
+        if trigger && self.last_ring.is_none_or(|t| t.elapsed() >= MIN_GAP) {
+            self.last_ring = Some(Instant::now());
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+// Just enough of the bpf(2) and perf_event_open(2) interfaces to load a small
+// hand-assembled program onto a kernel tracepoint and read the hash map it
+// fills. Programs are written with the helpers at the bottom.
+
+const BPF_MAP_CREATE: libc::c_long = 0;
+const BPF_MAP_LOOKUP_ELEM: libc::c_long = 1;
+const BPF_MAP_DELETE_ELEM: libc::c_long = 3;
+const BPF_MAP_GET_NEXT_KEY: libc::c_long = 4;
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_MAP_TYPE_HASH: u32 = 1;
+const BPF_PROG_TYPE_TRACEPOINT: u32 = 5;
+// union bpf_attr is larger, the kernel reads the fields it needs and wants
+// the rest zeroed.
+const ATTR_LEN: usize = 128;
+const LOG_LEN: usize = 64 * 1024;
+
+const PERF_TYPE_TRACEPOINT: u32 = 2;
+const PERF_ATTR_SIZE_VER0: u32 = 64;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_SET_BPF: libc::c_ulong = 0x4004_2408;
+
+const TRACEFS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+fn put_u32(attr: &mut [u8], at: usize, value: u32) {
+    attr[at..at + 4].copy_from_slice(&value.to_ne_bytes());
+}
+
+fn put_u64(attr: &mut [u8], at: usize, value: u64) {
+    attr[at..at + 8].copy_from_slice(&value.to_ne_bytes());
+}
+
+fn bpf(cmd: libc::c_long, attr: &mut [u8; ATTR_LEN]) -> io::Result<libc::c_long> {
+    let ret = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr.as_mut_ptr(), ATTR_LEN) };
+    if ret < 0 { Err(io::Error::last_os_error()) } else { Ok(ret) }
+}
+
+fn owned(fd: libc::c_long) -> OwnedFd {
+    unsafe { OwnedFd::from_raw_fd(fd as RawFd) }
+}
+
+pub struct HashMap {
+    fd: OwnedFd,
+    key_size: usize,
+    value_size: usize,
+}
+
+impl HashMap {
+    pub fn create(key_size: usize, value_size: usize, max_entries: u32) -> io::Result<Self> {
+        let mut attr = [0u8; ATTR_LEN];
+        put_u32(&mut attr, 0, BPF_MAP_TYPE_HASH);
+        put_u32(&mut attr, 4, key_size as u32);
+        put_u32(&mut attr, 8, value_size as u32);
+        put_u32(&mut attr, 12, max_entries);
+        let fd = bpf(BPF_MAP_CREATE, &mut attr)?;
+        Ok(Self { fd: owned(fd), key_size, value_size })
+    }
+
+    fn key_op(&self, cmd: libc::c_long, key: Option<&[u8]>, out: &mut [u8]) -> io::Result<()> {
+        let mut attr = [0u8; ATTR_LEN];
+        put_u32(&mut attr, 0, self.fd.as_raw_fd() as u32);
+        put_u64(&mut attr, 8, key.map_or(0, |k| k.as_ptr() as u64));
+        put_u64(&mut attr, 16, out.as_mut_ptr() as u64);
+        bpf(cmd, &mut attr).map(|_| ())
+    }
+
+    // Every key and value the map holds right now.
+    pub fn entries(&self) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        let mut key: Option<Vec<u8>> = None;
+        loop {
+            let mut next = vec![0u8; self.key_size];
+            match self.key_op(BPF_MAP_GET_NEXT_KEY, key.as_deref(), &mut next) {
+                Ok(()) => {}
+                Err(e) if e.raw_os_error() == Some(libc::ENOENT) => return Ok(entries),
+                Err(e) => return Err(e),
+            }
+            let mut value = vec![0u8; self.value_size];
+            // Deleted by the time it is read, it is just skipped.
+            if self.key_op(BPF_MAP_LOOKUP_ELEM, Some(&next), &mut value).is_ok() {
+                entries.push((next.clone(), value));
+            }
+            key = Some(next);
+        }
+    }
+
+    pub fn delete(&self, key: &[u8]) {
+        let mut attr = [0u8; ATTR_LEN];
+        put_u32(&mut attr, 0, self.fd.as_raw_fd() as u32);
+        put_u64(&mut attr, 8, key.as_ptr() as u64);
+        let _ = bpf(BPF_MAP_DELETE_ELEM, &mut attr);
+    }
+
+    pub fn raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+// A loaded program attached to a tracepoint. Dropping it detaches it.
+pub struct Attached {
+    _program: OwnedFd,
+    _event: OwnedFd,
+}
+
+fn tracepoint_dir(category: &str, name: &str) -> io::Result<std::path::PathBuf> {
+    TRACEFS
+        .iter()
+        .map(|root| Path::new(root).join("events").join(category).join(name))
+        .find(|dir| dir.join("id").exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("tracepoint {}:{} not found (is tracefs mounted?)", category, name)))
+}
+
+// The offset of a field in the tracepoint's record, from its format file, as
+// the layout differs between kernel versions.
+pub fn tracepoint_field(category: &str, name: &str, field: &str) -> io::Result<usize> {
+    let format = std::fs::read_to_string(tracepoint_dir(category, name)?.join("format"))?;
+    format
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("field:"))
+        .find_map(|line| {
+            let (declaration, rest) = line.split_once(';')?;
+            let declared = declaration.split_whitespace().last()?;
+            let declared = declared.split('[').next()?;
+            if declared != field {
+                return None;
+            }
+            rest.split(';').find_map(|part| part.trim().strip_prefix("offset:")?.parse().ok())
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("tracepoint {}:{} has no field {}", category, name, field)))
+}
+
+pub fn attach_tracepoint(category: &str, name: &str, program: &[Insn]) -> io::Result<Attached> {
+    let id: u64 = std::fs::read_to_string(tracepoint_dir(category, name)?.join("id"))?
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "unreadable tracepoint id"))?;
+
+    let license = c"GPL";
+    let mut log = vec![0u8; LOG_LEN];
+    let mut attr = [0u8; ATTR_LEN];
+    put_u32(&mut attr, 0, BPF_PROG_TYPE_TRACEPOINT);
+    put_u32(&mut attr, 4, program.len() as u32);
+    put_u64(&mut attr, 8, program.as_ptr() as u64);
+    put_u64(&mut attr, 16, license.as_ptr() as u64);
+    put_u32(&mut attr, 24, 1);
+    put_u32(&mut attr, 28, LOG_LEN as u32);
+    put_u64(&mut attr, 32, log.as_mut_ptr() as u64);
+    let program = match bpf(BPF_PROG_LOAD, &mut attr) {
+        Ok(fd) => owned(fd),
+        Err(e) => {
+            let end = log.iter().position(|b| *b == 0).unwrap_or(log.len());
+            let verifier = String::from_utf8_lossy(&log[..end]);
+            let last = verifier.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or_default();
+            return Err(if last.is_empty() { e } else { io::Error::new(e.kind(), format!("{} ({})", e, last)) });
+        }
+    };
+
+    let mut perf_attr = [0u8; PERF_ATTR_SIZE_VER0 as usize];
+    put_u32(&mut perf_attr, 0, PERF_TYPE_TRACEPOINT);
+    put_u32(&mut perf_attr, 4, PERF_ATTR_SIZE_VER0);
+    put_u64(&mut perf_attr, 8, id);
+    put_u64(&mut perf_attr, 16, 1);
+    // A tracepoint program runs on every CPU, whichever one the event is opened on.
+    let event = unsafe { libc::syscall(libc::SYS_perf_event_open, perf_attr.as_ptr(), -1, 0, -1, PERF_FLAG_FD_CLOEXEC) };
+    if event < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let event = owned(event);
+    for (request, arg) in [(PERF_EVENT_IOC_SET_BPF, program.as_raw_fd()), (PERF_EVENT_IOC_ENABLE, 0)] {
+        if unsafe { libc::ioctl(event.as_raw_fd(), request as _, arg) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(Attached { _program: program, _event: event })
+}
+
+// One instruction, struct bpf_insn.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Insn {
+    code: u8,
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+pub const R0: u8 = 0;
+pub const R1: u8 = 1;
+pub const R2: u8 = 2;
+pub const R3: u8 = 3;
+pub const R4: u8 = 4;
+pub const R6: u8 = 6;
+pub const FP: u8 = 10;
+
+pub const HELPER_MAP_UPDATE_ELEM: i32 = 2;
+pub const HELPER_KTIME_GET_NS: i32 = 5;
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+    let regs = if cfg!(target_endian = "little") { dst | src << 4 } else { dst << 4 | src };
+    Insn { code, regs, off, imm }
+}
+
+pub fn mov_reg(dst: u8, src: u8) -> Insn {
+    insn(0xbf, dst, src, 0, 0)
+}
+
+pub fn mov_imm(dst: u8, imm: i32) -> Insn {
+    insn(0xb7, dst, 0, 0, imm)
+}
+
+pub fn add_imm(dst: u8, imm: i32) -> Insn {
+    insn(0x07, dst, 0, 0, imm)
+}
+
+// Loads of 2, 4 and 8 bytes from `src + off`.
+pub fn load(size: usize, dst: u8, src: u8, off: i16) -> Insn {
+    let code = match size {
+        2 => 0x69,
+        4 => 0x61,
+        _ => 0x79,
+    };
+    insn(code, dst, src, off, 0)
+}
+
+// Stores of 4 and 8 bytes to `dst + off`.
+pub fn store(size: usize, dst: u8, off: i16, src: u8) -> Insn {
+    insn(if size == 4 { 0x63 } else { 0x7b }, dst, src, off, 0)
+}
+
+pub fn store_imm(dst: u8, off: i16, imm: i32) -> Insn {
+    insn(0x62, dst, 0, off, imm)
+}
+
+// The map's file descriptor, turned into a map pointer by the loader. Takes
+// two instruction slots.
+pub fn load_map(dst: u8, map: &HashMap) -> [Insn; 2] {
+    [insn(0x18, dst, 1, 0, map.raw_fd()), insn(0, 0, 0, 0, 0)]
+}
+
+pub fn call(helper: i32) -> Insn {
+    insn(0x85, 0, 0, 0, helper)
+}
+
+pub fn exit() -> Insn {
+    insn(0x95, 0, 0, 0, 0)
+}
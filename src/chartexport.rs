@@ -0,0 +1,237 @@
+use crate::analyze::HostSamples;
+use crate::app::{App, HostStats};
+use crate::args::format_duration;
+use crate::gifexport::rgb;
+use crate::locale;
+use crate::theme::Theme;
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 600;
+// Long sessions are reduced to this many columns, each keeping its highest
+// latency, so spikes survive and an SVG of a week stays small.
+const MAX_COLUMNS: usize = 2000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChartFormat {
+    Png,
+    Svg,
+}
+
+impl ChartFormat {
+    pub fn of(path: &Path) -> Result<ChartFormat> {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("png") => Ok(ChartFormat::Png),
+            Some("svg") => Ok(ChartFormat::Svg),
+            _ => bail!("{} must end in .png or .svg", path.display()),
+        }
+    }
+}
+
+struct Series {
+    label: String,
+    color: RGBColor,
+    loss_color: RGBColor,
+    points: Vec<(f64, f64)>,
+    losses: Vec<f64>,
+}
+
+fn color(color: ratatui::style::Color) -> RGBColor {
+    let [r, g, b] = rgb(color);
+    RGBColor(r, g, b)
+}
+
+fn reduce(points: Vec<(f64, f64)>, window: (f64, f64)) -> Vec<(f64, f64)> {
+    if points.len() <= MAX_COLUMNS {
+        return points;
+    }
+    let span = (window.1 - window.0).max(1.0);
+    let mut columns: Vec<(f64, f64)> = Vec::with_capacity(MAX_COLUMNS);
+    let mut last_column = usize::MAX;
+    for (t, ms) in points {
+        let column = ((t - window.0) / span * MAX_COLUMNS as f64) as usize;
+        match columns.last_mut() {
+            Some(peak) if column == last_column => {
+                if ms > peak.1 {
+                    *peak = (t, ms);
+                }
+            }
+            _ => {
+                columns.push((t, ms));
+                last_column = column;
+            }
+        }
+    }
+    columns
+}
+
+fn at(start: DateTime<Local>, secs: f64) -> DateTime<Local> {
+    start + chrono::Duration::milliseconds((secs * 1000.0) as i64)
+}
+
+fn live_series(label: &str, stats: &HostStats, colors: (RGBColor, RGBColor), window: (f64, f64)) -> Series {
+    let inside = |t: f64| t >= window.0 && t <= window.1;
+    let points = stats.points.range(window).iter().copied().filter(|(t, _)| inside(*t)).collect();
+    let losses = stats.loss_points.range(window).iter().map(|(t, _)| *t).filter(|t| inside(*t)).collect();
+    let label = format!(
+        "{} {} (loss {}, P99 {})",
+        label,
+        stats.display_name,
+        locale::percent(stats.loss_percent(), 1),
+        locale::host_ms(stats.p99, 0, stats.lan)
+    );
+    Series { label, color: colors.0, loss_color: colors.1, points: reduce(points, window), losses }
+}
+
+fn draw<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, title: &str, start: DateTime<Local>, window: (f64, f64), all: &[Series]) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let failed = |e: DrawingAreaErrorKind<DB::ErrorType>| anyhow!("Drawing the chart failed: {}", e);
+
+    let max = all.iter().flat_map(|s| s.points.iter().map(|(_, ms)| *ms)).fold(0.0, f64::max);
+    let y_limit = if max > 90.0 { max * 1.1 } else { 100.0 };
+    let window = (window.0, window.1.max(window.0 + 1.0));
+    // Loss bars are one pixel column wide whatever the zoom.
+    let bar = (window.1 - window.0) / WIDTH as f64;
+
+    root.fill(&WHITE).map_err(failed)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 24))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(window.0..window.1, 0.0..y_limit)
+        .map_err(failed)?;
+
+    let span = window.1 - window.0;
+    let format = if span > 86_400.0 { "%m-%d %H:%M" } else if span < 20.0 { "%H:%M:%S%.3f" } else { "%H:%M:%S" };
+    let time_label = |t: &f64| at(start, *t).format(format).to_string();
+    chart
+        .configure_mesh()
+        .x_labels(10)
+        .x_label_formatter(&time_label)
+        .y_desc("Latency (ms)")
+        .light_line_style(WHITE.mix(0.0))
+        .draw()
+        .map_err(failed)?;
+
+    for s in all {
+        let loss_style = s.loss_color.mix(0.35).filled();
+        chart
+            .draw_series(s.losses.iter().map(|t| Rectangle::new([(*t, 0.0), (*t + bar, y_limit)], loss_style)))
+            .map_err(failed)?;
+    }
+    for s in all {
+        let color = s.color;
+        chart
+            .draw_series(LineSeries::new(s.points.iter().copied(), color.stroke_width(1)))
+            .map_err(failed)?
+            .label(s.label.clone())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2)));
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.85))
+        .border_style(BLACK)
+        .draw()
+        .map_err(failed)?;
+
+    root.present().map_err(failed)?;
+    Ok(())
+}
+
+fn render(path: &Path, title: &str, start: DateTime<Local>, window: (f64, f64), all: &[Series]) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    match ChartFormat::of(path)? {
+        ChartFormat::Png => draw(BitMapBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(), title, start, window, all),
+        ChartFormat::Svg => draw(SVGBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area(), title, start, window, all),
+    }
+}
+
+fn live(app: &App, window: (f64, f64)) -> Vec<Series> {
+    let palette = app.theme.palette();
+    let mut all = Vec::new();
+    if let Some(gw) = app.gw_stats.as_ref().filter(|_| app.show_gateway) {
+        all.push(live_series("Gateway", gw, (color(palette.gateway), color(palette.gateway_loss)), window));
+    }
+    all.push(live_series("Target", &app.net_stats, (color(palette.target), color(palette.target_loss)), window));
+    all
+}
+
+// The visible chart window (current zoom and history position), like the
+// GIF export.
+pub fn export(app: &App, directory: &Path, format: ChartFormat) -> Result<PathBuf> {
+    let end = (app.recorded_duration - app.scroll_offset_seconds).max(0.0);
+    let window = ((end - app.zoom_window_seconds).max(0.0), end);
+    let extension = if format == ChartFormat::Png { "png" } else { "svg" };
+    let path = directory.join(format!("vasili_{}_chart.{}", Local::now().format("%Y-%m-%d_%H-%M-%S"), extension));
+
+    let title = format!("VASILI {} - {}", app.net_stats.display_name, at(app.start_time, window.0).format("%Y-%m-%d %H:%M:%S"));
+    render(&path, &title, app.start_time, window, &live(app, window))?;
+    Ok(path)
+}
+
+// The whole session, written at its end for --export-chart.
+pub fn export_session(app: &App, path: &Path) -> Result<()> {
+    let window = (0.0, app.recorded_duration);
+    let title = format!(
+        "VASILI {} - {} ({})",
+        app.net_stats.display_name,
+        app.start_time.format("%Y-%m-%d %H:%M:%S"),
+        format_duration(Duration::from_secs(app.recorded_duration as u64))
+    );
+    render(path, &title, app.start_time, window, &live(app, window))
+}
+
+// Target and gateway of a recorded session, for `vasili report --chart`.
+pub fn export_recorded(hosts: &[HostSamples], path: &Path) -> Result<()> {
+    let start = hosts.iter().filter_map(|(_, samples)| samples.first().map(|(t, _, time)| at(*time, -*t))).min();
+    let Some(start) = start else {
+        bail!("The log contains no samples");
+    };
+    let end = hosts.iter().filter_map(|(_, samples)| samples.last().map(|(t, _, _)| *t)).fold(0.0, f64::max);
+    let window = (0.0, end);
+
+    let palette = Theme::default().palette();
+    let mut all = Vec::new();
+    let mut target = None;
+    for kind in ["Gateway", "Target"] {
+        let Some(((target_type, host), samples)) = hosts.iter().find(|((t, _), _)| t == kind) else {
+            continue;
+        };
+        let colors = if kind == "Target" {
+            target = Some(host.clone());
+            (color(palette.target), color(palette.target_loss))
+        } else {
+            (color(palette.gateway), color(palette.gateway_loss))
+        };
+        let points = samples.iter().filter_map(|(t, ms, _)| ms.map(|ms| (*t, ms))).collect();
+        let losses = samples.iter().filter(|(_, ms, _)| ms.is_none()).map(|(t, _, _)| *t).collect();
+        let lost = samples.iter().filter(|(_, ms, _)| ms.is_none()).count();
+        let label = format!("{} {} (loss {:.1}%)", target_type, host, lost as f64 * 100.0 / samples.len().max(1) as f64);
+        all.push(Series { label, color: colors.0, loss_color: colors.1, points: reduce(points, window), losses });
+    }
+    let Some(target) = target else {
+        bail!("The log contains no target samples");
+    };
+
+    let title = format!(
+        "VASILI {} - {} ({})",
+        target,
+        start.format("%Y-%m-%d %H:%M:%S"),
+        format_duration(Duration::from_secs(end as u64))
+    );
+    render(path, &title, start, window, &all)
+}
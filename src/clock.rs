@@ -0,0 +1,118 @@
+use chrono::{DateTime, Local};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Everything in App that depends on time reads it from here, so a session
+// can be replayed with a clock that only moves when told to.
+pub trait Clock: Send + Sync {
+    // Wall-clock time, for timestamps and the labels shown.
+    fn now(&self) -> DateTime<Local>;
+    // A steady count since some fixed point, for the time axis of the
+    // session. Only differences between two readings mean anything.
+    fn elapsed(&self) -> Duration;
+}
+
+// Reads the system clock for timestamps, but a monotonic one for the time
+// axis, so an NTP step or a manual clock change does not tear the chart.
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { started: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+// Stands still until it is set, replays move it along the recorded timestamps.
+pub struct ManualClock(Mutex<DateTime<Local>>);
+
+impl ManualClock {
+    pub fn new(at: DateTime<Local>) -> Self {
+        ManualClock(Mutex::new(at))
+    }
+
+    pub fn set(&self, at: DateTime<Local>) {
+        *self.0.lock().unwrap() = at;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.0.lock().unwrap()
+    }
+
+    // The recorded timestamps are all a replay has.
+    fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.now().timestamp_millis().max(0) as u64)
+    }
+}
+
+// When the probes are due, on the grid start + n * period of Clock::elapsed,
+// so the schedule does not drift however long a probe took. Ticks missed
+// while a probe was still waiting are skipped instead of fired in a burst,
+// which would crowd several probes into a few milliseconds.
+pub struct Schedule {
+    next: Duration,
+    period: Duration,
+}
+
+impl Schedule {
+    pub fn new(start: Duration, period: Duration) -> Self {
+        Schedule { next: start, period }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    // How long from `now` until the next tick, zero once it is due.
+    pub fn wait(&self, now: Duration) -> Duration {
+        self.next.saturating_sub(now)
+    }
+
+    // Takes the tick due at `now` and moves on to the first one after it.
+    pub fn advance(&mut self, now: Duration) {
+        let missed = now.saturating_sub(self.next).as_nanos() / self.period.as_nanos().max(1);
+        self.next += self.period * (missed as u32 + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn ticks_stay_on_the_grid() {
+        let mut schedule = Schedule::new(SECOND, SECOND);
+        assert_eq!(schedule.wait(Duration::ZERO), SECOND);
+        assert_eq!(schedule.wait(SECOND), Duration::ZERO);
+
+        // Taken a little late, the next tick is still due at 2s.
+        schedule.advance(Duration::from_millis(1300));
+        assert_eq!(schedule.wait(Duration::from_millis(1300)), Duration::from_millis(700));
+    }
+
+    #[test]
+    fn missed_ticks_are_skipped() {
+        let mut schedule = Schedule::new(SECOND, SECOND);
+        // A probe held the loop up until 3.5s: the ticks of 2s and 3s are
+        // gone, the next one is due at 4s rather than at once.
+        schedule.advance(Duration::from_millis(3500));
+        assert_eq!(schedule.wait(Duration::from_millis(3500)), Duration::from_millis(500));
+        schedule.advance(Duration::from_secs(4));
+        assert_eq!(schedule.wait(Duration::from_secs(4)), SECOND);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::analyze::{self, HostAnalysis, HostSamples};
+use crate::args::{CompareArgs, format_duration};
+use crate::locale;
+use anyhow::{Result, bail};
+use std::time::Duration;
+
+const CHART_WIDTH: usize = 60;
+const CHART_HEIGHT: usize = 12;
+// Differences below these are what two runs over the same connection show
+// anyway, they do not make one run better.
+const LOSS_MARGIN: f64 = 0.2;
+const LATENCY_MARGIN: f64 = 0.1;
+
+fn duration(hosts: &[HostSamples]) -> String {
+    let secs = hosts.iter().filter_map(|(_, s)| s.last().map(|(t, _, _)| *t)).fold(0.0, f64::max);
+    format_duration(Duration::from_secs(secs as u64))
+}
+
+// Target and gateway are compared whatever their address was (the point is
+// often the same target over another link), the other hosts by address.
+fn counterpart<'a>(key: &(String, String), hosts: &'a [HostSamples]) -> Option<&'a HostSamples> {
+    match key.0.as_str() {
+        "Target" | "Gateway" => hosts.iter().find(|(k, _)| k.0 == key.0),
+        _ => hosts.iter().find(|(k, _)| k == key),
+    }
+}
+
+fn row(label: &str, a: String, b: String, delta: String) {
+    println!("  {:<15} {:>12} {:>12} {:>12}", label, a, b, delta);
+}
+
+// The sign of a difference, like {:+}.
+fn signed(value: f64, text: String) -> String {
+    if value >= 0.0 { format!("+{}", text) } else { text }
+}
+
+fn latency_row(label: &str, a: f64, b: f64) {
+    row(label, locale::ms(a, 1), locale::ms(b, 1), signed(b - a, locale::ms(b - a, 1)));
+}
+
+fn print_table(a: &HostAnalysis, b: &HostAnalysis) {
+    println!();
+    println!("{} {} vs {}", a.target_type, a.host, b.host);
+    row("", "A".to_string(), "B".to_string(), "B - A".to_string());
+    row("Samples", a.samples.to_string(), b.samples.to_string(), String::new());
+    row(
+        "Loss",
+        locale::percent(a.loss_percent, 2),
+        locale::percent(b.loss_percent, 2),
+        signed(b.loss_percent - a.loss_percent, locale::percent(b.loss_percent - a.loss_percent, 2)),
+    );
+    latency_row("Min", a.min, b.min);
+    latency_row("P25", a.p25, b.p25);
+    latency_row("P50", a.p50, b.p50);
+    latency_row("P95", a.p95, b.p95);
+    latency_row("P99", a.p99, b.p99);
+    latency_row("Max", a.max, b.max);
+    row(
+        "Spikes >100ms",
+        a.spikes_major.to_string(),
+        b.spikes_major.to_string(),
+        format!("{:+}", b.spikes_major as i64 - a.spikes_major as i64),
+    );
+    let outage = |h: &HostAnalysis| h.longest_outage.as_ref().map(|o| format!("{} lost", o.lost)).unwrap_or("-".to_string());
+    row("Longest outage", outage(a), outage(b), String::new());
+    row("Grade", a.grade_label(), b.grade_label(), String::new());
+}
+
+// Loss decides first, like in the grade, then the tail and the median.
+fn verdict(a: &HostAnalysis, b: &HostAnalysis) -> String {
+    let relative = |a: f64, b: f64| (b - a) / a.max(1.0);
+    let mut findings: Vec<(f64, String)> = Vec::new();
+
+    let loss = b.loss_percent - a.loss_percent;
+    if loss.abs() > LOSS_MARGIN {
+        findings.push((loss, format!("{} less loss", locale::percent(loss.abs(), 2))));
+    }
+    for (label, a, b) in [("P99", a.p99, b.p99), ("P50", a.p50, b.p50)] {
+        if relative(a, b).abs() > LATENCY_MARGIN {
+            findings.push((b - a, format!("{} {} lower", label, locale::ms((b - a).abs(), 1))));
+        }
+    }
+
+    let Some(&(first, _)) = findings.first() else {
+        return "no meaningful difference".to_string();
+    };
+    let (winner, loser) = if first > 0.0 { ("A", "B") } else { ("B", "A") };
+    let pro: Vec<&str> = findings.iter().filter(|(d, _)| d.signum() == first.signum()).map(|(_, m)| m.as_str()).collect();
+    let con: Vec<&str> = findings.iter().filter(|(d, _)| d.signum() != first.signum()).map(|(_, m)| m.as_str()).collect();
+
+    let mut text = format!("{} was better ({})", winner, pro.join(", "));
+    if !con.is_empty() {
+        text.push_str(&format!(", although {} had {}", loser, con.join(", ")));
+    }
+    text
+}
+
+// Both latency distributions on one axis: every column is a percentile, so
+// sessions of different length line up.
+fn print_chart(a: &HostSamples, b: &HostSamples) {
+    let sorted = |samples: &[analyze::Sample]| {
+        let mut latencies: Vec<f64> = samples.iter().filter_map(|(_, l, _)| *l).collect();
+        latencies.sort_by(|x, y| x.total_cmp(y));
+        latencies
+    };
+    let (a, b) = (sorted(&a.1), sorted(&b.1));
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+
+    let at = |latencies: &[f64], column: usize| {
+        let p = column as f64 / (CHART_WIDTH - 1) as f64 * 0.99;
+        latencies[(p * (latencies.len() - 1) as f64).round() as usize]
+    };
+    let top = (at(&a, CHART_WIDTH - 1).max(at(&b, CHART_WIDTH - 1)) * 1.1).max(1.0);
+    let row_of = |value: f64| (((value / top) * (CHART_HEIGHT - 1) as f64).round() as usize).min(CHART_HEIGHT - 1);
+
+    let columns: Vec<(usize, usize)> = (0..CHART_WIDTH).map(|c| (row_of(at(&a, c)), row_of(at(&b, c)))).collect();
+
+    println!();
+    println!("Latency by percentile (a = A, b = B, # = both):");
+    for i in (0..CHART_HEIGHT).rev() {
+        let line: String = columns
+            .iter()
+            .map(|&(ra, rb)| match (ra == i, rb == i) {
+                (true, true) => '#',
+                (true, false) => 'a',
+                (false, true) => 'b',
+                (false, false) => ' ',
+            })
+            .collect();
+        let label = if i % 3 == 0 || i == CHART_HEIGHT - 1 {
+            format!("{:>9}", locale::ms(top * i as f64 / (CHART_HEIGHT - 1) as f64, 1))
+        } else {
+            String::new()
+        };
+        println!("{:>9} |{}", label, line);
+    }
+    println!("{:>9} +{}", "", "-".repeat(CHART_WIDTH));
+    println!("{:>9}  P0{:^w$}P99", "", "P50", w = CHART_WIDTH - 5);
+}
+
+pub fn run(args: CompareArgs) -> Result<()> {
+    let a = analyze::load_hosts(&args.a)?;
+    let b = analyze::load_hosts(&args.b)?;
+
+    println!("A: {} ({})", args.a.display(), duration(&a));
+    println!("B: {} ({})", args.b.display(), duration(&b));
+
+    let pairs: Vec<(&HostSamples, &HostSamples)> =
+        a.iter().filter_map(|host| counterpart(&host.0, &b).map(|other| (host, other))).collect();
+    if pairs.is_empty() {
+        bail!("The sessions have no host in common");
+    }
+
+    let mut verdicts = Vec::new();
+    for (host_a, host_b) in &pairs {
+        let analysis_a = analyze::analyze_host(&host_a.0.0, &host_a.0.1, &host_a.1);
+        let analysis_b = analyze::analyze_host(&host_b.0.0, &host_b.0.1, &host_b.1);
+        print_table(&analysis_a, &analysis_b);
+        verdicts.push((analysis_a.target_type.clone(), verdict(&analysis_a, &analysis_b)));
+    }
+
+    let (chart_a, chart_b) = pairs[0];
+    print_chart(chart_a, chart_b);
+
+    println!();
+    for (target_type, verdict) in verdicts {
+        println!("Verdict {}: {}", target_type, verdict);
+    }
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use crate::app::SessionSummary;
+use crate::args::{PingMode, parse_size};
+use crate::baseline::Access;
+use crate::locale::{self, PanelUnits, Unit, Units};
+use crate::sinks::{OutputFormat, Rotation};
+use crate::theme::Theme;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const RELOAD_POLL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LayoutMode {
+    #[default]
+    Chart,
+    Stacked,
+    Dashboard,
+}
+
+impl LayoutMode {
+    pub fn next(self) -> Self {
+        match self {
+            LayoutMode::Chart => LayoutMode::Stacked,
+            LayoutMode::Stacked => LayoutMode::Dashboard,
+            LayoutMode::Dashboard => LayoutMode::Chart,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    pub zoom_window_seconds: Option<f64>,
+    pub show_jitter: bool,
+    pub show_loss: bool,
+    pub show_gateway: bool,
+    pub show_events: bool,
+    pub show_log: bool,
+    pub stats_window: bool,
+    pub layout: LayoutMode,
+    pub theme: Theme,
+}
+
+impl Default for UiState {
+    fn default() -> Self {
+        Self {
+            zoom_window_seconds: None,
+            show_jitter: true,
+            show_loss: true,
+            show_gateway: true,
+            show_events: false,
+            show_log: false,
+            stats_window: false,
+            layout: LayoutMode::default(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub access: Option<Access>,
+    pub theme: Option<Theme>,
+    pub units: UnitsConfig,
+    pub alerts: AlertsConfig,
+    pub output: OutputConfig,
+    pub modes: ModesConfig,
+    pub targets: HashMap<String, TargetConfig>,
+    pub profile: HashMap<String, toml::Table>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TargetConfig {
+    pub note: Option<String>,
+    pub expected_ms: Option<f64>,
+}
+
+// [units] sets the unit and the decimals of every latency shown, the
+// [units.chart], [units.stats] and [units.table] tables those of one TUI
+// panel. --units and --precision win over all of them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UnitsConfig {
+    pub unit: Option<Unit>,
+    pub precision: Option<usize>,
+    pub chart: PanelUnitsConfig,
+    pub stats: PanelUnitsConfig,
+    pub table: PanelUnitsConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PanelUnitsConfig {
+    pub unit: Option<Unit>,
+    pub precision: Option<usize>,
+}
+
+impl UnitsConfig {
+    fn resolve(&self, panel: &PanelUnitsConfig, unit: Option<Unit>, precision: Option<usize>) -> Units {
+        Units {
+            unit: unit.or(panel.unit).or(self.unit).unwrap_or_default(),
+            precision: precision.or(panel.precision).or(self.precision).map(|n| n.min(2)),
+        }
+    }
+
+    pub fn global(&self, unit: Option<Unit>, precision: Option<usize>) -> Units {
+        self.resolve(&PanelUnitsConfig::default(), unit, precision)
+    }
+
+    pub fn panels(&self, unit: Option<Unit>, precision: Option<usize>) -> PanelUnits {
+        PanelUnits {
+            chart: self.resolve(&self.chart, unit, precision),
+            stats: self.resolve(&self.stats, unit, precision),
+            table: self.resolve(&self.table, unit, precision),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub webhook: Option<String>,
+    pub discord: Option<String>,
+    pub loss_percent: Option<f64>,
+    pub window: Option<String>,
+    pub consecutive_timeouts: Option<u64>,
+    pub p95_ms: Option<f64>,
+    pub grade_below: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub formats: Vec<OutputFormat>,
+    pub directory: Option<PathBuf>,
+    pub split: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModesConfig {
+    pub gaming: ModeConfig,
+    pub standard: ModeConfig,
+    pub monitor: ModeConfig,
+    pub path: ModeConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModeConfig {
+    pub record: Option<bool>,
+    pub rotate: Option<String>,
+    pub rotate_size: Option<String>,
+    pub compress: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModeOutput {
+    pub record: bool,
+    pub rotate: Option<Rotation>,
+    pub rotate_size: Option<u64>,
+    pub compress: bool,
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("vasili"))
+}
+
+pub fn config_path(explicit: Option<&Path>) -> Option<PathBuf> {
+    explicit.map(Path::to_path_buf).or_else(|| config_dir().map(|dir| dir.join("config.toml")))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Polls the modification time, which also catches editors that save by
+// replacing the file.
+pub fn watch(path: PathBuf) -> mpsc::Receiver<()> {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut last = modified(&path);
+        let mut timer = tokio::time::interval(RELOAD_POLL);
+        loop {
+            timer.tick().await;
+            let current = modified(&path);
+            if current != last {
+                last = current;
+                if current.is_some() && tx.send(()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    rx
+}
+
+impl Config {
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let required = path.is_some();
+        let Some(path) = config_path(path) else {
+            return Ok(Self::default());
+        };
+
+        if !required && !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    // Entries may be keyed by the name given on the command line or by the
+    // address it resolved to.
+    pub fn target(&self, host: &str, ip: IpAddr) -> Option<&TargetConfig> {
+        self.targets.get(host).or_else(|| self.targets.get(&ip.to_string()))
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&toml::Table> {
+        self.profile.get(name).ok_or_else(|| {
+            let mut known: Vec<&str> = self.profile.keys().map(String::as_str).collect();
+            known.sort();
+            if known.is_empty() {
+                anyhow!("Unknown profile '{}' (the config file defines no [profile.*] sections)", name)
+            } else {
+                anyhow!("Unknown profile '{}' (available: {})", name, known.join(", "))
+            }
+        })
+    }
+
+    pub fn mode_output(&self, mode: PingMode) -> ModeOutput {
+        let (defaults, overrides) = match mode {
+            PingMode::Gaming => (ModeOutput { record: false, rotate: None, rotate_size: None, compress: false }, &self.modes.gaming),
+            PingMode::Standard => (ModeOutput { record: true, rotate: None, rotate_size: None, compress: false }, &self.modes.standard),
+            PingMode::Monitor => (
+                ModeOutput { record: true, rotate: Some(Rotation::Daily), rotate_size: None, compress: true },
+                &self.modes.monitor,
+            ),
+            PingMode::Path => (ModeOutput { record: true, rotate: None, rotate_size: None, compress: false }, &self.modes.path),
+        };
+
+        ModeOutput {
+            record: overrides.record.unwrap_or(defaults.record),
+            rotate: match overrides.rotate.as_deref() {
+                Some("off") => None,
+                Some(r) => Rotation::parse(r),
+                None => defaults.rotate,
+            },
+            rotate_size: match overrides.rotate_size.as_deref() {
+                Some("off") => None,
+                Some(size) => parse_size(size),
+                None => defaults.rotate_size,
+            },
+            compress: overrides.compress.unwrap_or(defaults.compress),
+        }
+    }
+}
+
+fn ui_state_path(profile: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("state").join(format!("{}.json", profile)))
+}
+
+impl UiState {
+    pub fn load(profile: &str) -> Option<Self> {
+        let path = ui_state_path(profile)?;
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, profile: &str) -> Result<()> {
+        let Some(path) = ui_state_path(profile) else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastRun {
+    pub ended_at: DateTime<Local>,
+    pub summary: SessionSummary,
+}
+
+fn last_run_path(profile: &str, target: &str) -> Option<PathBuf> {
+    let safe_target = target.replace([':', '/', '\\'], "_");
+    config_dir().map(|dir| dir.join("history").join(profile).join(format!("{}.json", safe_target)))
+}
+
+impl LastRun {
+    pub fn load(profile: &str, target: &str) -> Option<Self> {
+        let path = last_run_path(profile, target)?;
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(profile: &str, summary: &SessionSummary) -> Result<()> {
+        if summary.samples == 0 {
+            return Ok(());
+        }
+        let Some(path) = last_run_path(profile, &summary.target_ip) else {
+            return Ok(());
+        };
+
+        let last_run = LastRun { ended_at: Local::now(), summary: summary.clone() };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&last_run)?)?;
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let today = Local::now().date_naive();
+        let day = match (today - self.ended_at.date_naive()).num_days() {
+            0 => "today".to_string(),
+            1 => "yesterday".to_string(),
+            _ => self.ended_at.format("%Y-%m-%d").to_string(),
+        };
+
+        format!(
+            "P99 {} / {} loss / grade {} ({} {})",
+            locale::ms(self.summary.p99, 0),
+            locale::percent(self.summary.loss_percent, 1),
+            self.summary.grade_label(),
+            day,
+            self.ended_at.format("%H:%M")
+        )
+    }
+}
@@ -0,0 +1,237 @@
+use crate::events::{Event, EventKind};
+use crate::pinger::SourceType;
+
+const TARGET_DEGRADED_MS: f64 = 100.0;
+const GATEWAY_DEGRADED_MS: f64 = 50.0;
+const RECOVERY_SAMPLES: u32 = 3;
+
+#[derive(Default)]
+struct SeriesHealth {
+    degraded_since: Option<f64>,
+    good_streak: u32,
+}
+
+impl SeriesHealth {
+    fn observe(&mut self, bad: bool, time_val: f64) {
+        if bad {
+            self.good_streak = 0;
+            if self.degraded_since.is_none() {
+                self.degraded_since = Some(time_val);
+            }
+        } else if self.degraded_since.is_some() {
+            self.good_streak += 1;
+            if self.good_streak >= RECOVERY_SAMPLES {
+                self.degraded_since = None;
+                self.good_streak = 0;
+            }
+        }
+    }
+}
+
+pub struct DivergenceDetector {
+    hold_secs: f64,
+    has_gateway: bool,
+    target: SeriesHealth,
+    gateway: SeriesHealth,
+    // The kind reported for the ongoing degradation and since when it lasts.
+    reported: Option<(EventKind, f64)>,
+}
+
+impl DivergenceDetector {
+    pub fn new(hold_secs: f64, has_gateway: bool) -> Self {
+        Self {
+            hold_secs,
+            has_gateway,
+            target: SeriesHealth::default(),
+            gateway: SeriesHealth::default(),
+            reported: None,
+        }
+    }
+
+    // The gateway is probed from now on, e.g. after taking it over from
+    // another session.
+    pub fn enable_gateway(&mut self) {
+        self.has_gateway = true;
+    }
+
+    pub fn observe(
+        &mut self,
+        source: &SourceType,
+        latency: Option<f64>,
+        time_val: f64,
+    ) -> Option<Event> {
+        match source {
+            SourceType::Target => {
+                let bad = latency.is_none_or(|ms| ms >= TARGET_DEGRADED_MS);
+                self.target.observe(bad, time_val);
+            }
+            SourceType::Gateway => {
+                let bad = latency.is_none_or(|ms| ms >= GATEWAY_DEGRADED_MS);
+                self.gateway.observe(bad, time_val);
+                // Classified with the next Target sample, which tells
+                // whether the target is still degraded at that time.
+                return None;
+            }
+            SourceType::Hop(_) | SourceType::Cycle(_) | SourceType::Passive => return None,
+        }
+
+        // Without a gateway there is nothing to tell an ISP problem from a
+        // local one by; the alert rules cover a target on its own.
+        if !self.has_gateway {
+            return None;
+        }
+
+        let current = self.target.degraded_since.map(|t_since| match self.gateway.degraded_since {
+            Some(g_since) => (EventKind::LocalDegraded, t_since.max(g_since)),
+            None => (EventKind::InternetDegraded, t_since),
+        });
+
+        match (current, self.reported) {
+            (None, Some((_, since))) => {
+                self.reported = None;
+                Some(Event::new(
+                    EventKind::Recovered,
+                    time_val,
+                    format!("Internet recovered after {:.0}s", time_val - since),
+                ))
+            }
+            // Good replies while it recovers do not count towards the hold.
+            (Some(_), _) if self.target.good_streak > 0 => None,
+            (Some((kind, since)), reported) => {
+                let held_for = time_val - since;
+                let already_reported = reported.is_some_and(|(r, _)| r == kind);
+
+                if held_for < self.hold_secs || already_reported {
+                    return None;
+                }
+
+                let first_since = reported.map_or(since, |(_, s)| s);
+                self.reported = Some((kind, first_since));
+
+                let message = match kind {
+                    EventKind::InternetDegraded => format!(
+                        "Gateway fine, internet degraded for {:.0}s - likely ISP / upstream route",
+                        held_for
+                    ),
+                    _ => format!(
+                        "Gateway and internet both degraded for {:.0}s - likely local network / router",
+                        held_for
+                    ),
+                };
+
+                Some(Event::new(kind, time_val, message))
+            }
+            (None, None) => None,
+        }
+    }
+
+    pub fn target_healthy(&self) -> bool {
+        self.target.degraded_since.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: f64 = 3.0;
+
+    // One Target and one Gateway sample per second, from second 1 on.
+    fn play(detector: &mut DivergenceDetector, samples: &[(Option<f64>, Option<f64>)]) -> Vec<(EventKind, f64)> {
+        let mut events = Vec::new();
+        for (i, (target, gateway)) in samples.iter().enumerate() {
+            let time_val = (i + 1) as f64;
+            for (source, latency) in [(SourceType::Gateway, *gateway), (SourceType::Target, *target)] {
+                events.extend(detector.observe(&source, latency, time_val).map(|e| (e.kind, e.time_val)));
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn gateway_fine_internet_degraded() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        let mut samples = vec![(Some(20.0), Some(1.0)); 2];
+        samples.extend([(None, Some(1.0)); 5]);
+        samples.extend([(Some(20.0), Some(1.0)); 3]);
+
+        // Degraded from 3s, reported once it held 3s, recovered after three good samples.
+        assert_eq!(play(&mut detector, &samples), [(EventKind::InternetDegraded, 6.0), (EventKind::Recovered, 10.0)]);
+    }
+
+    #[test]
+    fn both_degraded_is_local() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        let samples = [(Some(150.0), Some(60.0)); 5];
+        assert_eq!(play(&mut detector, &samples), [(EventKind::LocalDegraded, 4.0)]);
+    }
+
+    #[test]
+    fn thresholds_are_inclusive() {
+        let just_below = [(Some(99.9), Some(49.9)); 10];
+        assert!(play(&mut DivergenceDetector::new(HOLD, true), &just_below).is_empty());
+
+        let at_target = [(Some(100.0), Some(49.9)); 5];
+        assert_eq!(play(&mut DivergenceDetector::new(HOLD, true), &at_target), [(EventKind::InternetDegraded, 4.0)]);
+        let at_both = [(Some(100.0), Some(50.0)); 5];
+        assert_eq!(play(&mut DivergenceDetector::new(HOLD, true), &at_both), [(EventKind::LocalDegraded, 4.0)]);
+    }
+
+    #[test]
+    fn shorter_than_the_hold_is_not_reported() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        let mut samples = vec![(None, Some(1.0)); 3];
+        samples.extend([(Some(20.0), Some(1.0)); 5]);
+        assert!(play(&mut detector, &samples).is_empty());
+    }
+
+    // Two good samples do not end a degradation, so a flapping target is
+    // one event rather than one per flap.
+    #[test]
+    fn recovery_needs_three_good_samples_in_a_row() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        let mut samples = vec![(None, Some(1.0)); 4];
+        for _ in 0..3 {
+            samples.extend([(Some(20.0), Some(1.0)), (Some(20.0), Some(1.0)), (None, Some(1.0))]);
+        }
+        samples.extend([(Some(20.0), Some(1.0)); 3]);
+
+        let events = play(&mut detector, &samples);
+        assert_eq!(events, [(EventKind::InternetDegraded, 4.0), (EventKind::Recovered, 16.0)]);
+    }
+
+    #[test]
+    fn a_gateway_degrading_later_is_reported_as_local_once() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        let mut samples = vec![(None, Some(1.0)); 4];
+        samples.extend([(None, None); 6]);
+
+        // The gateway went down at 5s and held the hold from then on.
+        assert_eq!(play(&mut detector, &samples), [(EventKind::InternetDegraded, 4.0), (EventKind::LocalDegraded, 8.0)]);
+    }
+
+    #[test]
+    fn without_a_gateway_nothing_is_classified() {
+        let mut detector = DivergenceDetector::new(HOLD, false);
+        assert!(play(&mut detector, &[(None, Some(1.0)); 6]).is_empty());
+        assert!(!detector.target_healthy());
+
+        // Once the gateway is probed, e.g. after taking it over, the
+        // degradation is classified from when it began.
+        detector.enable_gateway();
+        assert!(detector.observe(&SourceType::Gateway, Some(1.0), 7.0).is_none());
+        let event = detector.observe(&SourceType::Target, None, 7.0).unwrap();
+        assert_eq!((event.kind, event.time_val), (EventKind::InternetDegraded, 7.0));
+        assert!(event.message.contains("for 6s"));
+    }
+
+    #[test]
+    fn other_sources_are_ignored() {
+        let mut detector = DivergenceDetector::new(HOLD, true);
+        for t in 1..10 {
+            assert!(detector.observe(&SourceType::Hop(3), None, t as f64).is_none());
+            assert!(detector.observe(&SourceType::Cycle(0), None, t as f64).is_none());
+        }
+        assert!(detector.target_healthy());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::events::{EventKind, SystemEvent};
+use default_net::get_default_interface;
+use std::{
+    collections::HashMap,
+    fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::mpsc;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const LEASE_DIRS: &[&str] = &[
+    "/var/lib/dhcp",
+    "/var/lib/dhclient",
+    "/var/lib/NetworkManager",
+    "/run/systemd/netif/leases",
+    "/var/lib/dhcpcd",
+    "/var/lib/dhcpcd5",
+    "/var/db/dhcpcd",
+    "/var/db/dhcpclient/leases",
+    "/tmp/dhcp",
+];
+
+fn is_lease_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    name.contains("lease")
+        || path.starts_with("/run/systemd/netif/leases")
+        || path.starts_with("/var/db/dhcpclient/leases")
+}
+
+fn lease_files() -> Vec<PathBuf> {
+    LEASE_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())))
+        .filter(|p| p.is_file() && is_lease_file(p))
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn interface_addrs() -> Vec<Ipv4Addr> {
+    get_default_interface()
+        .map(|iface| iface.ipv4.iter().map(|net| net.addr).collect())
+        .unwrap_or_default()
+}
+
+pub async fn run_dhcp_watcher(tx: mpsc::Sender<SystemEvent>) {
+    let mut lease_times: HashMap<PathBuf, SystemTime> = lease_files()
+        .into_iter()
+        .filter_map(|p| modified(&p).map(|t| (p, t)))
+        .collect();
+    let mut addrs = interface_addrs();
+
+    let mut timer = tokio::time::interval(POLL_INTERVAL);
+    timer.tick().await;
+
+    loop {
+        timer.tick().await;
+
+        for path in lease_files() {
+            let Some(mtime) = modified(&path) else {
+                continue;
+            };
+
+            let changed = lease_times.get(&path).is_some_and(|prev| *prev != mtime);
+            lease_times.insert(path.clone(), mtime);
+
+            if changed {
+                let _ = tx
+                    .send(SystemEvent {
+                        kind: EventKind::DhcpRenewal,
+                        message: format!("DHCP lease renewed ({})", path.display()),
+                    })
+                    .await;
+            }
+        }
+
+        let current = interface_addrs();
+        if !current.is_empty() && current != addrs {
+            let fmt = |list: &[Ipv4Addr]| {
+                list.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+            };
+
+            let message = if addrs.is_empty() {
+                format!("Local address acquired: {}", fmt(&current))
+            } else {
+                format!("Local address changed: {} -> {}", fmt(&addrs), fmt(&current))
+            };
+
+            let _ = tx
+                .send(SystemEvent {
+                    kind: EventKind::DhcpRenewal,
+                    message,
+                })
+                .await;
+        }
+        addrs = current;
+    }
+}
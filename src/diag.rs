@@ -0,0 +1,169 @@
+use crate::config::config_dir;
+use chrono::Local;
+use ratatui::style::Color;
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+const MAX_LINES: usize = 200;
+const DEDUPE_WINDOW: usize = 5;
+// The log file is kept across runs. Past this size it is moved to
+// vasili.log.1, replacing the one before, so it never grows without bound.
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Level::Info => Color::Gray,
+            Level::Warn => Color::Yellow,
+            Level::Error => Color::Red,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub timestamp: String,
+    pub level: Level,
+    pub message: String,
+}
+
+struct DiagLog {
+    lines: VecDeque<LogLine>,
+    file: Option<File>,
+    path: Option<PathBuf>,
+    // Bytes in the file, to know when to rotate it.
+    size: u64,
+    echo: bool,
+}
+
+impl DiagLog {
+    fn open(&mut self, path: PathBuf) {
+        self.file = OpenOptions::new().create(true).append(true).open(&path).ok();
+        self.size = self.file.as_ref().and_then(|f| f.metadata().ok()).map_or(0, |m| m.len());
+        self.path = self.file.is_some().then_some(path);
+    }
+
+    fn rotate(&mut self) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+        self.file = None;
+        let mut old = path.clone().into_os_string();
+        old.push(".1");
+        let _ = fs::rename(&path, old);
+        self.open(path);
+    }
+}
+
+static LOG: OnceLock<Mutex<DiagLog>> = OnceLock::new();
+
+fn log() -> &'static Mutex<DiagLog> {
+    LOG.get_or_init(|| {
+        Mutex::new(DiagLog {
+            lines: VecDeque::with_capacity(MAX_LINES),
+            file: None,
+            path: None,
+            size: 0,
+            echo: true,
+        })
+    })
+}
+
+pub fn init() {
+    let Some(path) = config_dir().map(|dir| dir.join("logs").join("vasili.log")) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+
+    if let Ok(mut log) = log().lock() {
+        log.open(path);
+        if log.size >= MAX_FILE_BYTES {
+            log.rotate();
+        }
+    }
+}
+
+pub fn set_echo(echo: bool) {
+    if let Ok(mut log) = log().lock() {
+        log.echo = echo;
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    log().lock().ok().and_then(|log| log.path.clone())
+}
+
+fn push(level: Level, message: String) {
+    let message = crate::redact::text(&message);
+    let Ok(mut log) = log().lock() else {
+        return;
+    };
+
+    if log.lines.iter().rev().take(DEDUPE_WINDOW).any(|l| l.message == message) {
+        return;
+    }
+
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    if log.echo && level != Level::Info {
+        eprintln!("{}: {}", level.label(), message);
+    }
+    if log.size >= MAX_FILE_BYTES {
+        log.rotate();
+    }
+    if let Some(file) = &mut log.file {
+        let line = format!("{} [{}] {}\n", timestamp, level.label(), message);
+        if file.write_all(line.as_bytes()).is_ok() {
+            log.size += line.len() as u64;
+        }
+    }
+
+    if log.lines.len() == MAX_LINES {
+        log.lines.pop_front();
+    }
+    log.lines.push_back(LogLine { timestamp, level, message });
+}
+
+pub fn info(message: impl Into<String>) {
+    push(Level::Info, message.into());
+}
+
+pub fn warn(message: impl Into<String>) {
+    push(Level::Warn, message.into());
+}
+
+pub fn error(message: impl Into<String>) {
+    push(Level::Error, message.into());
+}
+
+pub fn tail(count: usize) -> Vec<LogLine> {
+    log()
+        .lock()
+        .map(|log| log.lines.iter().rev().take(count).rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn len() -> usize {
+    log().lock().map(|log| log.lines.len()).unwrap_or(0)
+}
@@ -0,0 +1,117 @@
+use std::f64::consts::PI;
+
+// A merging t-digest (Dunning): latencies are kept as a few hundred
+// weighted centroids, small at the tails and larger in the middle, so P99.9
+// stays close while an update costs the same after a week as after a minute.
+// Until the first merge the samples are still all there and the percentiles
+// are exact.
+
+// More centroids, closer percentiles: about this many at most.
+const COMPRESSION: f64 = 200.0;
+// Samples collected before they are merged into the centroids.
+const BUFFER: usize = 1000;
+
+#[derive(Default)]
+pub struct Digest {
+    // (mean, weight), in order of the mean.
+    centroids: Vec<(f64, f64)>,
+    buffer: Vec<f64>,
+    total: f64,
+    min: f64,
+    max: f64,
+}
+
+// The k1 scale function and its inverse: how much weight a centroid at
+// quantile q may carry.
+fn k(q: f64) -> f64 {
+    COMPRESSION / (2.0 * PI) * (2.0 * q - 1.0).asin()
+}
+
+fn q(k: f64) -> f64 {
+    ((k * 2.0 * PI / COMPRESSION).sin() + 1.0) / 2.0
+}
+
+impl Digest {
+    pub fn add(&mut self, value: f64) {
+        if self.total == 0.0 && self.buffer.is_empty() {
+            (self.min, self.max) = (value, value);
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() >= BUFFER {
+            self.merge();
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total as u64 + self.buffer.len() as u64
+    }
+
+    fn merge(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut incoming: Vec<(f64, f64)> = self.buffer.drain(..).map(|v| (v, 1.0)).collect();
+        incoming.append(&mut self.centroids);
+        incoming.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let total: f64 = incoming.iter().map(|c| c.1).sum();
+
+        let mut merged = Vec::with_capacity(COMPRESSION as usize);
+        let mut current = incoming[0];
+        let mut before = 0.0;
+        let mut limit = q(k(0.0) + 1.0) * total;
+        for &next in &incoming[1..] {
+            if before + current.1 + next.1 <= limit {
+                let weight = current.1 + next.1;
+                current = (current.0 + (next.0 - current.0) * next.1 / weight, weight);
+            } else {
+                before += current.1;
+                merged.push(current);
+                // Past the top of the scale, the rest may go into one.
+                limit = q((k(before / total) + 1.0).min(COMPRESSION / 4.0)) * total;
+                current = next;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+        self.total = total;
+    }
+
+    // The value below which `quantile` (0 to 1) of the samples lie.
+    pub fn quantile(&mut self, quantile: f64) -> f64 {
+        if self.centroids.is_empty() {
+            // Nothing merged yet: nearest rank of the samples themselves.
+            if self.buffer.is_empty() {
+                return 0.0;
+            }
+            self.buffer.sort_by(f64::total_cmp);
+            let last = (self.buffer.len() - 1) as f64;
+            return self.buffer[(last * quantile).round() as usize];
+        }
+        self.merge();
+
+        // Each centroid stands at the middle of its weight; between two of
+        // them, and between the outer ones and min / max, it is linear.
+        let target = quantile * self.total;
+        let first = self.centroids[0];
+        if target < first.1 / 2.0 {
+            return self.min + (first.0 - self.min) * target / (first.1 / 2.0);
+        }
+        let mut before = 0.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let (from, to) = (before + left.1 / 2.0, before + left.1 + right.1 / 2.0);
+            if target < to {
+                return left.0 + (right.0 - left.0) * (target - from) / (to - from);
+            }
+            before += left.1;
+        }
+        let last = self.centroids[self.centroids.len() - 1];
+        let from = self.total - last.1 / 2.0;
+        if last.1 <= 1.0 || target <= from {
+            return last.0;
+        }
+        last.0 + (self.max - last.0) * (target - from) / (last.1 / 2.0)
+    }
+}
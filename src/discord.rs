@@ -0,0 +1,157 @@
+use crate::app::{HostStats, SessionSummary};
+use crate::args::format_duration;
+use crate::diag;
+use crate::events::{Event, EventKind};
+use chrono::Local;
+use serde_json::{Value, json};
+use std::{thread, time::Duration};
+
+const POST_TIMEOUT: Duration = Duration::from_secs(5);
+const WORST_EVENTS: usize = 5;
+// Discord rejects embed field values longer than this.
+const FIELD_LIMIT: usize = 1024;
+
+const RED: u32 = 0xe74c3c;
+const GREEN: u32 = 0x2ecc71;
+const BLUE: u32 = 0x3498db;
+const YELLOW: u32 = 0xf1c40f;
+
+fn is_outage(kind: EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::InternetDegraded | EventKind::LocalDegraded | EventKind::RuleFired
+    )
+}
+
+fn field(name: &str, value: String, inline: bool) -> Value {
+    let value = if value.chars().count() > FIELD_LIMIT {
+        format!("{}…", value.chars().take(FIELD_LIMIT - 1).collect::<String>())
+    } else {
+        value
+    };
+    json!({ "name": name, "value": value, "inline": inline })
+}
+
+fn grade_color(grade: &str) -> u32 {
+    match grade {
+        "S" | "A" => GREEN,
+        "B" => BLUE,
+        "C" => YELLOW,
+        _ => RED,
+    }
+}
+
+pub struct Discord {
+    url: String,
+}
+
+impl Discord {
+    pub fn new(url: String) -> Self {
+        Self { url }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn payload(embed: Value) -> Value {
+        json!({ "username": "Vasili", "embeds": [embed] })
+    }
+
+    // Outage start and end are posted in the background so a slow webhook
+    // never stalls the probes.
+    pub fn post_event(&self, event: &Event, stats: &HostStats) {
+        let (title, color) = match event.kind {
+            kind if is_outage(kind) => (format!("Outage started ({})", kind.label()), RED),
+            EventKind::Recovered | EventKind::RuleResolved => ("Outage ended".to_string(), GREEN),
+            _ => return,
+        };
+
+        let embed = json!({
+            "title": title,
+            "description": event.message,
+            "color": color,
+            "fields": [
+                field("Target", stats.display_name.clone(), true),
+                field("Loss", format!("{:.1}%", stats.loss_percent()), true),
+                field("P25/P75/P99", format!("{:.0}/{:.0}/{:.0}ms", stats.p25, stats.p75, stats.p99), true),
+                field("Grade", stats.grade_label(), true),
+            ],
+            "timestamp": Local::now().to_rfc3339(),
+        });
+
+        let url = self.url.clone();
+        thread::spawn(move || send(&url, Self::payload(embed)));
+    }
+
+    // Called on the way out, so it blocks until Discord has the summary.
+    pub fn post_summary(&self, summaries: &[SessionSummary], stats: &HostStats, events: &[Event]) {
+        let Some(target) = summaries.first() else {
+            return;
+        };
+
+        let mut fields: Vec<Value> = summaries
+            .iter()
+            .map(|s| {
+                field(
+                    &format!("{} {}", s.target_type, s.target_ip),
+                    format!(
+                        "Loss {:.1}% ({} of {}) · P25/P75/P99 {:.0}/{:.0}/{:.0}ms · Max {:.0}ms · Grade **{}**",
+                        s.loss_percent, s.lost, s.samples, s.p25, s.p75, s.p99, s.max, s.grade_label()
+                    ),
+                    false,
+                )
+            })
+            .collect();
+
+        let mut outages: Vec<(&Event, usize, f64)> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| is_outage(e.kind))
+            .map(|(i, e)| {
+                let end = events[i + 1..]
+                    .iter()
+                    .find(|next| matches!(next.kind, EventKind::Recovered | EventKind::RuleResolved))
+                    .map(|next| next.time_val)
+                    .unwrap_or(f64::MAX);
+                let during = |t: f64| t >= e.time_val && t <= end;
+                let lost = stats.loss_points.range((e.time_val, end)).iter().filter(|(t, _)| during(*t)).count();
+                let peak = stats.points.range((e.time_val, end)).iter().filter(|(t, _)| during(*t)).map(|(_, ms)| *ms).fold(0.0, f64::max);
+                (e, lost, peak)
+            })
+            .collect();
+        if !outages.is_empty() {
+            outages.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+            let lines: Vec<String> = outages
+                .iter()
+                .take(WORST_EVENTS)
+                .map(|(e, lost, peak)| {
+                    format!("`{}` **{}** {} lost, peak {:.0}ms: {}", e.timestamp, e.kind.label(), lost, peak, e.message)
+                })
+                .collect();
+            fields.push(field(&format!("Worst outages ({} total)", outages.len()), lines.join("\n"), false));
+        }
+
+        let ending = target.ending();
+        let embed = json!({
+            "title": format!("Session {}: {}", ending, target.target_ip),
+            "description": format!(
+                "Started {}, ran for {}, probe {}",
+                target.started,
+                format_duration(Duration::from_secs(target.duration_secs as u64)),
+                target.protocol
+            ),
+            "color": grade_color(&target.grade),
+            "fields": fields,
+            "timestamp": Local::now().to_rfc3339(),
+        });
+
+        send(&self.url, Self::payload(embed));
+    }
+}
+
+fn send(url: &str, body: Value) {
+    if let Err(e) = ureq::post(url).timeout(POST_TIMEOUT).send_json(body) {
+        diag::warn(format!("Discord webhook POST failed: {}", e));
+    }
+}
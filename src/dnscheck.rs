@@ -0,0 +1,235 @@
+use crate::diag;
+use crate::events::{EventKind, SystemEvent};
+use crate::pinger::{build_dns_query, connected_udp_socket};
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::{
+    collections::BTreeSet,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(300);
+const RESOLVE_INTERVAL: Duration = Duration::from_secs(300);
+// Nonexistent names are made up below this domain.
+const CHECK_DOMAIN: &str = "example.com";
+// Compared with DNS-over-HTTPS. Its answer is the same two anycast addresses
+// everywhere, unlike CDN and geo-DNS names, whose answers legitimately differ
+// from resolver to resolver and would look like interception.
+const CONTROL_NAME: &str = "one.one.one.one";
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+// Addressed by IP so the check does not depend on the resolver under test.
+const DOH_URL: &str = "https://1.1.1.1/dns-query";
+
+#[derive(Debug, PartialEq)]
+enum Lookup {
+    NxDomain,
+    Addrs(BTreeSet<Ipv4Addr>),
+}
+
+impl Lookup {
+    fn describe(&self) -> String {
+        match self {
+            Lookup::NxDomain => "NXDOMAIN".to_string(),
+            Lookup::Addrs(addrs) if addrs.is_empty() => "no address".to_string(),
+            Lookup::Addrs(addrs) => addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", "),
+        }
+    }
+}
+
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        pos += 1 + len as usize;
+    }
+}
+
+fn parse_response(packet: &[u8], id: u16) -> Option<Lookup> {
+    let header = packet.get(..12)?;
+    if u16::from_be_bytes([header[0], header[1]]) != id || header[2] & 0x80 == 0 {
+        return None;
+    }
+    if header[3] & 0x0f == 3 {
+        return Some(Lookup::NxDomain);
+    }
+
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(packet, pos)? + 4;
+    }
+
+    let mut addrs = BTreeSet::new();
+    for _ in 0..answers {
+        pos = skip_name(packet, pos)?;
+        let record = packet.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([record[0], record[1]]);
+        let rdlen = u16::from_be_bytes([record[8], record[9]]) as usize;
+        pos += 10;
+
+        let rdata = packet.get(pos..pos + rdlen)?;
+        if rtype == 1 && rdlen == 4 {
+            addrs.insert(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]));
+        }
+        pos += rdlen;
+    }
+    Some(Lookup::Addrs(addrs))
+}
+
+async fn query_resolver(resolver: IpAddr, name: &str) -> Result<Lookup> {
+    let socket = connected_udp_socket(SocketAddr::new(resolver, 53)).await?;
+    let id: u16 = rand::random();
+    socket.send(&build_dns_query(id, name)).await?;
+
+    let mut buf = [0u8; 512];
+    tokio::time::timeout(QUERY_TIMEOUT, async {
+        loop {
+            let len = socket.recv(&mut buf).await?;
+            if let Some(lookup) = parse_response(&buf[..len], id) {
+                return Ok(lookup);
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("{} did not answer a DNS query for {}", resolver, name))?
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    rtype: u16,
+    data: String,
+}
+
+fn query_doh(name: &str) -> Result<Lookup> {
+    let response: DohResponse = ureq::get(DOH_URL)
+        .query("name", name)
+        .query("type", "A")
+        .set("accept", "application/dns-json")
+        .timeout(QUERY_TIMEOUT)
+        .call()?
+        .into_json()?;
+
+    if response.status == 3 {
+        return Ok(Lookup::NxDomain);
+    }
+    Ok(Lookup::Addrs(
+        response.answer.iter().filter(|a| a.rtype == 1).filter_map(|a| a.data.parse().ok()).collect(),
+    ))
+}
+
+async fn check(resolver: IpAddr) -> Result<Option<String>> {
+    let bogus = format!("vasili-{:08x}.{}", rand::random::<u32>(), CHECK_DOMAIN);
+    if let Lookup::Addrs(addrs) = query_resolver(resolver, &bogus).await?
+        && !addrs.is_empty()
+    {
+        return Ok(Some(format!(
+            "DNS {} answers nonexistent names with {}, likely ISP NXDOMAIN rewriting",
+            resolver,
+            Lookup::Addrs(addrs).describe()
+        )));
+    }
+
+    let local = query_resolver(resolver, CONTROL_NAME).await?;
+    let doh = tokio::task::spawn_blocking(|| query_doh(CONTROL_NAME)).await??;
+
+    let mismatch = match (&local, &doh) {
+        (Lookup::Addrs(l), Lookup::Addrs(d)) => !l.is_empty() && !d.is_empty() && l.is_disjoint(d),
+        (Lookup::NxDomain, Lookup::Addrs(d)) => !d.is_empty(),
+        _ => false,
+    };
+
+    Ok(mismatch.then(|| {
+        format!(
+            "DNS {} resolves {} to {}, DNS-over-HTTPS says {} - possible DNS interception",
+            resolver,
+            CONTROL_NAME,
+            local.describe(),
+            doh.describe()
+        )
+    }))
+}
+
+pub async fn run_dns_check_watcher(resolver: IpAddr, tx: mpsc::Sender<SystemEvent>) {
+    let mut timer = tokio::time::interval(CHECK_INTERVAL);
+    timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut reported: Option<String> = None;
+
+    loop {
+        timer.tick().await;
+
+        let finding = match check(resolver).await {
+            Ok(finding) => finding,
+            Err(e) => {
+                diag::warn(format!("DNS interception check failed: {}", e));
+                continue;
+            }
+        };
+
+        let event = match (&finding, &reported) {
+            (Some(message), previous) if previous.as_ref() != Some(message) => {
+                Some(SystemEvent { kind: EventKind::DnsHijack, message: message.clone() })
+            }
+            (None, Some(_)) => Some(SystemEvent {
+                kind: EventKind::DnsRestored,
+                message: format!("DNS {} answers match DNS-over-HTTPS again", resolver),
+            }),
+            _ => None,
+        };
+        reported = finding;
+
+        if let Some(event) = event
+            && tx.send(event).await.is_err()
+        {
+            return;
+        }
+    }
+}
+
+// A target given by name is resolved once at the start and probed at that
+// address for the whole session. Resolving it again now and then and logging
+// the answer shows in the diagnostic log when the name moved elsewhere (a
+// CDN or game server failover) or stopped resolving.
+pub async fn run_resolve_watcher(host: String, probing: IpAddr) {
+    let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + RESOLVE_INTERVAL, RESOLVE_INTERVAL);
+    timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut previous: Option<BTreeSet<IpAddr>> = None;
+
+    loop {
+        timer.tick().await;
+
+        let addrs: BTreeSet<IpAddr> = match tokio::net::lookup_host(format!("{}:0", host)).await {
+            Ok(addrs) => addrs.map(|a| a.ip()).filter(|ip| ip.is_ipv4() == probing.is_ipv4()).collect(),
+            Err(e) => {
+                diag::warn(format!("Re-resolving {} failed: {}", host, e));
+                continue;
+            }
+        };
+        let list = addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+        if addrs.is_empty() {
+            diag::warn(format!("Re-resolving {} returned no address, still probing {}", host, probing));
+        } else if !addrs.contains(&probing) {
+            diag::warn(format!("{} now resolves to {}, still probing {} until the next session", host, list, probing));
+        } else if previous.as_ref() != Some(&addrs) {
+            diag::info(format!("Re-resolved {} to {}", host, list));
+        }
+        previous = Some(addrs);
+    }
+}
@@ -0,0 +1,79 @@
+use std::io;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+// --dscp: the Differentiated Services code point every probe goes out with.
+// A run marked like game or VoIP traffic (EF) next to an unmarked one shows
+// whether the router's QoS or the ISP treat marked packets any differently.
+
+// 0 is best effort, what unmarked packets carry anyway.
+static CURRENT: OnceLock<u8> = OnceLock::new();
+
+// A class name (`ef`, `be`, `cs0`-`cs7`, `af11`-`af43`, `va`) or the code
+// point itself (0-63).
+pub fn parse(text: &str) -> Option<u8> {
+    let name = text.trim().to_ascii_lowercase();
+    match name.as_str() {
+        "be" | "default" => return Some(0),
+        "ef" => return Some(46),
+        "va" => return Some(44),
+        _ => {}
+    }
+    if let Some(class) = name.strip_prefix("cs") {
+        return class.parse::<u8>().ok().filter(|n| *n <= 7).map(|n| n << 3);
+    }
+    if let Some(class) = name.strip_prefix("af") {
+        let &[class, drop] = class.as_bytes() else {
+            return None;
+        };
+        let (class, drop) = (class.wrapping_sub(b'0'), drop.wrapping_sub(b'0'));
+        return ((1..=4).contains(&class) && (1..=3).contains(&drop)).then_some(class * 8 + drop * 2);
+    }
+    name.parse().ok().filter(|code| *code < 64)
+}
+
+// The class name where there is one: "EF (46)", "AF41 (34)", "13".
+pub fn label(code: u8) -> String {
+    let name = match code {
+        0 => Some("BE".to_string()),
+        46 => Some("EF".to_string()),
+        44 => Some("VA".to_string()),
+        _ if code.is_multiple_of(8) => Some(format!("CS{}", code / 8)),
+        _ if (1..=4).contains(&(code / 8)) && matches!(code % 8, 2 | 4 | 6) => Some(format!("AF{}{}", code / 8, code % 8 / 2)),
+        _ => None,
+    };
+    match name {
+        Some(name) => format!("{} ({})", name, code),
+        None => code.to_string(),
+    }
+}
+
+pub fn init(code: u8) {
+    let _ = CURRENT.set(code);
+}
+
+pub fn current() -> u8 {
+    CURRENT.get().copied().unwrap_or(0)
+}
+
+// Sets the code point on a probe socket before anything is sent. It sits in
+// the upper six bits of the IPv4 TOS byte and of the IPv6 traffic class.
+#[cfg(unix)]
+pub fn mark<S: std::os::fd::AsFd>(socket: &S, ip: IpAddr) -> io::Result<()> {
+    let code = current();
+    if code == 0 {
+        return Ok(());
+    }
+    let socket = socket2::SockRef::from(socket);
+    let tos = u32::from(code) << 2;
+    match ip {
+        IpAddr::V4(_) => socket.set_tos_v4(tos),
+        IpAddr::V6(_) => socket.set_tclass_v6(tos),
+    }
+}
+
+// main refuses --dscp here, so there is never anything to mark.
+#[cfg(not(unix))]
+pub fn mark<S>(_socket: &S, _ip: IpAddr) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use crate::args::EchoServerArgs;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::signal;
+
+// A UDP echo that also tells the client how many of its probes arrived, so a
+// lost probe can be put on the way out or the way back. Requests are padded
+// to the size of the reply, the server never sends more than it receives.
+//
+//   request: "VSLI" | 1 | 0 | session u64 | seq u64 | 8 bytes padding
+//   reply:   "VSLI" | 1 | 1 | session u64 | seq u64 | received u64
+
+pub const DEFAULT_PORT: u16 = 7007;
+const MAGIC: &[u8; 4] = b"VSLI";
+const VERSION: u8 = 1;
+const PACKET_LEN: usize = 30;
+const REQUEST: u8 = 0;
+const REPLY: u8 = 1;
+// Counters of clients that went quiet are dropped once there are many.
+const MAX_SESSIONS: usize = 10_000;
+const SESSION_IDLE: Duration = Duration::from_secs(600);
+
+fn packet(kind: u8, session: u64, seq: u64, received: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[..4].copy_from_slice(MAGIC);
+    packet[4] = VERSION;
+    packet[5] = kind;
+    packet[6..14].copy_from_slice(&session.to_be_bytes());
+    packet[14..22].copy_from_slice(&seq.to_be_bytes());
+    packet[22..30].copy_from_slice(&received.to_be_bytes());
+    packet
+}
+
+// (kind, session, seq, received)
+fn parse(packet: &[u8]) -> Option<(u8, u64, u64, u64)> {
+    if packet.len() != PACKET_LEN || &packet[..4] != MAGIC || packet[4] != VERSION {
+        return None;
+    }
+    let field = |at: usize| u64::from_be_bytes(packet[at..at + 8].try_into().unwrap());
+    Some((packet[5], field(6), field(14), field(22)))
+}
+
+pub fn request(session: u64, seq: u64) -> [u8; PACKET_LEN] {
+    packet(REQUEST, session, seq, 0)
+}
+
+// The number of probes of the session the server has received, including
+// this one, if the packet is the reply to it.
+pub fn parse_reply(packet: &[u8], session: u64, seq: u64) -> Option<u64> {
+    match parse(packet) {
+        Some((REPLY, s, q, received)) if s == session && q == seq => Some(received),
+        _ => None,
+    }
+}
+
+// Tracks the probes lost since the last reply and splits them by direction
+// with the server's count once a reply arrives again.
+#[derive(Default)]
+pub struct LossSplit {
+    last_seq: u64,
+    upstream_lost: u64,
+}
+
+impl LossSplit {
+    // Returns (lost on the way out, lost on the way back) when the reply ends
+    // a run of lost probes. A reply that came too late counts as lost on the
+    // way back.
+    pub fn on_reply(&mut self, seq: u64, received: u64) -> Option<(u64, u64)> {
+        let lost = seq.saturating_sub(self.last_seq + 1);
+        // A restarted server counts from zero again.
+        let upstream_total = seq.saturating_sub(received);
+        let upstream = upstream_total.saturating_sub(self.upstream_lost).min(lost);
+        self.last_seq = seq;
+        self.upstream_lost = upstream_total;
+        (lost > 0).then_some((upstream, lost - upstream))
+    }
+}
+
+// The probes received per session, and when the last one came.
+struct Sessions {
+    counts: HashMap<u64, (u64, Instant)>,
+    max: usize,
+}
+
+impl Sessions {
+    fn new(max: usize) -> Self {
+        Self { counts: HashMap::new(), max }
+    }
+
+    // Counts a probe and returns the session's total and whether the session
+    // is new. Room for a new session is made before it is added: idle ones go
+    // first, then the one heard from longest ago, which starts over from zero
+    // if it comes back.
+    fn receive(&mut self, session: u64, now: Instant) -> (u64, bool) {
+        let new = !self.counts.contains_key(&session);
+        if new && self.counts.len() >= self.max {
+            self.counts.retain(|_, (_, seen)| now.duration_since(*seen) < SESSION_IDLE);
+            if self.counts.len() >= self.max {
+                let oldest = self.counts.iter().min_by_key(|(_, (_, seen))| *seen).map(|(id, _)| *id);
+                if let Some(oldest) = oldest {
+                    self.counts.remove(&oldest);
+                }
+            }
+        }
+        let (received, seen) = self.counts.entry(session).or_insert((0, now));
+        *received += 1;
+        *seen = now;
+        (*received, new)
+    }
+}
+
+pub async fn run(args: EchoServerArgs) -> Result<()> {
+    let socket = UdpSocket::bind(args.listen).await.with_context(|| format!("Could not listen on {}", args.listen))?;
+    println!("VASILI echo server listening on UDP {}", socket.local_addr()?);
+    println!("Probe it with: vasili -t <this host> --protocol echo --port {}", args.listen.port());
+    println!("Press Ctrl+C to stop.");
+
+    let mut sessions = Sessions::new(MAX_SESSIONS);
+    let mut buf = [0u8; 64];
+    loop {
+        let (len, from): (usize, SocketAddr) = tokio::select! {
+            received = socket.recv_from(&mut buf) => match received {
+                Ok(received) => received,
+                Err(_) => continue,
+            },
+            _ = signal::ctrl_c() => {
+                println!("\nStopping echo server. Bye!");
+                return Ok(());
+            }
+        };
+        let Some((REQUEST, session, seq, _)) = parse(&buf[..len]) else {
+            continue;
+        };
+
+        let (received, new) = sessions.receive(session, Instant::now());
+        if new {
+            println!("[{}] New session {:016x} from {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), session, from);
+        }
+
+        let _ = socket.send_to(&packet(REPLY, session, seq, received), from).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_in_a_row_report_nothing() {
+        let mut split = LossSplit::default();
+        for seq in 1..=5 {
+            assert_eq!(split.on_reply(seq, seq), None);
+        }
+    }
+
+    #[test]
+    fn lost_probes_are_split_by_the_server_count() {
+        let mut split = LossSplit::default();
+        assert_eq!(split.on_reply(1, 1), None);
+        // 2 and 3 never reached the server.
+        assert_eq!(split.on_reply(4, 2), Some((2, 0)));
+        // 5 and 6 reached it, their replies were lost or came too late.
+        assert_eq!(split.on_reply(7, 5), Some((0, 2)));
+        // 8 reached it, 9 did not; the earlier upstream losses are not counted again.
+        assert_eq!(split.on_reply(10, 7), Some((1, 1)));
+        assert_eq!(split.on_reply(11, 8), None);
+    }
+
+    #[test]
+    fn a_restarted_server_does_not_split_more_than_was_lost() {
+        let mut split = LossSplit::default();
+        for seq in 1..=5 {
+            split.on_reply(seq, seq);
+        }
+        assert_eq!(split.on_reply(8, 1), Some((2, 0)));
+        assert_eq!(split.on_reply(9, 2), None);
+    }
+
+    #[test]
+    fn sessions_count_their_probes() {
+        let mut sessions = Sessions::new(4);
+        let now = Instant::now();
+        assert_eq!(sessions.receive(1, now), (1, true));
+        assert_eq!(sessions.receive(1, now), (2, false));
+        assert_eq!(sessions.receive(2, now), (1, true));
+        assert_eq!(sessions.receive(1, now), (3, false));
+    }
+
+    #[test]
+    fn sessions_stay_within_the_cap() {
+        let mut sessions = Sessions::new(3);
+        let start = Instant::now();
+        for id in 0..3 {
+            sessions.receive(id, start + Duration::from_secs(id));
+        }
+        // All active: the one heard from longest ago makes room.
+        assert_eq!(sessions.receive(10, start + Duration::from_secs(5)), (1, true));
+        assert_eq!(sessions.counts.len(), 3);
+        assert!(!sessions.counts.contains_key(&0));
+
+        // Idle ones all go at once.
+        let later = start + SESSION_IDLE + Duration::from_secs(3);
+        assert_eq!(sessions.receive(11, later), (1, true));
+        assert_eq!(sessions.counts.len(), 2);
+        assert!(sessions.counts.contains_key(&10) && sessions.counts.contains_key(&11));
+
+        // Known sessions never evict anyone.
+        sessions.receive(12, later);
+        assert_eq!(sessions.receive(10, later), (2, false));
+        assert_eq!(sessions.counts.len(), 3);
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::Local;
+use ratatui::style::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    InternetDegraded,
+    LocalDegraded,
+    Recovered,
+    DhcpRenewal,
+    ProbeFallback,
+    TraceSnapshot,
+    DnsHijack,
+    DnsRestored,
+    ChartExport,
+    Marker,
+    RuleFired,
+    RuleResolved,
+    ConfigReload,
+    EchoLoss,
+    Anomaly,
+    RouteChange,
+    Readiness,
+}
+
+impl EventKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventKind::InternetDegraded => "ISP",
+            EventKind::LocalDegraded => "LOCAL",
+            EventKind::Recovered => "OK",
+            EventKind::DhcpRenewal => "DHCP",
+            EventKind::ProbeFallback => "PROBE",
+            EventKind::TraceSnapshot => "TRACE",
+            EventKind::DnsHijack => "DNS",
+            EventKind::DnsRestored => "DNS OK",
+            EventKind::ChartExport => "EXPORT",
+            EventKind::Marker => "MARK",
+            EventKind::RuleFired => "RULE",
+            EventKind::RuleResolved => "RULE OK",
+            EventKind::ConfigReload => "CONFIG",
+            EventKind::EchoLoss => "UDP LOSS",
+            EventKind::Anomaly => "ANOMALY",
+            EventKind::RouteChange => "ROUTE",
+            EventKind::Readiness => "EVENT",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            EventKind::InternetDegraded => Color::Yellow,
+            EventKind::LocalDegraded => Color::Red,
+            EventKind::Recovered => Color::Green,
+            EventKind::DhcpRenewal => Color::Cyan,
+            EventKind::ProbeFallback => Color::Magenta,
+            EventKind::TraceSnapshot => Color::Blue,
+            EventKind::DnsHijack => Color::Red,
+            EventKind::DnsRestored => Color::Green,
+            EventKind::ChartExport => Color::Cyan,
+            EventKind::Marker => Color::LightMagenta,
+            EventKind::RuleFired => Color::LightRed,
+            EventKind::RuleResolved => Color::Green,
+            EventKind::ConfigReload => Color::Cyan,
+            EventKind::EchoLoss => Color::Yellow,
+            EventKind::Anomaly => Color::LightYellow,
+            EventKind::RouteChange => Color::LightBlue,
+            EventKind::Readiness => Color::LightCyan,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time_val: f64,
+    pub timestamp: String,
+    pub kind: EventKind,
+    pub message: String,
+    pub suppressed: bool,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, time_val: f64, message: String) -> Self {
+        Self {
+            time_val,
+            timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            kind,
+            message,
+            suppressed: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemEvent {
+    pub kind: EventKind,
+    pub message: String,
+}
@@ -0,0 +1,179 @@
+use crate::pinger::{PingUpdate, ProbeError, ProbeTimer, SourceType, late};
+use anyhow::{Context, Result, bail};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+// An in-process stand-in for the ICMP responder, compiled in with the
+// `fake-ping` feature, so the whole pipeline (pinger, App, sinks, summary)
+// runs in CI without raw sockets. Replies follow a profile given per host:
+//
+//   VASILI_FAKE_TARGET="20~2*100,timeout*5,35~4/1%"
+//
+// Steps are separated by commas and play in order, the last one forever.
+// A step is `timeout`, `unreachable` (an ICMP destination unreachable in
+// place of the reply) or a latency in ms with an optional `~jitter` and
+// `/loss%`, followed by an optional `*count` of probes. Jitter and loss come
+// from a seeded RNG (VASILI_FAKE_SEED), so a run is reproducible.
+
+const TARGET_VAR: &str = "VASILI_FAKE_TARGET";
+const GATEWAY_VAR: &str = "VASILI_FAKE_GATEWAY";
+const SEED_VAR: &str = "VASILI_FAKE_SEED";
+// Used when a gateway profile is set but the machine has no default route.
+const FALLBACK_GATEWAY: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+const GATEWAY_PROFILE: &str = "1~0.2";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Reply {
+    Timeout,
+    Unreachable,
+    Latency { ms: f64, jitter: f64, loss: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeProfile {
+    steps: Vec<(Reply, Option<u64>)>,
+}
+
+fn number(text: &str, what: &str) -> Result<f64> {
+    let value: f64 = text.trim().parse().with_context(|| format!("Invalid {} '{}'", what, text))?;
+    if !value.is_finite() || value < 0.0 {
+        bail!("Invalid {} '{}'", what, text);
+    }
+    Ok(value)
+}
+
+impl FakeProfile {
+    pub fn parse(spec: &str) -> Result<FakeProfile> {
+        let mut steps = Vec::new();
+        for step in spec.split(',').map(str::trim) {
+            let (reply, count) = match step.split_once('*') {
+                Some((reply, count)) => {
+                    (reply.trim(), Some(count.trim().parse::<u64>().with_context(|| format!("Invalid count in '{}'", step))?))
+                }
+                None => (step, None),
+            };
+            let reply = if reply.eq_ignore_ascii_case("timeout") {
+                Reply::Timeout
+            } else if reply.eq_ignore_ascii_case("unreachable") {
+                Reply::Unreachable
+            } else {
+                let (reply, loss) = match reply.split_once('/') {
+                    Some((reply, loss)) => (reply, number(loss.trim_end_matches('%'), "loss")?),
+                    None => (reply, 0.0),
+                };
+                let (ms, jitter) = match reply.split_once('~') {
+                    Some((ms, jitter)) => (number(ms, "latency")?, number(jitter, "jitter")?),
+                    None => (number(reply, "latency")?, 0.0),
+                };
+                if loss > 100.0 {
+                    bail!("Invalid loss '{}%'", loss);
+                }
+                Reply::Latency { ms, jitter, loss }
+            };
+            steps.push((reply, count));
+        }
+        Ok(FakeProfile { steps })
+    }
+
+    fn from_env(var: &str) -> Option<FakeProfile> {
+        let spec = std::env::var(var).ok()?;
+        match FakeProfile::parse(&spec) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                eprintln!("Error: {}: {}", var, e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// Fake mode starts with a target profile. The gateway then answers from its
+// own profile (or a steady 1ms) instead of the real one.
+pub fn target() -> Option<FakeProfile> {
+    FakeProfile::from_env(TARGET_VAR)
+}
+
+pub fn gateway() -> Option<FakeProfile> {
+    target()?;
+    Some(FakeProfile::from_env(GATEWAY_VAR).unwrap_or_else(|| FakeProfile::parse(GATEWAY_PROFILE).unwrap()))
+}
+
+pub fn gateway_addr(detected: Option<IpAddr>, no_gateway: bool) -> Option<IpAddr> {
+    if no_gateway || target().is_none() || std::env::var_os(GATEWAY_VAR).is_none() {
+        return detected;
+    }
+    detected.or(Some(FALLBACK_GATEWAY))
+}
+
+fn seed(source: &SourceType) -> u64 {
+    let base: u64 = std::env::var(SEED_VAR).ok().and_then(|s| s.parse().ok()).unwrap_or(1);
+    match source {
+        SourceType::Gateway => base.wrapping_add(1),
+        _ => base,
+    }
+}
+
+pub async fn run_fake_pinger(
+    profile: FakeProfile,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let mut rng = StdRng::seed_from_u64(seed(&source_type));
+    let interval = interval_timer.period();
+    let mut step = 0;
+    let mut played = 0u64;
+
+    let mut in_flight = JoinSet::new();
+
+    // Like run_pinger, each reply is awaited on its own so slow ones do not
+    // hold back the probes after them.
+    loop {
+        tokio::select! {
+            _ = interval_timer.tick() => {
+                let (reply, count) = &profile.steps[step];
+                let reply = match *reply {
+                    Reply::Latency { loss, .. } if loss > 0.0 && rng.gen_range(0.0..100.0) < loss => Reply::Timeout,
+                    Reply::Latency { ms, jitter, loss } => {
+                        let ms = if jitter > 0.0 { (ms + rng.gen_range(-jitter..=jitter)).max(0.0) } else { ms };
+                        Reply::Latency { ms, jitter, loss }
+                    }
+                    ref reply => reply.clone(),
+                };
+                in_flight.spawn(async move {
+                    let sent = Instant::now();
+                    let latency = match reply {
+                        Reply::Latency { ms, .. } => {
+                            // A reply slower than the timeout counts as lost, like a real one.
+                            let wait = Duration::from_secs_f64(ms / 1000.0);
+                            tokio::time::sleep(wait.min(timeout)).await;
+                            (wait <= timeout).then_some(ms)
+                        }
+                        _ => None,
+                    };
+                    let error = match reply {
+                        _ if latency.is_some() => None,
+                        Reply::Unreachable => Some(ProbeError::Unreachable),
+                        _ => Some(ProbeError::Timeout),
+                    };
+                    (sent, latency, error)
+                });
+
+                played += 1;
+                if count.is_some_and(|count| played >= count) && step + 1 < profile.steps.len() {
+                    step += 1;
+                    played = 0;
+                }
+            }
+
+            Some(Ok((sent, latency, error))) = in_flight.join_next() => {
+                let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(sent, interval) }).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::app::HostStats;
+use crate::rules::{GRADES, grade_rank};
+use anyhow::{Context, Result, bail};
+
+// Exit code of a session that ran but missed --fail-on-grade or
+// --fail-on-loss. 1 stays with errors and 2 with invalid arguments, so a
+// script can tell a bad connection from a broken call.
+pub const EXIT_CODE: i32 = 3;
+
+// Quality thresholds the Target has to meet over the whole session. Unlike
+// the displayed grade, the gate also judges sessions still warming up: a
+// short pre-match check is exactly what it is for.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QualityGate {
+    grade: Option<usize>,
+    loss: Option<f64>,
+}
+
+impl QualityGate {
+    pub fn new(grade: Option<&str>, loss: Option<&str>) -> Result<QualityGate> {
+        let grade = grade.map(|g| grade_rank(&g.to_ascii_uppercase()));
+        let loss = match loss {
+            Some(text) => {
+                let value: f64 = text
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse()
+                    .with_context(|| format!("Invalid --fail-on-loss '{}'. Expected e.g. '1%'.", text))?;
+                if !(0.0..100.0).contains(&value) {
+                    bail!("Invalid --fail-on-loss '{}'. Expected a percentage from 0 to below 100.", text);
+                }
+                Some(value)
+            }
+            None => None,
+        };
+        Ok(QualityGate { grade, loss })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grade.is_none() && self.loss.is_none()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(rank) = self.grade {
+            parts.push(format!("grade {} or worse", GRADES[rank]));
+        }
+        if let Some(loss) = self.loss {
+            parts.push(format!("loss above {}%", loss));
+        }
+        format!("fails on {}", parts.join(" or "))
+    }
+
+    // Why the Target missed the gate, empty if it passed.
+    pub fn failures(&self, stats: &HostStats) -> Vec<String> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        if stats.total_count == 0 {
+            return vec!["no probe completed".to_string()];
+        }
+
+        let mut failures = Vec::new();
+        if let Some(rank) = self.grade {
+            let grade = stats.final_grade();
+            if grade_rank(grade) >= rank {
+                failures.push(format!("grade {} (fails at {} or worse)", grade, GRADES[rank]));
+            }
+        }
+        if let Some(limit) = self.loss
+            && stats.loss_percent() > limit
+        {
+            failures.push(format!("loss {:.2}% (fails above {}%)", stats.loss_percent(), limit));
+        }
+        failures
+    }
+
+    // Prints the verdict and returns whether the session passed.
+    pub fn report(&self, stats: &HostStats) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let failures = self.failures(stats);
+        if failures.is_empty() {
+            println!("Quality gate passed ({}).", self.describe());
+            return true;
+        }
+        eprintln!("Quality gate failed: {}", failures.join(", "));
+        false
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use std::{net::IpAddr, time::Duration};
+
+// Only asked with --geo or --location. Over HTTPS, as the request names the
+// target being monitored.
+const LOOKUP_URL: &str = "https://ipapi.co/";
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const FIBER_KM_PER_MS: f64 = 200.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoEstimate {
+    pub distance_km: f64,
+    pub min_rtt_ms: f64,
+    pub target_place: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+    #[serde(default)]
+    error: bool,
+    reason: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    city: Option<String>,
+    country_name: Option<String>,
+}
+
+pub fn parse_coordinates(s: &str) -> Option<Coordinates> {
+    let (lat, lon) = s.split_once(',')?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+        .then_some(Coordinates { lat, lon })
+}
+
+fn lookup(ip: Option<IpAddr>) -> Result<(Coordinates, String)> {
+    let url = match ip {
+        Some(ip) => format!("{}{}/json/", LOOKUP_URL, ip),
+        None => format!("{}json/", LOOKUP_URL),
+    };
+
+    let response: LookupResponse = ureq::get(&url)
+        .timeout(Duration::from_secs(3))
+        .call()?
+        .into_json()?;
+
+    match (response.error, response.latitude, response.longitude) {
+        (false, Some(lat), Some(lon)) => {
+            let place = [response.city, response.country_name]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(", ");
+            Ok((Coordinates { lat, lon }, place))
+        }
+        _ => Err(anyhow!("no location available{}", response.reason.map(|r| format!(" ({})", r)).unwrap_or_default())),
+    }
+}
+
+pub fn distance_km(a: Coordinates, b: Coordinates) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (b.lon - a.lon).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+pub fn estimate(target: IpAddr, own_location: Option<Coordinates>) -> Result<GeoEstimate> {
+    let own = match own_location {
+        Some(c) => c,
+        None => lookup(None)?.0,
+    };
+    let (target_coords, target_place) = lookup(Some(target))?;
+
+    let distance_km = distance_km(own, target_coords);
+
+    Ok(GeoEstimate {
+        distance_km,
+        min_rtt_ms: 2.0 * distance_km / FIBER_KM_PER_MS,
+        target_place,
+    })
+}
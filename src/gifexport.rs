@@ -0,0 +1,232 @@
+use crate::app::{App, HostStats};
+use anyhow::Result;
+use chrono::Local;
+use gif::{Encoder, Frame, Repeat};
+use ratatui::style::Color;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 240;
+const MARGIN_LEFT: usize = 40;
+const MARGIN: usize = 10;
+const FRAMES: usize = 40;
+const FRAME_DELAY: u16 = 5;
+const FINAL_DELAY: u16 = 300;
+
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const LABEL: u8 = 2;
+const TARGET: u8 = 3;
+const TARGET_LOSS: u8 = 4;
+const GATEWAY: u8 = 5;
+const GATEWAY_LOSS: u8 = 6;
+
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+pub fn rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        Color::Red => [205, 49, 49],
+        Color::Green => [13, 188, 121],
+        Color::Yellow => [229, 229, 16],
+        Color::Blue => [36, 114, 200],
+        Color::Magenta => [188, 63, 188],
+        Color::Cyan => [17, 168, 205],
+        Color::Gray => [180, 180, 180],
+        Color::DarkGray => [102, 102, 102],
+        Color::LightRed => [241, 76, 76],
+        Color::LightGreen => [35, 209, 139],
+        Color::LightYellow => [245, 245, 67],
+        Color::LightBlue => [59, 142, 234],
+        Color::LightMagenta => [214, 112, 214],
+        Color::LightCyan => [41, 184, 219],
+        Color::Black => [0, 0, 0],
+        _ => [229, 229, 229],
+    }
+}
+
+struct Canvas {
+    pixels: Vec<u8>,
+    y_limit: f64,
+    window: (f64, f64),
+}
+
+impl Canvas {
+    fn set(&mut self, x: i64, y: i64, color: u8) {
+        if (0..WIDTH as i64).contains(&x) && (0..HEIGHT as i64).contains(&y) {
+            self.pixels[y as usize * WIDTH + x as usize] = color;
+        }
+    }
+
+    fn x_for(&self, t: f64) -> i64 {
+        let span = (self.window.1 - self.window.0).max(1.0);
+        MARGIN_LEFT as i64 + ((t - self.window.0) / span * (WIDTH - MARGIN_LEFT - MARGIN) as f64) as i64
+    }
+
+    fn y_for(&self, ms: f64) -> i64 {
+        let plot_height = (HEIGHT - 2 * MARGIN) as f64;
+        (HEIGHT - MARGIN) as i64 - (ms.min(self.y_limit) / self.y_limit * plot_height) as i64
+    }
+
+    fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), color: u8) {
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+
+        loop {
+            self.set(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    fn number(&mut self, value: u64, right: i64, top: i64) {
+        let text = value.to_string();
+        let mut x = right - text.len() as i64 * 8;
+        for digit in text.bytes().map(|b| (b - b'0') as usize) {
+            for (row, bits) in DIGITS[digit].iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        for (px, py) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                            self.set(x + col * 2 + px, top + row as i64 * 2 + py, LABEL);
+                        }
+                    }
+                }
+            }
+            x += 8;
+        }
+    }
+
+    fn axes(&mut self) {
+        for step in 0..=4 {
+            let ms = self.y_limit * step as f64 / 4.0;
+            let y = self.y_for(ms);
+            for x in MARGIN_LEFT as i64..(WIDTH - MARGIN) as i64 {
+                self.set(x, y, GRID);
+            }
+            self.number(ms.round() as u64, MARGIN_LEFT as i64 - 6, y - 5);
+        }
+    }
+
+    fn series(&mut self, trace: &Trace, until: f64, color: u8, loss_color: u8) {
+        let from = self.window.0;
+        let visible = |t: f64| t >= from && t <= until;
+
+        for t in trace.losses.iter().filter(|t| visible(**t)) {
+            let x = self.x_for(*t);
+            for y in MARGIN as i64..(HEIGHT - MARGIN) as i64 {
+                self.set(x, y, loss_color);
+            }
+        }
+
+        let points: Vec<(i64, i64)> = trace
+            .points
+            .iter()
+            .filter(|(t, _)| visible(*t))
+            .map(|&(t, ms)| (self.x_for(t), self.y_for(ms)))
+            .collect();
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], color);
+        }
+        if let [single] = points[..] {
+            self.set(single.0, single.1, color);
+        }
+    }
+}
+
+// The samples of one host in the exported window.
+struct Trace {
+    points: Vec<(f64, f64)>,
+    losses: Vec<f64>,
+}
+
+impl Trace {
+    fn of(stats: &HostStats, window: (f64, f64)) -> Self {
+        let inside = |t: f64| t >= window.0 && t <= window.1;
+        Trace {
+            points: stats.points.range(window).iter().filter(|(t, _)| inside(*t)).copied().collect(),
+            losses: stats.loss_points.range(window).iter().map(|(t, _)| *t).filter(|t| inside(*t)).collect(),
+        }
+    }
+}
+
+// Everything a GIF needs, copied out of the App so the frames can be encoded
+// on another thread while the TUI keeps running.
+pub struct Snapshot {
+    palette: Vec<u8>,
+    y_limit: f64,
+    window: (f64, f64),
+    target: Trace,
+    gateway: Option<Trace>,
+}
+
+pub fn snapshot(app: &App) -> Snapshot {
+    let end = (app.recorded_duration - app.scroll_offset_seconds).max(0.0);
+    let window = ((end - app.zoom_window_seconds).max(0.0), end);
+    let target = Trace::of(&app.net_stats, window);
+    let gateway = app.gw_stats.as_ref().filter(|_| app.show_gateway).map(|gw| Trace::of(gw, window));
+
+    let peak = |trace: &Trace| trace.points.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let max = peak(&target).max(gateway.as_ref().map(peak).unwrap_or(0.0));
+    let y_limit = if max > 90.0 { max * 1.1 } else { 100.0 };
+
+    let colors = app.theme.palette();
+    let mut palette = Vec::new();
+    for color in [Color::Black, Color::DarkGray, colors.muted, colors.target, colors.target_loss, colors.gateway, colors.gateway_loss] {
+        palette.extend_from_slice(&rgb(color));
+    }
+
+    Snapshot { palette, y_limit, window, target, gateway }
+}
+
+impl Snapshot {
+    pub fn write(self, directory: &Path) -> Result<PathBuf> {
+        let Snapshot { palette, y_limit, window, target, gateway } = self;
+
+        fs::create_dir_all(directory)?;
+        let path = directory.join(format!("vasili_{}_chart.gif", Local::now().format("%Y-%m-%d_%H-%M-%S")));
+        let mut encoder = Encoder::new(File::create(&path)?, WIDTH as u16, HEIGHT as u16, &palette)?;
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        for i in 1..=FRAMES {
+            let until = window.0 + (window.1 - window.0) * i as f64 / FRAMES as f64;
+            let mut canvas = Canvas { pixels: vec![BACKGROUND; WIDTH * HEIGHT], y_limit, window };
+
+            canvas.axes();
+            if let Some(gw) = &gateway {
+                canvas.series(gw, until, GATEWAY, GATEWAY_LOSS);
+            }
+            canvas.series(&target, until, TARGET, TARGET_LOSS);
+
+            let mut frame = Frame::from_indexed_pixels(WIDTH as u16, HEIGHT as u16, canvas.pixels, None);
+            frame.delay = if i == FRAMES { FINAL_DELAY } else { FRAME_DELAY };
+            encoder.write_frame(&frame)?;
+        }
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,480 @@
+use crate::diag;
+use crate::locale;
+use crate::rules::GRADES;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// How a host's numbers become a grade. The built-in profiles are threshold
+// tables tuned for different uses, `--grade-command` hands the numbers to a
+// program of the user's own, for organizations with a policy of their own.
+
+// What a grade is decided on.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct GradeInput {
+    pub lan: bool,
+    pub samples: u64,
+    pub loss_percent: f64,
+    pub p50: f64,
+    pub p99: f64,
+    // The average difference between consecutive replies.
+    pub jitter: f64,
+    // With it, only the part of P99 above it counts, so a far-away server is
+    // graded on its stability rather than its distance.
+    pub expected_ms: Option<f64>,
+}
+
+impl GradeInput {
+    fn graded_p99(&self) -> f64 {
+        match self.expected_ms {
+            Some(expected) => (self.p99 - expected).max(0.0),
+            None => self.p99,
+        }
+    }
+}
+
+// Asked while drawing and while handling replies, so neither call may block.
+// `host` tells the hosts apart for providers that answer asynchronously.
+pub trait GradeProvider: Send + Sync {
+    // One of S, A, B, C and F.
+    fn grade(&self, host: &str, input: &GradeInput) -> &'static str;
+    // Why, for the grade popup.
+    fn explain(&self, host: &str, input: &GradeInput) -> Vec<String>;
+    // The grade for the session summary, which may wait for the answer.
+    fn final_grade(&self, host: &str, input: &GradeInput) -> &'static str {
+        self.grade(host, input)
+    }
+}
+
+// The grading thresholds, worst grade first: a host drops to the first grade
+// whose loss, P99 or jitter it reaches, and is S if it reaches none. A loss of
+// 0.0 stands for any loss at all, None for a grade that ignores loss or jitter.
+struct GradeStep {
+    grade: &'static str,
+    loss: Option<f64>,
+    p99: f64,
+    jitter: Option<f64>,
+}
+
+impl GradeStep {
+    fn loss_reached(&self, loss: f64) -> bool {
+        match self.loss {
+            Some(0.0) => loss > 0.0,
+            Some(limit) => loss >= limit,
+            None => false,
+        }
+    }
+
+    fn p99_reached(&self, p99: f64) -> bool {
+        p99 >= self.p99
+    }
+
+    fn jitter_reached(&self, jitter: f64) -> bool {
+        self.jitter.is_some_and(|limit| jitter >= limit)
+    }
+}
+
+const GENERAL_GRADES: [GradeStep; 4] = [
+    GradeStep { grade: "F", loss: Some(5.0), p99: 150.0, jitter: None },
+    GradeStep { grade: "C", loss: Some(2.0), p99: 100.0, jitter: None },
+    GradeStep { grade: "B", loss: Some(0.5), p99: 70.0, jitter: None },
+    GradeStep { grade: "A", loss: Some(0.0), p99: 40.0, jitter: None },
+];
+
+// Competitive games notice every spike and every lost packet.
+const GAMING_GRADES: [GradeStep; 4] = [
+    GradeStep { grade: "F", loss: Some(2.0), p99: 100.0, jitter: Some(20.0) },
+    GradeStep { grade: "C", loss: Some(1.0), p99: 70.0, jitter: Some(10.0) },
+    GradeStep { grade: "B", loss: Some(0.2), p99: 45.0, jitter: Some(5.0) },
+    GradeStep { grade: "A", loss: Some(0.0), p99: 25.0, jitter: Some(3.0) },
+];
+
+// Calls hide a little loss and delay, but jitter beyond the jitter buffer
+// and round trips past 300 ms (150 ms each way, ITU-T G.114) are heard.
+const VOIP_GRADES: [GradeStep; 4] = [
+    GradeStep { grade: "F", loss: Some(3.0), p99: 300.0, jitter: Some(30.0) },
+    GradeStep { grade: "C", loss: Some(1.0), p99: 200.0, jitter: Some(20.0) },
+    GradeStep { grade: "B", loss: Some(0.5), p99: 150.0, jitter: Some(10.0) },
+    GradeStep { grade: "A", loss: Some(0.1), p99: 100.0, jitter: Some(5.0) },
+];
+
+// A LAN host is the same link whatever runs over it.
+const LAN_GRADES: [GradeStep; 4] = [
+    GradeStep { grade: "F", loss: Some(1.0), p99: 50.0, jitter: None },
+    GradeStep { grade: "C", loss: Some(0.0), p99: 25.0, jitter: None },
+    GradeStep { grade: "B", loss: None, p99: 10.0, jitter: None },
+    GradeStep { grade: "A", loss: None, p99: 5.0, jitter: None },
+];
+
+pub struct Thresholds {
+    name: &'static str,
+    wan: &'static [GradeStep; 4],
+}
+
+static GENERAL: Thresholds = Thresholds { name: "general", wan: &GENERAL_GRADES };
+static GAMING: Thresholds = Thresholds { name: "gaming", wan: &GAMING_GRADES };
+static VOIP: Thresholds = Thresholds { name: "voip", wan: &VOIP_GRADES };
+
+pub fn general() -> Arc<dyn GradeProvider> {
+    Arc::new(&GENERAL)
+}
+
+// general, gaming or voip, see --grading.
+pub fn profile(name: &str) -> Arc<dyn GradeProvider> {
+    match name.to_ascii_lowercase().as_str() {
+        "gaming" => Arc::new(&GAMING),
+        "voip" => Arc::new(&VOIP),
+        _ => general(),
+    }
+}
+
+impl Thresholds {
+    fn steps(&self, input: &GradeInput) -> &'static [GradeStep; 4] {
+        if input.lan { &LAN_GRADES } else { self.wan }
+    }
+
+    // Index of the grade step the host has reached, 4 for S.
+    fn index(&self, input: &GradeInput) -> usize {
+        let (loss, p99) = (input.loss_percent, input.graded_p99());
+        let steps = self.steps(input);
+        steps.iter().position(|s| s.loss_reached(loss) || s.p99_reached(p99) || s.jitter_reached(input.jitter)).unwrap_or(steps.len())
+    }
+}
+
+impl GradeProvider for &'static Thresholds {
+    fn grade(&self, _host: &str, input: &GradeInput) -> &'static str {
+        self.steps(input).get(self.index(input)).map_or("S", |s| s.grade)
+    }
+
+    // Which threshold decided the grade and how far each metric is from the
+    // next better and the next worse grade.
+    fn explain(&self, _host: &str, input: &GradeInput) -> Vec<String> {
+        let (loss, p99, jitter) = (input.loss_percent, input.graded_p99(), input.jitter);
+        let steps = self.steps(input);
+        let index = self.index(input);
+        let loss_limit = |limit: f64| if limit == 0.0 { "> 0%".to_string() } else { format!("≥ {}", locale::percent(limit, 1)) };
+        let mut lines = Vec::new();
+
+        match steps.get(index) {
+            Some(step) => {
+                let mut reasons = Vec::new();
+                if let Some(limit) = step.loss.filter(|_| step.loss_reached(loss)) {
+                    reasons.push(format!("loss {} {}", locale::percent(loss, 2), loss_limit(limit)));
+                }
+                if step.p99_reached(p99) {
+                    reasons.push(format!("P99 {} ≥ {}", locale::ms(p99, 1), locale::ms(step.p99, 0)));
+                }
+                if let Some(limit) = step.jitter.filter(|_| step.jitter_reached(jitter)) {
+                    reasons.push(format!("jitter {} ≥ {}", locale::ms(jitter, 1), locale::ms(limit, 0)));
+                }
+                lines.push(format!("{} because {}", step.grade, reasons.join(" and ")));
+            }
+            None => {
+                let best = &steps[steps.len() - 1];
+                let loss = match best.loss {
+                    Some(0.0) => "no loss and ".to_string(),
+                    Some(limit) => format!("loss {} < {} and ", locale::percent(loss, 2), locale::percent(limit, 1)),
+                    None => String::new(),
+                };
+                let jitter = best.jitter.map(|limit| format!(", jitter {} < {}", locale::ms(jitter, 1), locale::ms(limit, 0))).unwrap_or_default();
+                lines.push(format!("S because {}P99 {} < {}{}", loss, locale::ms(p99, 1), locale::ms(best.p99, 0), jitter));
+            }
+        }
+        if let Some(expected) = input.expected_ms {
+            lines.push(format!("P99 counted above the expected {}: {} - {}", locale::ms(expected, 0), locale::ms(input.p99, 1), locale::ms(expected, 0)));
+        }
+        if input.lan {
+            lines.push("Graded as a LAN host, with tighter thresholds".to_string());
+        } else if self.name != GENERAL.name {
+            lines.push(format!("Graded for {}", self.name));
+        }
+
+        // Better: every threshold of the current step has to be cleared.
+        if let Some(step) = steps.get(index) {
+            let better = steps.get(index + 1).map_or("S", |s| s.grade);
+            let mut needs = Vec::new();
+            if let Some(limit) = step.loss.filter(|_| step.loss_reached(loss)) {
+                needs.push(if limit == 0.0 {
+                    "no loss at all".to_string()
+                } else {
+                    format!("loss below {} ({} points less)", locale::percent(limit, 1), locale::num(loss - limit, 2))
+                });
+            }
+            if step.p99_reached(p99) {
+                needs.push(format!("P99 below {} ({} less)", locale::ms(step.p99, 0), locale::ms(p99 - step.p99, 1)));
+            }
+            if let Some(limit) = step.jitter.filter(|_| step.jitter_reached(jitter)) {
+                needs.push(format!("jitter below {} ({} less)", locale::ms(limit, 0), locale::ms(jitter - limit, 1)));
+            }
+            lines.push(format!("To reach {}: {}", better, needs.join(", ")));
+        }
+
+        // Worse: whichever threshold of the step above comes first.
+        if let Some(step) = index.checked_sub(1).map(|i| &steps[i]) {
+            let mut margins = vec![format!("P99 {} more", locale::ms(step.p99 - p99, 1))];
+            match step.loss {
+                Some(0.0) => margins.push("any loss".to_string()),
+                Some(limit) => margins.push(format!("loss {} points more", locale::num(limit - loss, 2))),
+                None => {}
+            }
+            if let Some(limit) = step.jitter {
+                margins.push(format!("jitter {} more", locale::ms(limit - jitter, 1)));
+            }
+            lines.push(format!("Drops to {} with: {}", step.grade, margins.join(" or ")));
+        }
+        lines
+    }
+}
+
+// A command that gets the GradeInput as JSON on stdin and prints the grade
+// on its first line, and optionally why on the lines after it.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+// Requests that come in this soon after one another are run as one, with the
+// latest numbers of each host.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// The numbers change with every sample, rounded they mostly do not, so the
+// command runs when the grade could change rather than on every frame.
+const MAX_CACHED: usize = 1024;
+
+type Decided = (&'static str, Vec<String>);
+
+#[derive(Default)]
+struct Answers {
+    cache: HashMap<String, Decided>,
+    // The latest answer per host, shown while newer numbers are graded.
+    last: HashMap<String, Decided>,
+    // The key last handed to the worker per host, so a frame does not queue
+    // the same numbers again.
+    requested: HashMap<String, String>,
+    failed: bool,
+}
+
+impl Answers {
+    fn store(&mut self, host: &str, key: String, decided: Decided) {
+        if self.cache.len() >= MAX_CACHED {
+            self.cache.clear();
+        }
+        self.cache.insert(key, decided.clone());
+        self.last.insert(host.to_string(), decided);
+    }
+}
+
+// Grades are asked for while drawing and while handling replies, neither of
+// which can wait for a process, so the command runs on a worker thread and
+// the providers answer from what it published last.
+struct GradeCommand {
+    command: String,
+    answers: Arc<Mutex<Answers>>,
+    tx: Mutex<mpsc::Sender<(String, GradeInput)>>,
+}
+
+// See --grade-command.
+pub fn command(command: String) -> Arc<dyn GradeProvider> {
+    let answers = Arc::new(Mutex::new(Answers::default()));
+    let (tx, rx) = mpsc::channel();
+    let worker = (command.clone(), answers.clone());
+    thread::spawn(move || run_worker(&worker.0, &worker.1, rx));
+    Arc::new(GradeCommand { command, answers, tx: Mutex::new(tx) })
+}
+
+fn key(input: &GradeInput) -> String {
+    format!("{}:{:.1}:{:.0}:{:.0}:{:.0}", input.lan, input.loss_percent, input.p50, input.p99, input.jitter)
+}
+
+fn run(command: &str, input: &GradeInput) -> Result<Decided, String> {
+    let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut child = Command::new(shell.0)
+        .args([shell.1, command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(input).map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(json.as_bytes());
+    }
+
+    // The output is read on a thread of its own, so the wait for it can time
+    // out and the command be killed.
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let (done_tx, done_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = String::new();
+        let _ = done_tx.send(stdout.read_to_string(&mut output).map(|_| output));
+    });
+    let output = match done_rx.recv_timeout(COMMAND_TIMEOUT) {
+        Ok(output) => {
+            let _ = child.wait();
+            output.map_err(|e| e.to_string())?
+        }
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("no answer within {}s", COMMAND_TIMEOUT.as_secs()));
+        }
+    };
+
+    let mut lines = output.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().unwrap_or_default().to_ascii_uppercase();
+    let grade = GRADES.iter().find(|g| **g == first).ok_or_else(|| format!("printed '{}' instead of one of {}", first, GRADES.join(", ")))?;
+    Ok((grade, lines.map(str::to_string).collect()))
+}
+
+fn run_worker(command: &str, answers: &Mutex<Answers>, rx: mpsc::Receiver<(String, GradeInput)>) {
+    // Ends when the provider is dropped.
+    while let Ok(first) = rx.recv() {
+        let mut latest = HashMap::from([first]);
+        let deadline = Instant::now() + DEBOUNCE;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match rx.recv_timeout(left) {
+                Ok((host, input)) => {
+                    latest.insert(host, input);
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        for (host, input) in latest {
+            if answers.lock().unwrap().failed {
+                return;
+            }
+            let key = key(&input);
+            if let Some(decided) = answers.lock().unwrap().cache.get(&key).cloned() {
+                answers.lock().unwrap().last.insert(host, decided);
+                continue;
+            }
+            match run(command, &input) {
+                Ok(decided) => answers.lock().unwrap().store(&host, key, decided),
+                Err(e) => {
+                    // Said once, the general profile grades from then on.
+                    answers.lock().unwrap().failed = true;
+                    diag::warn(format!("Grade command '{}' failed, grading with the general profile: {}", command, e));
+                }
+            }
+        }
+    }
+}
+
+impl GradeCommand {
+    fn fallback(input: &GradeInput, why: &str) -> Decided {
+        let grade = (&GENERAL).grade("", input);
+        (grade, vec![format!("{}, {} by the general profile", why, grade)])
+    }
+
+    // Never waits: the cached answer for these numbers, otherwise the last
+    // one for the host while the worker grades the new numbers.
+    fn decide(&self, host: &str, input: &GradeInput) -> Decided {
+        let key = key(input);
+        let mut answers = self.answers.lock().unwrap();
+        if answers.failed {
+            return Self::fallback(input, "The grade command failed");
+        }
+        if let Some(decided) = answers.cache.get(&key) {
+            return decided.clone();
+        }
+        if answers.requested.get(host) != Some(&key) {
+            answers.requested.insert(host.to_string(), key);
+            let _ = self.tx.lock().unwrap().send((host.to_string(), *input));
+        }
+        match answers.last.get(host) {
+            Some(decided) => decided.clone(),
+            None => Self::fallback(input, "Waiting for the grade command"),
+        }
+    }
+}
+
+impl GradeProvider for GradeCommand {
+    fn grade(&self, host: &str, input: &GradeInput) -> &'static str {
+        self.decide(host, input).0
+    }
+
+    fn explain(&self, host: &str, input: &GradeInput) -> Vec<String> {
+        let (grade, mut lines) = self.decide(host, input);
+        if lines.is_empty() {
+            lines.push(format!("{} by {}", grade, self.command));
+        }
+        lines
+    }
+
+    // The summary is written once at the end, it waits for the command.
+    fn final_grade(&self, host: &str, input: &GradeInput) -> &'static str {
+        let key = key(input);
+        {
+            let answers = self.answers.lock().unwrap();
+            if answers.failed {
+                return (&GENERAL).grade(host, input);
+            }
+            if let Some(decided) = answers.cache.get(&key) {
+                return decided.0;
+            }
+        }
+        match run(&self.command, input) {
+            Ok(decided) => {
+                let grade = decided.0;
+                self.answers.lock().unwrap().store(host, key, decided);
+                grade
+            }
+            Err(e) => {
+                self.answers.lock().unwrap().failed = true;
+                diag::warn(format!("Grade command '{}' failed, grading with the general profile: {}", self.command, e));
+                (&GENERAL).grade(host, input)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn input(p99: f64) -> GradeInput {
+        GradeInput { lan: false, samples: 500, loss_percent: 0.0, p50: 10.0, p99, jitter: 1.0, expected_ms: None }
+    }
+
+    #[test]
+    fn a_slow_command_does_not_hold_up_the_caller() {
+        let grading = command("sleep 1; echo B; echo too slow".to_string());
+        let started = Instant::now();
+        // The general profile answers until the command has.
+        assert_eq!(grading.grade("Target", &input(20.0)), "S");
+        assert!(grading.explain("Target", &input(20.0))[0].starts_with("Waiting for the grade command"));
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while grading.grade("Target", &input(20.0)) != "B" {
+            assert!(Instant::now() < deadline, "the command never answered");
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(grading.explain("Target", &input(20.0)), vec!["too slow"]);
+        // New numbers keep the last answer for the host meanwhile.
+        assert_eq!(grading.grade("Target", &input(30.0)), "B");
+        assert_eq!(grading.grade("Gateway", &input(30.0)), "S");
+    }
+
+    #[test]
+    fn the_summary_waits_for_the_command() {
+        let grading = command("cat > /dev/null; echo c".to_string());
+        assert_eq!(grading.final_grade("Target", &input(20.0)), "C");
+        assert_eq!(grading.grade("Target", &input(20.0)), "C");
+    }
+
+    #[test]
+    fn a_hanging_command_is_killed_and_the_general_profile_takes_over() {
+        let grading = command("sleep 30".to_string());
+        let started = Instant::now();
+        assert_eq!(grading.final_grade("Target", &input(20.0)), "S");
+        assert!(started.elapsed() < COMMAND_TIMEOUT + Duration::from_secs(1));
+        assert!(grading.explain("Target", &input(20.0))[0].starts_with("The grade command failed"));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::app::App;
+use crate::diag;
+use chrono::Local;
+use std::{fs, path::PathBuf, time::Duration};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const POST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Heartbeat {
+    file: Option<PathBuf>,
+    url: Option<String>,
+    last_samples: u64,
+}
+
+impl Heartbeat {
+    pub fn new(file: Option<PathBuf>, url: Option<String>) -> Option<Self> {
+        (file.is_some() || url.is_some()).then_some(Self { file, url, last_samples: 0 })
+    }
+
+    fn total_samples(app: &App) -> u64 {
+        app.net_stats.total_count + app.gw_stats.as_ref().map(|gw| gw.total_count).unwrap_or(0)
+    }
+
+    pub fn beat(&mut self, app: &App) {
+        let samples = Self::total_samples(app);
+        if samples == self.last_samples {
+            diag::warn("Heartbeat skipped: no probe results since the last heartbeat");
+            return;
+        }
+        self.last_samples = samples;
+
+        let timestamp = Local::now().to_rfc3339();
+
+        if let Some(path) = &self.file
+            && let Err(e) = fs::write(path, format!("{} samples={}\n", timestamp, samples))
+        {
+            diag::warn(format!("Could not write heartbeat file {}: {}", path.display(), e));
+        }
+
+        if let Some(url) = self.url.clone() {
+            let body = serde_json::json!({
+                "timestamp": timestamp,
+                "pid": std::process::id(),
+                "target": app.net_stats.display_name,
+                "samples": samples,
+            });
+
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = ureq::post(&url).timeout(POST_TIMEOUT).send_json(body) {
+                    diag::warn(format!("Heartbeat POST to {} failed: {}", url, e));
+                }
+            });
+        }
+    }
+}
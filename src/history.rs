@@ -0,0 +1,270 @@
+use crate::diag;
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// The chart data of a long session. Only the newest samples (the retention,
+// `--retention`) stay in memory; older ones move to a file in the cache dir,
+// so a week of probes costs a few MB of RAM and scrolling back still finds
+// them.
+
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+// Samples beyond the capacity are dropped in chunks of this share of it, so
+// dropping stays cheap and the rest stays one slice for the chart.
+const SLACK_DIVISOR: usize = 16;
+
+// A time and a value: 16 bytes on disk, little-endian.
+const RECORD: usize = 16;
+
+// Keeps the newest `capacity` items, unbounded until a capacity is set.
+pub struct Ring<T> {
+    items: Vec<T>,
+    capacity: usize,
+}
+
+impl<T> Ring<T> {
+    pub fn new() -> Self {
+        Ring { items: Vec::new(), capacity: usize::MAX }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+    }
+
+    // Returns what fell out at the front, oldest first.
+    pub fn push(&mut self, item: T) -> Vec<T> {
+        self.items.push(item);
+        let slack = (self.capacity / SLACK_DIVISOR).max(1);
+        if self.items.len() < self.capacity.saturating_add(slack) {
+            return Vec::new();
+        }
+        let excess = self.items.len() - self.capacity;
+        self.items.drain(..excess).collect()
+    }
+}
+
+impl<T> Default for Ring<T> {
+    fn default() -> Self {
+        Ring::new()
+    }
+}
+
+impl<T> Deref for Ring<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+// Where the samples dropped from memory went. The file is removed with it.
+struct Spill {
+    path: PathBuf,
+    file: File,
+    records: u64,
+}
+
+impl Spill {
+    fn create(path: PathBuf) -> std::io::Result<Spill> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        Ok(Spill { path, file, records: 0 })
+    }
+
+    fn append(&mut self, points: &[(f64, f64)]) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(points.len() * RECORD);
+        for (t, v) in points {
+            bytes.extend_from_slice(&t.to_le_bytes());
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        (&self.file).seek(SeekFrom::End(0))?;
+        (&self.file).write_all(&bytes)?;
+        self.records += points.len() as u64;
+        Ok(())
+    }
+
+    fn time_at(&self, index: u64) -> std::io::Result<f64> {
+        let mut bytes = [0u8; 8];
+        (&self.file).seek(SeekFrom::Start(index * RECORD as u64))?;
+        (&self.file).read_exact(&mut bytes)?;
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    // The records from the last one before `start` on, up to the first one
+    // after `end`, so a line drawn from them reaches the edges.
+    fn read(&self, (start, end): (f64, f64)) -> std::io::Result<Vec<(f64, f64)>> {
+        // The first record at or after `start`, found without reading the file.
+        let (mut low, mut high) = (0, self.records);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.time_at(mid)? < start {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        (&self.file).seek(SeekFrom::Start(low.saturating_sub(1) * RECORD as u64))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut points = Vec::new();
+        let mut bytes = [0u8; RECORD];
+        for _ in low.saturating_sub(1)..self.records {
+            reader.read_exact(&mut bytes)?;
+            let t = f64::from_le_bytes(bytes[..8].try_into().unwrap());
+            let v = f64::from_le_bytes(bytes[8..].try_into().unwrap());
+            points.push((t, v));
+            if t > end {
+                break;
+            }
+        }
+        Ok(points)
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        // Only goes once the last spill of the session is gone.
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+// A chart line: (seconds since the start, value), in time order.
+#[derive(Default)]
+pub struct Series {
+    ring: Ring<(f64, f64)>,
+    spill: Option<Spill>,
+}
+
+impl Series {
+    pub fn new() -> Self {
+        Series::default()
+    }
+
+    // Caps the samples in memory, the older ones go to `path`.
+    pub fn retain(&mut self, capacity: usize, path: PathBuf) {
+        self.ring.set_capacity(capacity);
+        match Spill::create(path) {
+            Ok(spill) => self.spill = Some(spill),
+            Err(e) => diag::warn(format!("Could not keep older chart data on disk, scrolling back ends at the retention: {}", e)),
+        }
+    }
+
+    pub fn push(&mut self, point: (f64, f64)) {
+        let dropped = self.ring.push(point);
+        if dropped.is_empty() {
+            return;
+        }
+        if let Some(spill) = &mut self.spill
+            && let Err(e) = spill.append(&dropped)
+        {
+            diag::warn(format!("Could not write older chart data to {}, scrolling back ends at the retention: {}", spill.path.display(), e));
+            self.spill = None;
+        }
+    }
+
+    // The samples in [start, end] plus one on either side, from memory while
+    // the window is still there, from disk when it reaches further back.
+    pub fn range(&self, (start, end): (f64, f64)) -> Cow<'_, [(f64, f64)]> {
+        let first = self.ring.partition_point(|(t, _)| *t < start);
+        let on_disk = self.spill.as_ref().filter(|spill| spill.records > 0);
+        match on_disk {
+            Some(spill) if first == 0 => {
+                let mut points = match spill.read((start, end)) {
+                    Ok(points) => points,
+                    Err(e) => {
+                        diag::warn(format!("Could not read older chart data from {}: {}", spill.path.display(), e));
+                        Vec::new()
+                    }
+                };
+                if points.last().is_none_or(|(t, _)| *t <= end) {
+                    let last = self.ring.partition_point(|(t, _)| *t <= end);
+                    points.extend_from_slice(&self.ring[..(last + 1).min(self.ring.len())]);
+                }
+                Cow::Owned(points)
+            }
+            _ => {
+                let last = self.ring.partition_point(|(t, _)| *t <= end);
+                Cow::Borrowed(&self.ring[first.saturating_sub(1)..(last + 1).min(self.ring.len())])
+            }
+        }
+    }
+}
+
+impl Deref for Series {
+    type Target = [(f64, f64)];
+
+    fn deref(&self) -> &[(f64, f64)] {
+        &self.ring
+    }
+}
+
+// The directory the spills of this process go to.
+pub fn spill_dir() -> PathBuf {
+    dirs::cache_dir().unwrap_or_else(std::env::temp_dir).join("vasili").join(format!("history-{}", std::process::id()))
+}
+
+// Spills of sessions that were killed before they could remove them.
+#[cfg(target_os = "linux")]
+pub fn remove_stale() {
+    let Some(parent) = spill_dir().parent().map(Path::to_path_buf) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(parent) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(pid) = name.strip_prefix("history-")
+            && !Path::new("/proc").join(pid).exists()
+        {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn remove_stale() {}
+
+// Largest-triangle-three-buckets: thins a line down to `threshold` points
+// that keep its shape, spikes included, so a window of tens of thousands of
+// samples draws as fast as a short one and does not alias on the canvas.
+pub fn downsample(points: &[(f64, f64)], threshold: usize) -> Cow<'_, [(f64, f64)]> {
+    if threshold < 3 || points.len() <= threshold {
+        return Cow::Borrowed(points);
+    }
+
+    // The first and last point stay, the rest is split into equal buckets.
+    let every = (points.len() - 2) as f64 / (threshold - 2) as f64;
+    let bucket = |i: usize| ((i as f64 * every) as usize + 1).min(points.len() - 1);
+    let mut sampled = Vec::with_capacity(threshold);
+    let mut a = points[0];
+    sampled.push(a);
+    for i in 0..threshold - 2 {
+        // The average of the next bucket stands in for its point.
+        let next = &points[bucket(i + 1)..bucket(i + 2).max(bucket(i + 1) + 1).min(points.len())];
+        let (sum_t, sum_v) = next.iter().fold((0.0, 0.0), |(t, v), p| (t + p.0, v + p.1));
+        let avg = (sum_t / next.len() as f64, sum_v / next.len() as f64);
+
+        // Of this bucket, the point spanning the largest triangle with the
+        // one picked before and that average.
+        let area = |p: &(f64, f64)| ((a.0 - avg.0) * (p.1 - a.1) - (a.0 - p.0) * (avg.1 - a.1)).abs();
+        let current = &points[bucket(i)..bucket(i + 1)];
+        if let Some(&picked) = current.iter().max_by(|x, y| area(x).total_cmp(&area(y))) {
+            sampled.push(picked);
+            a = picked;
+        }
+    }
+    sampled.push(points[points.len() - 1]);
+    Cow::Owned(sampled)
+}
@@ -0,0 +1,42 @@
+use crate::diag;
+use crossterm::event::{self, Event, KeyEvent};
+use std::{thread, time::Duration};
+use tokio::sync::mpsc;
+
+// How often the input thread looks whether the TUI is still listening.
+const POLL: Duration = Duration::from_millis(100);
+
+// Reads the terminal on a thread of its own for as long as the TUI runs and
+// forwards every key, so the main loop only waits on a channel: no blocking
+// task per poll, and no read on the async thread racing a poll still running.
+// The thread ends once the receiver is dropped.
+pub fn spawn() -> mpsc::Receiver<KeyEvent> {
+    let (tx, rx) = mpsc::channel(32);
+    let spawned = thread::Builder::new().name("vasili-input".to_string()).spawn(move || {
+        while !tx.is_closed() {
+            match event::poll(POLL) {
+                Ok(false) => {}
+                Ok(true) => match event::read() {
+                    Ok(Event::Key(key)) => {
+                        if tx.blocking_send(key).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        diag::error(format!("Could not read terminal input: {}", e));
+                        break;
+                    }
+                },
+                Err(e) => {
+                    diag::error(format!("Could not read terminal input: {}", e));
+                    break;
+                }
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        diag::error(format!("Could not start the input thread: {}", e));
+    }
+    rx
+}
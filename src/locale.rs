@@ -0,0 +1,237 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::sync::OnceLock;
+
+// How numbers and times read for people: the TUI, the console summaries,
+// `report`, `analyze`, `compare` and the Markdown summary. CSV, JSONL, the
+// databases, the API and `--once` keep '.' and ISO timestamps whatever the
+// locale, so they stay machine-readable.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locale {
+    decimal: char,
+    date: &'static str,
+    time: &'static str,
+}
+
+// Also what POSIX, C and unknown locales get: the formats Vasili always used.
+const ISO: Locale = Locale { decimal: '.', date: "%Y-%m-%d", time: "%H:%M:%S" };
+
+static CURRENT: OnceLock<Locale> = OnceLock::new();
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+// --units: the unit latencies are shown in. `auto` shows the sub-millisecond
+// values of LAN hosts (the gateway, a LAN target) in µs, which ms with one
+// decimal would all round to 0.2 or 0.3ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Ms,
+    Us,
+    Auto,
+}
+
+impl Unit {
+    pub fn next(self) -> Unit {
+        match self {
+            Unit::Ms => Unit::Us,
+            Unit::Us => Unit::Auto,
+            Unit::Auto => Unit::Ms,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Unit::Ms => "ms",
+            Unit::Us => "µs",
+            Unit::Auto => "auto",
+        }
+    }
+}
+
+// A unit and, with --precision, the decimals of every millisecond value.
+// Without it each place keeps its usual decimals (one for the stats, none
+// for thresholds); µs values have none.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Units {
+    pub unit: Unit,
+    pub precision: Option<usize>,
+}
+
+impl Units {
+    fn micro(&self, ms: f64, lan: bool) -> bool {
+        match self.unit {
+            Unit::Ms => false,
+            Unit::Us => true,
+            Unit::Auto => lan && ms.abs() < 1.0,
+        }
+    }
+
+    // Values shown together share the unit of the largest of them:
+    // "310/420/880µs" rather than "310µs/420µs/1.2ms".
+    pub fn join(&self, values: &[f64], decimals: usize, lan: bool) -> String {
+        let largest = values.iter().copied().fold(0.0, f64::max);
+        let (scale, decimals, suffix) = match self.micro(largest, lan) {
+            true => (1000.0, self.precision.unwrap_or(0), "µs"),
+            false => (1.0, self.precision.unwrap_or(decimals), "ms"),
+        };
+        let numbers: Vec<String> = values.iter().map(|value| num(value * scale, decimals)).collect();
+        format!("{}{}", numbers.join("/"), suffix)
+    }
+
+    pub fn format(&self, ms: f64, decimals: usize, lan: bool) -> String {
+        self.join(&[ms], decimals, lan)
+    }
+
+    // The title of a latency axis that goes up to `top`, and the labels of
+    // its middle and its top.
+    pub fn axis(&self, top: f64, lan: bool) -> (&'static str, [String; 2]) {
+        let (scale, title) = if self.micro(top, lan) { (1000.0, "µs") } else { (1.0, "ms") };
+        (title, [num(top / 2.0 * scale, 0), num(top * scale, 0)])
+    }
+}
+
+// The units of each TUI panel. 'U' and 'P' switch all of them at once.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PanelUnits {
+    // The legend and the latency axis.
+    pub chart: Units,
+    // The stats bars and the session summary screen.
+    pub stats: Units,
+    // The dashboard, path and round-robin tables.
+    pub table: Units,
+}
+
+impl PanelUnits {
+    pub fn uniform(units: Units) -> PanelUnits {
+        PanelUnits { chart: units, stats: units, table: units }
+    }
+
+    pub fn next_unit(&mut self) {
+        let unit = self.stats.unit.next();
+        for panel in [&mut self.chart, &mut self.stats, &mut self.table] {
+            panel.unit = unit;
+        }
+    }
+
+    // Usual decimals, then 0, 1 and 2.
+    pub fn next_precision(&mut self) {
+        let precision = match self.stats.precision {
+            None => Some(0),
+            Some(n) if n < 2 => Some(n + 1),
+            Some(_) => None,
+        };
+        for panel in [&mut self.chart, &mut self.stats, &mut self.table] {
+            panel.precision = precision;
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let precision = self.stats.precision.map(|n| format!(", {} decimals", n)).unwrap_or_default();
+        format!("{}{}", self.stats.unit.label(), precision)
+    }
+}
+
+impl Locale {
+    // A POSIX locale name or a language tag: `de_DE.UTF-8`, `fr-CH`, `en_US`.
+    pub fn parse(name: &str) -> Locale {
+        let name = name.split(['.', '@']).next().unwrap_or("").replace('-', "_");
+        let (language, region) = name.split_once('_').unwrap_or((&name, ""));
+        let (language, region) = (language.to_ascii_lowercase(), region.to_ascii_uppercase());
+
+        // Most of Europe writes 12,5 and a 24-hour clock.
+        let comma = |date| Locale { decimal: ',', date, time: "%H:%M:%S" };
+        match (language.as_str(), region.as_str()) {
+            ("en", "US") => Locale { decimal: '.', date: "%m/%d/%Y", time: "%I:%M:%S %p" },
+            ("en", "GB" | "IE" | "AU" | "NZ" | "IN") => Locale { decimal: '.', date: "%d/%m/%Y", time: "%H:%M:%S" },
+            ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "da" | "uk" | "tr" | "ro" | "hr" | "sl", _) => comma("%d.%m.%Y"),
+            ("fr" | "es" | "it" | "pt" | "el" | "ca", _) => comma("%d/%m/%Y"),
+            ("nl", _) => comma("%d-%m-%Y"),
+            ("sv", _) => comma("%Y-%m-%d"),
+            // Among others en_CA, ja, zh and ko, which write dates like this.
+            _ => ISO,
+        }
+    }
+
+    // LC_ALL wins over LC_NUMERIC, which wins over LANG, like in libc.
+    fn from_env() -> Locale {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+            .map(|name| Locale::parse(&name))
+            .next()
+            .unwrap_or(ISO)
+    }
+}
+
+// `--locale` or VASILI_LOCALE; `auto` (or nothing) reads the environment.
+pub fn init(name: Option<&str>) {
+    let locale = match name {
+        Some(name) if !name.eq_ignore_ascii_case("auto") => Locale::parse(name),
+        _ => Locale::from_env(),
+    };
+    let _ = CURRENT.set(locale);
+}
+
+fn current() -> Locale {
+    *CURRENT.get().unwrap_or(&ISO)
+}
+
+// --units and --precision for everything outside the TUI panels: the
+// console, `report`, `analyze`, `compare`, the Markdown summary and the
+// chart exports.
+pub fn init_units(units: Units) {
+    let _ = UNITS.set(units);
+}
+
+pub fn units() -> Units {
+    UNITS.get().copied().unwrap_or_default()
+}
+
+pub fn num(value: f64, decimals: usize) -> String {
+    let text = format!("{:.*}", decimals, value);
+    match current().decimal {
+        '.' => text,
+        decimal => text.replace('.', &decimal.to_string()),
+    }
+}
+
+pub fn ms(value: f64, decimals: usize) -> String {
+    units().format(value, decimals, false)
+}
+
+// A latency of a host that may be on the LAN, for `auto`.
+pub fn host_ms(value: f64, decimals: usize, lan: bool) -> String {
+    units().format(value, decimals, lan)
+}
+
+pub fn percent(value: f64, decimals: usize) -> String {
+    format!("{}%", num(value, decimals))
+}
+
+pub fn time<Tz: TimeZone>(at: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    at.format(current().time).to_string()
+}
+
+pub fn datetime<Tz: TimeZone>(at: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let locale = current();
+    format!("{} {}", at.format(locale.date), at.format(locale.time))
+}
+
+// A machine timestamp ("2026-10-16 21:04:05") as stored in summaries and
+// analyses, shown in the locale's format.
+pub fn timestamp(text: &str) -> String {
+    match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").ok().and_then(|t| Local.from_local_datetime(&t).single()) {
+        Some(at) => datetime(&at),
+        None => text.to_string(),
+    }
+}
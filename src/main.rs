@@ -1,51 +1,667 @@
+mod alerts;
+mod analyze;
+mod anomaly;
+mod api;
 mod app;
 mod args;
+mod baseline;
+mod bell;
+#[cfg(target_os = "linux")]
+mod bpf;
+mod chartexport;
+mod clock;
+mod compare;
+mod config;
+mod detector;
+mod diag;
+mod digest;
+mod dhcp;
+mod discord;
+mod dnscheck;
+mod dscp;
+mod echo;
+mod events;
+#[cfg(feature = "fake-ping")]
+mod fakeping;
+mod gate;
+mod geo;
+mod gifexport;
+mod grading;
+mod heartbeat;
+mod history;
+mod input;
+mod locale;
+mod markdown;
+mod nic;
+mod overlay;
+mod notify;
+#[cfg(target_os = "linux")]
+mod passive;
 mod pinger;
+mod pool;
+mod priority;
+mod readiness;
+mod reboot;
+mod redact;
+mod privileges;
+mod registry;
+mod replay;
+mod report;
+mod rules;
+mod scope;
+mod search;
+mod session;
+mod sinks;
+mod snapshot;
+mod status;
+mod theme;
+mod top;
+mod trace;
 mod ui;
 mod utils;
+mod validate;
 
-use anyhow::Result;
-use app::{App, PingRecord};
-use args::Args;
-use chrono::Local;
+use anyhow::{Context, Result};
+use app::App;
+use chartexport::ChartFormat;
+use bell::Bell;
+use clock::{Clock, SystemClock};
+use discord::Discord;
+use args::{Cli, Command, RunArgs};
 use clap::Parser;
+use config::{Config, LastRun, UiState};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyEventKind},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use default_net::get_default_gateway;
-use pinger::{PingUpdate, SourceType, run_pinger};
-use rand::seq::SliceRandom;
+use events::{EventKind, SystemEvent};
+use heartbeat::Heartbeat;
+use locale::Units;
+use overlay::BaselineOverlay;
+use readiness::ScheduledEvent;
+use pinger::{
+    ClientPool, PingUpdate, Probe, SourceType, run_a2s_pinger, run_cycle_pinger, run_dns_pinger,
+    run_echo_pinger, run_pinger, run_tcp_pinger, run_udp_pinger,
+};
+use pool::PoolHistory;
+use rand::{SeedableRng, rngs::StdRng};
+use gate::QualityGate;
+use registry::Registration;
+use rules::{Rule, Rules};
+use scope::Scope;
+use sinks::{OutputFormat, OutputOptions, Pipeline};
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Paragraph},
 };
 use std::net::ToSocketAddrs;
 use std::{
-    fs::OpenOptions,
-    io::{self, BufWriter},
-    net::IpAddr,
+    io,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
-use tokio::{signal, sync::mpsc};
+use tokio::{runtime::Handle, signal, sync::mpsc};
+
+const DEFAULT_DIVERGENCE_SECS: u64 = 5;
+const DEFAULT_CYCLE_INTERVAL: Duration = Duration::from_secs(1);
+const ONCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+fn spawn_pingers(
+    pool: &mut ClientPool,
+    target_ip: IpAddr,
+    probe: &Probe,
+    interval: Duration,
+    timeout: Duration,
+    tx: &mpsc::Sender<PingUpdate>,
+    events: &mpsc::Sender<SystemEvent>,
+) -> Result<()> {
+    let tx_net = tx.clone();
+    #[cfg(feature = "fake-ping")]
+    if let Some(profile) = fakeping::target() {
+        let timer = pool.probe_timer(interval);
+        tokio::spawn(async move {
+            fakeping::run_fake_pinger(profile, timer, timeout, SourceType::Target, tx_net).await;
+        });
+        return Ok(());
+    }
+
+    match probe.clone() {
+        Probe::Icmp => {
+            let (client, id, timer) = (pool.client_for(target_ip)?, pool.probe_id(), pool.probe_timer(interval));
+            tokio::spawn(async move {
+                run_pinger(client, target_ip, id, timer, timeout, SourceType::Target, tx_net).await;
+            });
+        }
+        Probe::Tcp(port) => {
+            let (target, timer) = (SocketAddr::new(target_ip, port), pool.probe_timer(interval));
+            tokio::spawn(async move {
+                run_tcp_pinger(target, timer, timeout, SourceType::Target, tx_net).await;
+            });
+        }
+        Probe::Udp(port) => {
+            let (target, session, timer) = (SocketAddr::new(target_ip, port), pool.probe_id(), pool.probe_timer(interval));
+            tokio::spawn(async move {
+                run_udp_pinger(target, session, timer, timeout, SourceType::Target, tx_net).await;
+            });
+        }
+        Probe::Echo(port) => {
+            let (target, session, timer, events) = (SocketAddr::new(target_ip, port), pool.probe_id(), pool.probe_timer(interval), events.clone());
+            tokio::spawn(async move {
+                run_echo_pinger(target, session, timer, timeout, SourceType::Target, tx_net, events).await;
+            });
+        }
+        Probe::A2s(port) => {
+            let (target, timer) = (SocketAddr::new(target_ip, port), pool.probe_timer(interval));
+            tokio::spawn(async move {
+                run_a2s_pinger(target, timer, timeout, SourceType::Target, tx_net).await;
+            });
+        }
+        Probe::Dns(name) => {
+            let (first_id, timer) = (pool.probe_id(), pool.probe_timer(interval));
+            tokio::spawn(async move {
+                run_dns_pinger(target_ip, name, first_id, timer, timeout, SourceType::Target, tx_net).await;
+            });
+        }
+    }
+    Ok(())
+}
 
-const TARGET_POOL: &[&str] = &[
-    "1.1.1.1",
-    "8.8.8.8",
-    "9.9.9.9",
-    "208.67.222.222",
-    "1.0.0.1",
-    "8.8.4.4",
-];
+fn spawn_gateway_pinger(
+    pool: &mut ClientPool,
+    gateway_ip: Option<IpAddr>,
+    use_arp: bool,
+    interval: Duration,
+    timeout: Duration,
+    tx: &mpsc::Sender<PingUpdate>,
+) -> Result<Option<&'static str>> {
+    let Some(gw_ip) = gateway_ip else {
+        return Ok(None);
+    };
+    let gw_interval = interval / 2;
+
+    #[cfg(feature = "fake-ping")]
+    if let Some(profile) = fakeping::gateway() {
+        let (tx_gw, timer) = (tx.clone(), pool.probe_timer(gw_interval));
+        tokio::spawn(async move {
+            fakeping::run_fake_pinger(profile, timer, timeout, SourceType::Gateway, tx_gw).await;
+        });
+        return Ok(Some("fake responder"));
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = use_arp;
+
+    #[cfg(target_os = "linux")]
+    if use_arp
+        && let IpAddr::V4(gw_v4) = gw_ip
+        && let Ok(prober) = pinger::ArpProber::new(gw_v4)
+    {
+        let (tx_gw, timer) = (tx.clone(), pool.probe_timer(gw_interval));
+        tokio::spawn(async move {
+            pinger::run_arp_pinger(prober, timer, timeout, SourceType::Gateway, tx_gw).await;
+        });
+        return Ok(Some("ARP request"));
+    }
+
+    let (client, id, timer) = (pool.client_for(gw_ip)?, pool.probe_id(), pool.probe_timer(gw_interval));
+    let tx_gw = tx.clone();
+    tokio::spawn(async move {
+        run_pinger(client, gw_ip, id, timer, timeout, SourceType::Gateway, tx_gw).await;
+    });
+
+    Ok(Some("ICMP echo"))
+}
+
+// Starts probing the shared gateway here once the session that probed it
+// has ended, see Registration::take_over_gateway.
+fn take_over_gateway(
+    registration: &mut Registration,
+    app: &mut App,
+    config: &Config,
+    spawn: impl FnOnce(IpAddr) -> Result<Option<&'static str>>,
+) -> Option<IpAddr> {
+    let gw = match registration.take_over_gateway() {
+        Ok(gw) => gw?,
+        Err(e) => {
+            diag::warn(format!("Could not check the session probing the gateway: {}", e));
+            return None;
+        }
+    };
+    let gateway_probe = match spawn(gw) {
+        Ok(probe) => probe,
+        Err(e) => {
+            diag::error(format!("Could not start probing gateway {}: {}", redact::ip(gw), e));
+            return None;
+        }
+    };
+    app.add_gateway(redact::ip(gw), config.target(&gw.to_string(), gw));
+    if let Some(gw_probe) = gateway_probe {
+        app.gateway_probe = gw_probe.to_string();
+    }
+    app.on_system_event(SystemEvent {
+        kind: EventKind::ProbeFallback,
+        message: format!("The session probing gateway {} ended, this session probes it now", redact::ip(gw)),
+    });
+    Some(gw)
+}
+
+fn spawn_hop_pingers(
+    pool: &mut ClientPool,
+    route: &[trace::Hop],
+    interval: Duration,
+    timeout: Duration,
+    tx: &mpsc::Sender<PingUpdate>,
+) -> Result<()> {
+    for hop in route {
+        let Some(addr) = hop.addr() else {
+            continue;
+        };
+        let (client, id, timer) = (pool.client_for(addr)?, pool.probe_id(), pool.probe_timer(interval));
+        let (ttl, tx_hop) = (hop.ttl, tx.clone());
+        tokio::spawn(async move {
+            run_pinger(client, addr, id, timer, timeout, SourceType::Hop(ttl), tx_hop).await;
+        });
+    }
+    Ok(())
+}
+
+fn spawn_cycle_pinger(
+    pool: &mut ClientPool,
+    targets: &[(String, IpAddr)],
+    interval: Duration,
+    timeout: Duration,
+    tx: &mpsc::Sender<PingUpdate>,
+) -> Result<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let mut slots = Vec::with_capacity(targets.len());
+    for (_, ip) in targets {
+        slots.push((pool.client_for(*ip)?, *ip, pool.probe_id()));
+    }
+    tokio::spawn(run_cycle_pinger(slots, pool.timer(interval), timeout, tx.clone()));
+    Ok(())
+}
+
+fn resolve_cycle(hosts: &[String], family_matches: impl Fn(&IpAddr) -> bool) -> Vec<(String, IpAddr)> {
+    let mut targets = Vec::new();
+    for host in hosts.iter().map(|h| h.trim()).filter(|h| !h.is_empty()) {
+        let resolved = match host.parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) => format!("{}:0", host)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.find(|a| family_matches(&a.ip())))
+                .map(|a| a.ip()),
+        };
+        match resolved {
+            Some(ip) => targets.push((host.to_string(), ip)),
+            None => diag::warn(format!("Could not resolve round-robin target '{}', skipping it", host)),
+        }
+    }
+    targets
+}
+
+#[cfg(target_os = "linux")]
+fn passive_available() -> bool {
+    passive::open()
+        .inspect_err(|e| diag::warn(format!("Passive TCP RTT disabled, the kernel connection table is not readable: {}", e)))
+        .is_ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn passive_available() -> bool {
+    diag::warn("Passive TCP RTT (--passive-rtt) is only available on Linux");
+    false
+}
+
+// eBPF or sock_diag, once passive_available said yes.
+#[cfg(target_os = "linux")]
+fn passive_source() -> &'static str {
+    passive::open().map(|source| source.describe()).unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn passive_source() -> &'static str {
+    ""
+}
+
+// --once: one probe to the Target (and the gateway), printed in a form
+// scripts can parse. Returns whether the Target replied.
+async fn ping_once(
+    pool: &mut ClientPool,
+    target_ip: IpAddr,
+    probe: &Probe,
+    timeout: Duration,
+    gateway_ip: Option<IpAddr>,
+    use_arp: bool,
+) -> Result<bool> {
+    let (tx, mut rx) = mpsc::channel::<PingUpdate>(4);
+    let (events, _events_rx) = mpsc::channel::<SystemEvent>(4);
+    // The first tick fires at once, the next one never comes.
+    spawn_pingers(pool, target_ip, probe, ONCE_INTERVAL, timeout, &tx, &events)?;
+    spawn_gateway_pinger(pool, gateway_ip, use_arp, ONCE_INTERVAL, timeout, &tx)?;
+
+    let (mut target, mut gateway) = (None, None);
+    while target.is_none() || (gateway_ip.is_some() && gateway.is_none()) {
+        let Some(update) = rx.recv().await else {
+            break;
+        };
+        match update.source {
+            SourceType::Target => target = Some(update.latency),
+            SourceType::Gateway => gateway = Some(update.latency),
+            _ => {}
+        }
+    }
+
+    let show = |latency: Option<f64>| latency.map(|ms| format!("{:.1}ms", ms)).unwrap_or_else(|| "timeout".to_string());
+    println!("Target {}: {}", redact::ip(target_ip), show(target.flatten()));
+    if let Some(gw) = gateway_ip {
+        println!("Gateway {}: {}", redact::ip(gw), show(gateway.flatten()));
+    }
+    Ok(target.flatten().is_some())
+}
+
+fn spawn_passive(port: Option<Option<u16>>, interval: Duration, tx: &mpsc::Sender<PingUpdate>) {
+    #[cfg(target_os = "linux")]
+    if let Some(port) = port {
+        tokio::spawn(passive::run_passive_watcher(port, interval, tx.clone()));
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (port, interval, tx);
+}
+
+fn finish_session(pipeline: &mut Pipeline, app: &App, profile: &str, chart: Option<&Path>) -> Vec<PathBuf> {
+    app.settle_grades();
+    let summaries = app.summaries();
+
+    if let Some(target) = summaries.first()
+        && let Err(e) = LastRun::save(profile, target).and_then(|_| PoolHistory::record(target))
+    {
+        diag::warn(format!("Could not save run summary: {}", e));
+    }
+
+    if let Some(discord) = &app.discord {
+        discord.post_summary(&summaries, &app.net_stats, &app.events);
+    }
+
+    let mut saved = pipeline.finish(&summaries);
+    if let Some(path) = pipeline.markdown_summary_path() {
+        let files: Vec<String> = saved.iter().map(|p| p.display().to_string()).collect();
+        match markdown::write(&path, app, &files) {
+            Ok(()) => saved.push(path),
+            Err(e) => diag::warn(format!("Could not write {}: {}", path.display(), e)),
+        }
+    }
+    if let Some(path) = chart {
+        match chartexport::export_session(app, path) {
+            Ok(()) => saved.push(path.to_path_buf()),
+            Err(e) => diag::warn(format!("Could not export the chart to {}: {}", path.display(), e)),
+        }
+    }
+    saved
+}
+
+fn print_session_report(app: &App, saved: &[PathBuf]) {
+    let summaries = app.summaries();
+    let duration = summaries.first().map(|s| s.duration_secs).unwrap_or(0.0);
+
+    println!();
+    println!("Session summary ({}):", args::format_duration(Duration::from_secs(duration as u64)));
+    for s in &summaries {
+        println!("  {}", s.describe());
+    }
+    if let Some(comparison) = app.baseline_comparison() {
+        println!("  Baseline: {}", comparison.details());
+    }
+    if app.aborted_by_user {
+        println!("  (stopped by user before the planned end)");
+    }
+    if app.terminated {
+        println!("  (terminated by SIGTERM before the planned end)");
+    }
+
+    if !saved.is_empty() {
+        let saved = saved.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+        println!("Log saved to: {}", saved.join(", "));
+    }
+}
+
+// Service managers (systemd, docker, `timeout`) stop daemons with SIGTERM
+// rather than Ctrl+C, which would otherwise skip the summary.
+async fn terminate() {
+    #[cfg(unix)]
+    if let Ok(mut sigterm) = signal::unix::signal(signal::unix::SignalKind::terminate()) {
+        sigterm.recv().await;
+        return;
+    }
+    std::future::pending::<()>().await;
+}
+
+async fn recv_optional<T>(rx: &mut Option<mpsc::Receiver<T>>) -> Option<T> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// A duration flag, or the exit with a usage error when it is malformed.
+fn duration_arg(flag: &str, spec: &str, example: &str) -> Duration {
+    args::parse_duration_string(spec).unwrap_or_else(|e| {
+        eprintln!("Error: Invalid {} '{}': {}. Expected e.g. {}.", flag, spec, e, example);
+        std::process::exit(1);
+    })
+}
+
+fn alert_rules(args: &RunArgs, config: &Config) -> Result<Rules> {
+    let window = match args.alert_window.as_deref().or(config.alerts.window.as_deref()) {
+        Some(spec) => args::parse_duration_string(spec).map_err(|e| anyhow::anyhow!("Invalid alert window '{}': {}", spec, e))?,
+        None => rules::DEFAULT_WINDOW,
+    };
+    let grade = match args.alert_grade.as_deref().or(config.alerts.grade_below.as_deref()) {
+        Some(grade) => match rules::GRADES[..4].iter().position(|g| g.eq_ignore_ascii_case(grade)) {
+            Some(rank) => Some(rank),
+            None => anyhow::bail!("Invalid alert grade '{}' (expected S, A, B or C)", grade),
+        },
+        None => None,
+    };
+    let list: Vec<Rule> = [
+        args.alert_loss.or(config.alerts.loss_percent).map(Rule::Loss),
+        args.alert_timeouts.or(config.alerts.consecutive_timeouts).filter(|n| *n > 0).map(Rule::Timeouts),
+        args.alert_p95.or(config.alerts.p95_ms).map(Rule::P95),
+        grade.map(Rule::Grade),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let webhook = args.webhook_url.clone().or(config.alerts.webhook.clone());
+    if webhook.is_some() && list.is_empty() {
+        diag::warn("A webhook URL is set but no alert rule is configured (--alert-loss, --alert-timeouts, --alert-p95, --alert-grade)");
+    }
+
+    let mut rules = Rules::new(list, window, webhook);
+    rules.notify = args.notify;
+    Ok(rules)
+}
+
+// Only settings that can change under a running session are applied; output
+// and mode settings keep their startup values until the next run.
+fn reload_config(app: &mut App, args: &RunArgs, target_ip: IpAddr, gateway_ip: Option<IpAddr>) {
+    let loaded = Config::load(args.config.as_deref()).and_then(|config| Ok((alert_rules(args, &config)?, config)));
+    let (rules, config) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            app.on_system_event(SystemEvent {
+                kind: EventKind::ConfigReload,
+                message: format!(
+                    "Config reload failed, keeping the current settings: {}",
+                    format!("{:#}", e).lines().next().unwrap_or_default()
+                ),
+            });
+            return;
+        }
+    };
+
+    let mut changed = Vec::new();
+    if rules.describe() != app.rules.describe() {
+        app.rules.reload(rules);
+        changed.push("alert rules");
+    }
+    let discord_url = args.discord_webhook.clone().or(config.alerts.discord.clone());
+    if discord_url.as_deref() != app.discord.as_ref().map(|d| d.url()) {
+        app.discord = discord_url.map(Discord::new);
+        changed.push("Discord webhook");
+    }
+    let access = args.access.or(config.access);
+    if access != app.access {
+        app.access = access;
+        changed.push("access baseline");
+    }
+    if let Some(theme) = config.theme
+        && theme != app.theme
+    {
+        app.theme = theme;
+        changed.push("theme");
+    }
+    let mut targets_changed = app.net_stats.apply_config(config.target(&app.net_stats.display_name, target_ip));
+    if let (Some(gw), Some(ip)) = (&mut app.gw_stats, gateway_ip) {
+        targets_changed |= gw.apply_config(config.target(&gw.display_name, ip));
+    }
+    if targets_changed {
+        changed.push("target notes");
+    }
+
+    let message = if changed.is_empty() {
+        "Config reloaded, nothing to apply (output and mode settings need a restart)".to_string()
+    } else {
+        format!("Config reloaded: {}", changed.join(", "))
+    };
+    app.on_system_event(SystemEvent { kind: EventKind::ConfigReload, message });
+}
+
+fn heartbeat_timer(period: Duration) -> tokio::time::Interval {
+    let mut timer = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    timer
+}
+
+// `target` is the Target's host name and address when it was given by name.
+fn spawn_watchers(dns_check: Option<IpAddr>, target: Option<(String, IpAddr)>) -> (mpsc::Sender<SystemEvent>, mpsc::Receiver<SystemEvent>) {
+    let (sys_tx, sys_rx) = mpsc::channel::<SystemEvent>(32);
+
+    tokio::spawn(dhcp::run_dhcp_watcher(sys_tx.clone()));
+    if let Some(resolver) = dns_check {
+        tokio::spawn(dnscheck::run_dns_check_watcher(resolver, sys_tx.clone()));
+    }
+    if let Some((host, ip)) = target {
+        tokio::spawn(dnscheck::run_resolve_watcher(host, ip));
+    }
+
+    (sys_tx, sys_rx)
+}
+
+fn load_config(path: Option<&std::path::Path>) -> Config {
+    match Config::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let cli = Cli::parse_from(Cli::argv());
+    locale::init(cli.locale.as_deref());
+    // `run` adds the [units] of the config file once it is read.
+    let (units, precision) = (cli.units, cli.precision.map(usize::from));
+    if !matches!(cli.command, Command::Run(_)) {
+        locale::init_units(Units { unit: units.unwrap_or_default(), precision });
+    }
+    diag::init();
+
+    let mut args = match cli.command {
+        Command::Run(args) => *args,
+        Command::Trace(trace_args) => {
+            let config = load_config(trace_args.config.as_deref());
+            return trace::run(trace_args, config.output.directory.clone()).await;
+        }
+        Command::Report(report_args) => return report::run(report_args),
+        Command::Analyze(analyze_args) => return analyze::run(analyze_args),
+        Command::Status(status_args) => return registry::run(status_args),
+        Command::Top(top_args) => return top::run(top_args),
+        Command::Replay(replay_args) => return replay::run(replay_args),
+        Command::Validate(validate_args) => return validate::run(validate_args),
+        Command::Compare(compare_args) => return compare::run(compare_args),
+        Command::EchoServer(echo_args) => return echo::run(echo_args).await,
+    };
+    redact::init(args.redact);
+
+    if args.pool_rankings {
+        PoolHistory::load().print_rankings();
+        return Ok(());
+    }
 
-    if args.daemon && args.no_csv {
+    let config = load_config(args.config.as_deref());
+    locale::init_units(config.units.global(units, precision));
+
+    if let Some(name) = args.profile.clone() {
+        let profile = config
+            .profile(&name)
+            .and_then(|profile| RunArgs::with_profile(profile).with_context(|| format!("Invalid profile '{}'", name)));
+        args = match profile {
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let mode_output = config.mode_output(args.mode);
+    let record = args.record || args.daemon || mode_output.record || !args.formats.is_empty();
+
+    let output_formats = if args.no_csv || !record {
+        Vec::new()
+    } else if !args.formats.is_empty() {
+        args.formats.clone()
+    } else if !config.output.formats.is_empty() {
+        config.output.formats.clone()
+    } else {
+        vec![OutputFormat::Csv]
+    };
+
+    let influx = match (&args.influx_url, &args.influx_bucket) {
+        (Some(url), Some(bucket)) => Some(sinks::InfluxOptions {
+            url: url.clone(),
+            bucket: bucket.clone(),
+            org: args.influx_org.clone(),
+            token: args
+                .influx_token
+                .clone()
+                .or_else(|| std::env::var("INFLUX_TOKEN").ok()),
+        }),
+        _ => None,
+    };
+
+    let mqtt = match args.mqtt_url.as_deref().map(|url| sinks::MqttOptions::parse(url, args.mqtt_topic.as_deref())) {
+        Some(Ok(options)) => Some(options),
+        Some(Err(e)) => {
+            eprintln!("Error: Invalid --mqtt-url: {}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
+    if args.daemon && output_formats.is_empty() && influx.is_none() && mqtt.is_none() {
         eprintln!(
-            "Error: Daemon mode (-D) requires CSV logging. You cannot use --no-csv with --daemon."
+            "Error: Daemon mode (-D) requires file output. You cannot use --no-csv with --daemon unless --influx-url or --mqtt-url is set."
         );
         std::process::exit(1);
     }
@@ -54,17 +670,11 @@ async fn main() -> Result<()> {
         args::PingMode::Gaming => (50, "GAMING"),
         args::PingMode::Standard => (500, "STANDARD"),
         args::PingMode::Monitor => (5000, "MONITOR"),
+        args::PingMode::Path => (1000, "PATH"),
     };
 
-    let (ping_interval, mode_display_name) = if let Some(i_str) = args.interval {
-        if let Some(d) = args::parse_duration_string(&i_str) {
-            (d, "USER SPECIFIED".to_string())
-        } else {
-            (
-                Duration::from_millis(default_interval_ms),
-                default_mode_name.to_string(),
-            )
-        }
+    let (ping_interval, mode_display_name) = if let Some(i_str) = args.interval.as_deref() {
+        (duration_arg("--interval", i_str, "'50ms' or '1s'"), "USER SPECIFIED".to_string())
     } else {
         (
             Duration::from_millis(default_interval_ms),
@@ -73,126 +683,615 @@ async fn main() -> Result<()> {
     };
 
     let ping_interval_ms = ping_interval.as_millis() as u64;
-    let max_duration = args
-        .duration
-        .as_ref()
-        .and_then(|d| args::parse_duration_string(d));
+    let probe_timeout = match args.timeout.as_deref() {
+        Some(spec) => duration_arg("--timeout", spec, "'500ms' or '2s'"),
+        None => pinger::DEFAULT_TIMEOUT,
+    };
+    let reboot_span = args.reboot_test.as_deref().map(|spec| duration_arg("--reboot-test", spec, "'60s' or '2m'"));
+    let rotate = match args.rotate.as_deref() {
+        None => mode_output.rotate,
+        Some("off") => None,
+        Some(spec) => match sinks::Rotation::parse(spec) {
+            Some(rotation) => Some(rotation),
+            None => {
+                eprintln!("Error: Invalid --rotate '{}'. Expected 'daily', a duration like '6h', or 'off'.", spec);
+                std::process::exit(1);
+            }
+        },
+    };
+    let rotate_size = match args.rotate_size.as_deref() {
+        None => mode_output.rotate_size,
+        Some("off") => None,
+        Some(spec) => match args::parse_size(spec) {
+            Some(size) => Some(size),
+            None => {
+                eprintln!("Error: Invalid --rotate-size '{}'. Expected e.g. '100MB' or '1G'.", spec);
+                std::process::exit(1);
+            }
+        },
+    };
+    let retention = match args.retention.as_deref() {
+        None => history::DEFAULT_RETENTION,
+        Some(spec) => duration_arg("--retention", spec, "'6h' or '48h'"),
+    };
+    let grading = match (&args.grade_command, &args.grading) {
+        (Some(command), _) => grading::command(command.clone()),
+        (None, Some(profile)) => grading::profile(profile),
+        (None, None) => grading::general(),
+    };
+    // ICMP probes wait for their replies side by side, the others one at a time.
+    let sequential = !matches!(args.protocol, args::Protocol::Icmp) || args.dns_server.is_some();
+    if args.timeout.is_some() && probe_timeout > ping_interval && sequential && !args.once {
+        diag::warn(format!(
+            "--timeout {} is longer than the {}ms interval, a probe waiting that long skips the next ticks",
+            args::format_duration(probe_timeout),
+            ping_interval_ms
+        ));
+    }
+    if let Some(spec) = args.dscp.as_deref() {
+        match dscp::parse(spec) {
+            Some(_) if !cfg!(unix) => {
+                eprintln!("Error: --dscp is not supported on this platform.");
+                std::process::exit(1);
+            }
+            Some(code) => dscp::init(code),
+            None => {
+                eprintln!("Error: Invalid --dscp '{}'. Expected a class like 'ef', 'af41' or 'cs5', or a number from 0 to 63.", spec);
+                std::process::exit(1);
+            }
+        }
+    }
+    let probe_marking = match dscp::current() {
+        0 => String::new(),
+        code => format!(", DSCP {}", dscp::label(code)),
+    };
+    let max_duration = args.duration.as_deref().map(|d| duration_arg("--duration", d, "'30m' or '1h30m'"));
+    let heartbeat_interval = args
+        .heartbeat_interval
+        .as_deref()
+        .map_or(heartbeat::DEFAULT_INTERVAL, |d| duration_arg("--heartbeat-interval", d, "'60s' or '5m'"));
+    let divergence_hold = args
+        .divergence_after
+        .as_deref()
+        .map_or(Duration::from_secs(DEFAULT_DIVERGENCE_SECS), |d| duration_arg("--divergence-after", d, "'10s' or '1m'"));
+    let min_samples = args.min_samples.unwrap_or(app::DEFAULT_MIN_SAMPLES);
+    if let Some(path) = &args.export_chart {
+        ChartFormat::of(path)?;
+    }
 
-    let (target_host, target_source_label, target_source_color) = match args.target {
+    let (target_host, target_source_label, target_source_color) = match args.dns_server.clone().or(args.target.clone()) {
         Some(t) => (t, "User Specified", Color::Cyan),
-        None => {
-            let mut rng = rand::thread_rng();
-            (
-                TARGET_POOL
-                    .choose(&mut rng)
-                    .unwrap_or(&"8.8.8.8")
-                    .to_string(),
-                "Randomized Default",
-                Color::Magenta,
-            )
-        }
+        None => (
+            PoolHistory::load().choose(args.ipv6).to_string(),
+            "Randomized Default",
+            Color::Magenta,
+        ),
+    };
+
+    let shown_target = redact::host(&target_host);
+
+    let family_matches = |ip: &IpAddr| (!args.ipv4 || ip.is_ipv4()) && (!args.ipv6 || ip.is_ipv6());
+    let fallback_ip: IpAddr = if args.ipv6 {
+        "2001:4860:4860::8888".parse().unwrap()
+    } else {
+        "8.8.8.8".parse().unwrap()
     };
 
-    let target_ip: IpAddr = match target_host.parse() {
-        Ok(ip) => ip,
+    let target_ip: IpAddr = match target_host.parse::<IpAddr>() {
+        Ok(ip) => {
+            if !family_matches(&ip) {
+                diag::warn(format!(
+                    "'{}' does not match the requested address family. Using it anyway.",
+                    target_host
+                ));
+            }
+            ip
+        }
         Err(_) => {
             let resolve_str = format!("{}:0", target_host);
 
             match resolve_str.to_socket_addrs() {
-                Ok(mut iter) => {
-                    if let Some(socket_addr) = iter.next() {
-                        socket_addr.ip()
+                Ok(iter) => {
+                    let addrs: Vec<IpAddr> = iter.map(|a| a.ip()).collect();
+                    if let Some(ip) = addrs.iter().find(|ip| family_matches(ip)) {
+                        diag::info(format!("Resolved {} to {}", target_host, ip));
+                        *ip
+                    } else if !addrs.is_empty() {
+                        diag::warn(format!(
+                            "'{}' has no {} address. Using fallback {}",
+                            target_host,
+                            if args.ipv6 { "IPv6" } else { "IPv4" },
+                            fallback_ip
+                        ));
+                        fallback_ip
                     } else {
-                        eprintln!(
-                            "Warning: Could not resolve '{}'. Using fallback 8.8.8.8",
-                            target_host
-                        );
-                        "8.8.8.8".parse().unwrap()
+                        diag::warn(format!(
+                            "Could not resolve '{}'. Using fallback {}",
+                            target_host, fallback_ip
+                        ));
+                        fallback_ip
                     }
                 }
                 Err(_) => {
-                    eprintln!(
-                        "Warnung: DNS error for '{}'. Using fallback 8.8.8.8",
-                        target_host
-                    );
-                    "8.8.8.8".parse().unwrap()
+                    diag::warn(format!(
+                        "DNS error for '{}'. Using fallback {}",
+                        target_host, fallback_ip
+                    ));
+                    fallback_ip
                 }
             }
         }
     };
 
+    let target_scope = Scope::of(target_ip);
+    let named_target = target_host.parse::<IpAddr>().is_err().then(|| (target_host.clone(), target_ip));
+    let access = args.access.or(config.access);
+    let rules = match alert_rules(&args, &config) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let quality_gate = match QualityGate::new(args.fail_on_grade.as_deref(), args.fail_on_loss.as_deref()) {
+        Ok(gate) => gate,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let discord_url = args.discord_webhook.clone().or(config.alerts.discord.clone());
+    if !target_scope.is_internet() {
+        diag::warn(format!(
+            "Target {} is a {} address, not an internet host.{}",
+            target_ip,
+            target_scope.label().to_lowercase(),
+            if target_scope.is_local() { " Grading uses LAN thresholds." } else { "" }
+        ));
+    }
+
     let gateway_ip_addr = if args.no_gateway {
         None
     } else {
         match get_default_gateway() {
-            Ok(gw) => match gw.ip_addr.to_string().parse::<IpAddr>() {
-                Ok(ip) => Some(ip),
-                Err(_) => None,
-            },
+            Ok(gw) => gw.ip_addr.to_string().parse::<IpAddr>().ok(),
             Err(_) => None,
         }
     };
+    #[cfg(feature = "fake-ping")]
+    let gateway_ip_addr = fakeping::gateway_addr(gateway_ip_addr, args.no_gateway);
+    // The fake responder needs neither sockets nor privileges.
+    #[cfg(feature = "fake-ping")]
+    let fake = fakeping::target().is_some();
+    #[cfg(not(feature = "fake-ping"))]
+    let fake = false;
+
+    let dns_check_resolver = match args.dns_check {
+        Some(Some(resolver)) => Some(resolver),
+        Some(None) if gateway_ip_addr.is_none() => {
+            diag::warn("--dns-check needs a gateway or an explicit resolver address. DNS check disabled.");
+            None
+        }
+        Some(None) => gateway_ip_addr,
+        None => None,
+    };
+
+    let probe_runtime = match priority::spawn_probe_thread(args.high_priority) {
+        Ok(probe_thread) => {
+            match probe_thread.elevation.as_ref().map(|e| (&e.priority, &e.pinned)) {
+                Some((Ok(priority), Ok(cpu))) => println!("Probe thread: {} priority, pinned to CPU {}", priority, cpu),
+                Some((Ok(priority), Err(e))) => diag::warn(format!("Probe thread: {} priority, but could not pin it to a CPU: {}", priority, e)),
+                Some((Err(e), _)) => diag::warn(format!("Could not raise the probe thread's priority: {}", e)),
+                None => {}
+            }
+            Some(probe_thread.handle)
+        }
+        Err(e) => {
+            diag::warn(format!("Could not start the probe thread, probing on the main runtime: {}", e));
+            None
+        }
+    };
+    // App and the probe timers read the one clock.
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock::new());
+    let mut client_pool = ClientPool::new(StdRng::from_entropy())
+        .with_runtime(probe_runtime.clone())
+        .with_ttl(args.ttl)
+        .with_clock(clock.clone());
+    let mut probe = match args.protocol {
+        _ if args.dns_server.is_some() => Probe::Dns(args.dns_name.clone()),
+        args::Protocol::Icmp => Probe::Icmp,
+        args::Protocol::Tcp => Probe::Tcp(args.port.unwrap_or(443)),
+        args::Protocol::Udp => Probe::Udp(args.port.unwrap_or(7)),
+        args::Protocol::Echo => Probe::Echo(args.port.unwrap_or(echo::DEFAULT_PORT)),
+        args::Protocol::A2s => Probe::A2s(args.port.unwrap_or(27015)),
+        args::Protocol::Dns => Probe::Dns(args.dns_name.clone()),
+    };
+    let fallback_probe = match args.fallback.as_deref() {
+        Some(spec) => match Probe::parse(spec, &args.dns_name) {
+            Some(p) => Some(p),
+            None => {
+                eprintln!("Error: Invalid --fallback '{}'. Expected e.g. 'tcp:443' or 'dns'.", spec);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let socket_result = match probe {
+        _ if fake => Ok(()),
+        Probe::Icmp => client_pool.client_for(target_ip).map(|_| ()),
+        _ => Ok(()),
+    }
+    .and_then(|_| match gateway_ip_addr {
+        Some(gw_ip) if !fake => client_pool.client_for(gw_ip).map(|_| ()),
+        _ => Ok(()),
+    });
+
+    if let Err(e) = socket_result {
+        if privileges::is_permission_error(&e) {
+            privileges::print_guidance(&e);
+            if !args.daemon {
+                privileges::offer_fix();
+            }
+        } else {
+            eprintln!("Error: Could not open an ICMP socket: {}", e);
+        }
+        std::process::exit(1);
+    }
+
+    let mut fallback_message = None;
+    // --once sends exactly one probe, not the three of this check.
+    if matches!(probe, Probe::Icmp) && !fake && !args.once {
+        let client = client_pool.client_for(target_ip)?;
+        println!("Checking whether {} answers ICMP...", redact::ip(target_ip));
+
+        if !pinger::answers_icmp(client, target_ip, client_pool.probe_id(), 3).await {
+            let switch_to = match fallback_probe {
+                Some(p) => Some(p),
+                None if !args.daemon
+                    && utils::confirm(&format!(
+                        "{} does not answer ICMP (ping is probably blocked). Switch to TCP port 443 probes?",
+                        target_ip
+                    )) =>
+                {
+                    Some(Probe::Tcp(443))
+                }
+                None => {
+                    diag::warn(format!(
+                        "{} does not answer ICMP. Use --fallback tcp:443 to switch probes automatically.",
+                        target_ip
+                    ));
+                    None
+                }
+            };
+
+            if let Some(p) = switch_to.filter(|p| !matches!(p, Probe::Icmp)) {
+                let message = format!("{} does not answer ICMP, switched to {}", target_ip, p.label());
+                if !args.daemon {
+                    println!("{}", message);
+                }
+                fallback_message = Some(message);
+                probe = p;
+            }
+        }
+    }
+    let probe_label = probe.label();
+    if args.ttl.is_some() && !matches!(probe, Probe::Icmp) {
+        diag::warn(format!("--ttl only applies to ICMP probes, the {} probes keep the system default", probe_label));
+    }
+
+    if args.once {
+        let replied = ping_once(&mut client_pool, target_ip, &probe, probe_timeout, gateway_ip_addr, !args.no_arp).await?;
+        std::process::exit(if replied { 0 } else { gate::EXIT_CODE });
+    }
+
+    if let Some(span) = reboot_span {
+        let (tx, rx) = mpsc::channel::<PingUpdate>(100);
+        let (events, _events_rx) = mpsc::channel::<SystemEvent>(4);
+        spawn_pingers(&mut client_pool, target_ip, &probe, ping_interval, probe_timeout, &tx, &events)?;
+        spawn_gateway_pinger(&mut client_pool, gateway_ip_addr, !args.no_arp, ping_interval, probe_timeout, &tx)?;
+        let test = reboot::RebootTest::new(span, Scope::of(target_ip).is_local(), gateway_ip_addr.is_some(), chrono::Local::now());
+        let recovered = reboot::run(test, rx, redact::ip(target_ip), gateway_ip_addr.map(redact::ip)).await;
+        std::process::exit(if recovered { 0 } else { gate::EXIT_CODE });
+    }
+
+    let route = if args.mode == args::PingMode::Path {
+        println!("Discovering route to {}...", redact::ip(target_ip));
+        let discovered = tokio::task::spawn_blocking(move || {
+            trace::discover(target_ip, trace::DEFAULT_MAX_HOPS, trace::DEFAULT_QUERIES, trace::DEFAULT_TIMEOUT)
+        })
+        .await?;
+
+        match discovered {
+            Ok(mut hops) => {
+                if !hops.last().is_some_and(|h| h.reached) {
+                    diag::warn(format!("Route discovery did not reach {}", target_ip));
+                    while hops.last().is_some_and(|h| h.addr().is_none()) {
+                        hops.pop();
+                    }
+                }
+                hops
+            }
+            Err(e) => {
+                eprintln!("Error: Could not discover the route to {}: {}", redact::ip(target_ip), e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        Vec::new()
+    };
 
+    let own_location = args.location.as_deref().and_then(|l| {
+        let parsed = geo::parse_coordinates(l);
+        if parsed.is_none() {
+            diag::warn(format!("Invalid --location '{}'. Expected 'LAT,LON'.", l));
+        }
+        parsed
+    });
+
+    let baseline_overlay = match &args.baseline {
+        Some(_) if args.daemon => {
+            diag::warn("--baseline is drawn in the interactive chart only and is ignored in daemon mode.");
+            None
+        }
+        Some(path) => match BaselineOverlay::load(path) {
+            Ok(overlay) => Some(overlay),
+            Err(e) => {
+                eprintln!("Error: Could not load --baseline: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let geo_estimate = if args.geo || own_location.is_some() {
+        println!("Looking up target location...");
+        match tokio::task::spawn_blocking(move || geo::estimate(target_ip, own_location)).await? {
+            Ok(estimate) => Some(estimate),
+            Err(e) => {
+                diag::warn(format!("Geo lookup failed: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let cycle_targets = resolve_cycle(&args.cycle, family_matches);
+    let cycle_interval = args
+        .cycle_interval
+        .as_deref()
+        .map_or(DEFAULT_CYCLE_INTERVAL, |d| duration_arg("--cycle-interval", d, "'1s' or '500ms'"));
+    let passive_rtt = args.passive_rtt.filter(|_| passive_available());
+    let last_run = LastRun::load(args.profile_name(), &target_host);
+
+    let output_options = OutputOptions {
+        formats: output_formats,
+        directory: args
+            .output_dir
+            .clone()
+            .or(config.output.directory.clone())
+            .unwrap_or_else(|| PathBuf::from(".")),
+        rotate,
+        rotate_size,
+        compress: args.compress.unwrap_or(mode_output.compress),
+        split: args.split_output || config.output.split,
+        influx,
+        mqtt,
+    };
+    let output_name = format!("{}ms_{}", ping_interval_ms, shown_target.replace(":", "_"));
+
+    let (mut registration, claim) = match Registration::register(
+        &target_host,
+        args.profile_name(),
+        gateway_ip_addr,
+        &output_options.directory,
+        &output_name,
+        !args.probe_shared_gateway,
+    ) {
+        Ok((registration, claim)) => (Some(registration), claim),
+        Err(e) => {
+            diag::warn(format!("Could not register the session: {}", e));
+            (None, registry::Claim { output_name, gateway_owner: None, shares_directory: false })
+        }
+    };
+    let output_name = claim.output_name;
+    let shared_gateway = gateway_ip_addr.zip(claim.gateway_owner);
+    if let Some((gw, pid)) = shared_gateway {
+        diag::info(format!(
+            "Gateway {} is already probed by session {} (see `vasili status`). Use --probe-shared-gateway to probe it here too.",
+            gw, pid
+        ));
+    }
+    let mut gateway_ip_addr = gateway_ip_addr.filter(|_| shared_gateway.is_none());
     let has_gateway = gateway_ip_addr.is_some();
     let gateway_host_str = gateway_ip_addr
-        .map(|ip| ip.to_string())
+        .map(redact::ip)
         .unwrap_or_else(|| "N/A".to_string());
+    let target_config = config.target(&target_host, target_ip).cloned();
+    let gateway_config = gateway_ip_addr.and_then(|ip| config.target(&ip.to_string(), ip).cloned());
 
-    let timestamp_str = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-    let safe_target = target_host.replace(":", "_");
-    let csv_path = format!(
-        "vasili_{}_{}ms_{}.csv",
-        timestamp_str, ping_interval_ms, safe_target
-    );
+    let scheduled_event = args.event.as_deref().map(|spec| {
+        let Some(time) = readiness::parse_time(spec) else {
+            eprintln!("Error: Invalid --event '{}'. Expected a time of day like '20:00'.", spec);
+            std::process::exit(1);
+        };
+        let now = chrono::Local::now();
+        let mut event = ScheduledEvent::new(time, now);
+        if let Err(e) = event.load_history(&output_options.directory, &shown_target.replace(":", "_"), target_scope.is_local(), now) {
+            diag::info(format!("--event: {}", e));
+        }
+        event
+    });
+
+    let snapshot_path = output_options.directory.join(format!("vasili_{}_traces.log", output_name));
+    let mut loss_trigger = args.trace_on_loss.map(snapshot::LossTrigger::new);
+
+    let mut api_rx = match args.api {
+        Some(addr) => match api::start(addr, args.api_token.clone()).await {
+            Ok(rx) => Some(rx),
+            Err(e) => {
+                eprintln!("Error: Could not start the API on {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let mut reload_rx = config::config_path(args.config.as_deref()).map(config::watch);
 
     if args.daemon {
         println!("VASILI Daemon Mode started.");
-        println!("Target: {} ({})", target_host, target_ip);
+        if let Some(profile) = &args.profile {
+            println!("Profile: {}", profile);
+        }
+        println!("Target: {} ({}, {})", shown_target, redact::ip(target_ip), target_scope.label());
+        if let Some(target_config) = &target_config {
+            let expected = target_config.expected_ms.map(|ms| format!("expected ~{}ms", ms));
+            let parts: Vec<String> = target_config.note.iter().cloned().chain(expected).collect();
+            println!("Note: {}", parts.join(", "));
+        }
+        println!("Probe: {}{}", probe_label, probe_marking);
+        if let Some(last) = &last_run {
+            println!("Last run: {}", last.describe());
+        }
+        if let Some(event) = &scheduled_event {
+            println!("Event: {}", event.describe());
+        }
+        if let Some(estimate) = &geo_estimate {
+            println!(
+                "Physical minimum RTT: ~{} ({:.0} km to {})",
+                locale::ms(estimate.min_rtt_ms, 1), estimate.distance_km, estimate.target_place
+            );
+        }
         println!("Interval: {}ms", ping_interval_ms);
-        println!("Logging to: {}", csv_path);
-
-        if let Some(d) = max_duration {
-            println!("Duration limit: {:?} (Daemon will exit automatically)", d);
+        println!("Timeout: {}", args::format_duration(probe_timeout));
+        if let Some((gw, pid)) = shared_gateway {
+            println!("Gateway: {} (probed by session {})", redact::ip(gw), pid);
+        }
+        if !cycle_targets.is_empty() {
+            println!(
+                "Round robin: {} targets, one probe every {}, each target every {}",
+                cycle_targets.len(),
+                args::format_duration(cycle_interval),
+                args::format_duration(cycle_interval * cycle_targets.len() as u32)
+            );
+        }
+        if let Some(port) = passive_rtt {
+            let filter = port.map(|p| format!(" to port {}", p)).unwrap_or_default();
+            println!("Passive RTT: active TCP connections{} (kernel estimate via {}, no extra traffic)", filter, passive_source());
+        }
+        if let Some(addr) = args.api {
+            println!("API: http://{}/ (status, pause, resume, marker, rotate, stop)", addr);
+        }
+        if let Some(access) = access {
+            let baseline = access.baseline();
+            println!(
+                "Baseline: typical {} (median {}, p95 {}, loss {})",
+                access.label(),
+                locale::ms(baseline.median_ms, 0),
+                locale::ms(baseline.p95_ms, 0),
+                locale::percent(baseline.loss_percent, 1)
+            );
+        }
+        if !route.is_empty() {
+            let hops = route
+                .iter()
+                .map(|h| h.addr().map(|a| a.to_string()).unwrap_or_else(|| "*".to_string()))
+                .collect::<Vec<_>>();
+            println!("Route ({} hops): {}", route.len(), hops.join(" > "));
+        }
+        let mut pipeline = Pipeline::open(output_options.clone(), output_name.clone())?;
+        println!("Logging to: {}", pipeline.describe());
+        if let Some(registration) = &mut registration {
+            registration.set_output(pipeline.describe());
         }
-        println!("Press Ctrl+C to stop manually.");
-
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&csv_path)?;
-        let is_new_file = file.metadata()?.len() == 0;
 
-        let buf_writer = BufWriter::new(file);
-        let mut csv_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(buf_writer);
+        let status_path = args.status_file.clone().or_else(|| {
+            (args.mode == args::PingMode::Monitor)
+                .then(|| output_options.directory.join(status::default_status_file(claim.shares_directory)))
+        });
+        if let Some(path) = &status_path {
+            println!("Status file: {}", path.display());
+        }
+        if !rules.is_empty() {
+            println!("Alert rules: {}", rules.describe());
+        }
+        if !quality_gate.is_empty() {
+            println!("Quality gate: {} (exit code {})", quality_gate.describe(), gate::EXIT_CODE);
+        }
+        if discord_url.is_some() {
+            println!("Discord: outages and the session summary are posted to the webhook");
+        }
+        if let Some(trigger) = &loss_trigger {
+            println!(
+                "Traceroute snapshots: after {} consecutive timeouts -> {}",
+                trigger.threshold(),
+                snapshot_path.display()
+            );
+        }
 
-        if is_new_file {
-            csv_writer.write_record(&[
-                "Timestamp",
-                "Target Type",
-                "Target IP",
-                "Latency (ms)",
-                "Status",
-            ])?;
-            csv_writer.flush()?;
+        if let Some(d) = max_duration {
+            println!(
+                "Duration limit: {} (Daemon will exit automatically)",
+                args::format_duration(d)
+            );
         }
+        if let Some(count) = args.count {
+            println!("Probe limit: {} target probes (Daemon will exit automatically)", count);
+        }
+        println!("Press Ctrl+C to stop manually.");
 
         let (tx, mut rx) = mpsc::channel::<PingUpdate>(100);
-        let tx_net = tx.clone();
-        let interval_clone = ping_interval.clone();
-
-        tokio::spawn(async move {
-            run_pinger(target_ip, interval_clone, SourceType::Target, tx_net).await;
-        });
+        let (sys_tx, mut sys_rx) = spawn_watchers(dns_check_resolver, named_target.clone());
+        let probe_context = probe_runtime.as_ref().map(Handle::enter);
+        client_pool.pace(ping_interval, 1 + route.iter().filter(|hop| hop.addr().is_some()).count());
+        spawn_pingers(&mut client_pool, target_ip, &probe, ping_interval, probe_timeout, &tx, &sys_tx)?;
+        let gateway_probe = spawn_gateway_pinger(&mut client_pool, gateway_ip_addr, !args.no_arp, ping_interval, probe_timeout, &tx)?;
+        spawn_hop_pingers(&mut client_pool, &route, ping_interval, probe_timeout, &tx)?;
+        spawn_cycle_pinger(&mut client_pool, &cycle_targets, cycle_interval, probe_timeout, &tx)?;
+        spawn_passive(passive_rtt, ping_interval, &tx);
+        drop(probe_context);
 
-        if let Some(gw_ip) = gateway_ip_addr {
-            let tx_gw = tx.clone();
-            let gw_interval = ping_interval / 2;
-            tokio::spawn(async move {
-                run_pinger(gw_ip, gw_interval, SourceType::Gateway, tx_gw).await;
-            });
+        let mut app = App::new(
+            shown_target.clone(),
+            has_gateway.then(|| gateway_host_str.clone()),
+            ping_interval_ms as f64,
+            ping_interval_ms,
+            None,
+            divergence_hold.as_secs_f64(),
+            clock.clone(),
+        );
+        app.geo = geo_estimate.clone();
+        app.max_probes = args.count;
+        app.scheduled_event = scheduled_event;
+        app.alerts.notify = args.notify;
+        app.rules = rules;
+        app.bell = args.bell.map(Bell::new);
+        app.discord = discord_url.clone().map(Discord::new);
+        app.access = access;
+        app.net_stats.lan = target_scope.is_local();
+        app.net_stats.apply_config(target_config.as_ref());
+        if let Some(gw) = &mut app.gw_stats {
+            gw.apply_config(gateway_config.as_ref());
+        }
+        app.set_route(&route);
+        app.set_cycle(&cycle_targets, cycle_interval.as_millis() as f64);
+        if let Some(port) = passive_rtt {
+            app.enable_passive(port);
         }
+        app.set_min_samples(min_samples);
+        app.set_retention(retention);
+        app.set_grading(grading.clone());
+        app.probe = probe_label.clone();
+        app.timeout_ms = probe_timeout.as_millis() as u64;
+        app.dscp = dscp::current();
+        if let Some(gw_probe) = gateway_probe {
+            app.gateway_probe = gw_probe.to_string();
+        }
+        if let Some(message) = &fallback_message {
+            app.on_system_event(SystemEvent { kind: EventKind::ProbeFallback, message: message.clone() });
+        }
+        let mut printed_events = 0;
 
         let duration_signal = async {
             if let Some(d) = max_duration {
@@ -203,47 +1302,120 @@ async fn main() -> Result<()> {
         };
 
         tokio::pin!(duration_signal);
+        let terminate_signal = terminate();
+        tokio::pin!(terminate_signal);
+
+        let mut status_timer = tokio::time::interval(status::STATUS_INTERVAL);
+        let mut registry_timer = tokio::time::interval(status::STATUS_INTERVAL);
+        let mut heartbeat = Heartbeat::new(args.heartbeat_file.clone(), args.heartbeat_url.clone());
+        let mut heartbeat_timer = heartbeat_timer(heartbeat_interval);
 
-        loop {
+        let aborted = loop {
             tokio::select! {
                 Some(update) = rx.recv() => {
-                    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S.%3f").to_string();
-                    let (status, latency_ms) = match update.latency {
-                        Some(ms) => ("OK".to_string(), Some(ms)),
-                        None => ("TIMEOUT".to_string(), None),
-                    };
-
-                    let (t_type, t_ip) = match update.source {
-                        SourceType::Target => ("Target".to_string(), target_host.clone()),
-                        SourceType::Gateway => ("Gateway".to_string(), gateway_host_str.clone()),
-                    };
-
-                    let record = PingRecord {
-                        timestamp,
-                        target_type: t_type,
-                        target_ip: t_ip,
-                        latency_ms,
-                        status,
-                    };
-
-                    let _ = csv_writer.serialize(record);
+                    if let Some(trigger) = &mut loss_trigger
+                        && trigger.observe(&update.source, update.latency)
+                    {
+                        let reason = format!("{} consecutive timeouts", trigger.threshold());
+                        snapshot::spawn(target_ip, snapshot_path.clone(), reason, sys_tx.clone());
+                    }
+                    if let Some(record) = app.on_ping(update) {
+                        pipeline.write_record(&record);
+                    }
+
+                    for event in app.events[printed_events..].iter().filter(|e| !e.suppressed) {
+                        println!("[{}] {}", event.timestamp, event.message);
+                    }
+                    printed_events = app.events.len();
+                    if app.probe_count_reached() {
+                        println!("\nProbe count reached. Stopping Daemon.");
+                        break false;
+                    }
+                }
+                Some(event) = sys_rx.recv() => {
+                    app.on_system_event(event);
+                    if let Some(event) = app.events.last().filter(|e| !e.suppressed) {
+                        println!("[{}] {}", event.timestamp, event.message);
+                    }
+                    printed_events = app.events.len();
+                }
+                _ = status_timer.tick(), if status_path.is_some() => {
+                    if let Some(path) = &status_path
+                        && let Err(e) = status::write_status(path, &app)
+                    {
+                        diag::warn(format!("Could not write status file: {}", e));
+                    }
+                }
+                _ = registry_timer.tick(), if registration.is_some() => {
+                    if let Some(registration) = &mut registration {
+                        if let Err(e) = registration.update(&app) {
+                            diag::warn(format!("Could not update the session registry: {}", e));
+                        }
+                        let taken_over = take_over_gateway(registration, &mut app, &config, |gw| {
+                            let _probe_context = probe_runtime.as_ref().map(Handle::enter);
+                            spawn_gateway_pinger(&mut client_pool, Some(gw), !args.no_arp, ping_interval, probe_timeout, &tx)
+                        });
+                        gateway_ip_addr = taken_over.or(gateway_ip_addr);
+                    }
+                }
+                _ = heartbeat_timer.tick(), if heartbeat.is_some() => {
+                    if let Some(hb) = &mut heartbeat {
+                        hb.beat(&app);
+                    }
+                }
+                Some(request) = recv_optional(&mut api_rx) => {
+                    let _ = request.reply.send(api::apply(request.action, &mut app, &mut pipeline));
+                    for event in app.events[printed_events..].iter().filter(|e| !e.suppressed) {
+                        println!("[{}] {}", event.timestamp, event.message);
+                    }
+                    printed_events = app.events.len();
+                    if app.should_quit {
+                        println!("\nStopping Daemon (API request). Bye!");
+                        break true;
+                    }
+                }
+                Some(()) = recv_optional(&mut reload_rx) => {
+                    reload_config(&mut app, &args, target_ip, gateway_ip_addr);
+                    if let Some(event) = app.events.last() {
+                        println!("[{}] {}", event.timestamp, event.message);
+                    }
+                    printed_events = app.events.len();
                 }
                 _ = signal::ctrl_c() => {
                     println!("\nStopping Daemon (Ctrl+C). Bye!");
-                    let _ = csv_writer.flush();
-                    break;
+                    break true;
+                }
+                _ = &mut terminate_signal => {
+                    println!("\nStopping Daemon (SIGTERM). Bye!");
+                    // A service manager rather than the user, recorded apart.
+                    app.terminated = true;
+                    break false;
                 }
                 _ = &mut duration_signal => {
                     println!("\nDuration limit reached. Stopping Daemon.");
-                    let _ = csv_writer.flush();
-                    break;
+                    break false;
                 }
             }
+        };
+
+        app.aborted_by_user = aborted;
+        if let Some(path) = &status_path
+            && let Err(e) = status::write_status(path, &app)
+        {
+            diag::warn(format!("Could not write status file: {}", e));
         }
 
+        let saved = finish_session(&mut pipeline, &app, args.profile_name(), args.export_chart.as_deref());
+        print_session_report(&app, &saved);
+        if !quality_gate.report(&app.net_stats) {
+            drop(registration);
+            drop(app);
+            std::process::exit(gate::EXIT_CODE);
+        }
         return Ok(());
     }
 
+    diag::set_echo(false);
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
@@ -270,6 +1442,11 @@ async fn main() -> Result<()> {
                     ),
                     Span::styled("(Auto-Detected)", Style::default().fg(Color::Green)),
                 ])
+            } else if let Some((gw, pid)) = shared_gateway {
+                Line::from(Span::styled(
+                    format!("Gateway: {} probed by session {} (vasili status)", gw, pid),
+                    Style::default().fg(Color::Yellow),
+                ))
             } else if args.no_gateway {
                 Line::from(Span::styled(
                     "Gateway: Disabled via argument",
@@ -308,7 +1485,7 @@ async fn main() -> Result<()> {
                 ])
             };
 
-            let welcome_text = vec![
+            let mut welcome_text = vec![
                 Line::from(Span::styled(
                     "Welcome to VASILI",
                     Style::default()
@@ -330,21 +1507,55 @@ async fn main() -> Result<()> {
                 Line::from(vec![
                     Span::raw("Target: "),
                     Span::styled(
-                        format!("{} ", target_host),
+                        format!("{} ", shown_target),
                         Style::default()
                             .fg(Color::White)
                             .add_modifier(Modifier::BOLD),
                     ),
                     Span::styled(
-                        format!("({})", target_source_label),
+                        format!("({}) ", target_source_label),
                         Style::default().fg(target_source_color),
                     ),
+                    if !target_scope.is_internet() {
+                        Span::styled(
+                            format!("{} - not an internet host!", target_scope.label()),
+                            Style::default().fg(Color::Yellow),
+                        )
+                    } else {
+                        Span::styled(target_scope.label(), Style::default().fg(Color::Gray))
+                    },
+                ]),
+                Line::from(vec![
+                    Span::raw("Probe: "),
+                    Span::styled(format!("{}{}", probe_label, probe_marking), Style::default().fg(Color::Cyan)),
                 ]),
+                match &last_run {
+                    Some(last) => Line::from(vec![
+                        Span::raw("Last run: "),
+                        Span::styled(last.describe(), Style::default().fg(Color::Gray)),
+                    ]),
+                    None => Line::from("Last run: none"),
+                },
                 gw_line,
-                Line::from(if let Some(d) = max_duration {
-                    format!("Limit: {}m {}s", d.as_secs() / 60, d.as_secs() % 60)
-                } else {
-                    "Limit: Infinite".to_string()
+                match &geo_estimate {
+                    Some(estimate) => Line::from(vec![
+                        Span::raw("Physical minimum RTT: "),
+                        Span::styled(
+                            format!("~{} ", locale::ms(estimate.min_rtt_ms, 1)),
+                            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                        ),
+                        Span::styled(
+                            format!("({:.0} km to {})", estimate.distance_km, estimate.target_place),
+                            Style::default().fg(Color::Gray),
+                        ),
+                    ]),
+                    None => Line::from(""),
+                },
+                Line::from(match (max_duration, args.count) {
+                    (Some(d), Some(count)) => format!("Limit: {} or {} probes", args::format_duration(d), count),
+                    (Some(d), None) => format!("Limit: {}", args::format_duration(d)),
+                    (None, Some(count)) => format!("Limit: {} probes", count),
+                    (None, None) => "Limit: Infinite".to_string(),
                 }),
                 Line::from(""),
                 Line::from(Span::styled(
@@ -354,10 +1565,21 @@ async fn main() -> Result<()> {
                 Line::from("[+/-] Zoom Time Axis"),
                 Line::from("[Left/Right] Scroll History"),
                 Line::from("[Space] Pause / Resume"),
+                Line::from("[E] Event Log  [/] Search Events  [L] Diagnostic Log"),
+                Line::from("[J/X/H] Toggle Jitter / Loss / Gateway"),
+                Line::from("[V] Layout  [S] Stats Page  [T] Theme  [U/P] Units"),
+                Line::from("[G] Explain Grade  [M] Export Chart Window as GIF"),
                 Line::from("[Q] Quit"),
                 Line::from(""),
                 Line::from("Press [ENTER] to start monitoring"),
             ];
+            if let Some(overlay) = &baseline_overlay {
+                // Below the "Last run" line.
+                welcome_text.insert(8, Line::from(vec![
+                    Span::raw("Usual line: "),
+                    Span::styled(overlay.describe(), Style::default().fg(Color::Gray)),
+                ]));
+            }
 
             let content_height = welcome_text.len() as u16;
             let block = Block::default()
@@ -380,64 +1602,36 @@ async fn main() -> Result<()> {
             );
         })?;
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Enter {
-                    break;
-                }
-                if key.code == KeyCode::Char('q') {
-                    disable_raw_mode()?;
-                    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-                    return Ok(());
-                }
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+        {
+            if key.code == KeyCode::Enter {
+                break;
+            }
+            if key.code == KeyCode::Char('q') {
+                disable_raw_mode()?;
+                execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                diag::set_echo(true);
+                return Ok(());
             }
         }
     }
 
-    let mut csv_writer = if !args.no_csv {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&csv_path)?;
-
-        let is_new_file = file.metadata()?.len() == 0;
-        let buf_writer = BufWriter::new(file);
-        let mut writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(buf_writer);
-
-        if is_new_file {
-            writer.write_record(&[
-                "Timestamp",
-                "Target Type",
-                "Target IP",
-                "Latency (ms)",
-                "Status",
-            ])?;
-            writer.flush()?;
-        }
-
-        Some(writer)
-    } else {
-        None
-    };
+    let mut pipeline = Pipeline::open(output_options.clone(), output_name)?;
+    if let Some(registration) = &mut registration {
+        registration.set_output(pipeline.describe());
+    }
 
     let (tx, mut rx) = mpsc::channel::<PingUpdate>(100);
-    let tx_net = tx.clone();
-    let interval_clone = ping_interval.clone();
-
-    tokio::spawn(async move {
-        run_pinger(target_ip, interval_clone, SourceType::Target, tx_net).await;
-    });
-
-    if let Some(gw_ip) = gateway_ip_addr {
-        let tx_gw = tx.clone();
-        let gw_interval = ping_interval / 2;
-
-        tokio::spawn(async move {
-            run_pinger(gw_ip, gw_interval, SourceType::Gateway, tx_gw).await;
-        });
-    }
+    let (sys_tx, mut sys_rx) = spawn_watchers(dns_check_resolver, named_target.clone());
+    let probe_context = probe_runtime.as_ref().map(Handle::enter);
+    client_pool.pace(ping_interval, 1 + route.iter().filter(|hop| hop.addr().is_some()).count());
+    spawn_pingers(&mut client_pool, target_ip, &probe, ping_interval, probe_timeout, &tx, &sys_tx)?;
+    let gateway_probe = spawn_gateway_pinger(&mut client_pool, gateway_ip_addr, !args.no_arp, ping_interval, probe_timeout, &tx)?;
+    spawn_hop_pingers(&mut client_pool, &route, ping_interval, probe_timeout, &tx)?;
+    spawn_cycle_pinger(&mut client_pool, &cycle_targets, cycle_interval, probe_timeout, &tx)?;
+    spawn_passive(passive_rtt, ping_interval, &tx);
+    drop(probe_context);
 
     let ui_interval_ms_f64 = if has_gateway {
         ping_interval_ms as f64 / 3.0
@@ -446,32 +1640,115 @@ async fn main() -> Result<()> {
     };
 
     let mut app = App::new(
-        target_host,
-        gateway_host_str.ne("N/A").then(|| gateway_host_str),
+        shown_target,
+        has_gateway.then_some(gateway_host_str),
         ui_interval_ms_f64,
         ping_interval_ms,
         max_duration,
+        divergence_hold.as_secs_f64(),
+        clock,
     );
+    app.geo = geo_estimate;
+    app.max_probes = args.count;
+    app.baseline_overlay = baseline_overlay;
+    app.scheduled_event = scheduled_event;
+    app.alerts.notify = args.notify;
+    app.rules = rules;
+    app.bell = args.bell.map(Bell::new);
+    app.discord = discord_url.clone().map(Discord::new);
+    app.access = access;
+    app.net_stats.lan = target_scope.is_local();
+    app.net_stats.apply_config(target_config.as_ref());
+    if let Some(gw) = &mut app.gw_stats {
+        gw.apply_config(gateway_config.as_ref());
+    }
+    app.set_route(&route);
+    app.set_cycle(&cycle_targets, cycle_interval.as_millis() as f64);
+    if let Some(port) = passive_rtt {
+        app.enable_passive(port);
+    }
+    app.set_min_samples(min_samples);
+    app.set_retention(retention);
+    app.set_grading(grading);
+    app.export_dir = output_options.directory.clone();
+    app.probe = probe_label;
+    app.timeout_ms = probe_timeout.as_millis() as u64;
+    app.dscp = dscp::current();
+    if let Some(gw_probe) = gateway_probe {
+        app.gateway_probe = gw_probe.to_string();
+    }
+    if let Some(message) = fallback_message {
+        app.on_system_event(SystemEvent { kind: EventKind::ProbeFallback, message });
+    }
+
+    if let Some(state) = UiState::load(args.profile_name()) {
+        app.apply_ui_state(&state);
+    }
+    if let Some(theme) = config.theme {
+        app.theme = theme;
+    }
+    app.units = config.units.panels(units, precision);
+
+    let mut heartbeat = Heartbeat::new(args.heartbeat_file.clone(), args.heartbeat_url.clone());
+    let mut heartbeat_timer = heartbeat_timer(heartbeat_interval);
+    let mut registry_timer = tokio::time::interval(status::STATUS_INTERVAL);
+    let mut keys = input::spawn();
+    // Keeps the clock and the runtime moving while no probe results come in.
+    let mut redraw_timer = tokio::time::interval(Duration::from_millis(200));
 
     loop {
+        app.collect_export(false);
         terminal.draw(|f| ui::draw(f, &app))?;
 
         tokio::select! {
             Some(update) = rx.recv() => {
-                if let Some(record) = app.on_ping(update.source, update.latency) {
-                    if let Some(writer) = &mut csv_writer {
-                        let _ = writer.serialize(record);
-                    }
+                if let Some(trigger) = &mut loss_trigger
+                    && trigger.observe(&update.source, update.latency)
+                {
+                    let reason = format!("{} consecutive timeouts", trigger.threshold());
+                    snapshot::spawn(target_ip, snapshot_path.clone(), reason, sys_tx.clone());
+                }
+                if let Some(record) = app.on_ping(update) {
+                    pipeline.write_record(&record);
+                }
+            }
+
+            Some(event) = sys_rx.recv() => {
+                app.on_system_event(event);
+            }
+
+            _ = heartbeat_timer.tick(), if heartbeat.is_some() => {
+                if let Some(hb) = &mut heartbeat {
+                    hb.beat(&app);
                 }
             }
 
-            event = async { tokio::task::spawn_blocking(|| event::poll(Duration::from_millis(50))).await } => {
-                if let Ok(Ok(true)) = event {
-                    if let Event::Key(key) = event::read()? {
-                       app.on_key(key.code);
+            _ = registry_timer.tick(), if registration.is_some() => {
+                if let Some(registration) = &mut registration {
+                    if let Err(e) = registration.update(&app) {
+                        diag::warn(format!("Could not update the session registry: {}", e));
                     }
+                    let taken_over = take_over_gateway(registration, &mut app, &config, |gw| {
+                        let _probe_context = probe_runtime.as_ref().map(Handle::enter);
+                        spawn_gateway_pinger(&mut client_pool, Some(gw), !args.no_arp, ping_interval, probe_timeout, &tx)
+                    });
+                    gateway_ip_addr = taken_over.or(gateway_ip_addr);
                 }
             }
+
+            Some(request) = recv_optional(&mut api_rx) => {
+                let _ = request.reply.send(api::apply(request.action, &mut app, &mut pipeline));
+            }
+
+            Some(()) = recv_optional(&mut reload_rx) => {
+                reload_config(&mut app, &args, target_ip, gateway_ip_addr);
+            }
+
+            Some(key) = keys.recv() => {
+                app.on_key(key.code);
+            }
+
+            _ = redraw_timer.tick() => {}
         }
 
         if app.should_quit {
@@ -479,17 +1756,42 @@ async fn main() -> Result<()> {
         }
     }
 
+    app.aborted_by_user = !app.is_finished;
+    app.collect_export(true);
+    if !args.no_summary_screen {
+        terminal.draw(|f| ui::draw_session_end(f, &app))?;
+        while let Some(key) = keys.recv().await {
+            if key.kind == KeyEventKind::Press {
+                break;
+            }
+        }
+    }
+    drop(keys);
+
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    diag::set_echo(true);
+
+    if let Err(e) = app.ui_state().save(args.profile_name()) {
+        diag::warn(format!("Could not save UI state: {}", e));
+    }
+
+    let saved = finish_session(&mut pipeline, &app, args.profile_name(), args.export_chart.as_deref());
 
-    if let Some(mut writer) = csv_writer {
-        let _ = writer.flush();
+    if let Some(comparison) = app.baseline_comparison() {
+        println!("Baseline: {}", comparison.details());
     }
 
-    if !args.no_csv {
-        println!("VASILI finished. Log saved to: {}", csv_path);
+    if !saved.is_empty() {
+        let saved = saved.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+        println!("VASILI finished. Log saved to: {}", saved.join(", "));
     } else {
-        println!("VASILI finished. (CSV logging disabled)");
+        println!("VASILI finished. (File output disabled, use --record to save the session)");
+    }
+    if !quality_gate.report(&app.net_stats) {
+        drop(registration);
+        drop(app);
+        std::process::exit(gate::EXIT_CODE);
     }
 
     Ok(())
@@ -0,0 +1,111 @@
+use crate::app::App;
+use crate::args::format_duration;
+use crate::locale;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::{fmt::Write, fs, path::Path, time::Duration};
+
+// Outage intervals listed per host, the rest are only counted.
+const MAX_OUTAGES: usize = 20;
+
+fn at(app: &App, secs: f64) -> DateTime<Local> {
+    app.start_time + chrono::Duration::milliseconds((secs * 1000.0) as i64)
+}
+
+// The end-of-session summary as Markdown, readable as plain text as well.
+pub fn session_summary(app: &App, files: &[String]) -> String {
+    let summaries = app.summaries();
+    let duration = summaries.first().map(|s| s.duration_secs).unwrap_or(0.0);
+    let mut md = String::new();
+
+    let _ = writeln!(md, "# VASILI session summary: {}", app.net_stats.display_name);
+    let _ = writeln!(md);
+    let _ = writeln!(md, "- Started: {}", locale::datetime(&app.start_time));
+    let _ = writeln!(md, "- Duration: {}", format_duration(Duration::from_secs(duration as u64)));
+    if let Some(measured) = summaries.first().and_then(|s| s.measured()) {
+        let _ = writeln!(md, "- Measured: {}", measured);
+    }
+    let marking = match app.dscp {
+        0 => String::new(),
+        code => format!(", DSCP {}", crate::dscp::label(code)),
+    };
+    let _ = writeln!(md, "- Probe: {} (timeout {}ms{})", app.probe, app.timeout_ms, marking);
+    let _ = writeln!(md, "- Ended: {}", app.ending());
+    if let Some(comparison) = app.baseline_comparison() {
+        let _ = writeln!(md, "- Baseline: {}", comparison.details());
+    }
+    for file in files {
+        let _ = writeln!(md, "- Log: `{}`", file);
+    }
+
+    let _ = writeln!(md);
+    let _ = writeln!(md, "## Hosts");
+    let _ = writeln!(md);
+    let _ = writeln!(md, "| Role | Host | Samples | Lost | Loss | Min | Mean | Std dev | P25 | P75 | P99 | P99.9 | Max | >30ms | >100ms | Anomalies | Late | Grade |");
+    let _ = writeln!(md, "|---|---|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|---|");
+    for s in &summaries {
+        let _ = writeln!(
+            md,
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+            s.target_type,
+            s.target_ip,
+            s.samples,
+            s.lost,
+            locale::percent(s.loss_percent, 2),
+            locale::host_ms(s.min, 1, s.lan()),
+            locale::host_ms(s.mean, 1, s.lan()),
+            locale::host_ms(s.stddev, 1, s.lan()),
+            locale::host_ms(s.p25, 1, s.lan()),
+            locale::host_ms(s.p75, 1, s.lan()),
+            locale::host_ms(s.p99, 1, s.lan()),
+            locale::host_ms(s.p999, 1, s.lan()),
+            locale::host_ms(s.max, 1, s.lan()),
+            s.spikes_minor,
+            s.spikes_major,
+            s.anomalies,
+            s.deadline_misses,
+            s.grade_label()
+        );
+    }
+    if let Some(max_at) = summaries.first().and_then(|s| s.max_at.as_ref()) {
+        let _ = writeln!(md);
+        let _ = writeln!(md, "The Target's maximum was reached at {}.", locale::timestamp(max_at));
+    }
+
+    let _ = writeln!(md);
+    let _ = writeln!(md, "## Outages");
+    let _ = writeln!(md);
+    let mut any = false;
+    for (target_type, stats) in app.hosts() {
+        let outages = stats.outages();
+        if outages.is_empty() {
+            continue;
+        }
+        any = true;
+        let _ = writeln!(md, "{} {} ({} total):", target_type, stats.display_name, outages.len());
+        let _ = writeln!(md);
+        for &(start, end, lost) in outages.iter().take(MAX_OUTAGES) {
+            let _ = writeln!(
+                md,
+                "- {} to {} ({}, {} lost)",
+                locale::datetime(&at(app, start)),
+                locale::time(&at(app, end)),
+                format_duration(Duration::from_secs_f64(end - start)),
+                lost
+            );
+        }
+        if outages.len() > MAX_OUTAGES {
+            let _ = writeln!(md, "- ... and {} more", outages.len() - MAX_OUTAGES);
+        }
+        let _ = writeln!(md);
+    }
+    if !any {
+        let _ = writeln!(md, "No outages.");
+    }
+    md
+}
+
+pub fn write(path: &Path, app: &App, files: &[String]) -> Result<()> {
+    fs::write(path, session_summary(app, files))?;
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::{
+    fs,
+    path::Path,
+    sync::Once,
+    time::{Duration, Instant},
+};
+
+// Counters of the default interface that point at local hardware or driver
+// trouble rather than at the path beyond the router. TCP retransmits are
+// system-wide, the kernel does not count them per interface.
+#[cfg(target_os = "linux")]
+const COUNTERS: &[&str] = &[
+    "rx_errors",
+    "rx_dropped",
+    "rx_crc_errors",
+    "rx_missed_errors",
+    "tx_errors",
+    "tx_dropped",
+    "tx_carrier_errors",
+];
+const REFRESH_SECS: f64 = 1.0;
+// The default interface rarely changes, looking it up means a netlink dump.
+#[cfg(target_os = "linux")]
+const IFACE_REFRESH: Duration = Duration::from_secs(30);
+
+// The counters are read on their own thread once a second, so the sysfs and
+// procfs reads never hold up the App. NicWatch only copies the last reading.
+static LATEST: Mutex<Option<Counters>> = Mutex::new(None);
+#[cfg(target_os = "linux")]
+static SAMPLER: Once = Once::new();
+
+#[cfg(target_os = "linux")]
+fn sample() {
+    let mut iface: Option<(String, Instant)> = None;
+    loop {
+        if iface.as_ref().is_none_or(|(_, since)| since.elapsed() >= IFACE_REFRESH) {
+            iface = default_net::get_default_interface().ok().map(|i| (i.name, Instant::now()));
+        }
+        let counters = iface.as_ref().map(|(name, _)| Counters::read(name));
+        if let Ok(mut latest) = LATEST.lock() {
+            *latest = counters;
+        }
+        std::thread::sleep(Duration::from_secs_f64(REFRESH_SECS));
+    }
+}
+
+fn latest() -> Option<Counters> {
+    #[cfg(target_os = "linux")]
+    SAMPLER.call_once(|| {
+        std::thread::spawn(sample);
+    });
+    LATEST.lock().ok()?.clone()
+}
+
+#[derive(Debug, Clone)]
+pub struct Counters {
+    iface: String,
+    values: Vec<(&'static str, u64)>,
+}
+
+#[cfg(target_os = "linux")]
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(target_os = "linux")]
+fn tcp_retransmits() -> Option<u64> {
+    let snmp = fs::read_to_string("/proc/net/snmp").ok()?;
+    let mut tcp = snmp.lines().filter(|l| l.starts_with("Tcp:"));
+    let (header, values) = (tcp.next()?, tcp.next()?);
+    let idx = header.split_whitespace().position(|h| h == "RetransSegs")?;
+    values.split_whitespace().nth(idx)?.parse().ok()
+}
+
+impl Counters {
+    #[cfg(target_os = "linux")]
+    fn read(iface: &str) -> Self {
+        let dir = Path::new("/sys/class/net").join(iface).join("statistics");
+        let mut values: Vec<(&'static str, u64)> =
+            COUNTERS.iter().filter_map(|name| read_u64(&dir.join(name)).map(|v| (*name, v))).collect();
+        values.extend(tcp_retransmits().map(|v| ("tcp_retransmits", v)));
+        Self { iface: iface.to_string(), values }
+    }
+
+    pub fn delta(&self, earlier: &Counters) -> String {
+        if earlier.iface != self.iface {
+            return format!("NIC changed from {} to {}", earlier.iface, self.iface);
+        }
+
+        let changes: Vec<String> = self
+            .values
+            .iter()
+            .filter_map(|(name, now)| {
+                let before = earlier.values.iter().find(|(n, _)| n == name)?.1;
+                (*now > before).then(|| format!("+{} {}", now - before, name))
+            })
+            .collect();
+
+        if changes.is_empty() {
+            format!("NIC {}: no errors, drops or retransmits", self.iface)
+        } else {
+            format!("NIC {}: {}", self.iface, changes.join(", "))
+        }
+    }
+}
+
+// Keeps the counters of the last healthy moment, so errors during the hold
+// time before an outage is reported are part of its delta as well.
+#[derive(Default)]
+pub struct NicWatch {
+    baseline: Option<(f64, Counters)>,
+    outage: Option<Counters>,
+}
+
+impl NicWatch {
+    pub fn healthy(&mut self, time_val: f64) {
+        if self.outage.is_none() && self.baseline.as_ref().is_none_or(|(t, _)| time_val - t >= REFRESH_SECS) {
+            self.baseline = latest().map(|c| (time_val, c));
+        }
+    }
+
+    pub fn outage_started(&mut self) -> Option<String> {
+        if self.outage.is_some() {
+            return None;
+        }
+        let now = latest()?;
+        let start = self.baseline.take().map(|(_, c)| c).unwrap_or_else(|| now.clone());
+        let delta = now.delta(&start);
+        self.outage = Some(start);
+        Some(delta)
+    }
+
+    pub fn outage_ended(&mut self) -> Option<String> {
+        let start = self.outage.take()?;
+        Some(latest()?.delta(&start))
+    }
+}
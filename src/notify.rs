@@ -0,0 +1,20 @@
+use crate::diag;
+use notify_rust::Notification;
+use std::thread;
+
+// Toast notifications on Windows, the notification center on macOS and the
+// freedesktop D-Bus service elsewhere, all spoken to directly rather than
+// through helper binaries that may not be installed.
+pub fn send(title: &str, body: &str) {
+    let mut notification = Notification::new();
+    notification.appname("vasili").summary(title).body(body);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    notification.icon("dialog-warning");
+
+    // Waits for the notification service, which may take a while or hang.
+    thread::spawn(move || {
+        if let Err(e) = notification.show() {
+            diag::warn(format!("Desktop notification was not delivered (no notification service?): {}", e));
+        }
+    });
+}
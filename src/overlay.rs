@@ -0,0 +1,74 @@
+use crate::analyze;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Local, Timelike};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// The Target's median latency per five minutes of the day in an earlier
+// session, drawn under the live chart so tonight can be held against the
+// same hours of another evening.
+const BUCKET_SECS: u32 = 300;
+
+#[derive(Debug, Clone)]
+pub struct BaselineOverlay {
+    pub source: String,
+    medians: BTreeMap<u32, f64>,
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+fn clock(bucket: u32) -> String {
+    let secs = bucket * BUCKET_SECS;
+    format!("{:02}:{:02}", secs / 3600, (secs / 60) % 60)
+}
+
+impl BaselineOverlay {
+    pub fn load(path: &Path) -> Result<BaselineOverlay> {
+        let hosts = analyze::load_hosts(path)?;
+        let Some((_, samples)) = hosts.iter().find(|((target_type, _), _)| target_type == "Target") else {
+            bail!("{} contains no target samples", path.display());
+        };
+
+        let mut buckets: BTreeMap<u32, Vec<f64>> = BTreeMap::new();
+        for (_, latency, at) in samples {
+            if let Some(ms) = latency {
+                buckets.entry(at.num_seconds_from_midnight() / BUCKET_SECS).or_default().push(*ms);
+            }
+        }
+        if buckets.is_empty() {
+            bail!("{} contains no target replies", path.display());
+        }
+
+        let source = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Ok(BaselineOverlay { source, medians: buckets.into_iter().map(|(b, values)| (b, median(values))).collect() })
+    }
+
+    pub fn describe(&self) -> String {
+        let first = self.medians.keys().next().copied().unwrap_or(0);
+        let last = self.medians.keys().next_back().copied().unwrap_or(0);
+        format!("{} ({} to {})", self.source, clock(first), clock(last + 1))
+    }
+
+    // Chart points (seconds since `start`, median) for the visible window,
+    // one flat step per bucket. Times of day the earlier session did not
+    // cover are bridged by the line.
+    pub fn points(&self, start: DateTime<Local>, view: (f64, f64)) -> Vec<(f64, f64)> {
+        let mut points = Vec::new();
+        let mut x = view.0;
+        while x < view.1 {
+            let at = start + Duration::milliseconds((x * 1000.0) as i64);
+            let secs = at.num_seconds_from_midnight();
+            let into = (secs % BUCKET_SECS) as f64 + at.nanosecond() as f64 / 1e9;
+            let end = (x + BUCKET_SECS as f64 - into).min(view.1);
+            if let Some(ms) = self.medians.get(&(secs / BUCKET_SECS)) {
+                points.push((x, *ms));
+                points.push((end, *ms));
+            }
+            x = end;
+        }
+        points
+    }
+}
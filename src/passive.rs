@@ -0,0 +1,392 @@
+use crate::bpf::{self, FP, R0, R1, R2, R3, R4, R6};
+use crate::diag;
+use crate::pinger::{PingUpdate, SourceType};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// The kernel keeps a smoothed RTT estimate for every TCP connection (what
+// `ss -ti` prints). Reading it adds no traffic, so the latency of the stream
+// or game that is actually running can be drawn next to the synthetic probes.
+//
+// It is read with eBPF: a small program on the tcp:tcp_probe tracepoint,
+// which the kernel hits for every segment an established connection
+// receives, keeps each connection's RTT and the time of its last segment in
+// a map. Loading it takes root (CAP_BPF and CAP_PERFMON) and a mounted
+// tracefs. Without them, the connection table is dumped through sock_diag
+// instead, which reports the same estimate with no privileges.
+
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const INET_DIAG_INFO: u16 = 2;
+const TCP_ESTABLISHED: u32 = 1;
+const NLMSG_HEADER_LEN: usize = 16;
+const REQUEST_LEN: usize = 72;
+const INET_DIAG_MSG_LEN: usize = 72;
+// Offsets into struct tcp_info (linux/tcp.h), both in the stable part.
+const TCPI_LAST_DATA_RECV: usize = 52;
+const TCPI_RTT: usize = 68;
+const MIN_POLL: Duration = Duration::from_millis(200);
+
+// The map key is the remote address as the tracepoint stores it (a
+// sockaddr_in6-sized buffer) and the local port, the value the RTT in
+// microseconds and when it was seen (CLOCK_MONOTONIC nanoseconds).
+const SOCKADDR_LEN: usize = 28;
+const TRACE_KEY_LEN: usize = SOCKADDR_LEN + 4;
+const TRACE_VALUE_LEN: usize = 16;
+const TRACE_MAX_CONNECTIONS: u32 = 4096;
+// Connections quiet for this long are dropped from the map, so it does not
+// fill up with closed ones.
+const TRACE_FORGET: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    remote: SocketAddr,
+    rtt_ms: f64,
+    idle_ms: u32,
+}
+
+fn request(family: u8) -> [u8; REQUEST_LEN] {
+    let mut msg = [0u8; REQUEST_LEN];
+    msg[0..4].copy_from_slice(&(REQUEST_LEN as u32).to_ne_bytes());
+    msg[4..6].copy_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    msg[6..8].copy_from_slice(&((libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16).to_ne_bytes());
+    // inet_diag_req_v2: family, protocol, extensions, pad, states, socket id.
+    msg[16] = family;
+    msg[17] = libc::IPPROTO_TCP as u8;
+    msg[18] = 1 << (INET_DIAG_INFO - 1);
+    msg[20..24].copy_from_slice(&(1u32 << TCP_ESTABLISHED).to_ne_bytes());
+    msg
+}
+
+fn parse_connection(msg: &[u8]) -> Option<Connection> {
+    if msg.len() < INET_DIAG_MSG_LEN {
+        return None;
+    }
+    let port = u16::from_be_bytes([msg[6], msg[7]]);
+    let ip = match msg[0] as i32 {
+        libc::AF_INET => IpAddr::V4(Ipv4Addr::new(msg[24], msg[25], msg[26], msg[27])),
+        libc::AF_INET6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&msg[24..40]).ok()?)),
+        _ => return None,
+    };
+
+    let mut attrs = &msg[INET_DIAG_MSG_LEN..];
+    while attrs.len() >= 4 {
+        let len = u16::from_ne_bytes([attrs[0], attrs[1]]) as usize;
+        let kind = u16::from_ne_bytes([attrs[2], attrs[3]]);
+        if len < 4 || len > attrs.len() {
+            break;
+        }
+        let info = &attrs[4..len];
+        if kind == INET_DIAG_INFO && info.len() >= TCPI_RTT + 4 {
+            let field = |at: usize| u32::from_ne_bytes([info[at], info[at + 1], info[at + 2], info[at + 3]]);
+            return Some(Connection {
+                remote: SocketAddr::new(ip, port),
+                rtt_ms: field(TCPI_RTT) as f64 / 1000.0,
+                idle_ms: field(TCPI_LAST_DATA_RECV),
+            });
+        }
+        attrs = &attrs[((len + 3) & !3).min(attrs.len())..];
+    }
+    None
+}
+
+fn dump(socket: &OwnedFd, family: u8, connections: &mut Vec<Connection>) -> io::Result<()> {
+    let fd = socket.as_raw_fd();
+    let req = request(family);
+    if unsafe { libc::send(fd, req.as_ptr() as *const libc::c_void, req.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let n = unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut rest = &buf[..n as usize];
+        while rest.len() >= NLMSG_HEADER_LEN {
+            let len = u32::from_ne_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let kind = u16::from_ne_bytes([rest[4], rest[5]]);
+            if len < NLMSG_HEADER_LEN || len > rest.len() {
+                break;
+            }
+            match kind as i32 {
+                libc::NLMSG_DONE => return Ok(()),
+                libc::NLMSG_ERROR => {
+                    let errno = rest.get(16..20).map(|e| i32::from_ne_bytes([e[0], e[1], e[2], e[3]])).unwrap_or(0);
+                    return Err(io::Error::from_raw_os_error(-errno));
+                }
+                _ => connections.extend(parse_connection(&rest[NLMSG_HEADER_LEN..len])),
+            }
+            rest = &rest[((len + 3) & !3).min(rest.len())..];
+        }
+    }
+}
+
+fn open_socket() -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_SOCK_DIAG) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn sock_diag_connections() -> io::Result<Vec<Connection>> {
+    let socket = open_socket()?;
+    let mut list = Vec::new();
+    dump(&socket, libc::AF_INET as u8, &mut list)?;
+    dump(&socket, libc::AF_INET6 as u8, &mut list)?;
+    Ok(list)
+}
+
+fn parse_sockaddr(addr: &[u8]) -> Option<SocketAddr> {
+    let port = u16::from_be_bytes([*addr.get(2)?, *addr.get(3)?]);
+    let ip = match u16::from_ne_bytes([*addr.first()?, *addr.get(1)?]) as i32 {
+        libc::AF_INET => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(addr.get(4..8)?).ok()?)),
+        libc::AF_INET6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(addr.get(8..24)?).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+fn monotonic_ns() -> u64 {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+}
+
+pub struct Tracer {
+    map: bpf::HashMap,
+    _attached: bpf::Attached,
+}
+
+impl Tracer {
+    fn open() -> io::Result<Self> {
+        let field = |name| bpf::tracepoint_field("tcp", "tcp_probe", name).map(|offset| offset as i16);
+        let (daddr, sport, srtt) = (field("daddr")?, field("sport")?, field("srtt")?);
+        let map = bpf::HashMap::create(TRACE_KEY_LEN, TRACE_VALUE_LEN, TRACE_MAX_CONNECTIONS)?;
+
+        // The key goes to fp-32, the value to fp-48.
+        let mut program = vec![bpf::mov_reg(R6, R1)];
+        for word in 0..(SOCKADDR_LEN / 4) as i16 {
+            program.push(bpf::load(4, R0, R6, daddr + 4 * word));
+            program.push(bpf::store(4, FP, -32 + 4 * word, R0));
+        }
+        program.extend([
+            bpf::load(2, R0, R6, sport),
+            bpf::store(4, FP, -4, R0),
+            bpf::load(4, R0, R6, srtt),
+            bpf::store(4, FP, -48, R0),
+            bpf::store_imm(FP, -44, 0),
+            bpf::call(bpf::HELPER_KTIME_GET_NS),
+            bpf::store(8, FP, -40, R0),
+        ]);
+        program.extend(bpf::load_map(R1, &map));
+        program.extend([
+            bpf::mov_reg(R2, FP),
+            bpf::add_imm(R2, -32),
+            bpf::mov_reg(R3, FP),
+            bpf::add_imm(R3, -48),
+            bpf::mov_imm(R4, 0),
+            bpf::call(bpf::HELPER_MAP_UPDATE_ELEM),
+            bpf::mov_imm(R0, 0),
+            bpf::exit(),
+        ]);
+
+        let attached = bpf::attach_tracepoint("tcp", "tcp_probe", &program)?;
+        Ok(Self { map, _attached: attached })
+    }
+
+    fn connections(&self) -> io::Result<Vec<Connection>> {
+        let now = monotonic_ns();
+        let mut list = Vec::new();
+        for (key, value) in self.map.entries()? {
+            let word = |at: usize| u32::from_ne_bytes([value[at], value[at + 1], value[at + 2], value[at + 3]]);
+            let seen = u64::from_ne_bytes(value[8..16].try_into().unwrap_or_default());
+            let idle = Duration::from_nanos(now.saturating_sub(seen));
+            if idle > TRACE_FORGET {
+                self.map.delete(&key);
+                continue;
+            }
+            if let Some(remote) = parse_sockaddr(&key[..SOCKADDR_LEN]) {
+                list.push(Connection { remote, rtt_ms: word(0) as f64 / 1000.0, idle_ms: idle.as_millis() as u32 });
+            }
+        }
+        Ok(list)
+    }
+}
+
+pub enum Source {
+    Tracer(Tracer),
+    SockDiag,
+}
+
+// One per process: the program stays attached until it exits.
+static SOURCE: OnceLock<Source> = OnceLock::new();
+
+// eBPF where it can be loaded, sock_diag otherwise. Checked once at startup
+// and fails only when neither works, so an unsupported system is reported
+// instead of leaving an empty series.
+pub fn open() -> io::Result<&'static Source> {
+    if let Some(source) = SOURCE.get() {
+        return Ok(source);
+    }
+    let source = match Tracer::open() {
+        Ok(tracer) => Source::Tracer(tracer),
+        Err(e) => {
+            sock_diag_connections()?;
+            diag::info(format!("Passive TCP RTT reads sock_diag, the eBPF program could not be loaded: {}", e));
+            Source::SockDiag
+        }
+    };
+    Ok(SOURCE.get_or_init(|| source))
+}
+
+impl Source {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Source::Tracer(_) => "eBPF",
+            Source::SockDiag => "sock_diag",
+        }
+    }
+
+    fn connections(&self) -> io::Result<Vec<Connection>> {
+        match self {
+            Source::Tracer(tracer) => tracer.connections(),
+            Source::SockDiag => sock_diag_connections(),
+        }
+    }
+}
+
+// Only connections that received data since the previous poll count, an idle
+// connection keeps reporting the RTT of its last exchange. Local connections
+// (e.g. to a database on the same host) would drag the median to zero.
+fn sample(source: &Source, port: Option<u16>, period: Duration) -> Option<f64> {
+    let mut rtts: Vec<f64> = source
+        .connections()
+        .ok()?
+        .into_iter()
+        .filter(|c| !c.remote.ip().is_loopback() && !c.remote.ip().is_unspecified())
+        .filter(|c| port.is_none_or(|p| c.remote.port() == p))
+        .filter(|c| (c.idle_ms as u128) <= period.as_millis())
+        .map(|c| c.rtt_ms)
+        .collect();
+    if rtts.is_empty() {
+        return None;
+    }
+    rtts.sort_by(|a, b| a.total_cmp(b));
+    Some(rtts[rtts.len() / 2])
+}
+
+// There is nothing to lose passively, so polls without active connections
+// are skipped rather than reported as timeouts.
+pub async fn run_passive_watcher(port: Option<u16>, interval: Duration, tx: mpsc::Sender<PingUpdate>) {
+    let Ok(source) = open() else {
+        return;
+    };
+    let period = interval.max(MIN_POLL);
+    let mut timer = tokio::time::interval(period);
+    timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        timer.tick().await;
+
+        let Ok(Some(rtt)) = tokio::task::spawn_blocking(move || sample(source, port, period)).await else {
+            continue;
+        };
+        if tx.send(PingUpdate { source: SourceType::Passive, latency: Some(rtt), error: None, ttl: None, late: false }).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // inet_diag_msg for 192.0.2.10:443 followed by the given attributes.
+    fn message(attrs: &[u8]) -> Vec<u8> {
+        let mut msg = vec![0u8; INET_DIAG_MSG_LEN];
+        msg[0] = libc::AF_INET as u8;
+        msg[6..8].copy_from_slice(&443u16.to_be_bytes());
+        msg[24..28].copy_from_slice(&[192, 0, 2, 10]);
+        msg.extend_from_slice(attrs);
+        msg
+    }
+
+    fn attr(kind: u16, payload: &[u8]) -> Vec<u8> {
+        let mut attr = Vec::new();
+        attr.extend_from_slice(&((4 + payload.len()) as u16).to_ne_bytes());
+        attr.extend_from_slice(&kind.to_ne_bytes());
+        attr.extend_from_slice(payload);
+        attr
+    }
+
+    // A tcp_info with an RTT of 12.5ms, last data 40ms ago.
+    fn tcp_info() -> Vec<u8> {
+        let mut info = vec![0u8; TCPI_RTT + 4];
+        info[TCPI_LAST_DATA_RECV..TCPI_LAST_DATA_RECV + 4].copy_from_slice(&40u32.to_ne_bytes());
+        info[TCPI_RTT..TCPI_RTT + 4].copy_from_slice(&12_500u32.to_ne_bytes());
+        info
+    }
+
+    #[test]
+    fn the_rtt_is_read_from_the_info_attribute() {
+        let connection = parse_connection(&message(&attr(INET_DIAG_INFO, &tcp_info()))).unwrap();
+        assert_eq!(connection.remote, "192.0.2.10:443".parse().unwrap());
+        assert_eq!(connection.rtt_ms, 12.5);
+        assert_eq!(connection.idle_ms, 40);
+    }
+
+    #[test]
+    fn attributes_are_skipped_with_their_padding() {
+        let mut attrs = attr(1, &[7]);
+        attrs.extend_from_slice(&[0, 0, 0]);
+        attrs.extend(attr(INET_DIAG_INFO, &tcp_info()));
+        assert_eq!(parse_connection(&message(&attrs)).unwrap().rtt_ms, 12.5);
+    }
+
+    #[test]
+    fn a_truncated_attribute_is_ignored() {
+        let mut attrs = attr(INET_DIAG_INFO, &tcp_info());
+        attrs.truncate(40);
+        assert!(parse_connection(&message(&attrs)).is_none());
+        // Too short for the header of an attribute.
+        assert!(parse_connection(&message(&[8, 0])).is_none());
+        assert!(parse_connection(&message(&[])[..INET_DIAG_MSG_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn padding_past_the_end_of_the_buffer_ends_the_walk() {
+        // A 5-byte attribute last in the buffer, its padding missing.
+        assert!(parse_connection(&message(&attr(1, &[7]))).is_none());
+    }
+
+    #[test]
+    fn a_zero_length_attribute_ends_the_walk() {
+        let mut attrs = vec![0, 0, 0, 0];
+        attrs.extend(attr(INET_DIAG_INFO, &tcp_info()));
+        assert!(parse_connection(&message(&attrs)).is_none());
+    }
+
+    #[test]
+    fn traced_addresses_are_read_from_the_sockaddr() {
+        let mut v4 = [0u8; SOCKADDR_LEN];
+        v4[..2].copy_from_slice(&(libc::AF_INET as u16).to_ne_bytes());
+        v4[2..4].copy_from_slice(&443u16.to_be_bytes());
+        v4[4..8].copy_from_slice(&[192, 0, 2, 10]);
+        assert_eq!(parse_sockaddr(&v4), Some("192.0.2.10:443".parse().unwrap()));
+
+        let mut v6 = [0u8; SOCKADDR_LEN];
+        v6[..2].copy_from_slice(&(libc::AF_INET6 as u16).to_ne_bytes());
+        v6[2..4].copy_from_slice(&8080u16.to_be_bytes());
+        v6[8..24].copy_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(parse_sockaddr(&v6), Some("[2001:db8::1]:8080".parse().unwrap()));
+
+        assert_eq!(parse_sockaddr(&[0u8; SOCKADDR_LEN]), None);
+    }
+}
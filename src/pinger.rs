@@ -1,50 +1,908 @@
-use std::net::IpAddr;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::runtime::Handle;
 use tokio::sync::mpsc;
-use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use rand::{Rng, distributions::{Distribution, Standard}, rngs::StdRng};
+use surge_ping::{Client, Config, ICMP, IcmpPacket, PingIdentifier, PingSequence, SurgeError};
+use crate::clock::{Clock, Schedule, SystemClock};
+use crate::dscp;
+use crate::echo;
+use crate::events::{EventKind, SystemEvent};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SourceType {
     Target,
     Gateway,
+    Hop(u8),
+    Cycle(usize),
+    Passive,
+}
+
+#[derive(Debug, Clone)]
+pub enum Probe {
+    Icmp,
+    Tcp(u16),
+    Udp(u16),
+    Echo(u16),
+    A2s(u16),
+    Dns(String),
+}
+
+impl Probe {
+    pub fn parse(spec: &str, dns_name: &str) -> Option<Self> {
+        let (kind, port) = match spec.split_once(':') {
+            Some((kind, port)) => (kind, Some(port.parse().ok()?)),
+            None => (spec, None),
+        };
+
+        match kind.to_lowercase().as_str() {
+            "tcp" => Some(Probe::Tcp(port.unwrap_or(443))),
+            "udp" => Some(Probe::Udp(port.unwrap_or(7))),
+            "echo" => Some(Probe::Echo(port.unwrap_or(echo::DEFAULT_PORT))),
+            "a2s" => Some(Probe::A2s(port.unwrap_or(27015))),
+            "dns" => Some(Probe::Dns(dns_name.to_string())),
+            "icmp" => Some(Probe::Icmp),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Probe::Icmp => "ICMP echo".to_string(),
+            Probe::Tcp(port) => format!("TCP connect (port {})", port),
+            Probe::Udp(port) => format!("UDP echo (port {})", port),
+            Probe::Echo(port) => format!("vasili UDP echo (port {})", port),
+            Probe::A2s(port) => format!("A2S_INFO query (port {})", port),
+            Probe::Dns(name) => format!("DNS query ({})", name),
+        }
+    }
+}
+
+// Why a probe got no latency. Only ICMP errors the target itself sends back
+// reach an ICMP probe; on the connected TCP and UDP sockets the kernel also
+// reports those of the routers on the way, as unreachable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeError {
+    Timeout,
+    Unreachable,
+    TtlExceeded,
+    SendError,
+}
+
+impl ProbeError {
+    // The status column of the logs.
+    pub fn status(&self) -> &'static str {
+        match self {
+            ProbeError::Timeout => "TIMEOUT",
+            ProbeError::Unreachable => "UNREACHABLE",
+            ProbeError::TtlExceeded => "TTL_EXCEEDED",
+            ProbeError::SendError => "SEND_ERROR",
+        }
+    }
+
+    pub fn from_status(status: &str) -> Option<ProbeError> {
+        [ProbeError::Timeout, ProbeError::Unreachable, ProbeError::TtlExceeded, ProbeError::SendError]
+            .into_iter()
+            .find(|e| e.status() == status)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProbeError::Timeout => "Timeout",
+            ProbeError::Unreachable => "Unreachable",
+            ProbeError::TtlExceeded => "TTL Exceeded",
+            ProbeError::SendError => "Send Error",
+        }
+    }
+
+    fn of_io(e: &io::Error) -> ProbeError {
+        match e.kind() {
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => ProbeError::Unreachable,
+            _ => ProbeError::SendError,
+        }
+    }
+
+    fn of_surge(e: &SurgeError) -> ProbeError {
+        match e {
+            SurgeError::Timeout { .. } => ProbeError::Timeout,
+            SurgeError::IOError(e) => ProbeError::of_io(e),
+            _ => ProbeError::SendError,
+        }
+    }
+
+    // An ICMP error that came back in place of the echo reply.
+    fn of_packet(packet: &IcmpPacket) -> Option<ProbeError> {
+        let (kind, echo_reply, time_exceeded) = match packet {
+            IcmpPacket::V4(packet) => (packet.get_icmp_type().0, 0, 11),
+            IcmpPacket::V6(packet) => (packet.get_icmpv6_type().0, 129, 3),
+        };
+        match kind {
+            _ if kind == echo_reply => None,
+            _ if kind == time_exceeded => Some(ProbeError::TtlExceeded),
+            _ => Some(ProbeError::Unreachable),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct PingUpdate {
     pub source: SourceType,
-    pub latency: Option<f64>, 
+    pub latency: Option<f64>,
+    // Set whenever latency is None.
+    pub error: Option<ProbeError>,
+    // TTL in the IP header of the reply. Only ICMP over IPv4 with a raw
+    // socket gets to see it.
+    pub ttl: Option<u8>,
+    // The result arrived after the next probe was already due.
+    pub late: bool,
+}
+
+// Hands out the ICMP clients as well as the random identifiers that keep
+// concurrent probe sessions apart; a seeded RNG makes those reproducible.
+pub struct ClientPool {
+    v4: Option<Arc<Client>>,
+    v6: Option<Arc<Client>>,
+    rng: StdRng,
+    runtime: Option<Handle>,
+    ttl: Option<u32>,
+    pacing: Option<Pacing>,
+    clock: Arc<dyn Clock>,
+}
+
+// The ICMP probers sharing one interval (Target, Gateway, the hops of Path
+// mode) start one slot apart instead of all at once, so dozens of hops do not
+// burst their probes onto a slow uplink together and queue behind each other.
+struct Pacing {
+    interval: Duration,
+    slots: u32,
+    next: u32,
+}
+
+impl ClientPool {
+    pub fn new(rng: StdRng) -> Self {
+        Self { v4: None, v6: None, rng, runtime: None, ttl: None, pacing: None, clock: Arc::new(SystemClock::new()) }
+    }
+
+    // The clock the probe timers handed out schedule on.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // --ttl: the TTL (hop limit on IPv6) of every ICMP probe sent.
+    pub fn with_ttl(mut self, ttl: Option<u32>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // The clients' receive tasks, which timestamp the replies, then run on
+    // that runtime instead of the current one.
+    pub fn with_runtime(mut self, runtime: Option<Handle>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    // Spreads the next `slots` timers handed out for `interval` evenly over it.
+    pub fn pace(&mut self, interval: Duration, slots: usize) {
+        self.pacing = Some(Pacing { interval, slots: slots.max(1) as u32, next: 0 });
+    }
+
+    pub fn probe_timer(&mut self, period: Duration) -> ProbeTimer {
+        let offset = match &mut self.pacing {
+            Some(pacing) if period == pacing.interval => {
+                let offset = pacing.interval * pacing.next / pacing.slots;
+                pacing.next += 1;
+                offset
+            }
+            // The gateway goes twice per interval. A quarter slot after the
+            // first slot keeps both of its probes off every slot, however
+            // many there are.
+            Some(pacing) => pacing.interval / pacing.slots / 4,
+            None => Duration::ZERO,
+        };
+        ProbeTimer::new(self.clock.clone(), offset, period)
+    }
+
+    // A timer outside the pacing, for probes that do not share its interval.
+    pub fn timer(&self, period: Duration) -> ProbeTimer {
+        ProbeTimer::new(self.clock.clone(), Duration::ZERO, period)
+    }
+
+    pub fn probe_id<T>(&mut self) -> T
+    where
+        Standard: Distribution<T>,
+    {
+        self.rng.r#gen()
+    }
+
+    pub fn client_for(&mut self, ip: IpAddr) -> io::Result<Arc<Client>> {
+        let (slot, kind) = match ip {
+            IpAddr::V4(_) => (&mut self.v4, ICMP::V4),
+            IpAddr::V6(_) => (&mut self.v6, ICMP::V6),
+        };
+
+        if let Some(client) = slot {
+            return Ok(client.clone());
+        }
+
+        let _runtime = self.runtime.as_ref().map(Handle::enter);
+        let mut config = Config::builder().kind(kind);
+        if let Some(ttl) = self.ttl {
+            config = config.ttl(ttl);
+        }
+        let client = Arc::new(Client::new(&config.build())?);
+        // The client keeps the socket open for as long as it lives.
+        #[cfg(unix)]
+        dscp::mark(&unsafe { std::os::fd::BorrowedFd::borrow_raw(client.get_socket().get_native_sock()) }, ip)?;
+        *slot = Some(client.clone());
+        Ok(client)
+    }
+}
+
+// A reply is late when it came after the next probe of its host was due.
+// That counts from when the probe actually went out: with missed ticks
+// skipped the deadline of a tick can lie well before it.
+pub fn late(sent: Instant, interval: Duration) -> bool {
+    sent.elapsed() > interval
+}
+
+// Waits out a Schedule on the session clock. Only tick() moves the schedule
+// on, and only once it returns, so a tick dropped in a select! is not lost.
+pub struct ProbeTimer {
+    clock: Arc<dyn Clock>,
+    schedule: Schedule,
+}
+
+impl ProbeTimer {
+    fn new(clock: Arc<dyn Clock>, offset: Duration, period: Duration) -> Self {
+        let schedule = Schedule::new(clock.elapsed() + offset, period);
+        Self { clock, schedule }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.schedule.period()
+    }
+
+    pub async fn tick(&mut self) {
+        loop {
+            let now = self.clock.elapsed();
+            let wait = self.schedule.wait(now);
+            if wait.is_zero() {
+                self.schedule.advance(now);
+                return;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// How long a probe waits for its reply unless --timeout says otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+const DNS_PORT: u16 = 53;
+
+fn reply_ttl(packet: &IcmpPacket) -> Option<u8> {
+    match packet {
+        IcmpPacket::V4(packet) => packet.get_ttl(),
+        IcmpPacket::V6(_) => None,
+    }
 }
 
+pub async fn answers_icmp(client: Arc<Client>, target_ip: IpAddr, id: u16, attempts: u16) -> bool {
+    let mut pinger = client.pinger(target_ip, PingIdentifier(id)).await;
+    pinger.timeout(Duration::from_secs(1));
+
+    for seq in 0..attempts {
+        if pinger.ping(PingSequence(seq), &[0; 8]).await.is_ok() {
+            return true;
+        }
+    }
+    false
+}
+
+// Probes go out on the schedule whatever their replies do. Each one waits
+// for its own reply, matched by sequence number, in a task of its own, so a
+// timeout longer than the interval no longer holds back the probes after it;
+// a lost probe is reported once its deadline has passed.
 pub async fn run_pinger(
+    client: Arc<Client>,
     target_ip: IpAddr,
-    interval: Duration,
+    id: u16,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
     source_type: SourceType,
     tx: mpsc::Sender<PingUpdate>,
 ) {
-    let client = match Client::new(&Config::default()) {
-        Ok(c) => c,
-        Err(_) => return,
-    };
+    let mut seq_cnt = 0u16;
+    let interval = interval_timer.period();
+    let mut in_flight = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            _ = interval_timer.tick() => {
+                let mut pinger = client.pinger(target_ip, PingIdentifier(id)).await;
+                pinger.timeout(timeout);
+                let seq = PingSequence(seq_cnt);
+                in_flight.spawn(async move {
+                    let sent = Instant::now();
+                    (sent, pinger.ping(seq, &[0; 8]).await)
+                });
+                seq_cnt = seq_cnt.wrapping_add(1);
+            }
+
+            Some(Ok((sent, result))) = in_flight.join_next() => {
+                let (latency, error, ttl) = match result {
+                    Ok((packet, duration)) => match ProbeError::of_packet(&packet) {
+                        Some(error) => (None, Some(error), reply_ttl(&packet)),
+                        None => (Some(duration.as_secs_f64() * 1000.0), None, reply_ttl(&packet)),
+                    },
+                    Err(e) => {
+                        if !matches!(e, SurgeError::Timeout { .. }) {
+                            crate::diag::warn(format!("ICMP to {} failed: {}", target_ip, e));
+                        }
+                        (None, Some(ProbeError::of_surge(&e)), None)
+                    }
+                };
+                let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl, late: late(sent, interval) }).await;
+            }
+        }
+    }
+}
+
+// One probe slot shared by many targets: every tick probes the next one in
+// the list, so each of them is sampled every interval * targets.len().
+pub async fn run_cycle_pinger(
+    targets: Vec<(Arc<Client>, IpAddr, u16)>,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let mut pingers = Vec::with_capacity(targets.len());
+    for (client, ip, id) in targets {
+        let mut pinger = client.pinger(ip, PingIdentifier(id)).await;
+        pinger.timeout(timeout);
+        pingers.push((ip, pinger));
+    }
+    if pingers.is_empty() {
+        return;
+    }
 
-    let mut pinger = client.pinger(target_ip, PingIdentifier(rand::random())).await;
     let mut seq_cnt = 0u16;
-    let mut interval_timer = tokio::time::interval(interval);
+    let mut next = 0;
+    let interval = interval_timer.period();
 
     loop {
         interval_timer.tick().await;
 
-        let payload = [0; 8];
-        match pinger.ping(PingSequence(seq_cnt), &payload).await {
-            Ok((_, duration)) => {
-                let ms = duration.as_secs_f64() * 1000.0;
-                let _ = tx.send(PingUpdate { source: source_type.clone(), latency: Some(ms) }).await;
+        let (ip, pinger) = &mut pingers[next];
+        let sent = Instant::now();
+        let (latency, error, ttl) = match pinger.ping(PingSequence(seq_cnt), &[0; 8]).await {
+            Ok((packet, duration)) => match ProbeError::of_packet(&packet) {
+                Some(error) => (None, Some(error), reply_ttl(&packet)),
+                None => (Some(duration.as_secs_f64() * 1000.0), None, reply_ttl(&packet)),
+            },
+            Err(e) => {
+                if !matches!(e, SurgeError::Timeout { .. }) {
+                    crate::diag::warn(format!("ICMP to {} failed: {}", ip, e));
+                }
+                (None, Some(ProbeError::of_surge(&e)), None)
             }
+        };
+        let _ = tx.send(PingUpdate { source: SourceType::Cycle(next), latency, error, ttl, late: late(sent, interval) }).await;
+
+        next = (next + 1) % pingers.len();
+        if next == 0 {
+            seq_cnt = seq_cnt.wrapping_add(1);
+        }
+    }
+}
 
-            Err(_) => {
-                let _ = tx.send(PingUpdate { source: source_type.clone(), latency: None }).await;
+async fn tcp_connect(target: SocketAddr) -> io::Result<TcpStream> {
+    let socket = match target {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    dscp::mark(&socket, target.ip())?;
+    socket.connect(target).await
+}
+
+pub async fn run_tcp_pinger(
+    target: SocketAddr,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let interval = interval_timer.period();
+
+    loop {
+        interval_timer.tick().await;
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, tcp_connect(target)).await {
+            Ok(Ok(_)) => (Some(start.elapsed().as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                (Some(start.elapsed().as_secs_f64() * 1000.0), None)
             }
+            Ok(Err(e)) => {
+                crate::diag::warn(format!("TCP connect to {} failed: {}", target, e));
+                (None, Some(ProbeError::of_io(&e)))
+            }
+            Err(_) => (None, Some(ProbeError::Timeout)),
         };
-        
-        seq_cnt = seq_cnt.wrapping_add(1);
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+pub fn build_dns_query(id: u16, name: &str) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(32 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label);
     }
-}
\ No newline at end of file
+    packet.push(0);
+    packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+    packet
+}
+
+async fn dns_round_trip(socket: &UdpSocket, query: &[u8], id: u16) -> io::Result<()> {
+    socket.send(query).await?;
+
+    let mut buf = [0u8; 512];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        if len >= 3 && u16::from_be_bytes([buf[0], buf[1]]) == id && buf[2] & 0x80 != 0 {
+            return Ok(());
+        }
+    }
+}
+
+pub async fn connected_udp_socket(target: SocketAddr) -> io::Result<UdpSocket> {
+    let bind_addr: SocketAddr = match target.ip() {
+        IpAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        IpAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    dscp::mark(&socket, target.ip())?;
+    socket.connect(target).await?;
+    Ok(socket)
+}
+
+async fn udp_round_trip(socket: &UdpSocket, payload: &[u8]) -> io::Result<()> {
+    socket.send(payload).await?;
+
+    let mut buf = [0u8; 64];
+    loop {
+        match socket.recv(&mut buf).await {
+            Ok(len) if buf[..len].starts_with(payload) => return Ok(()),
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset) => {
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub async fn run_udp_pinger(
+    target: SocketAddr,
+    session: u64,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let socket = match connected_udp_socket(target).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            crate::diag::error(format!("Could not open a UDP socket to {}", target));
+            return;
+        }
+    };
+
+    let mut seq = 0u64;
+    let interval = interval_timer.period();
+
+    loop {
+        interval_timer.tick().await;
+
+        seq = seq.wrapping_add(1);
+        let mut payload = [0u8; 16];
+        payload[..8].copy_from_slice(&session.to_be_bytes());
+        payload[8..].copy_from_slice(&seq.to_be_bytes());
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, udp_round_trip(&socket, &payload)).await {
+            Ok(Ok(())) => (Some(start.elapsed().as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) => (None, Some(ProbeError::of_io(&e))),
+            Err(_) => (None, Some(ProbeError::Timeout)),
+        };
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+async fn echo_round_trip(socket: &UdpSocket, session: u64, seq: u64) -> io::Result<u64> {
+    socket.send(&echo::request(session, seq)).await?;
+
+    let mut buf = [0u8; 64];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        if let Some(received) = echo::parse_reply(&buf[..len], session, seq) {
+            return Ok(received);
+        }
+    }
+}
+
+// Probes a `vasili echo-server`. Its count of received probes tells after a
+// loss whether the probes died on the way to the server or on the way back,
+// which is reported as an event once replies come in again.
+pub async fn run_echo_pinger(
+    target: SocketAddr,
+    session: u64,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+    events: mpsc::Sender<SystemEvent>,
+) {
+    let socket = match connected_udp_socket(target).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            crate::diag::error(format!("Could not open a UDP socket to {}", target));
+            return;
+        }
+    };
+
+    let mut seq = 0u64;
+    let mut split = echo::LossSplit::default();
+    let interval = interval_timer.period();
+
+    loop {
+        interval_timer.tick().await;
+        seq += 1;
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, echo_round_trip(&socket, session, seq)).await {
+            Ok(Ok(received)) => {
+                if let Some((upstream, downstream)) = split.on_reply(seq, received) {
+                    let message = format!(
+                        "{} UDP probes lost: {} on the way to {} (upload), {} on the way back (download)",
+                        upstream + downstream,
+                        upstream,
+                        target,
+                        downstream
+                    );
+                    let _ = events.try_send(SystemEvent { kind: EventKind::EchoLoss, message });
+                }
+                (Some(start.elapsed().as_secs_f64() * 1000.0), None)
+            }
+            Ok(Err(e)) => (None, Some(ProbeError::of_io(&e))),
+            Err(_) => (None, Some(ProbeError::Timeout)),
+        };
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+const A2S_INFO_REQUEST: &[u8] = b"\xFF\xFF\xFF\xFFTSource Engine Query\0";
+const A2S_CHALLENGE: u8 = b'A';
+const A2S_INFO: u8 = b'I';
+
+// Servers that answer A2S_INFO with an S2C_CHALLENGE only send the info once
+// the query is repeated with the challenge number. That first exchange is no
+// answer yet: the query is sent again and only the answer to that is timed.
+// The challenge is kept for the next probes, which then take one round trip
+// as long as the server accepts it.
+async fn a2s_round_trip(socket: &UdpSocket, challenge: &mut Option<[u8; 4]>) -> io::Result<Duration> {
+    let send = |challenge: Option<[u8; 4]>| {
+        let mut request = A2S_INFO_REQUEST.to_vec();
+        request.extend(challenge.iter().flatten());
+        async move {
+            socket.send(&request).await?;
+            Ok::<_, io::Error>(Instant::now())
+        }
+    };
+    let mut sent = send(*challenge).await?;
+
+    let mut buf = [0u8; 1400];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        let received = sent.elapsed();
+        if len >= 9 && buf[..4] == [0xFF; 4] && buf[4] == A2S_CHALLENGE {
+            *challenge = Some([buf[5], buf[6], buf[7], buf[8]]);
+            sent = send(*challenge).await?;
+            continue;
+        }
+        let single = len >= 5 && buf[..4] == [0xFF; 4] && buf[4] == A2S_INFO;
+        let split = len >= 4 && buf[..4] == [0xFE, 0xFF, 0xFF, 0xFF];
+        if single || split {
+            return Ok(received);
+        }
+    }
+}
+
+pub async fn run_a2s_pinger(
+    target: SocketAddr,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let socket = match connected_udp_socket(target).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            crate::diag::error(format!("Could not open a UDP socket to {}", target));
+            return;
+        }
+    };
+
+    let interval = interval_timer.period();
+    let mut challenge = None;
+
+    loop {
+        interval_timer.tick().await;
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, a2s_round_trip(&socket, &mut challenge)).await {
+            Ok(Ok(round_trip)) => (Some(round_trip.as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) => {
+                crate::diag::warn(format!("A2S query to {} failed: {}", target, e));
+                (None, Some(ProbeError::of_io(&e)))
+            }
+            Err(_) => (None, Some(ProbeError::Timeout)),
+        };
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+pub async fn run_dns_pinger(
+    server: IpAddr,
+    name: String,
+    first_id: u16,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let socket = match connected_udp_socket(SocketAddr::new(server, DNS_PORT)).await {
+        Ok(socket) => socket,
+        Err(_) => {
+            crate::diag::error(format!("Could not open a UDP socket to {}", server));
+            return;
+        }
+    };
+
+    let mut id = first_id;
+    let interval = interval_timer.period();
+
+    loop {
+        interval_timer.tick().await;
+
+        id = id.wrapping_add(1);
+        let query = build_dns_query(id, &name);
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, dns_round_trip(&socket, &query, id)).await {
+            Ok(Ok(())) => (Some(start.elapsed().as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) => (None, Some(ProbeError::of_io(&e))),
+            Err(_) => (None, Some(ProbeError::Timeout)),
+        };
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub struct ArpProber {
+    fd: tokio::io::unix::AsyncFd<std::os::fd::OwnedFd>,
+    ifindex: i32,
+    own_mac: [u8; 6],
+    own_ip: std::net::Ipv4Addr,
+    gateway_ip: std::net::Ipv4Addr,
+    gateway_mac: Option<[u8; 6]>,
+}
+
+#[cfg(target_os = "linux")]
+impl ArpProber {
+    const ETH_P_ARP: u16 = 0x0806;
+
+    pub fn new(gateway_ip: std::net::Ipv4Addr) -> io::Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        let unsupported = |msg: &str| io::Error::new(io::ErrorKind::Unsupported, msg.to_string());
+
+        let iface = default_net::get_default_interface().map_err(io::Error::other)?;
+        let own_mac = iface.mac_addr.map(|m| m.octets()).filter(|m| *m != [0; 6])
+            .ok_or_else(|| unsupported("interface has no MAC address"))?;
+
+        let mask = |prefix: u8| u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+        let own_net = iface.ipv4.iter()
+            .find(|net| u32::from(net.addr) & mask(net.prefix_len) == u32::from(gateway_ip) & mask(net.prefix_len))
+            .ok_or_else(|| unsupported("gateway is not on-link"))?;
+
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                Self::ETH_P_ARP.to_be() as i32,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) };
+
+        let prober = Self {
+            fd: tokio::io::unix::AsyncFd::new(fd)?,
+            ifindex: iface.index as i32,
+            own_mac,
+            own_ip: own_net.addr,
+            gateway_ip,
+            gateway_mac: None,
+        };
+
+        let addr = prober.link_addr([0xff; 6]);
+        let ret = unsafe {
+            libc::bind(
+                std::os::fd::AsRawFd::as_raw_fd(prober.fd.get_ref()),
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_ll>() as u32,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(prober)
+    }
+
+    fn link_addr(&self, mac: [u8; 6]) -> libc::sockaddr_ll {
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = Self::ETH_P_ARP.to_be();
+        addr.sll_ifindex = self.ifindex;
+        addr.sll_halen = 6;
+        addr.sll_addr[..6].copy_from_slice(&mac);
+        addr
+    }
+
+    fn request(&self) -> [u8; 28] {
+        let mut packet = [0u8; 28];
+        packet[..8].copy_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01]);
+        packet[8..14].copy_from_slice(&self.own_mac);
+        packet[14..18].copy_from_slice(&self.own_ip.octets());
+        packet[24..28].copy_from_slice(&self.gateway_ip.octets());
+        packet
+    }
+
+    async fn round_trip(&mut self) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let packet = self.request();
+        let dest = self.link_addr(self.gateway_mac.unwrap_or([0xff; 6]));
+        let sent = unsafe {
+            libc::sendto(
+                self.fd.get_ref().as_raw_fd(),
+                packet.as_ptr() as *const libc::c_void,
+                packet.len(),
+                0,
+                &dest as *const libc::sockaddr_ll as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_ll>() as u32,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = [0u8; 64];
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let len = match guard.try_io(|fd| {
+                let n = unsafe { libc::recv(fd.get_ref().as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+                if n < 0 { Err(io::Error::last_os_error()) } else { Ok(n as usize) }
+            }) {
+                Ok(result) => result?,
+                Err(_would_block) => continue,
+            };
+
+            let is_reply = len >= 28 && buf[6..8] == [0x00, 0x02] && buf[14..18] == self.gateway_ip.octets();
+            if is_reply {
+                let mut mac = [0u8; 6];
+                mac.copy_from_slice(&buf[8..14]);
+                self.gateway_mac = Some(mac);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub async fn run_arp_pinger(
+    mut prober: ArpProber,
+    mut interval_timer: ProbeTimer,
+    timeout: Duration,
+    source_type: SourceType,
+    tx: mpsc::Sender<PingUpdate>,
+) {
+    let interval = interval_timer.period();
+
+    loop {
+        interval_timer.tick().await;
+
+        let start = Instant::now();
+        let (latency, error) = match tokio::time::timeout(timeout, prober.round_trip()).await {
+            Ok(Ok(())) => (Some(start.elapsed().as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) => (None, Some(ProbeError::of_io(&e))),
+            Err(_) => (None, Some(ProbeError::Timeout)),
+        };
+
+        let _ = tx.send(PingUpdate { source: source_type.clone(), latency, error, ttl: None, late: late(start, interval) }).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn a_seed_gives_the_same_probe_ids() {
+        let ids = |seed| {
+            let mut pool = ClientPool::new(StdRng::seed_from_u64(seed));
+            (0..5).map(|_| pool.probe_id::<u16>()).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(7), ids(7));
+        assert_ne!(ids(7), ids(8));
+    }
+
+    // A server asking for a challenge: only the repeated query is timed,
+    // and the next probe sends the challenge right away.
+    #[tokio::test]
+    async fn a2s_times_the_answer_after_the_challenge() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client = connected_udp_socket(server.local_addr().unwrap()).await.unwrap();
+        let serve = async {
+            let mut buf = [0u8; 64];
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                let (len, from) = server.recv_from(&mut buf).await.unwrap();
+                let request = buf[..len].to_vec();
+                let reply: &[u8] = if request.ends_with(&[1, 2, 3, 4]) {
+                    b"\xFF\xFF\xFF\xFFI\x11"
+                } else {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    b"\xFF\xFF\xFF\xFFA\x01\x02\x03\x04"
+                };
+                server.send_to(reply, from).await.unwrap();
+                requests.push(request);
+            }
+            requests
+        };
+
+        let mut challenge = None;
+        let probes = async {
+            let first = a2s_round_trip(&client, &mut challenge).await.unwrap();
+            let second = a2s_round_trip(&client, &mut challenge).await.unwrap();
+            (first, second)
+        };
+        let (requests, (first, second)) = tokio::join!(serve, probes);
+
+        assert!(first < Duration::from_millis(200), "{:?}", first);
+        assert!(second < Duration::from_millis(200), "{:?}", second);
+        assert_eq!(challenge, Some([1, 2, 3, 4]));
+        assert_eq!(requests[0], A2S_INFO_REQUEST);
+        assert_eq!(requests[1], [A2S_INFO_REQUEST, &[1, 2, 3, 4]].concat());
+        assert_eq!(requests[2], requests[1]);
+    }
+}
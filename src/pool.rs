@@ -0,0 +1,135 @@
+use crate::app::SessionSummary;
+use crate::config::config_dir;
+use anyhow::Result;
+use rand::distributions::{Distribution, WeightedIndex};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf};
+
+pub const TARGET_POOL: &[&str] = &[
+    "1.1.1.1",
+    "8.8.8.8",
+    "9.9.9.9",
+    "208.67.222.222",
+    "1.0.0.1",
+    "8.8.4.4",
+];
+
+pub const TARGET_POOL_V6: &[&str] = &[
+    "2606:4700:4700::1111",
+    "2001:4860:4860::8888",
+    "2620:fe::fe",
+    "2620:119:35::35",
+    "2606:4700:4700::1001",
+    "2001:4860:4860::8844",
+];
+
+const SCORE_ALPHA: f64 = 0.3;
+const LOSS_PENALTY_MS: f64 = 20.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberQuality {
+    pub runs: u32,
+    pub score: f64,
+    pub p75: f64,
+    pub loss_percent: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PoolHistory {
+    pub members: HashMap<String, MemberQuality>,
+}
+
+fn history_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("pool.json"))
+}
+
+fn run_score(summary: &SessionSummary) -> f64 {
+    summary.p75 + summary.loss_percent * LOSS_PENALTY_MS
+}
+
+impl PoolHistory {
+    pub fn load() -> Self {
+        history_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = history_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn choose(&self, v6: bool) -> &'static str {
+        let pool = if v6 { TARGET_POOL_V6 } else { TARGET_POOL };
+
+        let known: Vec<f64> = pool.iter().filter_map(|m| self.members.get(*m)).map(|q| q.score).collect();
+        let best_known = known.iter().cloned().fold(f64::INFINITY, f64::min);
+        let weights: Vec<f64> = pool
+            .iter()
+            .map(|m| {
+                let score = self.members.get(*m).map(|q| q.score).unwrap_or(if known.is_empty() { 0.0 } else { best_known });
+                1.0 / (score.max(0.0) + 5.0)
+            })
+            .collect();
+
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => pool[dist.sample(&mut rand::thread_rng())],
+            Err(_) => pool[0],
+        }
+    }
+
+    pub fn record(summary: &SessionSummary) -> Result<()> {
+        let is_member = TARGET_POOL.contains(&summary.target_ip.as_str())
+            || TARGET_POOL_V6.contains(&summary.target_ip.as_str());
+        if !is_member || summary.samples == 0 {
+            return Ok(());
+        }
+
+        let mut history = Self::load();
+        let score = run_score(summary);
+        history
+            .members
+            .entry(summary.target_ip.clone())
+            .and_modify(|q| {
+                q.runs += 1;
+                q.score = q.score * (1.0 - SCORE_ALPHA) + score * SCORE_ALPHA;
+                q.p75 = summary.p75;
+                q.loss_percent = summary.loss_percent;
+            })
+            .or_insert(MemberQuality {
+                runs: 1,
+                score,
+                p75: summary.p75,
+                loss_percent: summary.loss_percent,
+            });
+        history.save()
+    }
+
+    pub fn print_rankings(&self) {
+        let mut members: Vec<&str> = TARGET_POOL.iter().chain(TARGET_POOL_V6.iter()).copied().collect();
+        members.sort_by(|a, b| {
+            let score = |m: &str| self.members.get(m).map(|q| q.score).unwrap_or(f64::INFINITY);
+            score(a).total_cmp(&score(b))
+        });
+
+        println!("{:<4} {:<24} {:>6} {:>10} {:>12} {:>10}", "#", "Target", "Runs", "Score", "Last P75", "Last Loss");
+        for (i, member) in members.iter().enumerate() {
+            match self.members.get(*member) {
+                Some(q) => println!(
+                    "{:<4} {:<24} {:>6} {:>10.1} {:>10.1}ms {:>9.1}%",
+                    i + 1, member, q.runs, q.score, q.p75, q.loss_percent
+                ),
+                None => println!("{:<4} {:<24} {:>6} {:>10} {:>12} {:>10}", i + 1, member, 0, "-", "-", "-"),
+            }
+        }
+        println!();
+        println!("Score = P75 + {}ms per % loss (moving average, lower is better).", LOSS_PENALTY_MS);
+    }
+}
@@ -0,0 +1,112 @@
+use std::io;
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use tokio::runtime::{Builder, Handle};
+
+// The probes run on a runtime of their own, on one thread apart from the TUI
+// and the sinks, so a heavy redraw or a slow database write never delays a
+// probe timer or the timestamp of a reply. With --high-priority that thread
+// is also raised above normal scheduling and pinned to a CPU, against a game
+// or other load on the same machine.
+
+#[cfg(target_os = "linux")]
+const RT_PRIORITY: libc::c_int = 10;
+#[cfg(target_os = "linux")]
+const NICE: libc::c_int = -10;
+
+// Real-time round robin where allowed, otherwise a lower nice value.
+#[cfg(target_os = "linux")]
+fn elevate_current_thread() -> Result<&'static str, String> {
+    let param = libc::sched_param { sched_priority: RT_PRIORITY };
+    // On Linux both calls apply to the calling thread only.
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_RR, &param) } == 0 {
+        return Ok("real-time (SCHED_RR)");
+    }
+    let realtime = io::Error::last_os_error();
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE) } == 0 {
+        return Ok("nice -10");
+    }
+    Err(format!("{} (needs root or CAP_SYS_NICE)", realtime))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn elevate_current_thread() -> Result<&'static str, String> {
+    Err("not supported on this platform".to_string())
+}
+
+// The last CPU the thread may run on, since games and the desktop tend to
+// crowd the first ones. Taken from the affinity mask rather than the CPU
+// count, which under taskset, a cgroup cpuset or with CPUs offline names a
+// CPU that is not allowed or does not exist.
+#[cfg(target_os = "linux")]
+fn pin_current_thread() -> Result<usize, String> {
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    unsafe {
+        let mut allowed: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, size, &mut allowed) != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let cpu = (0..libc::CPU_SETSIZE as usize)
+            .rev()
+            .find(|&cpu| libc::CPU_ISSET(cpu, &allowed))
+            .ok_or("no CPU in the affinity mask")?;
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, size, &set) != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(cpu)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread() -> Result<usize, String> {
+    Err("not supported on this platform".to_string())
+}
+
+pub struct Elevation {
+    pub priority: Result<&'static str, String>,
+    pub pinned: Result<usize, String>,
+}
+
+pub struct ProbeThread {
+    pub handle: Handle,
+    // Only with --high-priority.
+    pub elevation: Option<Elevation>,
+}
+
+pub fn spawn_probe_thread(high_priority: bool) -> io::Result<ProbeThread> {
+    let runtime = Builder::new_current_thread().enable_all().build()?;
+    let (tx, rx) = std_mpsc::channel();
+
+    thread::Builder::new().name("vasili-probes".to_string()).spawn(move || {
+        let elevation = high_priority.then(|| Elevation { priority: elevate_current_thread(), pinned: pin_current_thread() });
+        let _ = tx.send(ProbeThread { handle: runtime.handle().clone(), elevation });
+        // Runs the probe tasks until the process exits.
+        runtime.block_on(std::future::pending::<()>());
+    })?;
+
+    rx.recv().map_err(|_| io::Error::other("the probe thread did not start"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn allowed() -> Vec<usize> {
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            assert_eq!(libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set), 0);
+            (0..libc::CPU_SETSIZE as usize).filter(|&cpu| libc::CPU_ISSET(cpu, &set)).collect()
+        }
+    }
+
+    #[test]
+    fn pins_to_the_last_allowed_cpu() {
+        let before = allowed();
+        // On a thread of its own, the test harness keeps its affinity.
+        let (pinned, after) = thread::spawn(|| (pin_current_thread(), allowed())).join().unwrap();
+        assert_eq!(pinned, Ok(*before.last().unwrap()));
+        assert_eq!(after, [*before.last().unwrap()]);
+    }
+}
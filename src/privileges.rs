@@ -0,0 +1,85 @@
+use crate::utils::confirm;
+use std::{env, io, process::Command};
+
+pub fn is_permission_error(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::PermissionDenied || matches!(err.raw_os_error(), Some(1) | Some(13))
+}
+
+fn current_exe() -> String {
+    env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "vasili".to_string())
+}
+
+pub fn print_guidance(err: &io::Error) {
+    let exe = current_exe();
+
+    eprintln!("Error: Could not open an ICMP socket ({}).", err);
+    eprintln!();
+
+    if cfg!(target_os = "linux") || cfg!(target_os = "android") {
+        eprintln!("Vasili needs permission to send ICMP packets. Pick one of the following:");
+        eprintln!();
+        eprintln!("  1. Grant the raw socket capability to the binary (recommended):");
+        eprintln!("       sudo setcap cap_net_raw+ep {}", exe);
+        eprintln!();
+        eprintln!("  2. Allow unprivileged ping sockets for all groups:");
+        eprintln!("       sudo sysctl -w net.ipv4.ping_group_range=\"0 2147483647\"");
+        eprintln!("     (persist it via /etc/sysctl.d/99-vasili.conf)");
+        eprintln!();
+        eprintln!("  3. Run as root:");
+        eprintln!("       sudo {}", exe);
+    } else if cfg!(target_os = "macos") {
+        eprintln!("On macOS unprivileged ICMP normally works. If it does not, run Vasili with sudo:");
+        eprintln!("       sudo {}", exe);
+    } else if cfg!(windows) {
+        eprintln!("On Windows raw ICMP sockets require Administrator rights.");
+        eprintln!("Start Vasili from an elevated terminal (\"Run as administrator\").");
+    } else {
+        eprintln!("Run Vasili with elevated privileges (e.g. sudo {}).", exe);
+    }
+    eprintln!();
+}
+
+pub fn offer_fix() {
+    let exe = current_exe();
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if cfg!(target_os = "linux") || cfg!(target_os = "android") {
+        if !confirm(&format!("Run 'sudo setcap cap_net_raw+ep {}' now?", exe)) {
+            return;
+        }
+
+        match Command::new("sudo").args(["setcap", "cap_net_raw+ep", &exe]).status() {
+            Ok(status) if status.success() => {
+                eprintln!("Capability granted. Please start Vasili again.");
+            }
+            Ok(status) => eprintln!("setcap failed ({}).", status),
+            Err(e) => eprintln!("Could not run sudo: {}", e),
+        }
+    } else if cfg!(target_os = "macos") {
+        if !confirm("Relaunch Vasili with sudo now?") {
+            return;
+        }
+
+        let _ = Command::new("sudo").arg(&exe).args(&args).status();
+    } else if cfg!(windows) {
+        if !confirm("Relaunch Vasili as Administrator now?") {
+            return;
+        }
+
+        let arg_list = args
+            .iter()
+            .map(|a| format!("'{}'", a.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut script = format!("Start-Process -FilePath '{}' -Verb RunAs", exe);
+        if !arg_list.is_empty() {
+            script.push_str(&format!(" -ArgumentList {}", arg_list));
+        }
+
+        if let Err(e) = Command::new("powershell").args(["-NoProfile", "-Command", &script]).status() {
+            eprintln!("Could not relaunch elevated: {}", e);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::analyze;
+use crate::app::HostStats;
+use crate::args::format_duration;
+use crate::locale;
+use crate::rules::{GRADES, grade_rank};
+use crate::session;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Local, NaiveTime, Timelike};
+use std::fs;
+use std::path::Path;
+
+// --event 20:00: a readiness verdict for a scheduled event (a match, a
+// stream, a call), from how the Target did in the last minutes and how the
+// same hour usually goes in the earlier logs of the output directory.
+
+// The live samples the verdict is based on, and the trend compares them to
+// the same span just before.
+const RECENT_SECS: f64 = 600.0;
+// The verdict goes to the event log (and the daemon output) this long before
+// the event, and shows above the chart from BANNER_LEAD on.
+const ANNOUNCE_LEAD: Duration = Duration::minutes(15);
+const BANNER_LEAD: Duration = Duration::hours(1);
+// Announcing needs at least this much of the session to go on.
+const ANNOUNCE_MIN_SECS: f64 = 60.0;
+// Earlier samples count when they fall into the hour starting at the event's
+// time of day.
+const HOUR_SECS: i64 = 3600;
+const MAX_LOGS: usize = 60;
+const ENOUGH_RECENT: u64 = 60;
+const ENOUGH_SESSIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Ready,
+    AtRisk,
+    NotReady,
+}
+
+impl Verdict {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verdict::Ready => "READY",
+            Verdict::AtRisk => "AT RISK",
+            Verdict::NotReady => "NOT READY",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    fn label(&self) -> &'static str {
+        match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        }
+    }
+
+    fn lower(self) -> Confidence {
+        match self {
+            Confidence::High => Confidence::Medium,
+            _ => Confidence::Low,
+        }
+    }
+}
+
+pub struct ScheduledEvent {
+    pub at: DateTime<Local>,
+    history: Option<HostStats>,
+    history_sessions: usize,
+    pub announced: bool,
+}
+
+pub struct Readiness {
+    pub verdict: Verdict,
+    confidence: Confidence,
+    recent: Option<(f64, f64)>,
+    trend: &'static str,
+    down: bool,
+}
+
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+fn seconds_of_day(at: &DateTime<Local>) -> i64 {
+    at.num_seconds_from_midnight() as i64
+}
+
+impl ScheduledEvent {
+    // The next time the clock shows `time`, today or tomorrow.
+    pub fn new(time: NaiveTime, now: DateTime<Local>) -> ScheduledEvent {
+        let today = now.date_naive().and_time(time).and_local_timezone(Local).earliest();
+        let at = match today {
+            Some(at) if at > now => at,
+            _ => (now.date_naive() + Duration::days(1)).and_time(time).and_local_timezone(Local).earliest().unwrap_or(now),
+        };
+        ScheduledEvent { at, history: None, history_sessions: 0, announced: false }
+    }
+
+    // Pools the Target samples of the event hour from earlier logs of this
+    // target (`*_<name>.csv`, `.jsonl`, gzipped or not) in `dir`.
+    pub fn load_history(&mut self, dir: &Path, name: &str, lan: bool, before: DateTime<Local>) -> Result<()> {
+        let suffixes = ["csv", "jsonl"].map(|ext| format!("_{}.{}", name, ext));
+        let mut logs: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| suffixes.iter().any(|s| session::plain_name(p).ends_with(s.as_str())))
+            .collect();
+        if logs.is_empty() {
+            bail!("no earlier logs of {} in {}", name, dir.display());
+        }
+        logs.sort();
+        let start = logs.len().saturating_sub(MAX_LOGS);
+
+        let event_secs = seconds_of_day(&self.at);
+        let mut pooled = Vec::new();
+        for path in &logs[start..] {
+            let Ok(hosts) = analyze::load_hosts(path) else {
+                continue;
+            };
+            let Some((_, samples)) = hosts.iter().find(|((target_type, _), _)| target_type == "Target") else {
+                continue;
+            };
+            // The samples of different days are pooled one after the other.
+            let offset = pooled.len();
+            pooled.extend(
+                samples
+                    .iter()
+                    .filter(|(_, _, at)| *at < before && (seconds_of_day(at) - event_secs).rem_euclid(86_400) < HOUR_SECS)
+                    .enumerate()
+                    .map(|(i, (_, latency, at))| ((offset + i) as f64, *latency, *at)),
+            );
+            if pooled.len() > offset {
+                self.history_sessions += 1;
+            }
+        }
+
+        if pooled.is_empty() {
+            bail!("no earlier samples between {} and {}", self.at.format("%H:%M"), (self.at + Duration::seconds(HOUR_SECS)).format("%H:%M"));
+        }
+        self.history = Some(HostStats::from_samples(name.to_string(), lan, &pooled));
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let day = if self.at.date_naive() == Local::now().date_naive() { "today" } else { "tomorrow" };
+        let history = self.history_text().unwrap_or_else(|| "no earlier sessions at that hour".to_string());
+        format!("{} {}, {}", self.at.format("%H:%M"), day, history)
+    }
+
+    // `stats` is the live Target, `elapsed` the seconds since `start`.
+    pub fn assess(&self, stats: &HostStats, start: DateTime<Local>, elapsed: f64) -> Readiness {
+        let recent = stats.window(((elapsed - RECENT_SECS).max(0.0), elapsed), start);
+        let before = (elapsed >= 2.0 * RECENT_SECS).then(|| stats.window((elapsed - 2.0 * RECENT_SECS, elapsed - RECENT_SECS), start));
+
+        let trend = match &before {
+            Some(before) if recent.loss_percent() > before.loss_percent() + 0.5 || recent.p99 > before.p99 * 1.25 + 5.0 => "worsening",
+            Some(before) if before.loss_percent() > recent.loss_percent() + 0.5 || before.p99 > recent.p99 * 1.25 + 5.0 => "improving",
+            Some(_) => "steady",
+            None => "no trend yet",
+        };
+
+        let has_recent = recent.total_count > 0;
+        let recent_rank = has_recent.then(|| grade_rank(recent.calculate_grade()));
+        let history_rank = self.history.as_ref().map(|h| grade_rank(h.calculate_grade()));
+        let mut rank = recent_rank.max(history_rank).unwrap_or(0);
+        if trend == "worsening" {
+            rank = (rank + 1).min(GRADES.len() - 1);
+        }
+
+        let down = stats.down_for(elapsed).is_some();
+        let verdict = match rank {
+            _ if down => Verdict::NotReady,
+            0..=2 => Verdict::Ready,
+            3 => Verdict::AtRisk,
+            _ => Verdict::NotReady,
+        };
+
+        let mut confidence = match (recent.total_count >= ENOUGH_RECENT, self.history_sessions) {
+            (true, n) if n >= ENOUGH_SESSIONS => Confidence::High,
+            (true, _) => Confidence::Medium,
+            (false, n) if n >= ENOUGH_SESSIONS => Confidence::Medium,
+            _ => Confidence::Low,
+        };
+        // Today looks nothing like the usual evening: less to go on.
+        if let (Some(recent), Some(history)) = (recent_rank, history_rank)
+            && recent.abs_diff(history) >= 2
+        {
+            confidence = confidence.lower();
+        }
+
+        Readiness {
+            verdict,
+            confidence,
+            recent: has_recent.then(|| (recent.p99, recent.loss_percent())),
+            trend,
+            down,
+        }
+    }
+
+    pub fn in_banner_lead(&self, now: DateTime<Local>) -> bool {
+        now < self.at && now >= self.at - BANNER_LEAD
+    }
+
+    // Once the event has begun, the same time tomorrow is next.
+    pub fn roll_over(&mut self, now: DateTime<Local>) {
+        while now >= self.at {
+            self.at += Duration::days(1);
+            self.announced = false;
+        }
+    }
+
+    pub fn announcement_due(&self, now: DateTime<Local>, elapsed: f64) -> bool {
+        !self.announced && now >= self.at - ANNOUNCE_LEAD && elapsed >= ANNOUNCE_MIN_SECS
+    }
+
+    pub fn countdown(&self, now: DateTime<Local>) -> String {
+        let left = (self.at - now).to_std().unwrap_or_default();
+        format_duration(std::time::Duration::from_secs(left.as_secs().div_ceil(60) * 60))
+    }
+
+    pub fn history_text(&self) -> Option<String> {
+        self.history.as_ref().map(|history| {
+            format!(
+                "usually P99 {}, {} loss ({} sessions)",
+                locale::ms(history.p99, 0),
+                locale::percent(history.loss_percent(), 1),
+                self.history_sessions
+            )
+        })
+    }
+}
+
+impl Readiness {
+    pub fn describe(&self, event: &ScheduledEvent, now: DateTime<Local>) -> String {
+        let mut parts = vec![format!(
+            "Event at {} in {}: {} ({} confidence)",
+            event.at.format("%H:%M"),
+            event.countdown(now),
+            self.verdict.label(),
+            self.confidence.label()
+        )];
+        match self.recent {
+            _ if self.down => parts.push("the Target is down right now".to_string()),
+            Some((p99, loss)) => parts.push(format!("last 10m P99 {}, {} loss, {}", locale::ms(p99, 0), locale::percent(loss, 1), self.trend)),
+            None => parts.push("no recent samples".to_string()),
+        }
+        if let Some(history) = event.history_text() {
+            parts.push(history);
+        }
+        parts.join(" | ")
+    }
+}
@@ -0,0 +1,262 @@
+use crate::app::HostStats;
+use crate::args::format_duration;
+use crate::locale;
+use crate::pinger::{PingUpdate, SourceType};
+use chrono::{DateTime, Local};
+use std::time::Duration;
+use tokio::{signal, sync::mpsc};
+
+// --reboot-test: the "have you tried turning it off and on again" ritual,
+// measured. A baseline is recorded first, then the user reboots the router;
+// the outage is found from the probes alone, and once the Target answers
+// again the same span is recorded once more to hold against the baseline.
+
+// Lost probes in a row that mark the router (the Target without a gateway)
+// as down.
+const DOWN_AFTER: u32 = 3;
+// Replies in a row that mark the internet as back, so a single reply while
+// the modem is still syncing does not count.
+const UP_AFTER: u32 = 3;
+// How long to wait for the router to go down, and then to come back.
+const MAX_WAIT: Duration = Duration::from_secs(600);
+
+type Sample = (f64, Option<f64>, DateTime<Local>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Baseline,
+    WaitingForDrop,
+    Down,
+    After,
+    Done,
+}
+
+#[derive(Default)]
+struct Samples {
+    target: Vec<Sample>,
+    gateway: Vec<Sample>,
+}
+
+impl Samples {
+    fn push(&mut self, source: &SourceType, sample: Sample) {
+        match source {
+            SourceType::Target => self.target.push(sample),
+            SourceType::Gateway => self.gateway.push(sample),
+            _ => {}
+        }
+    }
+}
+
+pub struct RebootTest {
+    span: Duration,
+    target_lan: bool,
+    with_gateway: bool,
+    phase: Phase,
+    started: DateTime<Local>,
+    phase_started: DateTime<Local>,
+    before: Samples,
+    after: Samples,
+    // Of the router, or the Target without a gateway.
+    misses: u32,
+    last_reply: Option<DateTime<Local>>,
+    // Of the Target, while it comes back.
+    replies: u32,
+    first_reply: Option<DateTime<Local>>,
+    outage_start: Option<DateTime<Local>>,
+    router_back: Option<DateTime<Local>>,
+    internet_back: Option<DateTime<Local>>,
+}
+
+fn elapsed(from: DateTime<Local>, to: DateTime<Local>) -> Duration {
+    (to - from).to_std().unwrap_or_default()
+}
+
+// Reboots take seconds to minutes, milliseconds would only be noise.
+fn seconds(d: Duration) -> String {
+    format_duration(Duration::from_secs(d.as_secs_f64().round() as u64))
+}
+
+fn host_line(stats: &HostStats) -> String {
+    format!(
+        "loss {}, P50 {}, P99 {}, jitter {}, grade {}",
+        locale::percent(stats.loss_percent(), 1),
+        locale::host_ms(stats.p50, 1, stats.lan),
+        locale::host_ms(stats.p99, 1, stats.lan),
+        locale::host_ms(stats.current_jitter, 1, stats.lan),
+        stats.grade_label()
+    )
+}
+
+impl RebootTest {
+    // `span` is the length of the baseline and of the recording after the reboot.
+    pub fn new(span: Duration, target_lan: bool, with_gateway: bool, now: DateTime<Local>) -> Self {
+        Self {
+            span,
+            target_lan,
+            with_gateway,
+            phase: Phase::Baseline,
+            started: now,
+            phase_started: now,
+            before: Samples::default(),
+            after: Samples::default(),
+            misses: 0,
+            last_reply: None,
+            replies: 0,
+            first_reply: None,
+            outage_start: None,
+            router_back: None,
+            internet_back: None,
+        }
+    }
+
+    fn enter(&mut self, phase: Phase, now: DateTime<Local>) {
+        self.phase = phase;
+        self.phase_started = now;
+    }
+
+    pub fn finished(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    pub fn recovered(&self) -> bool {
+        self.internet_back.is_some()
+    }
+
+    // Feeds one probe result; returns a line for the user when the test
+    // moves on to its next step.
+    pub fn observe(&mut self, update: &PingUpdate, now: DateTime<Local>) -> Option<String> {
+        let secs = (now - self.started).num_milliseconds() as f64 / 1000.0;
+        let watched = if self.with_gateway { SourceType::Gateway } else { SourceType::Target };
+        let in_phase = elapsed(self.phase_started, now);
+
+        match self.phase {
+            Phase::Baseline => {
+                self.before.push(&update.source, (secs, update.latency, now));
+                if in_phase < self.span {
+                    return None;
+                }
+                self.enter(Phase::WaitingForDrop, now);
+                let target = HostStats::from_samples(String::new(), self.target_lan, &self.before.target);
+                Some(format!(
+                    "Baseline recorded (Target: {}).\nReboot your router now. Waiting for it to go down...",
+                    host_line(&target)
+                ))
+            }
+            Phase::WaitingForDrop => {
+                if update.source == watched {
+                    if update.latency.is_some() {
+                        self.misses = 0;
+                        self.last_reply = Some(now);
+                    } else {
+                        self.misses += 1;
+                    }
+                }
+                if self.misses >= DOWN_AFTER {
+                    // The outage started right after the last reply.
+                    let start = self.last_reply.unwrap_or(now);
+                    self.outage_start = Some(start);
+                    self.enter(Phase::Down, now);
+                    let host = if self.with_gateway { "Router" } else { "Target" };
+                    return Some(format!("{} down since {}. Waiting for the connection to come back...", host, locale::time(&start)));
+                }
+                (in_phase >= MAX_WAIT).then(|| {
+                    self.enter(Phase::Done, now);
+                    format!("No outage within {}, the router did not seem to reboot.", format_duration(MAX_WAIT))
+                })
+            }
+            Phase::Down => {
+                let start = self.outage_start.unwrap_or(now);
+                let mut message = None;
+                match update.source {
+                    SourceType::Gateway if update.latency.is_some() && self.router_back.is_none() => {
+                        self.router_back = Some(now);
+                        message = Some(format!("Router answers again after {}.", seconds(elapsed(start, now))));
+                    }
+                    SourceType::Target if update.latency.is_some() => {
+                        self.replies += 1;
+                        let first = *self.first_reply.get_or_insert(now);
+                        if self.replies >= UP_AFTER {
+                            self.internet_back = Some(first);
+                            self.enter(Phase::After, now);
+                            message = Some(format!(
+                                "Internet back after {}. Recording {} for comparison...",
+                                seconds(elapsed(start, first)),
+                                format_duration(self.span)
+                            ));
+                        }
+                    }
+                    SourceType::Target => {
+                        self.replies = 0;
+                        self.first_reply = None;
+                    }
+                    _ => {}
+                }
+                if message.is_none() && in_phase >= MAX_WAIT {
+                    self.enter(Phase::Done, now);
+                    message = Some(format!("The connection did not come back within {}.", format_duration(MAX_WAIT)));
+                }
+                message
+            }
+            Phase::After => {
+                self.after.push(&update.source, (secs, update.latency, now));
+                if in_phase >= self.span {
+                    self.enter(Phase::Done, now);
+                }
+                None
+            }
+            Phase::Done => None,
+        }
+    }
+
+    pub fn report(&self, target: &str, gateway: Option<&str>) -> String {
+        let mut lines = vec!["Reboot test".to_string()];
+        if let Some(start) = self.outage_start {
+            let end = self.internet_back.map(|back| format!("{} ({})", locale::time(&back), seconds(elapsed(start, back))));
+            lines.push(format!("  Outage         {} to {}", locale::time(&start), end.unwrap_or_else(|| "?".to_string())));
+            if let Some(back) = self.router_back {
+                lines.push(format!("  Router back    after {} at {}", seconds(elapsed(start, back)), locale::time(&back)));
+            }
+            if let Some(back) = self.internet_back {
+                lines.push(format!("  Internet back  after {} at {}", seconds(elapsed(start, back)), locale::time(&back)));
+            }
+        }
+
+        let hosts = [("Target", target, self.target_lan, &self.before.target, &self.after.target)]
+            .into_iter()
+            .chain(gateway.map(|gw| ("Gateway", gw, true, &self.before.gateway, &self.after.gateway)));
+        for (label, name, lan, before, after) in hosts {
+            lines.push(format!("  {:<8} {}", label, name));
+            for (when, samples) in [("before", before), ("after", after)] {
+                if !samples.is_empty() {
+                    let stats = HostStats::from_samples(name.to_string(), lan, samples);
+                    lines.push(format!("    {:<7} {}", when, host_line(&stats)));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+// Drives the test from the probe results until it is done or stopped with
+// Ctrl+C, then prints the report. Returns whether the connection came back.
+pub async fn run(mut test: RebootTest, mut rx: mpsc::Receiver<PingUpdate>, target: String, gateway: Option<String>) -> bool {
+    println!("Recording a {} baseline. Do not reboot the router yet.", format_duration(test.span));
+    loop {
+        tokio::select! {
+            Some(update) = rx.recv() => {
+                if let Some(message) = test.observe(&update, Local::now()) {
+                    println!("{}", message);
+                }
+                if test.finished() {
+                    break;
+                }
+            }
+            _ = signal::ctrl_c() => {
+                println!("Reboot test stopped.");
+                break;
+            }
+        }
+    }
+    println!("{}", test.report(&target, gateway.as_deref()));
+    test.recovered()
+}
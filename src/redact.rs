@@ -0,0 +1,105 @@
+use crate::scope::Scope;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Mutex, OnceLock};
+
+// --redact: private addresses and host names are swapped for pseudonyms
+// (`lan-1`, `host-1`) in the TUI, the console, the logs, the summaries and
+// the chart exports, so a screenshot or a log can be posted publicly. The
+// same host keeps its pseudonym for the whole session. Public addresses stay,
+// they are what a shared report is about.
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+// Real name and pseudonym, in the order they were first seen.
+static NAMES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+pub fn init(enabled: bool) {
+    let _ = ENABLED.set(enabled);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+// Carrier-grade NAT addresses are as telling as LAN ones. Loopback is the
+// same on every machine and stays.
+fn is_private(ip: IpAddr) -> bool {
+    matches!(Scope::of(ip), Scope::Private | Scope::LinkLocal | Scope::CarrierNat)
+}
+
+fn pseudonym(real: &str, kind: &str) -> String {
+    let Ok(mut names) = NAMES.lock() else {
+        return kind.to_string();
+    };
+    if let Some((_, alias)) = names.iter().find(|(name, _)| name == real) {
+        return alias.clone();
+    }
+    let prefix = format!("{}-", kind);
+    let alias = format!("{}{}", prefix, names.iter().filter(|(_, alias)| alias.starts_with(&prefix)).count() + 1);
+    names.push((real.to_string(), alias.clone()));
+    alias
+}
+
+// A host as typed or resolved: an address or a name.
+pub fn host(name: &str) -> String {
+    if !enabled() {
+        return name.to_string();
+    }
+    match name.parse::<IpAddr>() {
+        Ok(ip) if is_private(ip) => pseudonym(&ip.to_string(), "lan"),
+        Ok(_) => name.to_string(),
+        Err(_) => pseudonym(name, "host"),
+    }
+}
+
+pub fn ip(ip: IpAddr) -> String {
+    host(&ip.to_string())
+}
+
+// A private address in a run of address characters, alone or with a port.
+fn redact_token(token: &str) -> Option<String> {
+    let token = token.trim_end_matches(['.', ':']);
+    if let Ok(ip) = token.parse::<IpAddr>() {
+        return is_private(ip).then(|| host(token));
+    }
+    let addr = token.parse::<SocketAddr>().ok()?;
+    is_private(addr.ip()).then(|| format!("{}:{}", host(&addr.ip().to_string()), addr.port()))
+}
+
+// Free text such as events and diagnostics: the host names seen so far and
+// every private address get their pseudonyms.
+pub fn text(message: &str) -> String {
+    if !enabled() {
+        return message.to_string();
+    }
+
+    let mut message = message.to_string();
+    let mut names: Vec<(String, String)> = NAMES
+        .lock()
+        .map(|names| names.iter().filter(|(name, _)| name.parse::<IpAddr>().is_err()).cloned().collect())
+        .unwrap_or_default();
+    // sub.example.com before example.com.
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+    for (name, alias) in names {
+        message = message.replace(&name, &alias);
+    }
+
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message.as_str();
+    while let Some(start) = rest.find(|c: char| c.is_ascii_hexdigit() || c == ':') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| !(c.is_ascii_hexdigit() || c == '.' || c == ':')).unwrap_or(rest.len());
+        let token = &rest[..end];
+        match redact_token(token) {
+            Some(alias) => {
+                let kept = token.trim_end_matches(['.', ':']).len();
+                out.push_str(&alias);
+                out.push_str(&token[kept..]);
+            }
+            None => out.push_str(token),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
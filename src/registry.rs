@@ -0,0 +1,259 @@
+use crate::app::{App, SessionSummary};
+use crate::args::StatusArgs;
+use crate::status;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// Every running session keeps an entry in the runtime dir. Sessions started
+// next to each other read it to pick distinct file names and to probe a
+// shared gateway only once, and `vasili status` lists it.
+
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+const LOCK_STALE: Duration = Duration::from_secs(10);
+// Without /proc an entry counts as alive while its owner keeps rewriting it.
+#[cfg(not(target_os = "linux"))]
+const ENTRY_STALE: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub pid: u32,
+    pub started: String,
+    pub updated_at: String,
+    pub target: String,
+    pub profile: String,
+    pub gateway: Option<IpAddr>,
+    pub directory: PathBuf,
+    pub output_name: String,
+    #[serde(default)]
+    pub logging_to: Option<String>,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub hosts: Vec<SessionSummary>,
+}
+
+pub struct Claim {
+    pub output_name: String,
+    pub gateway_owner: Option<u32>,
+    pub shares_directory: bool,
+}
+
+pub struct Registration {
+    path: PathBuf,
+    entry: Entry,
+    // The gateway another session probes for this one, see `take_over_gateway`.
+    shared_gateway: Option<IpAddr>,
+}
+
+fn registry_dir() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(std::env::temp_dir).join("vasili")
+}
+
+fn sessions_dir() -> PathBuf {
+    registry_dir().join("sessions")
+}
+
+struct Lock(PathBuf);
+
+impl Lock {
+    fn acquire() -> Result<Lock> {
+        let path = registry_dir().join("registry.lock");
+        let waited = std::time::Instant::now();
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Lock(path)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    // A session that died while holding the lock leaves it behind.
+                    let age = fs::metadata(&path).and_then(|m| m.modified()).ok().and_then(|t| t.elapsed().ok());
+                    if age.is_some_and(|age| age > LOCK_STALE) {
+                        let _ = fs::remove_file(&path);
+                    } else if waited.elapsed() > LOCK_WAIT {
+                        bail!("{} is held by another session", path.display());
+                    } else {
+                        std::thread::sleep(Duration::from_millis(20));
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn alive(entry: &Entry) -> bool {
+    // The pid alone may have been reused by an unrelated process.
+    let comm = |pid: &str| fs::read_to_string(format!("/proc/{}/comm", pid)).ok();
+    comm(&entry.pid.to_string()).is_some_and(|c| Some(c) == comm("self"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn alive(entry: &Entry) -> bool {
+    DateTime::parse_from_rfc3339(&entry.updated_at)
+        .is_ok_and(|t| (Local::now() - t.with_timezone(&Local)).to_std().is_ok_and(|age| age < ENTRY_STALE))
+}
+
+// Reads the entries of running sessions and removes the ones left behind by
+// sessions that crashed or were killed.
+pub fn sessions() -> Vec<Entry> {
+    let Ok(dir) = fs::read_dir(sessions_dir()) else {
+        return Vec::new();
+    };
+
+    let mut entries = Vec::new();
+    for path in dir.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "json")) {
+        match fs::read(&path).ok().and_then(|data| serde_json::from_slice::<Entry>(&data).ok()) {
+            Some(entry) if entry.pid == std::process::id() || alive(&entry) => entries.push(entry),
+            _ => {
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    entries.sort_by_key(|e| e.started.clone());
+    entries
+}
+
+fn same_directory(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl Registration {
+    // The lock makes checking the other sessions and adding this one a single
+    // step, so two sessions started together cannot both claim a name.
+    pub fn register(
+        target: &str,
+        profile: &str,
+        gateway: Option<IpAddr>,
+        directory: &Path,
+        output_name: &str,
+        coordinate_gateway: bool,
+    ) -> Result<(Registration, Claim)> {
+        fs::create_dir_all(sessions_dir())?;
+        let _lock = Lock::acquire()?;
+        let others = sessions();
+        let pid = std::process::id();
+
+        let shares_directory = others.iter().any(|o| same_directory(&o.directory, directory));
+        let output_name = if others.iter().any(|o| o.output_name == output_name && same_directory(&o.directory, directory)) {
+            format!("{}_{}", output_name, pid)
+        } else {
+            output_name.to_string()
+        };
+        let gateway_owner = gateway
+            .filter(|_| coordinate_gateway)
+            .and_then(|gw| others.iter().find(|o| o.gateway == Some(gw)))
+            .map(|o| o.pid);
+
+        let now = Local::now().to_rfc3339();
+        let entry = Entry {
+            pid,
+            started: now.clone(),
+            updated_at: now,
+            target: target.to_string(),
+            profile: profile.to_string(),
+            gateway: gateway.filter(|_| gateway_owner.is_none()),
+            directory: directory.to_path_buf(),
+            output_name: output_name.clone(),
+            logging_to: None,
+            state: "starting".to_string(),
+            hosts: Vec::new(),
+        };
+        let registration = Registration {
+            path: sessions_dir().join(format!("{}.json", pid)),
+            entry,
+            shared_gateway: gateway.filter(|_| gateway_owner.is_some()),
+        };
+        registration.write()?;
+
+        Ok((registration, Claim { output_name, gateway_owner, shares_directory }))
+    }
+
+    fn write(&self) -> Result<()> {
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&self.entry)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn set_output(&mut self, description: String) {
+        self.entry.logging_to = Some(description).filter(|d| !d.is_empty());
+    }
+
+    // Once the session probing the shared gateway has ended, the first of the
+    // sessions relying on it claims the gateway and starts probing it itself.
+    // The others keep waiting for the new owner.
+    pub fn take_over_gateway(&mut self) -> Result<Option<IpAddr>> {
+        let Some(gateway) = self.shared_gateway else {
+            return Ok(None);
+        };
+        let _lock = Lock::acquire()?;
+        if sessions().iter().any(|o| o.pid != self.entry.pid && o.gateway == Some(gateway)) {
+            return Ok(None);
+        }
+        self.entry.gateway = Some(gateway);
+        self.write()?;
+        self.shared_gateway = None;
+        Ok(Some(gateway))
+    }
+
+    pub fn update(&mut self, app: &App) -> Result<()> {
+        self.entry.updated_at = Local::now().to_rfc3339();
+        self.entry.state = status::state(app).to_string();
+        self.entry.hosts = app.summaries();
+        self.write()
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub fn run(args: StatusArgs) -> Result<()> {
+    let entries = sessions();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("No running vasili sessions.");
+        return Ok(());
+    }
+
+    for entry in &entries {
+        let since = DateTime::parse_from_rfc3339(&entry.started)
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| entry.started.clone());
+        println!(
+            "PID {} - {} (profile {}, {}, since {})",
+            entry.pid, entry.target, entry.profile, entry.state, since
+        );
+        if let Some(output) = &entry.logging_to {
+            println!("  Logging to: {}", output);
+        }
+        if entry.hosts.is_empty() {
+            println!("  (no samples yet)");
+        }
+        for host in &entry.hosts {
+            println!("  {}", host.describe());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use crate::app::{App, HostStats, PingRecord};
+use crate::args::{ReplayArgs, parse_duration_string};
+use crate::clock::ManualClock;
+use crate::config::{DEFAULT_PROFILE, UiState};
+use crate::pinger::{PingUpdate, ProbeError, SourceType};
+use crate::scope::Scope;
+use crate::{DEFAULT_DIVERGENCE_SECS, diag, session, ui};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{Terminal, backend::CrosstermBackend};
+use std::{
+    io,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 256.0;
+const FRAME: Duration = Duration::from_millis(50);
+
+struct Sample {
+    at: DateTime<Local>,
+    source: Option<SourceType>,
+    record: PingRecord,
+}
+
+fn host(name: &str, interval_ms: f64) -> HostStats {
+    HostStats {
+        lan: name.parse::<IpAddr>().is_ok_and(|ip| Scope::of(ip).is_local()),
+        configured_ms: interval_ms,
+        ..HostStats::new(name.to_string())
+    }
+}
+
+// Intervals are not stored in the log, the typical gap between two samples
+// of a host is close enough for grading and the chart.
+fn typical_gap(samples: &[Sample], source: SourceType) -> u64 {
+    let times: Vec<DateTime<Local>> = samples.iter().filter(|s| s.source.as_ref() == Some(&source)).map(|s| s.at).collect();
+    let mut gaps: Vec<i64> = times.windows(2).map(|w| (w[1] - w[0]).num_milliseconds()).filter(|g| *g > 0).collect();
+    if gaps.is_empty() {
+        return 1000;
+    }
+    gaps.sort_unstable();
+    gaps[gaps.len() / 2] as u64
+}
+
+// Builds an App with the same hosts the recorded session had, in the order
+// the log first mentions them.
+fn build_app(records: Vec<PingRecord>, clock: Arc<ManualClock>, speed: f64) -> Result<(App, Vec<Sample>)> {
+    let mut target = None;
+    let mut gateway = None;
+    let mut hops: Vec<(u8, String)> = Vec::new();
+    let mut cycle: Vec<String> = Vec::new();
+    let mut passive = false;
+    let mut samples = Vec::new();
+
+    for record in records {
+        let Some(at) = session::parse_timestamp(&record.timestamp) else {
+            bail!("Invalid timestamp '{}'", record.timestamp);
+        };
+        let name = record.target_ip.clone();
+        let source = match record.target_type.as_str() {
+            "Target" => {
+                target.get_or_insert(name);
+                Some(SourceType::Target)
+            }
+            "Gateway" => {
+                gateway.get_or_insert(name);
+                Some(SourceType::Gateway)
+            }
+            "Cycle" => {
+                let index = cycle.iter().position(|c| *c == name).unwrap_or_else(|| {
+                    cycle.push(name);
+                    cycle.len() - 1
+                });
+                Some(SourceType::Cycle(index))
+            }
+            "Passive" => {
+                passive = true;
+                Some(SourceType::Passive)
+            }
+            "Marker" => None,
+            other => match other.strip_prefix("Hop ").and_then(|ttl| ttl.parse::<u8>().ok()) {
+                Some(ttl) => {
+                    if !hops.iter().any(|(t, _)| *t == ttl) {
+                        hops.push((ttl, name));
+                    }
+                    Some(SourceType::Hop(ttl))
+                }
+                None => continue,
+            },
+        };
+        samples.push(Sample { at, source, record });
+    }
+
+    let (Some(target), Some(first)) = (target, samples.first()) else {
+        bail!("The log contains no target samples");
+    };
+    clock.set(first.at);
+
+    let interval = typical_gap(&samples, SourceType::Target);
+    let ui_interval = if gateway.is_some() { interval as f64 / 3.0 } else { interval as f64 };
+    let mut app = App::new(target, gateway, ui_interval, interval, None, DEFAULT_DIVERGENCE_SECS as f64, clock);
+    app.net_stats.lan = app.net_stats.display_name.parse::<IpAddr>().is_ok_and(|ip| Scope::of(ip).is_local());
+    hops.sort_by_key(|(ttl, _)| *ttl);
+    app.hops = hops.iter().map(|(ttl, name)| (*ttl, host(name, interval as f64))).collect();
+    app.cycle = (0..cycle.len())
+        .map(|i| host(&cycle[i], typical_gap(&samples, SourceType::Cycle(i)) as f64))
+        .collect();
+    if passive {
+        app.enable_passive(None);
+    }
+    app.replay_speed = Some(speed);
+
+    Ok((app, samples))
+}
+
+fn feed(app: &mut App, sample: &Sample) {
+    match &sample.source {
+        Some(source) => {
+            app.on_ping(PingUpdate {
+                source: source.clone(),
+                latency: sample.record.latency_ms,
+                error: ProbeError::from_status(&sample.record.status),
+                ttl: sample.record.ttl,
+                late: false,
+            });
+        }
+        None => {
+            app.add_marker(sample.record.target_ip.clone());
+        }
+    }
+}
+
+pub fn run(args: ReplayArgs) -> Result<()> {
+    if !(MIN_SPEED..=MAX_SPEED).contains(&args.speed) {
+        bail!("--speed must be between {} and {}", MIN_SPEED, MAX_SPEED);
+    }
+    let skip = match args.from.as_deref() {
+        Some(from) => match parse_duration_string(from) {
+            Ok(d) => d,
+            Err(e) => bail!("Invalid --from '{}': {}. Expected e.g. '2h30m'.", from, e),
+        },
+        None => Duration::ZERO,
+    };
+
+    let records = session::load_session(&args.file)?;
+    let clock = Arc::new(ManualClock::new(Local::now()));
+    let (mut app, samples) = build_app(records, clock.clone(), args.speed)?;
+    if let Some(state) = UiState::load(DEFAULT_PROFILE) {
+        app.apply_ui_state(&state);
+    }
+
+    // Everything before --from is fed at once, so the stats and the history
+    // already cover it when the playback starts.
+    let mut position = samples[0].at + chrono::Duration::from_std(skip)?;
+    let mut next = 0;
+    while next < samples.len() && samples[next].at <= position {
+        clock.set(samples[next].at);
+        feed(&mut app, &samples[next]);
+        next += 1;
+    }
+
+    diag::set_echo(false);
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let mut last_frame = Instant::now();
+    while !app.should_quit {
+        app.collect_export(false);
+        terminal.draw(|f| ui::draw(f, &app))?;
+
+        if event::poll(FRAME)?
+            && let Event::Key(key) = event::read()?
+        {
+            let speed = app.replay_speed.unwrap_or(1.0);
+            match key.code {
+                KeyCode::Char('<' | ',') if !app.search.editing => app.replay_speed = Some((speed / 2.0).max(MIN_SPEED)),
+                KeyCode::Char('>' | '.') if !app.search.editing => app.replay_speed = Some((speed * 2.0).min(MAX_SPEED)),
+                code => app.on_key(code),
+            }
+        }
+
+        let elapsed = last_frame.elapsed();
+        last_frame = Instant::now();
+        if app.is_paused || app.is_finished {
+            continue;
+        }
+
+        position += chrono::Duration::from_std(elapsed.mul_f64(app.replay_speed.unwrap_or(1.0)))?;
+        while next < samples.len() && samples[next].at <= position {
+            clock.set(samples[next].at);
+            feed(&mut app, &samples[next]);
+            next += 1;
+        }
+        if next == samples.len() {
+            app.is_finished = true;
+        } else {
+            clock.set(position);
+        }
+    }
+    app.collect_export(true);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    diag::set_echo(true);
+    Ok(())
+}
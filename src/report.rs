@@ -0,0 +1,38 @@
+use crate::args::{ReportArgs, format_duration};
+use crate::{analyze, chartexport, locale, session};
+use anyhow::{Result, bail};
+use std::time::Duration;
+
+pub fn run(args: ReportArgs) -> Result<()> {
+    if let Some(chart) = &args.chart {
+        chartexport::ChartFormat::of(chart)?;
+        chartexport::export_recorded(&analyze::load_hosts(&args.file)?, chart)?;
+        println!("Chart saved to: {}", chart.display());
+    }
+    let summaries = session::load_summaries(&args.file)?;
+    let Some(first) = summaries.first() else {
+        bail!("{} contains no session summary", args.file.display());
+    };
+
+    let segments = match session::segments(&args.file).len() {
+        1 => String::new(),
+        n => format!(", {} rotated segments", n),
+    };
+    println!(
+        "Session {} ({}, started {}{}):",
+        args.file.display(),
+        format_duration(Duration::from_secs(first.duration_secs as u64)),
+        locale::timestamp(&first.started),
+        segments
+    );
+    for s in &summaries {
+        println!("  {}", s.describe());
+    }
+    if first.aborted_by_user {
+        println!("  (stopped by user before the planned end)");
+    }
+    if first.terminated {
+        println!("  (terminated by SIGTERM before the planned end)");
+    }
+    Ok(())
+}
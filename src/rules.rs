@@ -0,0 +1,249 @@
+use crate::app::HostStats;
+use crate::args::format_duration;
+use crate::diag;
+use crate::events::{Event, EventKind};
+use crate::notify;
+use chrono::Local;
+use serde_json::json;
+use std::{collections::VecDeque, thread, time::Duration};
+
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+const MIN_WINDOW_SAMPLES: usize = 10;
+pub const GRADES: [&str; 5] = ["S", "A", "B", "C", "F"];
+const POST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Rule {
+    Loss(f64),
+    Timeouts(u64),
+    P95(f64),
+    Grade(usize),
+}
+
+pub fn grade_rank(grade: &str) -> usize {
+    GRADES.iter().position(|g| *g == grade).unwrap_or(GRADES.len() - 1)
+}
+
+impl Rule {
+    fn name(&self) -> &'static str {
+        match self {
+            Rule::Loss(_) => "loss",
+            Rule::Timeouts(_) => "timeouts",
+            Rule::P95(_) => "p95",
+            Rule::Grade(_) => "grade",
+        }
+    }
+
+    fn threshold(&self) -> f64 {
+        match self {
+            Rule::Loss(percent) => *percent,
+            Rule::Timeouts(count) => *count as f64,
+            Rule::P95(ms) => *ms,
+            Rule::Grade(rank) => (*rank + 1) as f64,
+        }
+    }
+
+    fn describe(&self, window: Duration) -> String {
+        match self {
+            Rule::Loss(percent) => format!("loss >= {}% over {}", percent, format_duration(window)),
+            Rule::Timeouts(count) => format!(">= {} consecutive timeouts", count),
+            Rule::P95(ms) => format!("p95 >= {}ms over {}", ms, format_duration(window)),
+            Rule::Grade(rank) => format!("grade below {}", GRADES[*rank]),
+        }
+    }
+
+    fn format_value(&self, value: f64) -> String {
+        match self {
+            Rule::Loss(_) => format!("Loss {:.1}%", value),
+            Rule::Timeouts(_) => format!("{} consecutive timeouts", value),
+            Rule::P95(_) => format!("P95 {:.1}ms", value),
+            Rule::Grade(_) => format!("Grade {}", GRADES[value as usize]),
+        }
+    }
+
+    // Grades travel as letters, everything else as numbers.
+    fn json(&self, value: f64) -> (serde_json::Value, serde_json::Value) {
+        match self {
+            Rule::Grade(rank) => (json!(GRADES[value as usize]), json!(GRADES[*rank])),
+            _ => (json!(value), json!(self.threshold())),
+        }
+    }
+}
+
+struct RuleState {
+    rule: Rule,
+    firing: bool,
+}
+
+// Loss and p95 are judged over a sliding window rather than the whole
+// session, so an old outage does not keep a rule firing for hours.
+#[derive(Default)]
+pub struct Rules {
+    rules: Vec<RuleState>,
+    webhook: Option<String>,
+    window: Duration,
+    samples: VecDeque<(f64, Option<f64>)>,
+    pub notify: bool,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>, window: Duration, webhook: Option<String>) -> Self {
+        Self {
+            rules: rules.into_iter().map(|rule| RuleState { rule, firing: false }).collect(),
+            webhook,
+            window,
+            samples: VecDeque::new(),
+            notify: false,
+        }
+    }
+
+    // Takes over the rules of a reloaded config. A rule of the same kind keeps
+    // its firing state, so it neither fires again nor misses its resolve, and
+    // the window keeps its samples.
+    pub fn reload(&mut self, mut rules: Rules) {
+        for state in &mut rules.rules {
+            if let Some(old) = self.rules.iter().find(|old| old.rule.name() == state.rule.name()) {
+                state.firing = old.firing;
+            }
+        }
+        self.rules = rules.rules;
+        self.webhook = rules.webhook;
+        self.window = rules.window;
+        self.notify = rules.notify;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        let rules = self.rules.iter().map(|s| s.rule.describe(self.window)).collect::<Vec<_>>().join(", ");
+        match &self.webhook {
+            Some(url) => format!("{} -> {}", rules, url),
+            None => rules,
+        }
+    }
+
+    fn window_value(&self, rule: Rule) -> Option<f64> {
+        if self.samples.len() < MIN_WINDOW_SAMPLES {
+            return None;
+        }
+        match rule {
+            Rule::Loss(_) => {
+                let lost = self.samples.iter().filter(|(_, l)| l.is_none()).count();
+                Some(lost as f64 / self.samples.len() as f64 * 100.0)
+            }
+            Rule::P95(_) => {
+                let mut replies: Vec<f64> = self.samples.iter().filter_map(|(_, l)| *l).collect();
+                if replies.len() < MIN_WINDOW_SAMPLES {
+                    return None;
+                }
+                replies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                Some(replies[((replies.len() - 1) as f64 * 0.95).round() as usize])
+            }
+            Rule::Timeouts(_) | Rule::Grade(_) => None,
+        }
+    }
+
+    pub fn observe(&mut self, stats: &HostStats, latency: Option<f64>, time_val: f64) -> Vec<Event> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        self.samples.push_back((time_val, latency));
+        let oldest = time_val - self.window.as_secs_f64();
+        while self.samples.front().is_some_and(|(t, _)| *t < oldest) {
+            self.samples.pop_front();
+        }
+
+        let mut events = Vec::new();
+        for i in 0..self.rules.len() {
+            let rule = self.rules[i].rule;
+            let value = match rule {
+                Rule::Timeouts(_) => Some(stats.loss_burst as f64),
+                Rule::Grade(_) => (!stats.warming_up()).then(|| grade_rank(stats.calculate_grade()) as f64),
+                _ => self.window_value(rule),
+            };
+            let Some(value) = value else {
+                continue;
+            };
+
+            let firing = value >= rule.threshold();
+            if firing == self.rules[i].firing {
+                continue;
+            }
+            self.rules[i].firing = firing;
+
+            let (kind, state) = if firing { (EventKind::RuleFired, "firing") } else { (EventKind::RuleResolved, "resolved") };
+            let message = format!(
+                "{}{} on {} (rule: {})",
+                if firing { "" } else { "Resolved: " },
+                rule.format_value(value),
+                stats.display_name,
+                rule.describe(self.window)
+            );
+            if self.notify {
+                notify::send(&format!("Vasili: {}", kind.label()), &message);
+            }
+            if let Some(url) = &self.webhook {
+                let (value, threshold) = rule.json(value);
+                post(url.clone(), json!({
+                    "rule": rule.name(),
+                    "state": state,
+                    "target": stats.display_name,
+                    "value": value,
+                    "threshold": threshold,
+                    "message": message,
+                    "text": format!("Vasili: {}", message),
+                    "timestamp": Local::now().to_rfc3339(),
+                }));
+            }
+            events.push(Event::new(kind, time_val, message));
+        }
+        events
+    }
+}
+
+fn post(url: String, body: serde_json::Value) {
+    thread::spawn(move || {
+        if let Err(e) = ureq::post(&url).timeout(POST_TIMEOUT).send_json(body) {
+            diag::warn(format!("Webhook POST to {} failed: {}", url, e));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(events: &[Event]) -> Vec<EventKind> {
+        events.iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn a_reload_keeps_a_firing_rule_firing() {
+        let mut stats = HostStats::new("Target".to_string());
+        let mut rules = Rules::new(vec![Rule::Timeouts(3)], DEFAULT_WINDOW, None);
+        stats.loss_burst = 3;
+        assert_eq!(kinds(&rules.observe(&stats, None, 1.0)), [EventKind::RuleFired]);
+
+        // Same rule after the reload: no second alert, and the resolve still comes.
+        rules.reload(Rules::new(vec![Rule::Timeouts(3), Rule::Loss(50.0)], DEFAULT_WINDOW, None));
+        stats.loss_burst = 4;
+        assert!(rules.observe(&stats, None, 2.0).is_empty());
+        stats.loss_burst = 0;
+        assert_eq!(kinds(&rules.observe(&stats, Some(20.0), 3.0)), [EventKind::RuleResolved]);
+    }
+
+    #[test]
+    fn a_raised_threshold_resolves_on_the_next_sample() {
+        let mut stats = HostStats::new("Target".to_string());
+        let mut rules = Rules::new(vec![Rule::Timeouts(3)], DEFAULT_WINDOW, None);
+        stats.loss_burst = 4;
+        assert_eq!(kinds(&rules.observe(&stats, None, 1.0)), [EventKind::RuleFired]);
+
+        rules.reload(Rules::new(vec![Rule::Timeouts(10)], DEFAULT_WINDOW, None));
+        stats.loss_burst = 5;
+        assert_eq!(kinds(&rules.observe(&stats, None, 2.0)), [EventKind::RuleResolved]);
+    }
+}
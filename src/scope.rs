@@ -0,0 +1,80 @@
+use std::net::IpAddr;
+
+// Where an address lives, for the LAN grading curve, the warning about a
+// target that is not an internet host and what --redact hides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Loopback,
+    Private,
+    LinkLocal,
+    // 100.64.0.0/10, shared by the customers behind an ISP's NAT.
+    CarrierNat,
+    Public,
+}
+
+impl Scope {
+    pub fn of(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(v4) if v4.is_loopback() => Scope::Loopback,
+            IpAddr::V4(v4) if v4.is_private() => Scope::Private,
+            IpAddr::V4(v4) if v4.is_link_local() => Scope::LinkLocal,
+            IpAddr::V4(v4) if v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64 => Scope::CarrierNat,
+            IpAddr::V6(v6) if v6.is_loopback() => Scope::Loopback,
+            IpAddr::V6(v6) if v6.is_unique_local() => Scope::Private,
+            IpAddr::V6(v6) if v6.is_unicast_link_local() => Scope::LinkLocal,
+            _ => Scope::Public,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Scope::Loopback => "Loopback",
+            Scope::Private => "Private (LAN)",
+            Scope::LinkLocal => "Link-local",
+            Scope::CarrierNat => "Carrier-grade NAT",
+            Scope::Public => "Public",
+        }
+    }
+
+    // On this side of the router: graded like the gateway.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Scope::Loopback | Scope::Private | Scope::LinkLocal)
+    }
+
+    // A carrier-grade NAT address is behind the access link, so it is graded
+    // like an internet host, but it is still inside the ISP's network.
+    pub fn is_internet(&self) -> bool {
+        *self == Scope::Public
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(ip: &str) -> Scope {
+        Scope::of(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        assert_eq!(scope("127.0.0.1"), Scope::Loopback);
+        assert_eq!(scope("192.168.1.1"), Scope::Private);
+        assert_eq!(scope("172.31.255.255"), Scope::Private);
+        assert_eq!(scope("169.254.0.1"), Scope::LinkLocal);
+        assert_eq!(scope("1.1.1.1"), Scope::Public);
+        assert_eq!(scope("::1"), Scope::Loopback);
+        assert_eq!(scope("fd00::1"), Scope::Private);
+        assert_eq!(scope("fe80::1"), Scope::LinkLocal);
+        assert_eq!(scope("2606:4700::1111"), Scope::Public);
+    }
+
+    #[test]
+    fn carrier_nat_is_exactly_100_64_slash_10() {
+        assert_eq!(scope("100.63.255.255"), Scope::Public);
+        assert_eq!(scope("100.64.0.0"), Scope::CarrierNat);
+        assert_eq!(scope("100.127.255.255"), Scope::CarrierNat);
+        assert_eq!(scope("100.128.0.0"), Scope::Public);
+        assert!(!Scope::CarrierNat.is_local() && !Scope::CarrierNat.is_internet());
+    }
+}
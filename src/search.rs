@@ -0,0 +1,81 @@
+use crate::events::Event;
+use chrono::NaiveDateTime;
+
+// '/' in the event log: every word of the query has to appear in the time,
+// the kind or the message of an event, case-insensitively. The weekday is
+// part of the time, so "tue outage" finds Tuesday's outages. Enter jumps the
+// chart to the first match, n / N step through the others in time order.
+#[derive(Debug, Default)]
+pub struct EventSearch {
+    pub query: String,
+    // The query is being typed; keys go to it instead of the shortcuts.
+    pub editing: bool,
+    // Index into App::events.
+    pub selected: Option<usize>,
+}
+
+fn searchable(event: &Event) -> String {
+    let weekday = NaiveDateTime::parse_from_str(&event.timestamp, "%Y-%m-%d %H:%M:%S")
+        .map(|at| at.format("%A").to_string())
+        .unwrap_or_default();
+    format!("{} {} {} {}", weekday, event.timestamp, event.kind.label(), event.message).to_lowercase()
+}
+
+impl EventSearch {
+    fn words(&self) -> Vec<String> {
+        self.query.to_lowercase().split_whitespace().map(str::to_string).collect()
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.query.trim().is_empty()
+    }
+
+    // Indices of the matching events, oldest first.
+    pub fn matches(&self, events: &[Event]) -> Vec<usize> {
+        let words = self.words();
+        events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| {
+                let text = searchable(event);
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The next match after the selected one (or before it), wrapping around.
+    pub fn step(&mut self, events: &[Event], forward: bool) -> Option<usize> {
+        let matches = self.matches(events);
+        let next = match (self.selected, forward) {
+            (None, _) => matches.first(),
+            (Some(current), true) => matches.iter().find(|i| **i > current).or(matches.first()),
+            (Some(current), false) => matches.iter().rev().find(|i| **i < current).or(matches.last()),
+        };
+        self.selected = next.copied();
+        self.selected
+    }
+
+    // Byte ranges of the query's words in `text`, for highlighting.
+    pub fn highlights(&self, text: &str) -> Vec<(usize, usize)> {
+        let lower = text.to_lowercase();
+        // Lowercasing can change the length of non-ASCII text.
+        if lower.len() != text.len() {
+            return Vec::new();
+        }
+        let mut ranges: Vec<(usize, usize)> = self
+            .words()
+            .iter()
+            .flat_map(|word| lower.match_indices(word.as_str()).map(|(start, _)| (start, start + word.len())).collect::<Vec<_>>())
+            .collect();
+        ranges.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
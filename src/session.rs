@@ -0,0 +1,179 @@
+use crate::app::{PingRecord, SessionSummary};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+// Reading side of the file sinks, for the verbs that work on recorded
+// sessions instead of live probes.
+
+// A gzipped log that is still being written, or was cut off when its
+// session was killed, ends at its last flush instead of with an error.
+struct UntilTruncated<R>(R);
+
+impl<R: Read> Read for UntilTruncated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+            result => result,
+        }
+    }
+}
+
+pub fn open(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    if path.extension().is_some_and(|e| e == "gz") {
+        Ok(Box::new(BufReader::new(UntilTruncated(MultiGzDecoder::new(file)))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+// The file name without a trailing .gz, e.g. "x.csv" for "x.csv.gz".
+pub fn plain_name(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    name.strip_suffix(".gz").map(str::to_string).unwrap_or(name)
+}
+
+// "x" and 1 for "x", "x" and N for "x_partNNN": the name the segments of a
+// rotated session share, and which of them this is.
+fn segment_of(stem: &str) -> (&str, u32) {
+    if let Some((base, number)) = stem.rsplit_once("_part")
+        && number.len() >= 3
+        && let Ok(n) = number.parse()
+    {
+        return (base, n);
+    }
+    (stem, 1)
+}
+
+// Every segment of the session `path` belongs to, in order, found next to
+// it: x.csv, x_part002.csv.gz, x_part003.csv, ... Just `path` for a log
+// that was never rotated or a file that is no log.
+pub fn segments(path: &Path) -> Vec<PathBuf> {
+    let name = plain_name(path);
+    let Some((stem, extension)) = [".csv", ".jsonl"].iter().find_map(|ext| name.strip_suffix(ext).map(|stem| (stem, *ext))) else {
+        return vec![path.to_path_buf()];
+    };
+    let (base, _) = segment_of(stem);
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![path.to_path_buf()];
+    };
+
+    let mut found: Vec<(u32, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = plain_name(&entry.path());
+            let (other, n) = segment_of(name.strip_suffix(extension)?);
+            (other == base).then(|| (n, path.with_file_name(entry.file_name())))
+        })
+        .collect();
+    found.sort();
+    // A segment left both plain and gzipped by an interrupted compression.
+    found.dedup_by_key(|(n, _)| *n);
+    if found.is_empty() {
+        return vec![path.to_path_buf()];
+    }
+    found.into_iter().map(|(_, path)| path).collect()
+}
+
+// The CSV sink writes the summary next to the log as <stem>_summary.csv,
+// uncompressed even when the log itself was gzipped.
+fn summary_csv(path: &Path) -> Option<PathBuf> {
+    let name = plain_name(path);
+    if name.ends_with("_summary.csv") {
+        return Some(path.to_path_buf());
+    }
+    let stem = name.strip_suffix(".csv")?;
+    Some(path.with_file_name(format!("{}_summary.csv", stem)))
+}
+
+// Only the last segment of a rotated session carries the summary, so any
+// segment leads to it.
+pub fn load_summaries(path: &Path) -> Result<Vec<SessionSummary>> {
+    if !path.exists() {
+        bail!("{} does not exist", path.display());
+    }
+    let last = segments(path).pop().unwrap_or_else(|| path.to_path_buf());
+    let path = last.as_path();
+    let name = plain_name(path);
+
+    if name.ends_with(".jsonl") {
+        let mut summaries = Vec::new();
+        for line in open(path)?.lines() {
+            let line = line?;
+            if !line.contains("\"summary\"") {
+                continue;
+            }
+            let mut value: serde_json::Value = serde_json::from_str(&line)?;
+            if let Some(summary) = value.get_mut("summary") {
+                summaries.push(serde_json::from_value(summary.take())?);
+            }
+        }
+        return Ok(summaries);
+    }
+
+    let Some(summary_path) = summary_csv(path) else {
+        bail!("{} is not a CSV or JSONL session log", path.display());
+    };
+    if !summary_path.exists() {
+        bail!(
+            "{} has no summary (expected {}). The session may still be running or have been killed; `vasili analyze` works without one.",
+            path.display(),
+            summary_path.display()
+        );
+    }
+    let mut reader = csv::Reader::from_reader(open(&summary_path)?);
+    reader
+        .deserialize()
+        .collect::<Result<Vec<SessionSummary>, _>>()
+        .with_context(|| format!("Could not read {}", summary_path.display()))
+}
+
+pub fn parse_timestamp(s: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+// The records of every segment of the session, in order.
+pub fn load_session(path: &Path) -> Result<Vec<PingRecord>> {
+    let mut records = Vec::new();
+    for segment in segments(path) {
+        records.extend(load_records(&segment)?);
+    }
+    Ok(records)
+}
+
+// The CSV header uses display names ("Latency (ms)"), so rows are matched to
+// PingRecord by position rather than by name.
+pub fn load_records(path: &Path) -> Result<Vec<PingRecord>> {
+    let name = plain_name(path);
+
+    if name.ends_with(".jsonl") {
+        let mut records = Vec::new();
+        for line in open(path)?.lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with("{\"summary\"") {
+                continue;
+            }
+            records.push(serde_json::from_str(&line).with_context(|| format!("Invalid line in {}", path.display()))?);
+        }
+        return Ok(records);
+    }
+
+    if !name.ends_with(".csv") || name.ends_with("_summary.csv") {
+        bail!("{} is not a CSV or JSONL session log", path.display());
+    }
+    let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(open(path)?);
+    let mut records = Vec::new();
+    for (line, row) in reader.records().enumerate() {
+        let row = row?;
+        records.push(row.deserialize(None).with_context(|| format!("Invalid row {} in {}", line + 2, path.display()))?);
+    }
+    Ok(records)
+}
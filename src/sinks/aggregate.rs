@@ -0,0 +1,70 @@
+use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
+
+pub const AGGREGATE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct Window {
+    count: u64,
+    lost: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Aggregate {
+    pub target_type: String,
+    pub host: String,
+    pub samples: u64,
+    pub lost: u64,
+    pub loss_percent: f64,
+    pub avg_ms: Option<f64>,
+    pub min_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Aggregator {
+    windows: HashMap<(String, String), Window>,
+}
+
+impl Aggregator {
+    pub fn record(&mut self, target_type: &str, host: &str, latency_ms: Option<f64>) {
+        let window = self.windows.entry((target_type.to_string(), host.to_string())).or_default();
+        window.count += 1;
+        match latency_ms {
+            Some(ms) => {
+                let replies = window.count - window.lost;
+                window.min = if replies == 1 { ms } else { window.min.min(ms) };
+                window.max = window.max.max(ms);
+                window.sum += ms;
+            }
+            None => window.lost += 1,
+        }
+    }
+
+    pub fn drain(&mut self) -> Vec<Aggregate> {
+        self.windows
+            .drain()
+            .map(|((target_type, host), w)| {
+                let replies = w.count - w.lost;
+                let (avg_ms, min_ms, max_ms) = if replies > 0 {
+                    (Some(w.sum / replies as f64), Some(w.min), Some(w.max))
+                } else {
+                    (None, None, None)
+                };
+                Aggregate {
+                    target_type,
+                    host,
+                    samples: w.count,
+                    lost: w.lost,
+                    loss_percent: w.lost as f64 / w.count as f64 * 100.0,
+                    avg_ms,
+                    min_ms,
+                    max_ms,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,133 @@
+use super::Sink;
+use crate::app::{PingRecord, SessionSummary};
+use crate::diag;
+use anyhow::{Result, anyhow};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// Rows are serialized and written on a thread of their own, so a slow disk
+// (an SD card, a network share) never holds up the probes or the TUI. They
+// reach the file in batches, at the latest a second after the probe.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const FLUSH_RECORDS: usize = 50;
+
+enum Message {
+    Record(PingRecord),
+    // Everything sent before is on disk once the answer comes back.
+    Flush(mpsc::Sender<Result<()>>),
+}
+
+pub struct CsvSink {
+    path: PathBuf,
+    tx: Option<mpsc::Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl CsvSink {
+    pub fn open(path: PathBuf, compress: bool) -> Result<Self> {
+        let (file, is_new_file) = super::open_log(&path, compress)?;
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+
+        if is_new_file {
+            writer.write_record(super::CSV_HEADER)?;
+            writer.flush()?;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let worker_path = path.clone();
+        let worker = thread::spawn(move || run_worker(writer, &worker_path, rx));
+        Ok(Self { path, tx: Some(tx), worker: Some(worker) })
+    }
+
+    // Next to the log and never compressed: x_summary.csv for x.csv.gz too.
+    fn summary_path(&self) -> PathBuf {
+        let name = self.path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let stem = name.trim_end_matches(".gz").trim_end_matches(".csv");
+        self.path.with_file_name(format!("{}_summary.csv", stem))
+    }
+}
+
+impl Sink for CsvSink {
+    fn write_record(&mut self, record: &PingRecord) -> Result<()> {
+        let tx = self.tx.as_ref().ok_or_else(|| anyhow!("writer is closed"))?;
+        tx.send(Message::Record(record.clone())).map_err(|_| anyhow!("writer stopped"))?;
+        Ok(())
+    }
+
+    fn write_summary(&mut self, summaries: &[SessionSummary]) -> Result<()> {
+        if summaries.is_empty() {
+            return Ok(());
+        }
+        let mut writer = csv::Writer::from_path(self.summary_path())?;
+        for summary in summaries {
+            writer.serialize(summary)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let tx = self.tx.as_ref().ok_or_else(|| anyhow!("writer is closed"))?;
+        let (done_tx, done_rx) = mpsc::channel();
+        tx.send(Message::Flush(done_tx)).map_err(|_| anyhow!("writer stopped"))?;
+        done_rx.recv().map_err(|_| anyhow!("writer stopped"))?
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for CsvSink {
+    fn drop(&mut self) {
+        // The worker writes out what is left once the channel closes.
+        self.tx.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_worker(mut writer: csv::Writer<Box<dyn Write + Send>>, path: &Path, rx: mpsc::Receiver<Message>) {
+    let mut pending = 0;
+    let mut last_flush = Instant::now();
+
+    loop {
+        let connected = match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(Message::Record(record)) => {
+                match writer.serialize(&record) {
+                    Ok(()) => pending += 1,
+                    Err(e) => diag::error(format!("Writing {} failed: {}", path.display(), e)),
+                }
+                true
+            }
+            Ok(Message::Flush(done)) => {
+                let _ = done.send(writer.flush().map_err(Into::into));
+                pending = 0;
+                last_flush = Instant::now();
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+
+        if pending > 0 && (pending >= FLUSH_RECORDS || last_flush.elapsed() >= FLUSH_INTERVAL || !connected) {
+            if let Err(e) = writer.flush() {
+                diag::error(format!("Flushing {} failed: {}", path.display(), e));
+            }
+            pending = 0;
+            last_flush = Instant::now();
+        }
+
+        if !connected {
+            return;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use super::aggregate::{AGGREGATE_INTERVAL, Aggregator};
+use crate::app::{PingRecord, SessionSummary};
+use crate::diag;
+use chrono::Utc;
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const BATCH_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_LINES: usize = 1000;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct InfluxOptions {
+    pub url: String,
+    pub bucket: String,
+    pub org: Option<String>,
+    pub token: Option<String>,
+}
+
+enum Message {
+    Record { target_type: String, host: String, latency_ms: Option<f64>, status: String, ttl: Option<u8>, timestamp_ms: i64 },
+    Line(String),
+}
+
+pub struct InfluxWriter {
+    tx: Option<mpsc::Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+fn escape_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl InfluxWriter {
+    pub fn start(options: InfluxOptions) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker = thread::spawn(move || run_worker(options, rx));
+        Self { tx: Some(tx), worker: Some(worker) }
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(message);
+        }
+    }
+
+    pub fn write_record(&self, record: &PingRecord) {
+        // A marker is no probe: it goes to a measurement of its own, so it
+        // never reads as a lost reply or counts towards the aggregates.
+        if record.is_marker() {
+            self.send(Message::Line(format!(
+                "vasili_marker label=\"{}\" {}",
+                escape_string(&record.target_ip),
+                Utc::now().timestamp_millis()
+            )));
+            return;
+        }
+        self.send(Message::Record {
+            target_type: record.target_type.clone(),
+            host: record.target_ip.clone(),
+            latency_ms: record.latency_ms,
+            status: record.status.clone(),
+            ttl: record.ttl,
+            timestamp_ms: Utc::now().timestamp_millis(),
+        });
+    }
+
+    pub fn write_summary(&self, summaries: &[SessionSummary]) {
+        let now = Utc::now().timestamp_millis();
+        for s in summaries {
+            self.send(Message::Line(format!(
+                "vasili_summary,target_type={},host={} samples={}i,lost={}i,loss_percent={},p25={},p75={},p99={},p999={},max={},deadline_misses={}i,anomalies={}i,grade=\"{}\",protocol=\"{}\",aborted_by_user={},timeout_ms={}i,dscp={}i,measured_secs={},gaps={}i,min={},mean={},stddev={},warming_up={},terminated={} {}",
+                escape_tag(&s.target_type),
+                escape_tag(&s.target_ip),
+                s.samples,
+                s.lost,
+                s.loss_percent,
+                s.p25,
+                s.p75,
+                s.p99,
+                s.p999,
+                s.max,
+                s.deadline_misses,
+                s.anomalies,
+                escape_string(&s.grade),
+                escape_string(&s.protocol),
+                s.aborted_by_user,
+                s.timeout_ms,
+                s.dscp,
+                s.measured_secs,
+                s.gaps,
+                s.min,
+                s.mean,
+                s.stddev,
+                s.warming_up,
+                s.terminated,
+                now
+            )));
+        }
+    }
+}
+
+impl Drop for InfluxWriter {
+    fn drop(&mut self) {
+        self.tx.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn push(options: &InfluxOptions, lines: &mut Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut request = ureq::post(&format!("{}/api/v2/write", options.url.trim_end_matches('/')))
+        .query("bucket", &options.bucket)
+        .query("precision", "ms")
+        .timeout(WRITE_TIMEOUT);
+    if let Some(org) = &options.org {
+        request = request.query("org", org);
+    }
+    if let Some(token) = &options.token {
+        request = request.set("Authorization", &format!("Token {}", token));
+    }
+
+    if let Err(e) = request.send_string(&lines.join("\n")) {
+        diag::error(format!("InfluxDB write to {} failed ({} points dropped): {}", options.url, lines.len(), e));
+    }
+    lines.clear();
+}
+
+fn run_worker(options: InfluxOptions, rx: mpsc::Receiver<Message>) {
+    let mut lines = Vec::new();
+    let mut aggregator = Aggregator::default();
+    let mut last_push = Instant::now();
+    let mut last_aggregate = Instant::now();
+
+    loop {
+        let connected = match rx.recv_timeout(BATCH_INTERVAL) {
+            Ok(Message::Record { target_type, host, latency_ms, status, ttl, timestamp_ms }) => {
+                let tags = format!("target_type={},host={}", escape_tag(&target_type), escape_tag(&host));
+                let ttl = ttl.map(|ttl| format!(",ttl={}i", ttl)).unwrap_or_default();
+                lines.push(match latency_ms {
+                    Some(ms) => format!("vasili_ping,{} latency_ms={},lost=false{} {}", tags, ms, ttl, timestamp_ms),
+                    None => format!("vasili_ping,{} lost=true,status=\"{}\" {}", tags, escape_string(&status), timestamp_ms),
+                });
+
+                aggregator.record(&target_type, &host, latency_ms);
+                true
+            }
+            Ok(Message::Line(line)) => {
+                lines.push(line);
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+
+        if last_aggregate.elapsed() >= AGGREGATE_INTERVAL || !connected {
+            let now = Utc::now().timestamp_millis();
+            for agg in aggregator.drain() {
+                let mut fields = format!("samples={}i,lost={}i,loss_percent={}", agg.samples, agg.lost, agg.loss_percent);
+                if let (Some(avg), Some(min), Some(max)) = (agg.avg_ms, agg.min_ms, agg.max_ms) {
+                    fields.push_str(&format!(",avg_ms={},min_ms={},max_ms={}", avg, min, max));
+                }
+                lines.push(format!(
+                    "vasili_aggregate,target_type={},host={} {} {}",
+                    escape_tag(&agg.target_type),
+                    escape_tag(&agg.host),
+                    fields,
+                    now
+                ));
+            }
+            last_aggregate = Instant::now();
+        }
+
+        if lines.len() >= BATCH_LINES || last_push.elapsed() >= BATCH_INTERVAL || !connected {
+            push(&options, &mut lines);
+            last_push = Instant::now();
+        }
+
+        if !connected {
+            return;
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use super::Sink;
+use crate::app::{PingRecord, SessionSummary};
+use anyhow::Result;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub struct JsonlSink {
+    path: PathBuf,
+    writer: Box<dyn Write + Send>,
+}
+
+impl JsonlSink {
+    pub fn open(path: PathBuf, compress: bool) -> Result<Self> {
+        let (writer, _) = super::open_log(&path, compress)?;
+        Ok(Self { path, writer })
+    }
+}
+
+impl Sink for JsonlSink {
+    fn write_record(&mut self, record: &PingRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn write_summary(&mut self, summaries: &[SessionSummary]) -> Result<()> {
+        for summary in summaries {
+            serde_json::to_writer(&mut self.writer, &serde_json::json!({ "summary": summary }))?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
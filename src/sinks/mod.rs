@@ -0,0 +1,351 @@
+mod aggregate;
+mod csv;
+mod influx;
+mod jsonl;
+mod mqtt;
+mod parquet;
+mod sqlite;
+
+pub use influx::InfluxOptions;
+pub use mqtt::MqttOptions;
+
+use crate::app::{PingRecord, SessionSummary};
+use crate::args::{format_duration, format_size, parse_duration_string};
+use crate::diag;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use chrono::{Local, NaiveDate};
+use flate2::{Compression, write::GzEncoder};
+use std::{
+    fs::{self, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// How often the size of the open files is looked at for --rotate-size.
+const SIZE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const CSV_HEADER: [&str; 6] = ["Timestamp", "Target Type", "Target IP", "Latency (ms)", "Status", "Reply TTL"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Csv,
+    Jsonl,
+    Sqlite,
+    Parquet,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// When a long session starts a new segment: at local midnight, so every
+// file holds one calendar day, or after a fixed time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Daily,
+    Every(Duration),
+}
+
+impl Rotation {
+    // "daily" or a duration such as "6h"; "off" is left to the caller.
+    pub fn parse(s: &str) -> Option<Rotation> {
+        match s.trim() {
+            "daily" => Some(Rotation::Daily),
+            s => parse_duration_string(s).ok().map(Rotation::Every),
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            Rotation::Daily => "daily".to_string(),
+            Rotation::Every(every) => format!("every {}", format_duration(every)),
+        }
+    }
+}
+
+pub trait Sink: Send {
+    fn write_record(&mut self, record: &PingRecord) -> Result<()>;
+    fn write_summary(&mut self, summaries: &[SessionSummary]) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+    fn path(&self) -> &Path;
+}
+
+// The file a CSV or JSONL sink appends to, and whether it was empty. With
+// --compress it goes through gzip as it is written; every flush ends a
+// deflate block, so what was flushed reads back even if the session is
+// killed before the end of the file is written.
+fn open_log(path: &Path, compress: bool) -> Result<(Box<dyn Write + Send>, bool)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_new_file = file.metadata()?.len() == 0;
+    let writer = BufWriter::new(file);
+    Ok(match compress {
+        true => (Box::new(GzEncoder::new(writer, Compression::default())), is_new_file),
+        false => (Box::new(writer), is_new_file),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub formats: Vec<OutputFormat>,
+    pub directory: PathBuf,
+    pub rotate: Option<Rotation>,
+    pub rotate_size: Option<u64>,
+    pub compress: bool,
+    pub split: bool,
+    pub influx: Option<InfluxOptions>,
+    pub mqtt: Option<MqttOptions>,
+}
+
+pub struct Pipeline {
+    options: OutputOptions,
+    name_suffix: String,
+    timestamp: String,
+    groups: Vec<(String, Vec<Box<dyn Sink>>)>,
+    // Segments of the session so far, the current one included.
+    segment: u32,
+    opened_at: Instant,
+    opened_on: NaiveDate,
+    size_checked: Instant,
+    influx: Option<influx::InfluxWriter>,
+    mqtt: Option<mqtt::MqttWriter>,
+}
+
+impl Pipeline {
+    pub fn open(options: OutputOptions, name_suffix: String) -> Result<Self> {
+        if !options.formats.is_empty() {
+            fs::create_dir_all(&options.directory)?;
+        }
+
+        let influx = options.influx.clone().map(influx::InfluxWriter::start);
+        let mqtt = options.mqtt.clone().map(mqtt::MqttWriter::start);
+        let mut pipeline = Pipeline {
+            options,
+            name_suffix,
+            timestamp: Local::now().format("%Y-%m-%d_%H-%M-%S").to_string(),
+            groups: Vec::new(),
+            segment: 0,
+            opened_at: Instant::now(),
+            opened_on: Local::now().date_naive(),
+            size_checked: Instant::now(),
+            influx,
+            mqtt,
+        };
+        pipeline.start_segment();
+        if !pipeline.options.split {
+            pipeline.open_group("")?;
+        }
+
+        Ok(pipeline)
+    }
+
+    fn start_segment(&mut self) {
+        self.segment += 1;
+        self.opened_at = Instant::now();
+        self.opened_on = Local::now().date_naive();
+    }
+
+    fn base_name(&self, group: &str) -> PathBuf {
+        let mut name = format!("vasili_{}_{}", self.timestamp, self.name_suffix);
+        if !group.is_empty() {
+            name.push('_');
+            name.push_str(&group.to_lowercase().replace(' ', "_"));
+        }
+        self.options.directory.join(name)
+    }
+
+    // Every segment after the first carries its number, so the files of a
+    // rotated session sort together and in order: x.csv, x_part002.csv, ...
+    fn segment_name(&self, group: &str) -> PathBuf {
+        match self.segment {
+            0 | 1 => self.base_name(group),
+            n => PathBuf::from(format!("{}_part{:03}", self.base_name(group).display(), n)),
+        }
+    }
+
+    fn open_group(&mut self, group: &str) -> Result<usize> {
+        let base = self.segment_name(group);
+        let formats = &self.options.formats;
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+
+        for (i, format) in formats.iter().enumerate() {
+            if formats[..i].contains(format) {
+                continue;
+            }
+
+            // SQLite and Parquet files are not text and stay as they are.
+            let compress = self.options.compress && matches!(format, OutputFormat::Csv | OutputFormat::Jsonl);
+            let gz = if compress { ".gz" } else { "" };
+            let path = PathBuf::from(format!("{}.{}{}", base.display(), format.extension(), gz));
+            let sink: Box<dyn Sink> = match format {
+                OutputFormat::Csv => Box::new(csv::CsvSink::open(path, compress)?),
+                OutputFormat::Jsonl => Box::new(jsonl::JsonlSink::open(path, compress)?),
+                OutputFormat::Sqlite => Box::new(sqlite::SqliteSink::open(path)?),
+                OutputFormat::Parquet => Box::new(parquet::ParquetSink::open(path)?),
+            };
+            sinks.push(sink);
+        }
+
+        self.groups.push((group.to_string(), sinks));
+        Ok(self.groups.len() - 1)
+    }
+
+    fn close_groups(&mut self) -> Vec<PathBuf> {
+        let mut closed = Vec::new();
+
+        for (_, sinks) in self.groups.drain(..) {
+            for mut sink in sinks {
+                let path = sink.path().to_path_buf();
+                if let Err(e) = sink.flush() {
+                    diag::error(format!("Flushing {} failed: {}", path.display(), e));
+                }
+                drop(sink);
+                closed.push(path);
+            }
+        }
+
+        closed
+    }
+
+    // The readable summary sits next to the data files of the last segment.
+    pub fn markdown_summary_path(&self) -> Option<PathBuf> {
+        (!self.options.formats.is_empty()).then(|| PathBuf::from(format!("{}_summary.md", self.base_name("").display())))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut paths = if self.options.split {
+            let exts = self.options.formats.iter().map(|f| f.extension()).collect::<Vec<_>>().join("|");
+            format!("{}_<target|gateway>.{} (one file per target)", self.base_name("").display(), exts)
+        } else {
+            self.groups
+                .iter()
+                .flat_map(|(_, sinks)| sinks.iter())
+                .map(|s| s.path().display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let mut notes = Vec::new();
+        match (self.options.rotate, self.options.rotate_size) {
+            (Some(rotation), Some(size)) => notes.push(format!("rotated {} or at {}", rotation.describe(), format_size(size))),
+            (Some(rotation), None) => notes.push(format!("rotated {}", rotation.describe())),
+            (None, Some(size)) => notes.push(format!("rotated at {}", format_size(size))),
+            (None, None) => {}
+        }
+        if self.options.compress {
+            notes.push("gzip".to_string());
+        }
+        if !notes.is_empty() {
+            paths.push_str(&format!(" ({})", notes.join(", ")));
+        }
+
+        let mut targets = Vec::new();
+        if !self.options.formats.is_empty() {
+            targets.push(paths);
+        }
+        if let Some(influx) = &self.options.influx {
+            targets.push(format!("InfluxDB {} (bucket {})", influx.url, influx.bucket));
+        }
+        if let Some(mqtt) = &self.options.mqtt {
+            targets.push(format!("MQTT {}", mqtt.url));
+        }
+        targets.join(", ")
+    }
+
+    pub fn rotate(&mut self) -> Vec<PathBuf> {
+        let closed = self.close_groups();
+        self.start_segment();
+        closed
+    }
+
+    fn rotation_due(&mut self) -> bool {
+        let due = match self.options.rotate {
+            Some(Rotation::Daily) => Local::now().date_naive() != self.opened_on,
+            Some(Rotation::Every(every)) => self.opened_at.elapsed() >= every,
+            None => false,
+        };
+        if due {
+            return true;
+        }
+        let Some(limit) = self.options.rotate_size else {
+            return false;
+        };
+        if self.size_checked.elapsed() < SIZE_CHECK_INTERVAL {
+            return false;
+        }
+        self.size_checked = Instant::now();
+        self.groups
+            .iter()
+            .flat_map(|(_, sinks)| sinks.iter())
+            .any(|sink| fs::metadata(sink.path()).is_ok_and(|m| m.len() >= limit))
+    }
+
+    pub fn write_record(&mut self, record: &PingRecord) {
+        if let Some(influx) = &self.influx {
+            influx.write_record(record);
+        }
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.write_record(record);
+        }
+
+        if self.options.formats.is_empty() {
+            return;
+        }
+
+        if self.rotation_due() {
+            self.rotate();
+        }
+
+        let group = if self.options.split { record.target_type.as_str() } else { "" };
+        let idx = match self.groups.iter().position(|(g, _)| g == group) {
+            Some(idx) => idx,
+            None => match self.open_group(group) {
+                Ok(idx) => idx,
+                Err(e) => {
+                    diag::error(format!("Could not open output files: {}", e));
+                    return;
+                }
+            },
+        };
+
+        for sink in &mut self.groups[idx].1 {
+            if let Err(e) = sink.write_record(record) {
+                diag::error(format!("Writing {} failed: {}", sink.path().display(), e));
+            }
+        }
+    }
+
+    pub fn finish(&mut self, summaries: &[SessionSummary]) -> Vec<PathBuf> {
+        if let Some(influx) = self.influx.take() {
+            influx.write_summary(summaries);
+        }
+        if let Some(mqtt) = self.mqtt.take() {
+            mqtt.write_summary(summaries);
+        }
+
+        for (group, sinks) in &mut self.groups {
+            let relevant: Vec<SessionSummary> = summaries
+                .iter()
+                .filter(|s| group.is_empty() || s.target_type == *group)
+                .cloned()
+                .collect();
+
+            for sink in sinks {
+                if let Err(e) = sink.write_summary(&relevant) {
+                    diag::error(format!("Writing summary to {} failed: {}", sink.path().display(), e));
+                }
+            }
+        }
+        self.close_groups()
+    }
+}
@@ -0,0 +1,419 @@
+use super::aggregate::{AGGREGATE_INTERVAL, Aggregator};
+use crate::app::{PingRecord, SessionSummary};
+use crate::diag;
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_TOPIC: &str = "vasili/{target_type}/{kind}";
+
+const DEFAULT_PORT: u16 = 1883;
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+// A broker that does not answer a PINGREQ within this long is considered gone.
+const PING_TIMEOUT: Duration = Duration::from_secs(15);
+const PINGRESP: u8 = 13;
+
+#[derive(Debug, Clone)]
+pub struct MqttOptions {
+    pub url: String,
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+    topic: String,
+}
+
+impl MqttOptions {
+    pub fn parse(url: &str, topic: Option<&str>) -> Result<Self> {
+        let rest = match url.split_once("://") {
+            Some(("mqtt" | "tcp", rest)) => rest,
+            Some((scheme, _)) => bail!("unsupported MQTT scheme '{}' (only mqtt:// is supported)", scheme),
+            None => url,
+        };
+        let rest = rest.trim_end_matches('/');
+
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => (Some(credentials), address),
+            None => (None, rest),
+        };
+        let (username, password) = match credentials.map(|c| c.split_once(':').unwrap_or((c, ""))) {
+            Some((user, pass)) => (Some(user.to_string()), (!pass.is_empty()).then(|| pass.to_string())),
+            None => (None, None),
+        };
+
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') && port.chars().all(|c| c.is_ascii_digit()) => {
+                (host, port.parse().map_err(|_| anyhow!("invalid MQTT port '{}'", port))?)
+            }
+            _ => (address, DEFAULT_PORT),
+        };
+        if host.is_empty() {
+            bail!("missing MQTT broker host in '{}'", url);
+        }
+
+        Ok(Self {
+            url: format!("mqtt://{}:{}", host, port),
+            host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port,
+            username,
+            password,
+            topic: topic.unwrap_or(DEFAULT_TOPIC).to_string(),
+        })
+    }
+
+    fn topic(&self, target_type: &str, host: &str, kind: &str) -> String {
+        self.topic
+            .replace("{target_type}", &target_type.to_lowercase().replace(' ', "_"))
+            .replace("{host}", host)
+            .replace("{kind}", kind)
+    }
+}
+
+enum Message {
+    Record(PingRecord),
+    Summary(Vec<SessionSummary>),
+}
+
+pub struct MqttWriter {
+    tx: Option<mpsc::Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl MqttWriter {
+    pub fn start(options: MqttOptions) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker = thread::spawn(move || run_worker(options, rx));
+        Self { tx: Some(tx), worker: Some(worker) }
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(message);
+        }
+    }
+
+    pub fn write_record(&self, record: &PingRecord) {
+        self.send(Message::Record(record.clone()));
+    }
+
+    pub fn write_summary(&self, summaries: &[SessionSummary]) {
+        self.send(Message::Summary(summaries.to_vec()));
+    }
+}
+
+impl Drop for MqttWriter {
+    fn drop(&mut self) {
+        self.tx.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn write_remaining_length(packet: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    write_remaining_length(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+// Splits complete packets off the front of `buffer` and returns their types.
+fn take_packets(buffer: &mut Vec<u8>) -> Vec<u8> {
+    let mut types = Vec::new();
+    loop {
+        let (mut len, mut at) = (0usize, 1);
+        loop {
+            let Some(byte) = buffer.get(at) else {
+                return types;
+            };
+            len |= ((byte & 0x7f) as usize) << (7 * (at - 1));
+            at += 1;
+            if byte & 0x80 == 0 || at > 4 {
+                break;
+            }
+        }
+        if buffer.len() < at + len {
+            return types;
+        }
+        types.push(buffer[0] >> 4);
+        buffer.drain(..at + len);
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    last_ping: Instant,
+    // When the PINGREQ still waiting for its PINGRESP was sent.
+    ping_sent: Option<Instant>,
+}
+
+impl Connection {
+    fn open(options: &MqttOptions) -> Result<Self> {
+        let addr = (options.host.as_str(), options.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("could not resolve {}", options.host))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECT_TIMEOUT))?;
+
+        let mut flags = 0x02;
+        let mut body = Vec::new();
+        write_string(&mut body, "MQTT");
+        body.push(4);
+        if options.username.is_some() {
+            flags |= 0x80;
+        }
+        if options.password.is_some() {
+            flags |= 0x40;
+        }
+        body.push(flags);
+        body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+        write_string(&mut body, &format!("vasili-{:08x}", rand::random::<u32>()));
+        if let Some(username) = &options.username {
+            write_string(&mut body, username);
+        }
+        if let Some(password) = &options.password {
+            write_string(&mut body, password);
+        }
+        stream.write_all(&packet(0x10, &body))?;
+
+        let mut connack = [0u8; 4];
+        stream.read_exact(&mut connack)?;
+        match connack {
+            [0x20, 0x02, _, 0] => {}
+            [0x20, 0x02, _, 4 | 5] => bail!("broker rejected the credentials"),
+            [0x20, 0x02, _, code] => bail!("broker refused the connection (code {})", code),
+            _ => bail!("unexpected reply to CONNECT"),
+        }
+
+        Ok(Self { stream, received: Vec::new(), last_ping: Instant::now(), ping_sent: None })
+    }
+
+    fn write(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.stream.write_all(packet)
+    }
+
+    // Reads whatever the broker sent without waiting for more. Only PINGRESP
+    // matters, everything is published with QoS 0.
+    fn read_available(&mut self) -> io::Result<()> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk = [0u8; 512];
+        let read = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Err(io::Error::new(io::ErrorKind::UnexpectedEof, "broker closed the connection")),
+                Ok(n) => self.received.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        read?;
+
+        if take_packets(&mut self.received).contains(&PINGRESP) {
+            self.ping_sent = None;
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self, topic: &str, payload: &str, retain: bool) -> io::Result<()> {
+        let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+        write_string(&mut body, topic);
+        body.extend_from_slice(payload.as_bytes());
+        self.write(&packet(if retain { 0x31 } else { 0x30 }, &body))
+    }
+
+    // Pings the broker every half keep-alive, also while publishing, so a
+    // broker that went away without closing the connection is noticed.
+    fn keep_alive(&mut self) -> io::Result<()> {
+        self.read_available()?;
+        match self.ping_sent {
+            Some(sent) if sent.elapsed() >= PING_TIMEOUT => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("no PINGRESP within {}s", PING_TIMEOUT.as_secs())))
+            }
+            None if self.last_ping.elapsed() >= KEEP_ALIVE / 2 => {
+                self.write(&[0xc0, 0x00])?;
+                self.last_ping = Instant::now();
+                self.ping_sent = Some(self.last_ping);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+struct Publisher {
+    options: MqttOptions,
+    connection: Option<Connection>,
+    last_attempt: Option<Instant>,
+}
+
+impl Publisher {
+    fn connection(&mut self) -> Option<&mut Connection> {
+        if self.connection.is_none() && self.last_attempt.is_none_or(|t| t.elapsed() >= RECONNECT_DELAY) {
+            self.last_attempt = Some(Instant::now());
+            match Connection::open(&self.options) {
+                Ok(connection) => self.connection = Some(connection),
+                Err(e) => diag::error(format!("MQTT connection to {} failed: {}", self.options.url, e)),
+            }
+        }
+        self.connection.as_mut()
+    }
+
+    fn publish<T: Serialize>(&mut self, target_type: &str, host: &str, kind: &str, payload: &T, retain: bool) {
+        let topic = self.options.topic(target_type, host, kind);
+        let Ok(payload) = serde_json::to_string(payload) else {
+            return;
+        };
+        let Some(connection) = self.connection() else {
+            return;
+        };
+
+        if let Err(e) = connection.publish(&topic, &payload, retain) {
+            diag::error(format!("MQTT publish to {} failed: {}", self.options.url, e));
+            self.connection = None;
+        }
+    }
+
+    fn keep_alive(&mut self) {
+        if let Some(connection) = &mut self.connection
+            && let Err(e) = connection.keep_alive()
+        {
+            diag::warn(format!("MQTT connection to {} lost: {}, reconnecting", self.options.url, e));
+            self.connection = None;
+        }
+    }
+}
+
+fn run_worker(options: MqttOptions, rx: mpsc::Receiver<Message>) {
+    let mut publisher = Publisher { options, connection: None, last_attempt: None };
+    let mut aggregator = Aggregator::default();
+    let mut last_aggregate = Instant::now();
+
+    loop {
+        let connected = match rx.recv_timeout(KEEP_ALIVE / 4) {
+            Ok(Message::Record(record)) if record.is_marker() => {
+                // The label is free text, so it stays out of the topic.
+                publisher.publish(&record.target_type, "marker", "marker", &record, false);
+                true
+            }
+            Ok(Message::Record(record)) => {
+                aggregator.record(&record.target_type, &record.target_ip, record.latency_ms);
+                publisher.publish(&record.target_type, &record.target_ip, "ping", &record, false);
+                true
+            }
+            Ok(Message::Summary(summaries)) => {
+                for summary in &summaries {
+                    publisher.publish(&summary.target_type, &summary.target_ip, "summary", summary, true);
+                }
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+
+        if last_aggregate.elapsed() >= AGGREGATE_INTERVAL || !connected {
+            for aggregate in aggregator.drain() {
+                publisher.publish(&aggregate.target_type, &aggregate.host, "aggregate", &aggregate, true);
+            }
+            last_aggregate = Instant::now();
+        }
+
+        if !connected {
+            if let Some(connection) = &mut publisher.connection {
+                let _ = connection.write(&[0xe0, 0x00]);
+            }
+            return;
+        }
+        publisher.keep_alive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_are_split_once_complete() {
+        let mut buffer = vec![0xd0, 0x00, 0x30, 0x03, b'a'];
+        assert_eq!(take_packets(&mut buffer), vec![PINGRESP]);
+        assert_eq!(buffer, vec![0x30, 0x03, b'a']);
+
+        buffer.extend_from_slice(&[b'b', b'c', 0xd0]);
+        assert_eq!(take_packets(&mut buffer), vec![3]);
+        assert_eq!(buffer, vec![0xd0]);
+
+        buffer.push(0x00);
+        assert_eq!(take_packets(&mut buffer), vec![PINGRESP]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn long_remaining_lengths_are_decoded() {
+        let mut buffer = packet(0x30, &[0u8; 200]);
+        buffer.truncate(100);
+        assert!(take_packets(&mut buffer).is_empty());
+        let mut buffer = packet(0x30, &[0u8; 200]);
+        assert_eq!(take_packets(&mut buffer), vec![3]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn a_silent_broker_times_out() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut broker, _) = listener.accept().unwrap();
+        let mut connection = Connection {
+            stream: client,
+            received: Vec::new(),
+            last_ping: Instant::now() - KEEP_ALIVE,
+            ping_sent: None,
+        };
+
+        connection.keep_alive().unwrap();
+        let mut ping = [0u8; 2];
+        broker.read_exact(&mut ping).unwrap();
+        assert_eq!(ping, [0xc0, 0x00]);
+
+        connection.ping_sent = Some(Instant::now() - PING_TIMEOUT);
+        assert_eq!(connection.keep_alive().unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        connection.ping_sent = Some(Instant::now() - PING_TIMEOUT);
+        broker.write_all(&[0xd0, 0x00]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        connection.keep_alive().unwrap();
+        assert!(connection.ping_sent.is_none());
+
+        drop(broker);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(connection.keep_alive().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
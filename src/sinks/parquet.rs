@@ -0,0 +1,152 @@
+use super::Sink;
+use crate::app::{PingRecord, SessionSummary};
+use crate::diag;
+use anyhow::{Result, anyhow};
+use chrono::{Local, NaiveDateTime};
+use parquet::{
+    basic::Compression,
+    data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    format::KeyValue,
+    schema::parser::parse_message_type,
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const ROW_GROUP_ROWS: usize = 50_000;
+
+const SCHEMA: &str = "
+message ping {
+    REQUIRED INT64 timestamp (TIMESTAMP(MILLIS, true));
+    REQUIRED BYTE_ARRAY target_type (STRING);
+    REQUIRED BYTE_ARRAY target_ip (STRING);
+    OPTIONAL DOUBLE latency_ms;
+    REQUIRED BYTE_ARRAY status (STRING);
+    OPTIONAL INT32 ttl (INTEGER(8, false));
+}";
+
+#[derive(Default)]
+struct Columns {
+    timestamp: Vec<i64>,
+    target_type: Vec<ByteArray>,
+    target_ip: Vec<ByteArray>,
+    latency_ms: Vec<f64>,
+    latency_levels: Vec<i16>,
+    status: Vec<ByteArray>,
+    ttl: Vec<i32>,
+    ttl_levels: Vec<i16>,
+}
+
+pub struct ParquetSink {
+    path: PathBuf,
+    writer: SerializedFileWriter<File>,
+    pending: Columns,
+}
+
+impl ParquetSink {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let props = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+        let writer = SerializedFileWriter::new(File::create(&path)?, schema, props)?;
+
+        Ok(Self { path, writer, pending: Columns::default() })
+    }
+
+    fn write_row_group(&mut self) -> Result<()> {
+        if self.pending.timestamp.is_empty() {
+            return Ok(());
+        }
+        let columns = std::mem::take(&mut self.pending);
+        let mut row_group = self.writer.next_row_group()?;
+
+        let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("missing timestamp column"))?;
+        column.typed::<Int64Type>().write_batch(&columns.timestamp, None, None)?;
+        column.close()?;
+
+        for values in [&columns.target_type, &columns.target_ip] {
+            let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("missing string column"))?;
+            column.typed::<ByteArrayType>().write_batch(values, None, None)?;
+            column.close()?;
+        }
+
+        let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("missing latency column"))?;
+        column.typed::<DoubleType>().write_batch(&columns.latency_ms, Some(&columns.latency_levels), None)?;
+        column.close()?;
+
+        let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("missing status column"))?;
+        column.typed::<ByteArrayType>().write_batch(&columns.status, None, None)?;
+        column.close()?;
+
+        let mut column = row_group.next_column()?.ok_or_else(|| anyhow!("missing ttl column"))?;
+        column.typed::<Int32Type>().write_batch(&columns.ttl, Some(&columns.ttl_levels), None)?;
+        column.close()?;
+
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+impl Sink for ParquetSink {
+    fn write_record(&mut self, record: &PingRecord) -> Result<()> {
+        let timestamp = NaiveDateTime::parse_from_str(&record.timestamp, "%Y-%m-%d %H:%M:%S%.3f")
+            .ok()
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .unwrap_or_else(Local::now);
+
+        let columns = &mut self.pending;
+        columns.timestamp.push(timestamp.timestamp_millis());
+        columns.target_type.push(record.target_type.as_str().into());
+        columns.target_ip.push(record.target_ip.as_str().into());
+        match record.latency_ms {
+            Some(ms) => {
+                columns.latency_ms.push(ms);
+                columns.latency_levels.push(1);
+            }
+            None => columns.latency_levels.push(0),
+        }
+        columns.status.push(record.status.as_str().into());
+        match record.ttl {
+            Some(ttl) => {
+                columns.ttl.push(ttl as i32);
+                columns.ttl_levels.push(1);
+            }
+            None => columns.ttl_levels.push(0),
+        }
+
+        if columns.timestamp.len() >= ROW_GROUP_ROWS {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    // Parquet has no room for a second table, so the summaries travel in the
+    // file's key/value metadata as JSON.
+    fn write_summary(&mut self, summaries: &[SessionSummary]) -> Result<()> {
+        if !summaries.is_empty() {
+            self.writer.append_key_value_metadata(KeyValue::new(
+                "vasili.summary".to_string(),
+                serde_json::to_string(summaries)?,
+            ));
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_row_group()
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_row_group().and_then(|_| Ok(self.writer.finish()?)) {
+            diag::error(format!("Closing {} failed: {}", self.path.display(), e));
+        }
+    }
+}
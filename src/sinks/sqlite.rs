@@ -0,0 +1,127 @@
+use super::Sink;
+use crate::app::{PingRecord, SessionSummary};
+use anyhow::Result;
+use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+
+pub struct SqliteSink {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl SqliteSink {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS pings (
+                 timestamp TEXT NOT NULL,
+                 target_type TEXT NOT NULL,
+                 target_ip TEXT NOT NULL,
+                 latency_ms REAL,
+                 status TEXT NOT NULL,
+                 ttl INTEGER
+             );
+             CREATE TABLE IF NOT EXISTS summaries (
+                 target_type TEXT NOT NULL,
+                 target_ip TEXT NOT NULL,
+                 started TEXT NOT NULL,
+                 duration_secs REAL NOT NULL,
+                 samples INTEGER NOT NULL,
+                 lost INTEGER NOT NULL,
+                 loss_percent REAL NOT NULL,
+                 p25 REAL NOT NULL,
+                 p75 REAL NOT NULL,
+                 p99 REAL NOT NULL,
+                 p999 REAL NOT NULL,
+                 p999_at TEXT,
+                 max REAL NOT NULL,
+                 max_at TEXT,
+                 spikes_minor INTEGER NOT NULL,
+                 spikes_major INTEGER NOT NULL,
+                 deadline_misses INTEGER NOT NULL,
+                 grade TEXT NOT NULL,
+                 protocol TEXT NOT NULL,
+                 aborted_by_user INTEGER NOT NULL DEFAULT 0,
+                 anomalies INTEGER NOT NULL DEFAULT 0,
+                 timeout_ms INTEGER NOT NULL DEFAULT 0,
+                 dscp INTEGER NOT NULL DEFAULT 0,
+                 measured_secs REAL NOT NULL DEFAULT 0,
+                 gaps INTEGER NOT NULL DEFAULT 0,
+                 min REAL NOT NULL DEFAULT 0,
+                 mean REAL NOT NULL DEFAULT 0,
+                 stddev REAL NOT NULL DEFAULT 0,
+                 warming_up INTEGER NOT NULL DEFAULT 0,
+                 terminated INTEGER NOT NULL DEFAULT 0
+             );",
+        )?;
+
+        Ok(Self { path, conn })
+    }
+}
+
+impl Sink for SqliteSink {
+    fn write_record(&mut self, record: &PingRecord) -> Result<()> {
+        self.conn
+            .prepare_cached("INSERT INTO pings VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?
+            .execute(params![
+                record.timestamp,
+                record.target_type,
+                record.target_ip,
+                record.latency_ms,
+                record.status,
+                record.ttl
+            ])?;
+        Ok(())
+    }
+
+    fn write_summary(&mut self, summaries: &[SessionSummary]) -> Result<()> {
+        for s in summaries {
+            self.conn
+                .prepare_cached("INSERT INTO summaries VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)")?
+                .execute(params![
+                    s.target_type,
+                    s.target_ip,
+                    s.started,
+                    s.duration_secs,
+                    s.samples,
+                    s.lost,
+                    s.loss_percent,
+                    s.p25,
+                    s.p75,
+                    s.p99,
+                    s.p999,
+                    s.p999_at,
+                    s.max,
+                    s.max_at,
+                    s.spikes_minor,
+                    s.spikes_major,
+                    s.deadline_misses,
+                    s.grade,
+                    s.protocol,
+                    s.aborted_by_user,
+                    s.anomalies,
+                    s.timeout_ms,
+                    s.dscp,
+                    s.measured_secs,
+                    s.gaps,
+                    s.min,
+                    s.mean,
+                    s.stddev,
+                    s.warming_up,
+                    s.terminated
+                ])?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.conn.cache_flush()?;
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,98 @@
+use crate::diag;
+use crate::events::{EventKind, SystemEvent};
+use crate::pinger::SourceType;
+use crate::redact;
+use crate::trace::{self, Hop};
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc;
+
+pub struct LossTrigger {
+    threshold: u32,
+    target_timeouts: u32,
+    gateway_ok: bool,
+    fired: bool,
+}
+
+impl LossTrigger {
+    pub fn new(threshold: u32) -> Self {
+        Self { threshold: threshold.max(1), target_timeouts: 0, gateway_ok: true, fired: false }
+    }
+
+    pub fn observe(&mut self, source: &SourceType, latency: Option<f64>) -> bool {
+        match source {
+            SourceType::Target if latency.is_some() => {
+                self.target_timeouts = 0;
+                self.fired = false;
+            }
+            SourceType::Target => self.target_timeouts += 1,
+            SourceType::Gateway => self.gateway_ok = latency.is_some(),
+            SourceType::Hop(_) | SourceType::Cycle(_) | SourceType::Passive => {}
+        }
+
+        let fire = !self.fired && self.gateway_ok && self.target_timeouts >= self.threshold;
+        self.fired |= fire;
+        fire
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+}
+
+fn append_snapshot(path: &Path, target: IpAddr, reason: &str, hops: &[Hop]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    writeln!(file, "=== {} - {} ({}) ===", Local::now().format("%Y-%m-%d %H:%M:%S"), reason, redact::ip(target))?;
+    for hop in hops {
+        writeln!(file, "{}", redact::text(&hop.describe()))?;
+    }
+    if !hops.last().is_some_and(|h| h.reached) {
+        writeln!(file, "Destination not reached.")?;
+    }
+    writeln!(file)?;
+    Ok(())
+}
+
+pub fn spawn(target: IpAddr, path: PathBuf, reason: String, tx: mpsc::Sender<SystemEvent>) {
+    tokio::spawn(async move {
+        let discovered = tokio::task::spawn_blocking(move || {
+            trace::discover(target, trace::DEFAULT_MAX_HOPS, trace::DEFAULT_QUERIES, trace::DEFAULT_TIMEOUT)
+        })
+        .await;
+
+        let hops = match discovered {
+            Ok(Ok(hops)) => hops,
+            Ok(Err(e)) => {
+                diag::warn(format!("Traceroute snapshot failed: {}", e));
+                return;
+            }
+            Err(e) => {
+                diag::error(format!("Traceroute snapshot task failed: {}", e));
+                return;
+            }
+        };
+
+        if let Err(e) = append_snapshot(&path, target, &reason, &hops) {
+            diag::error(format!("Could not write traceroute snapshot to {}: {}", path.display(), e));
+            return;
+        }
+
+        let last_hop = hops
+            .iter()
+            .rev()
+            .find_map(|h| h.addr().map(|addr| format!("last answering hop {} ({})", h.ttl, addr)))
+            .unwrap_or_else(|| "no hop answered".to_string());
+        let message = format!("Traceroute after {}: {}, saved to {}", reason, last_hop, path.display());
+        let _ = tx.send(SystemEvent { kind: EventKind::TraceSnapshot, message }).await;
+    });
+}
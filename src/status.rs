@@ -0,0 +1,81 @@
+use crate::alerts::AlertState;
+use crate::app::{App, HostStats, SessionSummary};
+use anyhow::Result;
+use chrono::Local;
+use serde::Serialize;
+use std::{fs, path::Path, time::Duration};
+
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_STATUS_FILE: &str = "vasili_status.json";
+
+// A second session writing to the same directory gets its own status file.
+pub fn default_status_file(shared_directory: bool) -> String {
+    if shared_directory {
+        format!("vasili_status_{}.json", std::process::id())
+    } else {
+        DEFAULT_STATUS_FILE.to_string()
+    }
+}
+
+#[derive(Serialize)]
+struct HostStatus {
+    #[serde(flatten)]
+    summary: SessionSummary,
+    last_latency_ms: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    updated_at: String,
+    pid: u32,
+    state: &'a str,
+    alert: Option<&'a str>,
+    alert_state: Option<AlertState>,
+    alert_since: Option<&'a str>,
+    last_event: Option<String>,
+    hosts: Vec<HostStatus>,
+}
+
+pub fn state(app: &App) -> &'static str {
+    if app.alerts.active().is_some() { "degraded" } else { "ok" }
+}
+
+pub fn render(app: &App) -> Result<Vec<u8>> {
+    let alert = app.alerts.active();
+    let last = |stats: &HostStats| stats.recent.back().copied().flatten();
+
+    let mut last_latencies = vec![last(&app.net_stats)];
+    if let Some(gw) = &app.gw_stats {
+        last_latencies.push(last(gw));
+    }
+    last_latencies.extend(app.hops.iter().map(|(_, stats)| last(stats)));
+    last_latencies.extend(app.cycle.iter().map(last));
+    if let Some(passive) = &app.passive_stats {
+        last_latencies.push(last(passive));
+    }
+
+    let status = Status {
+        updated_at: Local::now().to_rfc3339(),
+        pid: std::process::id(),
+        state: state(app),
+        alert: alert.map(|a| a.kind.label()),
+        alert_state: alert.map(|a| a.state),
+        alert_since: alert.map(|a| a.fired_at.as_str()),
+        last_event: app.events.last().map(|e| format!("[{}] {}", e.timestamp, e.message)),
+        hosts: app
+            .summaries()
+            .into_iter()
+            .zip(last_latencies)
+            .map(|(summary, last_latency_ms)| HostStatus { summary, last_latency_ms })
+            .collect(),
+    };
+
+    Ok(serde_json::to_vec_pretty(&status)?)
+}
+
+pub fn write_status(path: &Path, app: &App) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, render(app)?)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Classic,
+    HighContrast,
+    Monochrome,
+}
+
+pub struct Palette {
+    pub target: Color,
+    pub target_jitter: Color,
+    pub target_loss: Color,
+    pub gateway: Color,
+    pub gateway_jitter: Color,
+    pub gateway_loss: Color,
+    pub passive: Color,
+    pub outage: Color,
+    pub text: Color,
+    pub muted: Color,
+    pub footer_bg: Color,
+}
+
+impl Theme {
+    pub fn next(self) -> Self {
+        match self {
+            Theme::Classic => Theme::HighContrast,
+            Theme::HighContrast => Theme::Monochrome,
+            Theme::Monochrome => Theme::Classic,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::HighContrast => "High Contrast",
+            Theme::Monochrome => "Monochrome",
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        match self {
+            Theme::Classic => Palette {
+                target: Color::Green,
+                target_jitter: Color::Yellow,
+                target_loss: Color::Red,
+                gateway: Color::Blue,
+                gateway_jitter: Color::LightCyan,
+                gateway_loss: Color::Magenta,
+                passive: Color::Cyan,
+                outage: Color::LightRed,
+                text: Color::White,
+                muted: Color::Gray,
+                footer_bg: Color::DarkGray,
+            },
+            Theme::HighContrast => Palette {
+                target: Color::LightGreen,
+                target_jitter: Color::LightYellow,
+                target_loss: Color::LightRed,
+                gateway: Color::LightBlue,
+                gateway_jitter: Color::White,
+                gateway_loss: Color::LightMagenta,
+                passive: Color::LightCyan,
+                outage: Color::Yellow,
+                text: Color::White,
+                muted: Color::White,
+                footer_bg: Color::Blue,
+            },
+            Theme::Monochrome => Palette {
+                target: Color::White,
+                target_jitter: Color::Gray,
+                target_loss: Color::White,
+                gateway: Color::Gray,
+                gateway_jitter: Color::DarkGray,
+                gateway_loss: Color::Gray,
+                passive: Color::DarkGray,
+                outage: Color::White,
+                text: Color::White,
+                muted: Color::Gray,
+                footer_bg: Color::DarkGray,
+            },
+        }
+    }
+}
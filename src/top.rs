@@ -0,0 +1,221 @@
+use crate::app::SessionSummary;
+use crate::args::TopArgs;
+use crate::registry;
+use crate::{diag, locale, ui};
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    layout::Constraint,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Row, Table},
+};
+use serde::Deserialize;
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+// `vasili top`: every session on this machine (from the registry) and on the
+// machines given with --remote (from their HTTP API) in one table, refreshed
+// like top does, for monitors running on several machines around the house.
+
+const REFRESH: Duration = Duration::from_secs(2);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+// What /status answers, as far as the table needs it.
+#[derive(Deserialize)]
+struct RemoteStatus {
+    pid: u32,
+    state: String,
+    hosts: Vec<RemoteHost>,
+}
+
+#[derive(Deserialize)]
+struct RemoteHost {
+    #[serde(flatten)]
+    summary: SessionSummary,
+    last_latency_ms: Option<f64>,
+}
+
+struct Session {
+    machine: String,
+    pid: u32,
+    state: String,
+    hosts: Vec<(SessionSummary, Option<f64>)>,
+}
+
+// A remote that did not answer keeps its row, with the reason.
+type Source = Result<Vec<Session>, (String, String)>;
+
+fn local() -> Vec<Session> {
+    registry::sessions()
+        .into_iter()
+        .map(|entry| Session {
+            machine: "local".to_string(),
+            pid: entry.pid,
+            state: entry.state,
+            hosts: entry.hosts.into_iter().map(|host| (host, None)).collect(),
+        })
+        .collect()
+}
+
+fn fetch(remote: &str, token: Option<&str>) -> Source {
+    let url = if remote.contains("://") { format!("{}/status", remote.trim_end_matches('/')) } else { format!("http://{}/status", remote) };
+    let mut request = ureq::get(&url).timeout(FETCH_TIMEOUT);
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    let status: RemoteStatus = request
+        .call()
+        .map_err(|e| (remote.to_string(), e.to_string()))?
+        .into_json()
+        .map_err(|e| (remote.to_string(), e.to_string()))?;
+    Ok(vec![Session {
+        machine: remote.to_string(),
+        pid: status.pid,
+        state: status.state,
+        hosts: status.hosts.into_iter().map(|h| (h.summary, h.last_latency_ms)).collect(),
+    }])
+}
+
+// The remotes are asked side by side, so one that is down costs a second
+// rather than a second each.
+fn collect(args: &TopArgs) -> Vec<Source> {
+    let token = args.token.as_deref();
+    let remotes: Vec<Source> = thread::scope(|scope| {
+        let handles: Vec<_> = args.remote.iter().map(|remote| scope.spawn(move || fetch(remote, token))).collect();
+        handles.into_iter().map(|h| h.join().unwrap_or_else(|_| Ok(Vec::new()))).collect()
+    });
+    std::iter::once(Ok(local())).chain(remotes).collect()
+}
+
+fn cells(sources: &[Source]) -> Vec<(Vec<String>, Option<String>)> {
+    let mut rows = Vec::new();
+    for source in sources {
+        match source {
+            Ok(sessions) => {
+                for session in sessions {
+                    if session.hosts.is_empty() {
+                        rows.push((vec![session.machine.clone(), session.pid.to_string(), session.state.clone(), String::new(), "(no samples yet)".to_string()], None));
+                    }
+                    for (host, last) in &session.hosts {
+                        rows.push((
+                            vec![
+                                session.machine.clone(),
+                                session.pid.to_string(),
+                                session.state.clone(),
+                                host.target_type.clone(),
+                                host.target_ip.clone(),
+                                last.map(|ms| locale::host_ms(ms, 1, host.lan())).unwrap_or_else(|| "-".to_string()),
+                                locale::percent(host.loss_percent, 1),
+                                locale::host_ms(host.p99, 1, host.lan()),
+                                host.samples.to_string(),
+                                if host.warming_up { format!("n={}", host.samples) } else { host.grade.clone() },
+                            ],
+                            (!host.warming_up).then(|| host.grade.clone()),
+                        ));
+                    }
+                }
+            }
+            Err((machine, error)) => {
+                // The innermost cause, ureq puts the URL and the error kind in front.
+                let cause = error.rsplit(": ").next().unwrap_or(error);
+                rows.push((vec![machine.clone(), "-".to_string(), "unreachable".to_string(), String::new(), cause.to_string()], None));
+            }
+        }
+    }
+    rows
+}
+
+const HEADER: [&str; 10] = ["Machine", "PID", "State", "Role", "Host", "Last", "Loss", "P99", "Samples", "Grade"];
+
+fn print(sources: &[Source]) {
+    let rows = cells(sources);
+    if rows.is_empty() {
+        println!("No running vasili sessions.");
+        return;
+    }
+    let mut widths: Vec<usize> = HEADER.iter().map(|h| h.chars().count()).collect();
+    for (row, _) in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = w)).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    println!("{}", line(HEADER.to_vec()));
+    for (row, _) in &rows {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+}
+
+fn draw(f: &mut Frame, sources: &[Source]) {
+    let rows = cells(sources);
+    let count = rows.len();
+    let rows = rows.into_iter().map(|(row, grade)| {
+        let mut cells: Vec<Cell> = row.into_iter().map(Cell::from).collect();
+        if let (Some(grade), Some(cell)) = (grade, cells.last_mut()) {
+            *cell = Cell::from(grade.clone()).style(Style::default().fg(ui::grade_color(&grade)).add_modifier(Modifier::BOLD));
+        }
+        Row::new(cells)
+    });
+    let widths = [
+        Constraint::Min(12),
+        Constraint::Length(7),
+        Constraint::Length(11),
+        Constraint::Length(8),
+        Constraint::Min(15),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(8),
+        Constraint::Length(20),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(HEADER.to_vec()).style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::BOLD)))
+        .block(Block::default().borders(Borders::ALL).title(format!(" VASILI top - {} hosts - [q] quit ", count)));
+    f.render_widget(table, f.area());
+}
+
+pub fn run(args: TopArgs) -> Result<()> {
+    if args.once {
+        print(&collect(&args));
+        return Ok(());
+    }
+
+    diag::set_echo(false);
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let mut sources = collect(&args);
+    let mut refreshed = Instant::now();
+    loop {
+        terminal.draw(|f| draw(f, &sources))?;
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+        {
+            break;
+        }
+        if refreshed.elapsed() >= REFRESH {
+            sources = collect(&args);
+            refreshed = Instant::now();
+        }
+    }
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    diag::set_echo(true);
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use crate::app::PingRecord;
+use crate::args::{TraceArgs, parse_duration_string};
+use crate::sinks::{OutputFormat, OutputOptions, Pipeline};
+use anyhow::{Context, Result};
+use chrono::Local;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::{
+    io,
+    mem::MaybeUninit,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_MAX_HOPS: u8 = 30;
+pub const DEFAULT_QUERIES: u8 = 3;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+const PAYLOAD: &[u8] = b"vasili-traceroute-probe!";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Reply {
+    pub addr: IpAddr,
+    pub rtt_ms: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub ttl: u8,
+    pub replies: Vec<Option<Reply>>,
+    pub reached: bool,
+}
+
+impl Hop {
+    pub fn addr(&self) -> Option<IpAddr> {
+        self.replies.iter().flatten().next().map(|r| r.addr)
+    }
+
+    pub fn describe(&self) -> String {
+        let addr = self.addr().map(|a| a.to_string()).unwrap_or_else(|| "*".to_string());
+        let rtts = self
+            .replies
+            .iter()
+            .map(|r| match r {
+                Some(r) => format!("{:>8.1} ms", r.rtt_ms),
+                None => format!("{:>11}", "*"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{:>3}  {:<39} {}", self.ttl, addr, rtts)
+    }
+}
+
+// surge-ping matches replies by the address that answered, so Time Exceeded
+// messages from intermediate routers never reach its waiters. Traceroute uses
+// its own raw socket.
+struct TraceSocket {
+    socket: Socket,
+    target: SocketAddr,
+    ident: u16,
+}
+
+impl TraceSocket {
+    fn open(target: IpAddr) -> io::Result<Self> {
+        let socket = match target {
+            IpAddr::V4(_) => Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?,
+            IpAddr::V6(_) => Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))?,
+        };
+
+        Ok(Self { socket, target: SocketAddr::new(target, 0), ident: rand::random() })
+    }
+
+    fn send(&self, ttl: u8, seq: u16) -> io::Result<()> {
+        let v6 = self.target.is_ipv6();
+        if v6 {
+            self.socket.set_unicast_hops_v6(ttl as u32)?;
+        } else {
+            self.socket.set_ttl_v4(ttl as u32)?;
+        }
+
+        self.socket.send_to(&echo_request(v6, self.ident, seq), &SockAddr::from(self.target))?;
+        Ok(())
+    }
+
+    fn recv(&self, deadline: Instant) -> io::Result<Option<(IpAddr, u16, bool)>> {
+        let mut buf = [MaybeUninit::<u8>::uninit(); 1500];
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(remaining))?;
+
+            let (n, from) = match self.socket.recv_from(&mut buf) {
+                Ok(r) => r,
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            // recv_from initialized the first n bytes.
+            let packet = unsafe { std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), n) };
+            if let Some((ident, seq, echo_reply)) = parse_reply(packet, self.target.is_ipv6())
+                && ident == self.ident
+                && let Some(from) = from.as_socket()
+            {
+                return Ok(Some((from.ip(), seq, echo_reply)));
+            }
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn echo_request(v6: bool, ident: u16, seq: u16) -> Vec<u8> {
+    let mut packet = vec![if v6 { 128 } else { 8 }, 0, 0, 0];
+    packet.extend_from_slice(&ident.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+
+    // The kernel fills in the ICMPv6 checksum since it covers the pseudo-header.
+    if !v6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(offset)?, *data.get(offset + 1)?]))
+}
+
+fn parse_reply(packet: &[u8], v6: bool) -> Option<(u16, u16, bool)> {
+    let icmp = if v6 { packet } else { packet.get((packet.first()? & 0x0f) as usize * 4..)? };
+    let (request, echo_reply, errors) = if v6 { (128, 129, [1, 3]) } else { (8, 0, [3, 11]) };
+
+    match *icmp.first()? {
+        t if t == echo_reply => Some((read_u16(icmp, 4)?, read_u16(icmp, 6)?, true)),
+        t if errors.contains(&t) => {
+            let inner = icmp.get(8..)?;
+            let inner_header = if v6 { 40 } else { (inner.first()? & 0x0f) as usize * 4 };
+            let echo = inner.get(inner_header..)?;
+            (*echo.first()? == request).then_some((read_u16(echo, 4)?, read_u16(echo, 6)?, false))
+        }
+        _ => None,
+    }
+}
+
+pub fn discover(target: IpAddr, max_hops: u8, queries: u8, timeout: Duration) -> io::Result<Vec<Hop>> {
+    let socket = TraceSocket::open(target)?;
+    let mut hops: Vec<Hop> = (1..=max_hops)
+        .map(|ttl| Hop { ttl, replies: vec![None; queries as usize], reached: false })
+        .collect();
+    let mut last_ttl = max_hops;
+
+    for query in 0..queries {
+        let mut sent_at = Vec::with_capacity(last_ttl as usize);
+        for ttl in 1..=last_ttl {
+            sent_at.push(Instant::now());
+            socket.send(ttl, (query as u16) << 8 | ttl as u16)?;
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut pending = last_ttl as usize;
+        while pending > 0
+            && let Some((from, seq, echo_reply)) = socket.recv(deadline)?
+        {
+            let (q, ttl) = ((seq >> 8) as u8, (seq & 0xff) as u8);
+            if q != query || ttl == 0 || ttl > last_ttl {
+                continue;
+            }
+
+            let hop = &mut hops[ttl as usize - 1];
+            if hop.replies[query as usize].is_none() {
+                pending -= 1;
+            }
+            hop.replies[query as usize] =
+                Some(Reply { addr: from, rtt_ms: sent_at[ttl as usize - 1].elapsed().as_secs_f64() * 1000.0 });
+            hop.reached |= echo_reply || from == target;
+        }
+
+        if let Some(pos) = hops.iter().position(|h| h.reached) {
+            last_ttl = pos as u8 + 1;
+        }
+    }
+
+    hops.truncate(last_ttl as usize);
+    Ok(hops)
+}
+
+fn resolve(host: &str, ipv4: bool, ipv6: bool) -> Result<IpAddr> {
+    if let Ok(ip) = host.parse() {
+        return Ok(ip);
+    }
+
+    format!("{}:0", host)
+        .to_socket_addrs()
+        .with_context(|| format!("Could not resolve '{}'", host))?
+        .map(|a| a.ip())
+        .find(|ip| (!ipv4 || ip.is_ipv4()) && (!ipv6 || ip.is_ipv6()))
+        .with_context(|| format!("'{}' has no matching address", host))
+}
+
+pub async fn run(args: TraceArgs, default_output_dir: Option<PathBuf>) -> Result<()> {
+    let target = resolve(&args.target, args.ipv4, args.ipv6)?;
+    let timeout = match args.timeout.as_deref() {
+        Some(t) => parse_duration_string(t).map_err(|e| anyhow::anyhow!("Invalid --timeout '{}': {}", t, e))?,
+        None => DEFAULT_TIMEOUT,
+    };
+
+    println!(
+        "Tracing route to {} ({}), {} hops max, {} probes per hop",
+        args.target, target, args.max_hops, args.queries
+    );
+
+    let (max_hops, queries) = (args.max_hops, args.queries);
+    let hops = match tokio::task::spawn_blocking(move || discover(target, max_hops, queries, timeout)).await? {
+        Ok(hops) => hops,
+        Err(e) if crate::privileges::is_permission_error(&e) => {
+            eprintln!("Error: Traceroute needs a raw ICMP socket ({}).", e);
+            eprintln!("Run Vasili as root/Administrator or grant it cap_net_raw on Linux.");
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e).context("Traceroute failed"),
+    };
+
+    for hop in &hops {
+        println!("{}", hop.describe());
+    }
+    if !hops.last().is_some_and(|h| h.reached) {
+        println!("Destination not reached within {} hops.", max_hops);
+    }
+
+    if !args.record && args.formats.is_empty() {
+        return Ok(());
+    }
+
+    let options = OutputOptions {
+        formats: if args.formats.is_empty() { vec![OutputFormat::Csv] } else { args.formats.clone() },
+        directory: args.output_dir.clone().or(default_output_dir).unwrap_or_else(|| PathBuf::from(".")),
+        rotate: None,
+        rotate_size: None,
+        compress: false,
+        split: false,
+        influx: None,
+        mqtt: None,
+    };
+    let mut pipeline = Pipeline::open(options, format!("trace_{}", args.target.replace(':', "_")))?;
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S.%3f").to_string();
+
+    for hop in &hops {
+        for reply in &hop.replies {
+            pipeline.write_record(&PingRecord {
+                timestamp: timestamp.clone(),
+                target_type: format!("Hop {}", hop.ttl),
+                target_ip: reply.map(|r| r.addr.to_string()).unwrap_or_else(|| "*".to_string()),
+                latency_ms: reply.map(|r| r.rtt_ms),
+                status: if reply.is_some() { "OK" } else { "TIMEOUT" }.to_string(),
+                ttl: None,
+            });
+        }
+    }
+
+    let saved = pipeline.finish(&[]);
+    let saved = saved.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+    println!("Trace saved to: {}", saved.join(", "));
+    Ok(())
+}
@@ -1,213 +1,1054 @@
 use ratatui::{
     prelude::*,
-    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, LegendPosition},
+    widgets::{Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, Paragraph, LegendPosition, Row, Table, Wrap},
 };
-use crate::app::{App, HostStats};
+use crate::alerts::{Alert, AlertState};
+use crate::baseline::Verdict;
+use crate::app::{App, CYCLE_TABLE_ROWS, HostStats, RECENT_SAMPLES};
+use crate::args::format_duration;
+use crate::config::LayoutMode;
+use crate::diag;
+use crate::history;
+use crate::locale;
+use crate::events::EventKind;
+use crate::pinger::ProbeError;
+use crate::readiness;
 use chrono::Duration;
 
 pub fn draw(f: &mut Frame, app: &App) {
+    let events_height = if app.show_events { 8 } else { 0 };
+    let log_height = if app.show_log { 8 } else { 0 };
+    let gw_stats = app.gw_stats.as_ref().filter(|_| app.show_gateway);
+
+    let stats_height = match (app.layout, gw_stats.is_some()) {
+        (LayoutMode::Stacked, true) => 6,
+        _ => 3,
+    };
+
+    let main_constraint = if app.layout == LayoutMode::Dashboard { Constraint::Min(5) } else { Constraint::Min(10) };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Min(10),
-            Constraint::Length(3),
+            main_constraint,
+            Constraint::Length(events_height),
+            Constraint::Length(log_height),
+            Constraint::Length(if app.layout == LayoutMode::Dashboard { 0 } else { stats_height }),
             Constraint::Length(1),
         ])
         .split(f.area());
 
-    draw_chart(f, chunks[0], app);
-    
-    if app.gw_stats.is_some() {
-        let stats_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(chunks[1]);
-        
-        draw_host_stats(f, stats_chunks[0], &app.net_stats, "TARGET", app);
-        if let Some(gw) = &app.gw_stats {
+    let down_for = app.net_stats.down_for(app.recorded_duration);
+    let readiness = app.readiness_banner();
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(if down_for.is_some() { 1 } else { 0 }),
+            Constraint::Length(if readiness.is_some() { 1 } else { 0 }),
+            Constraint::Min(5),
+            Constraint::Length(if app.hops.is_empty() { 0 } else { app.hops.len() as u16 + 3 }),
+            Constraint::Length(if app.cycle.is_empty() { 0 } else { app.cycle.len().min(CYCLE_TABLE_ROWS) as u16 + 3 }),
+        ])
+        .split(chunks[0]);
+
+    if let Some(secs) = down_for {
+        draw_outage_banner(f, main_chunks[0], app, secs);
+    }
+
+    if let Some((verdict, text)) = readiness {
+        draw_readiness_banner(f, main_chunks[1], verdict, text);
+    }
+
+    if app.layout == LayoutMode::Dashboard {
+        draw_dashboard(f, main_chunks[2], app);
+    } else {
+        draw_chart(f, main_chunks[2], app);
+    }
+
+    if !app.hops.is_empty() {
+        draw_hops(f, main_chunks[3], app);
+    }
+
+    if !app.cycle.is_empty() {
+        draw_cycle(f, main_chunks[4], app);
+    }
+
+    if app.show_events {
+        draw_events(f, chunks[1], app);
+    }
+
+    if app.show_log {
+        draw_log(f, chunks[2], app);
+    }
+
+    if app.layout != LayoutMode::Dashboard {
+        let windowed = app.stats_window.then(|| {
+            (app.net_stats.window(app.view(), app.start_time), gw_stats.map(|gw| gw.window(app.view(), app.start_time)))
+        });
+        let (net_stats, gw_stats) = match &windowed {
+            Some((net, gw)) => (net, gw.as_ref()),
+            None => (&app.net_stats, gw_stats),
+        };
+        if let Some(gw) = gw_stats {
+            let direction = if app.layout == LayoutMode::Stacked { Direction::Vertical } else { Direction::Horizontal };
+            let stats_chunks = Layout::default()
+                .direction(direction)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(chunks[3]);
+
+            draw_host_stats(f, stats_chunks[0], net_stats, "TARGET", app);
             draw_host_stats(f, stats_chunks[1], gw, "GATEWAY", app);
+        } else {
+            draw_host_stats(f, chunks[3], net_stats, "TARGET", app);
         }
+    }
+
+    draw_footer(f, chunks[4], app);
+
+    if app.show_grade {
+        draw_grade_popup(f, app);
+    }
+}
+
+// [G]: why each host has its grade, over the same samples as the stats bar.
+fn draw_grade_popup(f: &mut Frame, app: &App) {
+    let palette = app.theme.palette();
+    let mut hosts = vec![("TARGET", &app.net_stats)];
+    if let Some(gw) = app.gw_stats.as_ref().filter(|_| app.show_gateway) {
+        hosts.push(("GATEWAY", gw));
+    }
+    let windowed: Vec<HostStats> = if app.stats_window {
+        hosts.iter().map(|(_, stats)| stats.window(app.view(), app.start_time)).collect()
     } else {
-        draw_host_stats(f, chunks[1], &app.net_stats, "TARGET", app);
+        Vec::new()
+    };
+    for (host, stats) in hosts.iter_mut().zip(&windowed) {
+        host.1 = stats;
     }
 
-    draw_footer(f, chunks[2], app);
+    let mut lines = Vec::new();
+    for (label, stats) in &hosts {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        let grade = if stats.warming_up() { "-" } else { stats.calculate_grade() };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{} {} ", label, stats.display_name), Style::default().fg(palette.text).add_modifier(Modifier::BOLD)),
+            Span::styled(grade, Style::default().fg(grade_color(grade)).add_modifier(Modifier::BOLD)),
+        ]));
+        lines.extend(stats.explain_grade().into_iter().map(|l| Line::from(format!("  {}", l))));
+    }
+
+    let area = f.area();
+    let width = area.width.saturating_sub(4).min(76);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height);
+
+    let title = if app.stats_window { " GRADE (window) [G] close " } else { " GRADE [G] close " };
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        popup,
+    );
+}
+
+pub fn grade_color(grade: &str) -> Color {
+    match grade {
+        "S" | "A" => Color::Green,
+        "B" => Color::Cyan,
+        "C" => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+// While a host is warming up, its percentiles are shown dimmed and the grade
+// cell only counts the samples.
+fn grade_cell(stats: &HostStats, muted: Color) -> Cell<'static> {
+    if stats.warming_up() {
+        return Cell::from(format!("n={}", stats.total_count)).style(Style::default().fg(muted));
+    }
+    let grade = stats.calculate_grade();
+    Cell::from(grade).style(Style::default().fg(grade_color(grade)).add_modifier(Modifier::BOLD))
+}
+
+fn percentile_cell(stats: &HostStats, value: f64, app: &App) -> Cell<'static> {
+    let cell = Cell::from(app.units.table.format(value, 0, stats.lan));
+    if stats.warming_up() { cell.style(Style::default().fg(app.theme.palette().muted)) } else { cell }
+}
+
+fn draw_dashboard(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+
+    let mut hosts = vec![("TARGET", &app.net_stats, palette.target)];
+    if let Some(gw) = app.gw_stats.as_ref().filter(|_| app.show_gateway) {
+        hosts.push(("GATEWAY", gw, palette.gateway));
+    }
+    if let Some(passive) = &app.passive_stats {
+        hosts.push(("PASSIVE", passive, palette.passive));
+    }
+
+    let header = Row::new(vec!["Role", "Host", "Last", "Jitter", "Loss", "P25", "P75", "P99", "P99.9", "Max", ">30ms", ">100ms", "Grade", "Recent"])
+        .style(Style::default().fg(palette.muted).add_modifier(Modifier::BOLD));
+
+    let rows = hosts.into_iter().map(|(label, stats, color)| {
+        Row::new(vec![
+            Cell::from(label).style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Cell::from(stats.display_name.clone()),
+            Cell::from(app.units.table.format(stats.last_latency, 1, stats.lan)),
+            Cell::from(app.units.table.format(stats.current_jitter, 1, stats.lan)),
+            Cell::from(locale::percent(stats.loss_percent(), 1)),
+            percentile_cell(stats, stats.p25, app),
+            percentile_cell(stats, stats.p75, app),
+            percentile_cell(stats, stats.p99, app),
+            percentile_cell(stats, stats.p999, app),
+            Cell::from(app.units.table.format(stats.max_latency, 0, stats.lan)),
+            Cell::from(stats.spikes_minor.to_string()),
+            Cell::from(stats.spikes_major.to_string()),
+            grade_cell(stats, palette.muted),
+            Cell::from(Line::from(sparkline_spans(stats, DASHBOARD_SPARK_WIDTH, color))),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Min(15),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(6),
+        Constraint::Length(DASHBOARD_SPARK_WIDTH as u16),
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(format!(" VASILI Dashboard ({}ms) - Time: {} ", app.configured_interval, runtime_string(app))))
+        .style(Style::default().fg(palette.text));
+
+    f.render_widget(table, area);
+}
+
+fn draw_hops(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+
+    let header = Row::new(vec!["Hop", "Host", "Loss", "Sent", "Last", "P25", "P75", "P99", "Recent"])
+        .style(Style::default().fg(palette.muted).add_modifier(Modifier::BOLD));
+
+    let rows = app.hops.iter().map(|(ttl, stats)| {
+        if stats.display_name == "*" {
+            return Row::new(vec![Cell::from(ttl.to_string()), Cell::from("* (no reply during discovery)").style(Style::default().fg(palette.muted))]);
+        }
+
+        let loss = stats.loss_percent();
+        let loss_color = if loss == 0.0 { Color::Green } else if loss < 5.0 { Color::Yellow } else { Color::Red };
+        Row::new(vec![
+            Cell::from(ttl.to_string()),
+            Cell::from(stats.display_name.clone()),
+            Cell::from(locale::percent(loss, 1)).style(Style::default().fg(loss_color).add_modifier(Modifier::BOLD)),
+            Cell::from(stats.total_count.to_string()),
+            Cell::from(app.units.table.format(stats.last_latency, 1, stats.lan)),
+            percentile_cell(stats, stats.p25, app),
+            percentile_cell(stats, stats.p75, app),
+            percentile_cell(stats, stats.p99, app),
+            Cell::from(Line::from(sparkline_spans(stats, DASHBOARD_SPARK_WIDTH, palette.target))),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Min(15),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(DASHBOARD_SPARK_WIDTH as u16),
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(format!(" Path to {} ({} hops) ", app.net_stats.display_name, app.hops.len())))
+        .style(Style::default().fg(palette.text));
+
+    f.render_widget(table, area);
+}
+
+fn draw_cycle(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+
+    let header = Row::new(vec!["Host", "Loss", "Sent", "Last", "P25", "P75", "P99", "Grade", "Recent"])
+        .style(Style::default().fg(palette.muted).add_modifier(Modifier::BOLD));
+
+    // The terminal may leave less than CYCLE_TABLE_ROWS.
+    let visible = (area.height as usize).saturating_sub(3).max(1);
+    let first = app.cycle_scroll.min(app.cycle.len().saturating_sub(visible));
+    let shown = app.cycle.len().min(visible);
+
+    let rows = app.cycle.iter().skip(first).take(visible).map(|stats| {
+        let loss = stats.loss_percent();
+        let loss_color = if loss == 0.0 { Color::Green } else if loss < 5.0 { Color::Yellow } else { Color::Red };
+        Row::new(vec![
+            Cell::from(stats.display_name.clone()),
+            Cell::from(locale::percent(loss, 1)).style(Style::default().fg(loss_color).add_modifier(Modifier::BOLD)),
+            Cell::from(stats.total_count.to_string()),
+            Cell::from(app.units.table.format(stats.last_latency, 1, stats.lan)),
+            percentile_cell(stats, stats.p25, app),
+            percentile_cell(stats, stats.p75, app),
+            percentile_cell(stats, stats.p99, app),
+            grade_cell(stats, palette.muted),
+            Cell::from(Line::from(sparkline_spans(stats, DASHBOARD_SPARK_WIDTH, palette.target))),
+        ])
+    });
+
+    let widths = [
+        Constraint::Min(15),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Length(DASHBOARD_SPARK_WIDTH as u16),
+    ];
+
+    let every = app.cycle.first().map(|s| format_duration(std::time::Duration::from_millis(s.configured_ms as u64))).unwrap_or_default();
+    let range = if shown < app.cycle.len() {
+        format!(", {}-{} [PgUp/PgDn]", first + 1, first + shown)
+    } else {
+        String::new()
+    };
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(format!(" Round Robin ({} targets, each every {}{}) ", app.cycle.len(), every, range)))
+        .style(Style::default().fg(palette.text));
+
+    f.render_widget(table, area);
+}
+
+const OUTAGE_BURST: u64 = 10;
+const MAX_BURST_HEIGHT: u64 = 20;
+
+// Groups consecutive losses into bursts. Single losses become small dots at the top edge,
+// longer bursts columns that grow downwards with the burst length, so a blip and a real
+// outage no longer look the same. Returns (blips, bursts, outages).
+fn loss_markers(loss_points: &[(f64, f64)], view: (f64, f64), y_limit: f64) -> [Vec<(f64, f64)>; 3] {
+    let mut tiers: [Vec<(f64, f64)>; 3] = Default::default();
+    let step = y_limit / (MAX_BURST_HEIGHT * 2) as f64;
+
+    let mut start = 0;
+    while start < loss_points.len() {
+        let mut end = start + 1;
+        while end < loss_points.len() && loss_points[end].1 > 1.0 {
+            end += 1;
+        }
+
+        // The burst count of its last loss, as the window may cut off its start.
+        let len = loss_points[end - 1].1 as u64;
+        let tier = if len == 1 { 0 } else if len < OUTAGE_BURST { 1 } else { 2 };
+        for &(t, _) in loss_points[start..end].iter().filter(|(t, _)| *t >= view.0 && *t <= view.1) {
+            if tier == 0 {
+                tiers[0].push((t, y_limit));
+            } else {
+                tiers[tier].extend((0..len.min(MAX_BURST_HEIGHT)).map(|i| (t, y_limit - i as f64 * step)));
+            }
+        }
+        start = end;
+    }
+    tiers
+}
+
+fn push_loss_datasets<'a>(datasets: &mut Vec<Dataset<'a>>, label: &str, loss_count: u64, color: Color, outage: Color, [blips, bursts, outages]: [&'a [(f64, f64)]; 3]) {
+    datasets.push(Dataset::default()
+        .name(format!("{} Loss ({})", label, loss_count))
+        .marker(symbols::Marker::Block)
+        .style(Style::default().fg(color))
+        .graph_type(GraphType::Scatter)
+        .data(bursts));
+    datasets.push(Dataset::default()
+        .marker(symbols::Marker::Dot)
+        .style(Style::default().fg(color))
+        .graph_type(GraphType::Scatter)
+        .data(blips));
+    if !outages.is_empty() {
+        datasets.push(Dataset::default()
+            .name(format!("{} Outage (>={} lost)", label, OUTAGE_BURST))
+            .marker(symbols::Marker::Block)
+            .style(Style::default().fg(outage))
+            .graph_type(GraphType::Scatter)
+            .data(outages));
+    }
+}
+
+// Losses the network answered with an error instead of silence, one row of
+// markers per kind at the top edge, drawn over the timeout markers.
+fn error_markers(stats: &HostStats, view: (f64, f64), y_limit: f64) -> Vec<(ProbeError, Vec<(f64, f64)>)> {
+    let mut kinds: Vec<(ProbeError, Vec<(f64, f64)>)> = Vec::new();
+    for &(t, error) in stats.error_points.iter().filter(|(t, _)| *t >= view.0 && *t <= view.1) {
+        match kinds.iter_mut().find(|(kind, _)| *kind == error) {
+            Some((_, points)) => points.push((t, y_limit)),
+            None => kinds.push((error, vec![(t, y_limit)])),
+        }
+    }
+    kinds
+}
+
+fn error_color(error: ProbeError) -> Color {
+    match error {
+        ProbeError::Timeout => Color::Red,
+        ProbeError::Unreachable => Color::White,
+        ProbeError::TtlExceeded => Color::LightMagenta,
+        ProbeError::SendError => Color::DarkGray,
+    }
+}
+
+fn push_error_datasets<'a>(datasets: &mut Vec<Dataset<'a>>, label: &str, markers: &'a [(ProbeError, Vec<(f64, f64)>)]) {
+    for (error, points) in markers {
+        datasets.push(Dataset::default()
+            .name(format!("{} {}", label, error.label()))
+            .marker(symbols::Marker::Block)
+            .style(Style::default().fg(error_color(*error)))
+            .graph_type(GraphType::Scatter)
+            .data(points));
+    }
+}
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const DASHBOARD_SPARK_WIDTH: usize = 30;
+
+fn sparkline_spans(stats: &HostStats, width: usize, color: Color) -> Vec<Span<'static>> {
+    let samples: Vec<Option<f64>> = stats.recent.iter().rev().take(width).rev().copied().collect();
+    let max = samples.iter().flatten().fold(1.0_f64, |m, &v| m.max(v));
+
+    samples.iter().map(|sample| match sample {
+        Some(v) => {
+            let idx = ((v / max) * (SPARK_CHARS.len() - 1) as f64).round() as usize;
+            Span::styled(SPARK_CHARS[idx.min(SPARK_CHARS.len() - 1)].to_string(), Style::default().fg(color))
+        }
+        None => Span::styled("×", Style::default().fg(Color::Red)),
+    }).collect()
+}
+
+fn clock_string(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs/3600, (secs/60)%60, secs%60)
+    } else {
+        format!("{:02}:{:02}", secs/60, secs%60)
+    }
+}
+
+// The gateway state tells at a glance whether the outage is local or beyond
+// the router.
+fn draw_outage_banner(f: &mut Frame, area: Rect, app: &App, secs: f64) {
+    let gateway = match &app.gw_stats {
+        Some(gw) if gw.down_for(app.recorded_duration).is_some() => ", gateway unreachable too",
+        Some(gw) if gw.total_count > 0 => ", gateway still answers",
+        _ => "",
+    };
+    let text = format!(" TARGET UNREACHABLE for {} ({}{}) ", clock_string(secs as u64), app.net_stats.display_name, gateway);
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .style(Style::default().bg(app.theme.palette().outage).fg(Color::Black).add_modifier(Modifier::BOLD)),
+        area,
+    );
+}
+
+fn draw_readiness_banner(f: &mut Frame, area: Rect, verdict: readiness::Verdict, text: String) {
+    let color = match verdict {
+        readiness::Verdict::Ready => Color::Green,
+        readiness::Verdict::AtRisk => Color::Yellow,
+        readiness::Verdict::NotReady => Color::Red,
+    };
+    f.render_widget(
+        Paragraph::new(format!(" {} ", text))
+            .alignment(Alignment::Center)
+            .style(Style::default().bg(color).fg(Color::Black).add_modifier(Modifier::BOLD)),
+        area,
+    );
+}
+
+fn runtime_string(app: &App) -> String {
+    let limit_str = app.max_duration
+        .map(|max| format!("/{}", clock_string(max.as_secs())))
+        .unwrap_or_default();
+
+    let count_str = app.max_probes
+        .map(|max| format!(" ({}/{} probes)", app.net_stats.total_count, max))
+        .unwrap_or_default();
+
+    format!("{}{}{}", clock_string(app.recorded_duration as u64), limit_str, count_str)
 }
 
 fn draw_chart(f: &mut Frame, area: Rect, app: &App) {
-    let current_time_seconds = app.recorded_duration;
-    let view_end_sec = (current_time_seconds - app.scroll_offset_seconds).max(0.0);
-    let view_start_sec = (view_end_sec - app.zoom_window_seconds).max(0.0);
+    let (view_start_sec, view_end_sec) = app.view();
     
     let view_start_time_abs = app.start_time + Duration::milliseconds((view_start_sec * 1000.0) as i64);
     let view_end_time_abs = app.start_time + Duration::milliseconds((view_end_sec * 1000.0) as i64);
 
-    let status_text = if app.is_finished { "[FINISHED]" } else if app.is_paused { "[PAUSED]" } else { "[LIVE]" };
-    let title_prefix = format!(" VASILI ({}ms) - Target: {} -", app.configured_interval, app.net_stats.display_name);
+    let status_text = if app.is_finished { "[FINISHED]" } else if app.is_paused { "[PAUSED]" } else if app.replay_speed.is_some() { "[REPLAY]" } else { "[LIVE]" };
+    let live_label = app.replay_speed.map(|speed| format!("REPLAY {}x", speed)).unwrap_or_else(|| "LIVE".to_string());
+    let note = app.net_stats.note.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+    let title_prefix = format!(" VASILI ({}ms) - Target: {}{} -", app.configured_interval, app.net_stats.display_name, note);
 
     let (title, title_color) = if app.scroll_offset_seconds > 0.0 {
         (format!("{} HISTORY (-{:.0}s) {} [ {} - {} ] ", 
             title_prefix, app.scroll_offset_seconds, status_text, 
-            view_start_time_abs.format("%H:%M:%S"), view_end_time_abs.format("%H:%M:%S")), Color::Yellow)
+            locale::time(&view_start_time_abs), locale::time(&view_end_time_abs)), Color::Yellow)
     } else if app.is_paused || app.is_finished {
         (format!("{} {} [ {} - {} ] ", 
             title_prefix, status_text, 
-            view_start_time_abs.format("%H:%M:%S"), view_end_time_abs.format("%H:%M:%S")), Color::Magenta)
+            locale::time(&view_start_time_abs), locale::time(&view_end_time_abs)), Color::Magenta)
     } else {
-        (format!("{} LIVE [ {} - {} ] ", 
-            title_prefix, 
-            view_start_time_abs.format("%H:%M:%S"), view_end_time_abs.format("%H:%M:%S")), Color::Green)
+        (format!("{} {} [ {} - {} ] ", 
+            title_prefix, live_label, 
+            locale::time(&view_start_time_abs), locale::time(&view_end_time_abs)), Color::Green)
     };
 
     let max_ping = app.net_stats.points.iter()
         .map(|(_, v)| *v)
         .fold(0.0, f64::max);
     
-    let max_gw = if let Some(gw) = &app.gw_stats {
+    let palette = app.theme.palette();
+    let gw_stats = app.gw_stats.as_ref().filter(|_| app.show_gateway);
+
+    let max_gw = if let Some(gw) = gw_stats {
         gw.points.iter().map(|(_, v)| *v).fold(0.0, f64::max)
     } else { 0.0 };
 
-    let global_max = max_ping.max(max_gw);
+    let max_passive = app.passive_stats.as_ref()
+        .map(|passive| passive.points.iter().map(|(_, v)| *v).fold(0.0, f64::max))
+        .unwrap_or(0.0);
+
+    // The earlier session's line goes first, under everything live.
+    let overlay_points = app.baseline_overlay.as_ref()
+        .map(|overlay| overlay.points(app.start_time, (view_start_sec, view_end_sec)))
+        .unwrap_or_default();
+    let max_overlay = overlay_points.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+
+    let global_max = max_ping.max(max_gw).max(max_passive).max(max_overlay);
     let y_limit = if global_max > 90.0 { global_max * 1.1 } else { 100.0 };
 
     let mut datasets = Vec::new();
 
-    let net_ping_legend = format!("TARGET Ping ({:.1}ms)", app.net_stats.last_latency);
+    if let Some(overlay) = app.baseline_overlay.as_ref().filter(|_| !overlay_points.is_empty()) {
+        datasets.push(Dataset::default()
+            .name(format!("USUAL ({})", overlay.source))
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(palette.muted).add_modifier(Modifier::DIM))
+            .graph_type(GraphType::Line)
+            .data(&overlay_points));
+    }
+
+    // Only the visible part, which may come from disk when scrolled far back,
+    // thinned to what the canvas can show: two Braille dots per column.
+    let view = (view_start_sec, view_end_sec);
+    let budget = area.width.saturating_sub(2) as usize * 2;
+    let net_points = app.net_stats.points.range(view);
+    let net_points = history::downsample(&net_points, budget);
+    let net_jitter = app.net_stats.jitter_points.range(view);
+    let net_jitter = history::downsample(&net_jitter, budget);
+    let net_ping_legend = format!("TARGET Ping ({})", app.units.chart.format(app.net_stats.last_latency, 1, app.net_stats.lan));
     datasets.push(Dataset::default()
         .name(net_ping_legend)
         .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Green))
+        .style(Style::default().fg(palette.target))
         .graph_type(GraphType::Line)
-        .data(&app.net_stats.points));
+        .data(&net_points));
 
-    let net_jitter_legend = format!("TARGET Jitter ({:.1}ms)", app.net_stats.current_jitter);
-    datasets.push(Dataset::default()
-        .name(net_jitter_legend)
-        .marker(symbols::Marker::Braille)
-        .style(Style::default().fg(Color::Yellow))
-        .graph_type(GraphType::Line)
-        .data(&app.net_stats.jitter_points));
+    if app.show_jitter {
+        let net_jitter_legend = format!("TARGET Jitter ({})", app.units.chart.format(app.net_stats.current_jitter, 1, app.net_stats.lan));
+        datasets.push(Dataset::default()
+            .name(net_jitter_legend)
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(palette.target_jitter))
+            .graph_type(GraphType::Line)
+            .data(&net_jitter));
+    }
 
-    let net_loss_data: Vec<(f64, f64)> = app.net_stats.loss_points.iter()
-        .map(|(t, _)| (*t, y_limit))
-        .collect();
+    let [net_blips, net_bursts, net_outages] = loss_markers(&app.net_stats.loss_points.range(view), view, y_limit);
+    let net_errors = error_markers(&app.net_stats, view, y_limit);
 
-    let net_loss_legend = format!("TARGET Loss ({})", app.net_stats.loss_count);
-    datasets.push(Dataset::default()
-        .name(net_loss_legend)
-        .marker(symbols::Marker::Block)
-        .style(Style::default().fg(Color::Red))
-        .graph_type(GraphType::Scatter)
-        .data(&net_loss_data));
+    if app.show_loss {
+        push_loss_datasets(&mut datasets, "TARGET", app.net_stats.loss_count, palette.target_loss, palette.outage, [&net_blips, &net_bursts, &net_outages]);
+        push_error_datasets(&mut datasets, "TARGET", &net_errors);
+    }
 
-    let mut gw_loss_data = Vec::new();
+    let event_line = |kind: EventKind| -> Vec<(f64, f64)> {
+        app.events.iter()
+            .filter(|e| e.kind == kind && e.time_val >= view_start_sec && e.time_val <= view_end_sec)
+            .flat_map(|e| (0..=20).map(move |i| (e.time_val, y_limit * i as f64 / 20.0)))
+            .collect()
+    };
+    let dhcp_data = event_line(EventKind::DhcpRenewal);
+    let marker_data = event_line(EventKind::Marker);
 
-    if let Some(gw) = &app.gw_stats {
-        let gw_ping_legend = format!("GATEWAY Ping ({:.1}ms)", gw.last_latency);
+    for (name, kind, data) in [("DHCP Renewal", EventKind::DhcpRenewal, &dhcp_data), ("Marker", EventKind::Marker, &marker_data)] {
+        if !data.is_empty() {
+            datasets.push(Dataset::default()
+                .name(name)
+                .marker(symbols::Marker::Dot)
+                .style(Style::default().fg(kind.color()))
+                .graph_type(GraphType::Scatter)
+                .data(data));
+        }
+    }
+
+    let [gw_blips, gw_bursts, gw_outages] = gw_stats.map(|gw| loss_markers(&gw.loss_points.range(view), view, y_limit)).unwrap_or_default();
+    let gw_points = gw_stats.map(|gw| gw.points.range(view)).unwrap_or_default();
+    let gw_points = history::downsample(&gw_points, budget);
+    let gw_jitter = gw_stats.map(|gw| gw.jitter_points.range(view)).unwrap_or_default();
+    let gw_jitter = history::downsample(&gw_jitter, budget);
+    let passive_points = app.passive_stats.as_ref().map(|passive| passive.points.range(view)).unwrap_or_default();
+    let passive_points = history::downsample(&passive_points, budget);
+    let gw_errors = gw_stats.map(|gw| error_markers(gw, view, y_limit)).unwrap_or_default();
+
+    if let Some(gw) = gw_stats {
+        let gw_ping_legend = format!("GATEWAY Ping ({})", app.units.chart.format(gw.last_latency, 1, gw.lan));
         datasets.push(Dataset::default()
             .name(gw_ping_legend)
             .marker(symbols::Marker::Braille)
-            .style(Style::default().fg(Color::Blue))
+            .style(Style::default().fg(palette.gateway))
             .graph_type(GraphType::Line)
-            .data(&gw.points));
+            .data(&gw_points));
+
+        if app.show_jitter {
+            let gw_jitter_legend = format!("GATEWAY Jitter ({})", app.units.chart.format(gw.current_jitter, 1, gw.lan));
+            datasets.push(Dataset::default()
+                .name(gw_jitter_legend)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(palette.gateway_jitter))
+                .graph_type(GraphType::Line)
+                .data(&gw_jitter));
+        }
+
+        if app.show_loss {
+            push_loss_datasets(&mut datasets, "GATEWAY", gw.loss_count, palette.gateway_loss, palette.outage, [&gw_blips, &gw_bursts, &gw_outages]);
+            push_error_datasets(&mut datasets, "GATEWAY", &gw_errors);
+        }
+    }
 
-        let gw_jitter_legend = format!("GATEWAY Jitter ({:.1}ms)", gw.current_jitter);
+    if let Some(passive) = &app.passive_stats {
         datasets.push(Dataset::default()
-            .name(gw_jitter_legend)
+            .name(format!("PASSIVE TCP RTT ({})", app.units.chart.format(passive.last_latency, 1, passive.lan)))
             .marker(symbols::Marker::Braille)
-            .style(Style::default().fg(Color::LightCyan))
+            .style(Style::default().fg(palette.passive))
             .graph_type(GraphType::Line)
-            .data(&gw.jitter_points));
-            
-        gw_loss_data.extend(
-            gw.loss_points.iter().map(|(t, _)| (*t, y_limit))
-        );
-
-        let gw_loss_legend = format!("GATEWAY Loss ({})", gw.loss_count);
-        datasets.push(Dataset::default()
-            .name(gw_loss_legend)
-            .marker(symbols::Marker::Block)
-            .style(Style::default().fg(Color::Magenta))
-            .graph_type(GraphType::Scatter)
-            .data(&gw_loss_data));
+            .data(&passive_points));
     }
 
+    // The axis reads in µs only if every series shown would.
+    let (axis_title, [axis_middle, axis_top]) = app.units.chart.axis(y_limit, app.net_stats.lan);
     let chart = Chart::new(datasets)
         .block(Block::default()
             .title(Span::styled(title, Style::default().fg(title_color).add_modifier(Modifier::BOLD)))
-            .title_bottom(Line::from(format!(" Seconds (Zoom: {:.0}s) ", app.zoom_window_seconds)).alignment(Alignment::Center).style(Style::default().fg(Color::Gray)))
+            .title_bottom(Line::from(format!(" Seconds (Zoom: {:.0}s) ", app.zoom_window_seconds)).alignment(Alignment::Center).style(Style::default().fg(palette.muted)))
             .borders(Borders::ALL))
         .legend_position(Some(LegendPosition::TopRight))
         .x_axis(Axis::default()
-            .style(Style::default().fg(Color::Gray))
+            .style(Style::default().fg(palette.muted))
             .bounds([view_start_sec, view_end_sec]))
         .y_axis(Axis::default()
-            .title("ms")
-            .style(Style::default().fg(Color::Gray))
+            .title(axis_title)
+            .style(Style::default().fg(palette.muted))
             .bounds([0.0, y_limit])
             .labels(vec![
                 Span::styled("0", Style::default()),
-                Span::styled(format!("{:.0}", y_limit / 2.0), Style::default()),
-                Span::styled(format!("{:.0}", y_limit), Style::default().fg(Color::Red))
+                Span::styled(axis_middle, Style::default()),
+                Span::styled(axis_top, Style::default().fg(Color::Red))
             ]));
 
     f.render_widget(chart, area);
 }
 
 fn draw_host_stats(f: &mut Frame, area: Rect, stats: &HostStats, label: &str, app: &App) {
-    let loss_percent = if stats.total_count > 0 {
-        (stats.loss_count as f64 / stats.total_count as f64) * 100.0
-    } else {
-        0.0
-    };
+    let palette = app.theme.palette();
+    let loss_percent = stats.loss_percent();
 
     let (p25, p75, p99) = (stats.p25, stats.p75, stats.p99);
     
     let is_gateway = label == "GATEWAY";
-    let grade = stats.calculate_grade(is_gateway);
+    let (grade, grade_style) = if stats.warming_up() {
+        (stats.grade_label(), Style::default().fg(palette.muted))
+    } else {
+        let grade = stats.calculate_grade();
+        (grade.to_string(), Style::default().fg(grade_color(grade)).add_modifier(Modifier::BOLD))
+    };
+    // Early percentiles are rough, the ~ says so.
+    let approx = if stats.warming_up() { "~" } else { "" };
+    let runtime_str = runtime_string(app);
+    let units = app.units.stats;
+    let latency = |ms: f64| units.format(ms, 0, stats.lan);
 
-    let grade_color = match grade {
-        "S" | "A" => Color::Green,
-        "B" => Color::Cyan,
-        "C" => Color::Yellow,
-        _ => Color::Red,
+    let mut fields = vec![
+        StatField { priority: 1, spans: vec![
+            Span::raw("Loss: "),
+            Span::styled(locale::percent(loss_percent, 1), Style::default().fg(if stats.loss_count == 0 { Color::Green } else { Color::Red }).add_modifier(Modifier::BOLD)),
+        ]},
+        StatField { priority: 2, spans: vec![
+            Span::raw("P(25/75/99): "),
+            Span::styled(format!("{}{}", approx, units.join(&[p25, p75, p99], 0, stats.lan)), Style::default().fg(Color::Cyan)),
+        ]},
+        StatField { priority: 2, spans: vec![
+            Span::raw("Max: "),
+            Span::styled(latency(stats.max_latency), Style::default().fg(Color::Red)),
+            Span::styled(stats.max_at.map(|t| format!(" @{}", locale::time(&t))).unwrap_or_default(), Style::default().fg(palette.muted)),
+        ]},
+        StatField { priority: 3, spans: vec![
+            Span::raw("Min/Mean: "),
+            Span::styled(units.join(&[stats.min_latency, stats.mean_latency], 0, stats.lan), Style::default().fg(Color::Cyan)),
+            Span::styled(format!(" ±{}", latency(stats.stddev())), Style::default().fg(palette.muted)),
+        ]},
+        StatField { priority: 3, spans: vec![
+            Span::raw("P99.9: "),
+            Span::styled(latency(stats.p999), Style::default().fg(Color::Magenta)),
+            Span::styled(stats.p999_at.map(|t| format!(" (last @{})", locale::time(&t))).unwrap_or_default(), Style::default().fg(palette.muted)),
+        ]},
+        StatField { priority: 4, spans: vec![
+            Span::raw("Spikes >30ms: "),
+            Span::styled(format!("{}", stats.spikes_minor), Style::default().fg(if stats.spikes_minor == 0 { Color::Green } else { Color::Yellow })),
+        ]},
+        StatField { priority: 4, spans: vec![
+            Span::raw("Rate: "),
+            match stats.achieved_interval_ms() {
+                Some(achieved) => Span::styled(format!("{:.0}ms", achieved), Style::default().fg(if achieved <= stats.configured_ms * 1.05 { Color::Green } else { Color::Yellow })),
+                None => Span::styled("-", Style::default().fg(palette.muted)),
+            },
+            Span::styled(format!(" (set {:.0}ms)", stats.configured_ms), Style::default().fg(palette.muted)),
+        ]},
+        StatField { priority: 4, spans: vec![
+            Span::raw("Late: "),
+            Span::styled(format!("{}", stats.deadline_misses), Style::default().fg(if stats.deadline_misses == 0 { Color::Green } else { Color::Yellow })),
+        ]},
+        StatField { priority: 4, spans: vec![
+            Span::raw("Anomalies: "),
+            Span::styled(format!("{}", stats.anomalies), Style::default().fg(if stats.anomalies == 0 { Color::Green } else { Color::Yellow })),
+            Span::styled(
+                stats.anomaly.band().map(|(usual, upper)| format!(" (usual ~{}, up to {})", latency(usual), latency(upper))).unwrap_or_default(),
+                Style::default().fg(palette.muted),
+            ),
+        ]},
+        StatField { priority: 3, spans: vec![
+            Span::raw(">100ms: "),
+            Span::styled(format!("{}", stats.spikes_major), Style::default().fg(if stats.spikes_major == 0 { Color::Green } else { Color::Red })),
+        ]},
+        StatField { priority: 0, spans: vec![
+            Span::raw("Grade: "),
+            Span::styled(grade, grade_style),
+        ]},
+    ];
+
+    if let (true, Some(estimate)) = (label == "TARGET", &app.geo) {
+        let overhead = (stats.p25 - estimate.min_rtt_ms).max(0.0);
+        fields.push(StatField { priority: 5, spans: vec![
+            Span::raw("Physics: "),
+            Span::styled(format!("≥{} ", latency(estimate.min_rtt_ms)), Style::default().fg(Color::Cyan)),
+            Span::styled(format!("(+{} route, {:.0}km)", latency(overhead), estimate.distance_km), Style::default().fg(palette.muted)),
+        ]});
+    }
+
+    if let Some(expected) = stats.expected_ms {
+        let deviation = stats.p50 - expected;
+        fields.push(StatField { priority: 1, spans: vec![
+            Span::raw("Expected: "),
+            Span::styled(format!("~{} ", latency(expected)), Style::default().fg(Color::Cyan)),
+            Span::styled(format!("({}{} median)", if deviation >= 0.0 { "+" } else { "" }, units.format(deviation, 1, stats.lan)), Style::default().fg(palette.muted)),
+        ]});
+    }
+
+    if let (true, Some(comparison)) = (label == "TARGET", app.baseline_comparison()) {
+        let color = match comparison.overall() { Verdict::Better => Color::Green, Verdict::Typical => Color::Cyan, Verdict::Worse => Color::Yellow };
+        fields.push(StatField { priority: 5, spans: vec![
+            Span::raw(format!("vs {}: ", comparison.access.label())),
+            Span::styled(comparison.short(), Style::default().fg(color)),
+        ]});
+    }
+
+    let block = Block::default().borders(Borders::ALL);
+    let inner = block.inner(area);
+    let spark_width = (inner.width as usize / 4).min(RECENT_SAMPLES);
+    let spark_width = if spark_width >= 8 { spark_width } else { 0 };
+    let inner_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(spark_width as u16)])
+        .split(inner);
+
+    let pages = paginate_fields(&fields, inner_chunks[0].width.saturating_sub(1) as usize);
+    let page = app.stats_page % pages.len();
+
+    let mut spans = vec![Span::raw(" ")];
+    for (i, &idx) in pages[page].iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
+        }
+        spans.extend(fields[idx].spans.iter().cloned());
+    }
+
+    let name = match &stats.note {
+        Some(note) => format!("{}: {}", label, note),
+        None => label.to_string(),
+    };
+    let mut title = if app.stats_window {
+        let (start, end) = app.view();
+        let at = |t: f64| locale::time(&(app.start_time + Duration::milliseconds((t * 1000.0) as i64)));
+        format!(" Stats ({}) - Window: {}-{} ", name, at(start), at(end))
+    } else if label == "TARGET" {
+        format!(" Stats ({}) - Time: {} ", name, runtime_str)
+    } else {
+        format!(" Stats ({}) ", name)
     };
+    if pages.len() > 1 {
+        title.push_str(&format!("[{}/{} S] ", page + 1, pages.len()));
+    }
 
-    let limit_str = app.max_duration
-        .map(|max| format!("/{:02}:{:02}", max.as_secs()/60, max.as_secs()%60))
-        .unwrap_or_default();
-    
-    let runtime_str = format!("{:02}:{:02}{}", (app.recorded_duration as u64)/60, (app.recorded_duration as u64)%60, limit_str);
-
-    let spans = vec![
-        Span::raw(" Loss: "),
-        Span::styled(format!("{:.1}% ", loss_percent), Style::default().fg(if stats.loss_count == 0 { Color::Green } else { Color::Red }).add_modifier(Modifier::BOLD)),
-        Span::raw("| P(25/75/99): "),
-        Span::styled(format!("{:.0}/{:.0}/{:.0}ms ", p25, p75, p99), Style::default().fg(Color::Cyan)),
-        Span::raw("| Spikes >30ms: "),
-        Span::styled(format!("{} ", stats.spikes_minor), Style::default().fg(if stats.spikes_minor == 0 { Color::Green } else { Color::Yellow })),
-        Span::raw("| >100ms: "),
-        Span::styled(format!("{} ", stats.spikes_major), Style::default().fg(if stats.spikes_major == 0 { Color::Green } else { Color::Red })),
-        Span::raw("| Grade: "),
-        Span::styled(grade, Style::default().fg(grade_color).add_modifier(Modifier::BOLD)),
+    f.render_widget(block.title(title).style(Style::default().fg(palette.text)), area);
+    f.render_widget(Paragraph::new(Line::from(spans)).style(Style::default().fg(palette.text)), inner_chunks[0]);
+
+    if spark_width > 0 {
+        let color = if is_gateway { palette.gateway } else { palette.target };
+        let spark = Paragraph::new(Line::from(sparkline_spans(stats, spark_width, color))).alignment(Alignment::Right);
+        f.render_widget(spark, inner_chunks[1]);
+    }
+}
+
+struct StatField<'a> {
+    priority: u8,
+    spans: Vec<Span<'a>>,
+}
+
+fn paginate_fields(fields: &[StatField], width: usize) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..fields.len()).collect();
+    order.sort_by_key(|&i| fields[i].priority);
+
+    let mut pages: Vec<Vec<usize>> = Vec::new();
+    let mut used = 0;
+    for idx in order {
+        let field_width: usize = fields[idx].spans.iter().map(|s| s.width()).sum();
+        let needed = if used == 0 { field_width } else { used + 3 + field_width };
+
+        match pages.last_mut() {
+            Some(page) if needed <= width || page.is_empty() => {
+                page.push(idx);
+                used = needed;
+            }
+            _ => {
+                pages.push(vec![idx]);
+                used = field_width;
+            }
+        }
+    }
+
+    for page in &mut pages {
+        page.sort_unstable();
+    }
+    if pages.is_empty() {
+        pages.push(Vec::new());
+    }
+    pages
+}
+
+fn session_host_lines(label: &str, stats: &HostStats, color: Color, app: &App) -> Vec<Line<'static>> {
+    let palette = app.theme.palette();
+    let muted = Style::default().fg(palette.muted);
+    let at = |secs: f64| app.start_time + Duration::milliseconds((secs * 1000.0) as i64);
+    let latency = |ms: f64, decimals: usize| app.units.stats.format(ms, decimals, stats.lan);
+
+    let grade_style = if stats.warming_up() {
+        muted
+    } else {
+        Style::default().fg(grade_color(stats.calculate_grade())).add_modifier(Modifier::BOLD)
+    };
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(format!("{} ", label), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{}  ", stats.display_name)),
+            Span::styled("Grade ", muted),
+            Span::styled(stats.grade_label(), grade_style),
+        ]),
+        Line::from(vec![
+            Span::styled("  Samples       ", muted),
+            Span::raw(format!("{} sent, {} lost ({})", stats.total_count, stats.loss_count, locale::percent(stats.loss_percent(), 2))),
+        ]),
+        Line::from(vec![
+            Span::styled("  Latency       ", muted),
+            Span::raw(format!("P25 {} / P75 {} / P99 {} / P99.9 {} / Max {}", latency(stats.p25, 1), latency(stats.p75, 1), latency(stats.p99, 1), latency(stats.p999, 1), latency(stats.max_latency, 1))),
+            Span::styled(stats.max_at.map(|t| format!(" @{}", locale::time(&t))).unwrap_or_default(), muted),
+        ]),
+        Line::from(vec![
+            Span::styled("  Distribution  ", muted),
+            Span::raw(format!("Min {} / Mean {} / Std dev {}", latency(stats.min_latency, 1), latency(stats.mean_latency, 1), latency(stats.stddev(), 1))),
+        ]),
+        Line::from(vec![
+            Span::styled("  Spikes        ", muted),
+            Span::raw(format!("{} >30ms, {} >100ms, {} anomalies", stats.spikes_minor, stats.spikes_major, stats.anomalies)),
+        ]),
+    ];
+    if let Some((start, lost, avg, max)) = stats.worst_minute() {
+        lines.push(Line::from(vec![
+            Span::styled("  Worst minute  ", muted),
+            Span::raw(format!("{} to {}: {} lost, avg {}, max {}", locale::time(&at(start)), locale::time(&at(start + 60.0)), lost, latency(avg, 0), latency(max, 0))),
+        ]));
+    }
+    lines
+}
+
+// Shown after quitting, until a key is pressed (skipped with --no-summary-screen).
+pub fn draw_session_end(f: &mut Frame, app: &App) {
+    let palette = app.theme.palette();
+    let muted = Style::default().fg(palette.muted);
+
+    let ending = app.ending();
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("Session {} after {} (started {})", ending, format_duration(std::time::Duration::from_secs(app.recorded_duration as u64)), locale::datetime(&app.start_time)),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
     ];
+    lines.extend(session_host_lines("TARGET", &app.net_stats, palette.target, app));
+    if let Some(gw) = &app.gw_stats {
+        lines.push(Line::from(""));
+        lines.extend(session_host_lines("GATEWAY", gw, palette.gateway, app));
+    }
+    if let Some(comparison) = app.baseline_comparison() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(vec![Span::styled("Baseline  ", muted), Span::raw(comparison.details())]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Press any key to exit", muted)));
+
+    let area = f.area();
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(height), Constraint::Min(0)])
+        .split(area);
+
+    let p = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(format!(" VASILI - {} ", app.net_stats.display_name)))
+        .style(Style::default().fg(palette.text));
+    f.render_widget(p, vertical[1]);
+}
+
+// The message with the words of the search picked out.
+fn highlighted(message: &str, app: &App) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for (start, end) in app.search.highlights(message) {
+        spans.push(Span::raw(message[last..start].to_string()));
+        spans.push(Span::styled(message[start..end].to_string(), Style::default().fg(Color::Black).bg(Color::Yellow)));
+        last = end;
+    }
+    spans.push(Span::raw(message[last..].to_string()));
+    spans
+}
+
+fn draw_events(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+    let visible = area.height.saturating_sub(2) as usize;
+
+    // With a search, only the matches are listed, newest first like the
+    // rest, scrolled so the selected one stays in view.
+    let searching = app.search.is_active();
+    let shown: Vec<usize> = if searching {
+        app.search.matches(&app.events).into_iter().rev().collect()
+    } else {
+        (0..app.events.len()).rev().collect()
+    };
+    let skip = app.search.selected
+        .and_then(|selected| shown.iter().position(|i| *i == selected))
+        .map_or(0, |position| (position + 1).saturating_sub(visible));
 
-    let title = if label == "TARGET" {
-        format!(" Stats ({}) - Time: {} ", label, runtime_str)
+    let lines: Vec<Line> = if shown.is_empty() {
+        let text = if searching { " No matching events." } else { " No events recorded yet." };
+        vec![Line::from(Span::styled(text, Style::default().fg(palette.muted)))]
     } else {
-        format!(" Stats ({}) ", label)
+        shown.iter().skip(skip).take(visible)
+            .map(|&i| {
+                let event = &app.events[i];
+                let mut spans = vec![
+                    Span::styled(format!(" {} ", event.timestamp), Style::default().fg(palette.muted)),
+                    Span::styled(format!("[{}] ", event.kind.label()), Style::default().fg(event.kind.color()).add_modifier(Modifier::BOLD)),
+                ];
+                spans.extend(highlighted(&event.message, app));
+                let line = Line::from(spans);
+                if app.search.selected == Some(i) { line.add_modifier(Modifier::REVERSED) } else { line }
+            })
+            .collect()
     };
 
-    let p = Paragraph::new(Line::from(spans))
+    let title = match (&app.search, searching) {
+        (search, _) if search.editing => format!(" Events - find: {}_ ", search.query),
+        (search, true) => format!(" Events ({} of {} matching \"{}\", [N/Shift+N] Next/Previous, [Esc] Clear) ", shown.len(), app.events.len(), search.query.trim()),
+        _ => format!(" Events ({}) ", app.events.len()),
+    };
+    let p = Paragraph::new(lines)
         .block(Block::default().borders(Borders::ALL).title(title))
-        .style(Style::default().fg(Color::White));
-    
+        .style(Style::default().fg(palette.text));
+
     f.render_widget(p, area);
 }
 
-fn draw_footer(f: &mut Frame, area: Rect, _app: &App) {
-    let p = Paragraph::new(" [Q] Quit | [SPACE] Pause | [+/-] Zoom | [←/→] History ")
-        .style(Style::default().bg(Color::DarkGray).fg(Color::White))
-        .alignment(Alignment::Center);
+fn draw_log(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+    let visible = area.height.saturating_sub(2) as usize;
+
+    let lines: Vec<Line> = diag::tail(visible).into_iter()
+        .map(|line| Line::from(vec![
+            Span::styled(format!(" {} ", line.timestamp), Style::default().fg(palette.muted)),
+            Span::styled(format!("[{}] ", line.level.label()), Style::default().fg(line.level.color()).add_modifier(Modifier::BOLD)),
+            Span::raw(line.message),
+        ]))
+        .collect();
+
+    let lines = if lines.is_empty() {
+        vec![Line::from(Span::styled(" No diagnostics yet.", Style::default().fg(palette.muted)))]
+    } else {
+        lines
+    };
+
+    let title = match diag::path() {
+        Some(path) => format!(" Diagnostic Log ({}) - {} ", diag::len(), path.display()),
+        None => format!(" Diagnostic Log ({}) ", diag::len()),
+    };
+
+    let p = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .style(Style::default().fg(palette.text));
+
     f.render_widget(p, area);
+}
+
+fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
+    let palette = app.theme.palette();
+    let repeats = |alert: &Alert| if alert.repeats > 0 { format!(" (+{} repeats)", alert.repeats) } else { String::new() };
+
+    let p = if app.search.editing {
+        Paragraph::new(format!(" /{}_ | [Enter] Find | [Esc] Cancel ", app.search.query))
+            .style(Style::default().bg(palette.footer_bg).fg(palette.text).add_modifier(Modifier::BOLD))
+    } else if let Some(alert) = app.alerts.active().filter(|a| a.state == AlertState::Firing) {
+        Paragraph::new(format!(" [{}] {}{} | [A] Acknowledge | [E] Events ", alert.kind.label(), alert.message, repeats(alert)))
+            .style(Style::default().bg(alert.kind.color()).fg(Color::Black).add_modifier(Modifier::BOLD))
+    } else if let Some(alert) = app.alerts.active() {
+        Paragraph::new(format!(" [{} ACK] {}{} | [E] Events ", alert.kind.label(), alert.message, repeats(alert)))
+            .style(Style::default().bg(palette.footer_bg).fg(alert.kind.color()))
+    } else if let Some(notice) = app.notice() {
+        Paragraph::new(format!(" {} ", notice))
+            .style(Style::default().bg(palette.footer_bg).fg(palette.text).add_modifier(Modifier::BOLD))
+    } else {
+        let speed = if app.replay_speed.is_some() { " | [</>] Speed" } else { "" };
+        Paragraph::new(format!(" [Q] Quit | [SPACE] Pause{} | [+/-] Zoom | [←/→] History | [E] Events | [/] Search | [L] Log | [J/X/H] Datasets | [V] Layout | [S] Stats | [W] Window | [G] Grade | [M] GIF | [C] PNG | [T] Theme ({}) | [U/P] Units ({}) ", speed, app.theme.name(), app.units.describe()))
+            .style(Style::default().bg(palette.footer_bg).fg(palette.text))
+    };
+
+    f.render_widget(p.alignment(Alignment::Center), area);
 }
\ No newline at end of file
@@ -0,0 +1,255 @@
+use crate::app::PingRecord;
+use crate::args::{ValidateArgs, format_duration};
+use crate::pinger::ProbeError;
+use crate::session;
+use crate::sinks::CSV_HEADER;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use std::{io::BufRead, time::Duration};
+
+// Problems listed per kind before the rest is only counted.
+const SHOWN_PER_KIND: usize = 10;
+// A pause between two samples of a host this many times longer than usual is
+// reported as a gap, but never below MIN_GAP_MS.
+const GAP_FACTOR: f64 = 10.0;
+const MIN_GAP_MS: f64 = 2000.0;
+pub fn is_gap(delta_ms: f64, typical_ms: f64) -> bool {
+    delta_ms > (typical_ms * GAP_FACTOR).max(MIN_GAP_MS)
+}
+
+// Columns of a CSV log from before the Reply TTL column.
+const LEGACY_COLUMNS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Schema,
+    Backwards,
+    Duplicate,
+    Gap,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::Schema, Kind::Backwards, Kind::Duplicate, Kind::Gap];
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Schema => "Schema errors",
+            Kind::Backwards => "Time going backwards",
+            Kind::Duplicate => "Duplicate samples",
+            Kind::Gap => "Gaps",
+        }
+    }
+}
+
+struct Host {
+    key: (String, String),
+    times: Vec<(u64, DateTime<Local>)>,
+}
+
+#[derive(Default)]
+struct Report {
+    problems: Vec<(Kind, u64, String)>,
+    records: u64,
+    hosts: Vec<Host>,
+    first: Option<DateTime<Local>>,
+    last: Option<DateTime<Local>>,
+}
+
+fn known_type(target_type: &str) -> bool {
+    matches!(target_type, "Target" | "Gateway" | "Cycle" | "Passive" | "Marker")
+        || target_type.strip_prefix("Hop ").is_some_and(|ttl| ttl.parse::<u8>().is_ok())
+}
+
+fn print_record(record: &PingRecord) {
+    let latency = record.latency_ms.map(|l| format!("{:.2}ms", l)).unwrap_or_else(|| "-".to_string());
+    println!("{:<23}  {:<8} {:<24} {:>10}  {}", record.timestamp, record.target_type, record.target_ip, latency, record.status);
+}
+
+impl Report {
+    fn problem(&mut self, kind: Kind, line: u64, message: String) {
+        self.problems.push((kind, line, message));
+    }
+
+    fn check(&mut self, line: u64, record: &PingRecord) {
+        self.records += 1;
+        if !known_type(&record.target_type) {
+            self.problem(Kind::Schema, line, format!("unknown target type '{}'", record.target_type));
+        }
+        // Markers and failed probes (TIMEOUT, UNREACHABLE, ...) carry no latency.
+        let without_latency = record.is_marker() || ProbeError::from_status(&record.status).is_some();
+        match (record.status.as_str(), record.latency_ms) {
+            ("OK", Some(l)) if l >= 0.0 && l.is_finite() => {}
+            ("OK", Some(l)) => self.problem(Kind::Schema, line, format!("invalid latency {}", l)),
+            ("OK", None) => self.problem(Kind::Schema, line, "OK without a latency".to_string()),
+            (_, None) if without_latency => {}
+            (_, Some(_)) if without_latency => {
+                self.problem(Kind::Schema, line, format!("{} with a latency", record.status))
+            }
+            (status, _) => self.problem(Kind::Schema, line, format!("unknown status '{}'", status)),
+        }
+
+        let Some(at) = session::parse_timestamp(&record.timestamp) else {
+            self.problem(Kind::Schema, line, format!("invalid timestamp '{}'", record.timestamp));
+            return;
+        };
+        self.first.get_or_insert(at);
+        if let Some(last) = self.last
+            && at < last
+        {
+            let back = (last - at).to_std().unwrap_or_default();
+            self.problem(Kind::Backwards, line, format!("{} is {:.3}s before the row above", record.timestamp, back.as_secs_f64()));
+        }
+        self.last = Some(self.last.map_or(at, |last| last.max(at)));
+        if record.is_marker() {
+            return;
+        }
+
+        let key = (record.target_type.clone(), record.target_ip.clone());
+        let index = match self.hosts.iter().position(|h| h.key == key) {
+            Some(index) => index,
+            None => {
+                self.hosts.push(Host { key, times: Vec::new() });
+                self.hosts.len() - 1
+            }
+        };
+        if self.hosts[index].times.last().is_some_and(|(_, t)| *t == at) {
+            self.problem(
+                Kind::Duplicate,
+                line,
+                format!("{} {} sampled twice at {}", record.target_type, record.target_ip, record.timestamp),
+            );
+        }
+        self.hosts[index].times.push((line, at));
+    }
+
+    fn find_gaps(&mut self) {
+        let mut gaps = Vec::new();
+        for host in &self.hosts {
+            let mut deltas: Vec<i64> = host.times.windows(2).map(|w| (w[1].1 - w[0].1).num_milliseconds()).collect();
+            if deltas.is_empty() {
+                continue;
+            }
+            deltas.sort_unstable();
+            let typical = deltas[deltas.len() / 2] as f64;
+
+            for w in host.times.windows(2) {
+                let delta = (w[1].1 - w[0].1).num_milliseconds();
+                if is_gap(delta as f64, typical) {
+                    let (kind, ip) = &host.key;
+                    let gap = format_duration(Duration::from_millis(delta as u64));
+                    gaps.push((w[1].0, format!("{} {} silent for {} before {}", kind, ip, gap, w[1].1.format("%H:%M:%S"))));
+                }
+            }
+        }
+        gaps.sort_by_key(|(line, _)| *line);
+        for (line, message) in gaps {
+            self.problem(Kind::Gap, line, message);
+        }
+    }
+}
+
+fn check_csv(reader: Box<dyn BufRead>, report: &mut Report, print: bool) -> Result<()> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(reader);
+    let mut columns = CSV_HEADER.len();
+    for (i, row) in reader.records().enumerate() {
+        let row = row?;
+        let line = row.position().map(|p| p.line()).unwrap_or(i as u64 + 1);
+        if i == 0 {
+            if row.iter().eq(CSV_HEADER[..LEGACY_COLUMNS].iter().copied()) {
+                columns = LEGACY_COLUMNS;
+            } else if row.iter().ne(CSV_HEADER) {
+                report.problem(Kind::Schema, line, format!("unexpected header, expected '{}'", CSV_HEADER.join(",")));
+            }
+            continue;
+        }
+        if row.len() != columns {
+            report.problem(Kind::Schema, line, format!("{} columns instead of {}", row.len(), columns));
+            continue;
+        }
+        match row.deserialize::<PingRecord>(None) {
+            Ok(record) => {
+                if print {
+                    print_record(&record);
+                }
+                report.check(line, &record);
+            }
+            Err(e) => report.problem(Kind::Schema, line, e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+fn check_jsonl(reader: Box<dyn BufRead>, report: &mut Report, print: bool) -> Result<()> {
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let number = i as u64 + 1;
+        if line.trim().is_empty() || line.starts_with("{\"summary\"") {
+            continue;
+        }
+        match serde_json::from_str::<PingRecord>(&line) {
+            Ok(record) => {
+                if print {
+                    print_record(&record);
+                }
+                report.check(number, &record);
+            }
+            Err(e) => report.problem(Kind::Schema, number, e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: ValidateArgs) -> Result<()> {
+    let reader = session::open(&args.file)?;
+    let name = session::plain_name(&args.file);
+    let mut report = Report::default();
+
+    if name.ends_with(".jsonl") {
+        check_jsonl(reader, &mut report, args.print)?;
+    } else if name.ends_with(".csv") && !name.ends_with("_summary.csv") {
+        check_csv(reader, &mut report, args.print)?;
+    } else {
+        bail!("{} is not a CSV or JSONL session log", args.file.display());
+    }
+    report.find_gaps();
+    if args.print {
+        println!();
+    }
+
+    match (report.first, report.last) {
+        (Some(first), Some(last)) => println!(
+            "{}: {} records, {} hosts, {} to {}",
+            args.file.display(),
+            report.records,
+            report.hosts.len(),
+            first.format("%Y-%m-%d %H:%M:%S"),
+            last.format("%Y-%m-%d %H:%M:%S")
+        ),
+        _ => println!("{}: {} records", args.file.display(), report.records),
+    }
+
+    for kind in Kind::ALL {
+        let found: Vec<&(Kind, u64, String)> = report.problems.iter().filter(|(k, _, _)| *k == kind).collect();
+        if found.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} ({}):", kind.label(), found.len());
+        for (_, line, message) in found.iter().take(SHOWN_PER_KIND) {
+            println!("  Line {}: {}", line, message);
+        }
+        if found.len() > SHOWN_PER_KIND {
+            println!("  ... and {} more", found.len() - SHOWN_PER_KIND);
+        }
+    }
+
+    // Gaps, duplicates and clock jumps happen in healthy logs (a paused
+    // session, an NTP correction), only schema errors make the file unusable.
+    if report.problems.iter().any(|(k, _, _)| *k == Kind::Schema) {
+        std::process::exit(1);
+    }
+    if report.problems.is_empty() {
+        println!("No problems found.");
+    }
+    Ok(())
+}
@@ -0,0 +1,497 @@
+// End-to-end runs of the daemon against the in-process fake responder:
+//
+//   cargo test --features fake-ping
+#![cfg(feature = "fake-ping")]
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vasili-e2e-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// The home directory is the scratch directory, so the config, the session
+// registry and the last-run state of the machine are left alone.
+fn vasili(home: &Path, target: &str, gateway: &str, args: &[&str]) -> Output {
+    command(home, target, gateway, args).output().unwrap()
+}
+
+fn command(home: &Path, target: &str, gateway: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_vasili"));
+    command
+        .args(args)
+        .current_dir(home)
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("XDG_STATE_HOME")
+        .env_remove("XDG_CACHE_HOME")
+        .env("XDG_RUNTIME_DIR", home)
+        .env("VASILI_FAKE_TARGET", target)
+        .env("VASILI_FAKE_GATEWAY", gateway);
+    command
+}
+
+fn output_file(dir: &Path, suffix: &str) -> PathBuf {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .find(|p| p.to_string_lossy().ends_with(suffix))
+        .unwrap_or_else(|| panic!("no *{} in {}", suffix, dir.display()))
+}
+
+// The row of `target_type` in a _summary.csv, by column name, so tests
+// keep working when columns are added.
+fn summary_row(csv: &str, target_type: &str) -> HashMap<String, String> {
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let header = reader.headers().unwrap().clone();
+    let row = reader
+        .records()
+        .map(Result::unwrap)
+        .find(|r| &r[0] == target_type)
+        .unwrap();
+    assert_eq!(header.len(), row.len());
+    header.iter().map(str::to_string).zip(row.iter().map(str::to_string)).collect()
+}
+
+#[test]
+fn daemon_session_with_outage() {
+    let dir = scratch("outage");
+    let out = dir.join("out");
+    // 20 good replies, a 2.5s outage, then a jittery 25ms forever.
+    let run = vasili(
+        &dir,
+        "20*20,timeout*50,25~3",
+        "2",
+        &["-t", "192.0.2.10", "--daemon", "--duration", "6s", "--interval", "50ms", "--min-samples", "10", "--output-dir", out.to_str().unwrap()],
+    );
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(stdout.contains("Session summary"), "{}", stdout);
+
+    let log = fs::read_to_string(output_file(&out, "_192.0.2.10.csv")).unwrap();
+    let target_timeouts = log.lines().filter(|l| l.contains(",Target,") && l.ends_with(",TIMEOUT,")).count();
+    assert_eq!(target_timeouts, 50);
+    assert!(log.lines().any(|l| l.contains(",Gateway,") && l.ends_with(",2.0,OK,")));
+
+    let summary = fs::read_to_string(output_file(&out, "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!(target["lost"], "50");
+    assert!(target["samples"].parse::<u64>().unwrap() >= 100, "samples {}", target["samples"]);
+    let gateway = summary_row(&summary, "Gateway");
+    assert_eq!(gateway["lost"], "0");
+
+    let markdown = fs::read_to_string(output_file(&out, "_summary.md")).unwrap();
+    assert!(markdown.contains("## Outages"));
+    assert!(markdown.contains("50 lost"), "{}", markdown);
+
+    let analyze = Command::new(env!("CARGO_BIN_EXE_vasili"))
+        .args(["analyze", "--json"])
+        .arg(output_file(&out, "_192.0.2.10.csv"))
+        .env("HOME", &dir)
+        .output()
+        .unwrap();
+    assert!(analyze.status.success());
+    let hosts: serde_json::Value = serde_json::from_slice(&analyze.stdout).unwrap();
+    let target = hosts.as_array().unwrap().iter().find(|h| h["target_type"] == "Target").unwrap();
+    assert_eq!(target["lost"], 50);
+    assert_eq!(target["longest_outage"]["lost"], 50);
+    assert_eq!(target["min"], 20.0);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn samples_past_the_retention_are_kept_on_disk() {
+    let dir = scratch("retention");
+    let out = dir.join("out");
+    // A second in memory is 20 samples, the outage leaves it long before the end.
+    let run = vasili(
+        &dir,
+        "20*20,timeout*50,25",
+        "2",
+        &["-t", "192.0.2.10", "--daemon", "--duration", "6s", "--interval", "50ms", "--retention", "1s", "--output-dir", out.to_str().unwrap()],
+    );
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    let markdown = fs::read_to_string(output_file(&out, "_summary.md")).unwrap();
+    assert!(markdown.contains("50 lost"), "{}", markdown);
+    let spills = dir.join(".cache").join("vasili");
+    assert!(fs::read_dir(&spills).map(|d| d.count() == 0).unwrap_or(true), "left behind in {}", spills.display());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn top_lists_the_sessions_of_remotes() {
+    let dir = scratch("top");
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let api = format!("127.0.0.1:{}", port);
+    let mut daemon = command(&dir, "20", "2", &["-t", "192.0.2.10", "--daemon", "--duration", "5s", "--interval", "50ms", "--output-dir", "out", "--api", &api])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+
+    // Port 9 (discard) has nothing listening.
+    let remotes = format!("{},127.0.0.1:9", api);
+    let top = vasili(&dir, "1", "1", &["top", "--once", "--remote", &remotes]);
+    daemon.wait().unwrap();
+    assert!(top.status.success(), "{}", String::from_utf8_lossy(&top.stderr));
+    let stdout = String::from_utf8_lossy(&top.stdout);
+    assert!(stdout.lines().any(|l| l.starts_with(&api) && l.contains("Target") && l.contains("192.0.2.10")), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("127.0.0.1:9 ") && l.contains("unreachable")), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+fn http(addr: &str, request: &str) -> String {
+    use std::io::{Read, Write};
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+#[test]
+fn api_refuses_web_pages_without_a_token() {
+    let dir = scratch("api");
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let api = format!("127.0.0.1:{}", port);
+    let mut daemon = command(&dir, "20", "2", &["-t", "192.0.2.10", "--daemon", "--duration", "3s", "--interval", "50ms", "--output-dir", "out", "--api", &api])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+
+    let stop = http(&api, "POST /stop HTTP/1.1\r\nOrigin: http://example.com\r\nContent-Length: 0\r\n\r\n");
+    assert!(stop.starts_with("HTTP/1.1 403"), "{}", stop);
+    let marker = http(&api, "POST /marker HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+    assert!(marker.starts_with("HTTP/1.1 200"), "{}", marker);
+
+    // The session was not stopped: it runs for its full duration.
+    assert!(daemon.wait().unwrap().success());
+    let log = fs::read_to_string(output_file(&dir.join("out"), "_192.0.2.10.csv")).unwrap();
+    assert!(log.lines().any(|l| l.contains(",Marker,hello,,MARKER,")), "{}", log);
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    assert!(summary_row(&summary, "Target")["samples"].parse::<u64>().unwrap() > 40);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn the_shared_gateway_is_taken_over_when_its_session_ends() {
+    let dir = scratch("takeover");
+    let first_out = dir.join("first");
+    let second_out = dir.join("second");
+    let mut first = command(&dir, "20", "2", &["-t", "192.0.2.10", "--daemon", "--duration", "3s", "--output-dir", first_out.to_str().unwrap()])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let sessions = dir.join("vasili").join("sessions");
+    while fs::read_dir(&sessions).map(|d| d.count() == 0).unwrap_or(true) {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    let second = vasili(&dir, "20", "2", &["-t", "192.0.2.11", "--daemon", "--duration", "12s", "--output-dir", second_out.to_str().unwrap()]);
+    assert!(first.wait().unwrap().success());
+    assert!(second.status.success(), "{}", String::from_utf8_lossy(&second.stderr));
+    let stdout = String::from_utf8_lossy(&second.stdout);
+    assert!(stdout.contains("this session probes it now"), "{}", stdout);
+
+    let summary = fs::read_to_string(output_file(&second_out, "_summary.csv")).unwrap();
+    let gateway = summary_row(&summary, "Gateway");
+    assert!(gateway["samples"].parse::<u64>().unwrap() > 0, "samples {}", gateway["samples"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn invalid_profile_is_rejected() {
+    let dir = scratch("invalid");
+    let run = vasili(&dir, "20~x", "1", &["-t", "192.0.2.10", "--daemon", "--duration", "1s"]);
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("VASILI_FAKE_TARGET"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dns_server_and_target_conflict() {
+    let dir = scratch("dns-target");
+    let run = vasili(&dir, "20", "1", &["-t", "192.0.2.10", "--dns-server", "192.0.2.53", "--once"]);
+    assert_eq!(run.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("cannot be used with"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn quality_gate_sets_the_exit_code() {
+    let dir = scratch("gate");
+    let common = ["-t", "192.0.2.10", "--daemon", "--duration", "2s", "--interval", "50ms", "--no-gateway", "--output-dir", "out"];
+
+    let good = vasili(&dir, "15~1", "1", &[&common[..], &["--fail-on-grade", "C", "--fail-on-loss", "1%"]].concat());
+    assert_eq!(good.status.code(), Some(0), "{}", String::from_utf8_lossy(&good.stderr));
+    assert!(String::from_utf8_lossy(&good.stdout).contains("Quality gate passed"));
+
+    // Every tenth probe lost: 10% loss.
+    let lossy = vasili(&dir, "15*9,timeout*1,15*9,timeout*1,15*9,timeout*1,15*9,timeout*1,15", "1", &[&common[..], &["--fail-on-loss", "1%"]].concat());
+    assert_eq!(lossy.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&lossy.stderr).contains("Quality gate failed: loss"));
+
+    let slow = vasili(&dir, "180", "1", &[&common[..], &["--fail-on-grade", "C"]].concat());
+    assert_eq!(slow.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&slow.stderr).contains("grade F"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn grading_profiles_and_commands_decide_the_grade() {
+    let dir = scratch("grading");
+    let common = ["-t", "192.0.2.10", "--daemon", "--duration", "2s", "--interval", "50ms", "--no-gateway", "--output-dir", "out", "--fail-on-grade", "B"];
+
+    // A steady 50 ms is an A in general, but only a B for games.
+    let general = vasili(&dir, "50", "1", &common);
+    assert_eq!(general.status.code(), Some(0), "{}", String::from_utf8_lossy(&general.stderr));
+    let gaming = vasili(&dir, "50", "1", &[&common[..], &["--grading", "gaming"]].concat());
+    assert_eq!(gaming.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&gaming.stderr).contains("grade B"));
+
+    let command = vasili(&dir, "15", "1", &[&common[..], &["--grade-command", "cat > /dev/null; echo c"]].concat());
+    assert_eq!(command.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&command.stderr).contains("grade C"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+// A service manager stopping the daemon is not the user aborting it.
+#[cfg(unix)]
+#[test]
+fn sigterm_is_recorded_apart_from_a_user_abort() {
+    let dir = scratch("sigterm");
+    let daemon = command(&dir, "10", "1", &["-t", "192.0.2.10", "--daemon", "--interval", "50ms", "--output-dir", "out"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    let kill = Command::new("kill").args(["-TERM", &daemon.id().to_string()]).status().unwrap();
+    assert!(kill.success());
+    let run = daemon.wait_with_output().unwrap();
+    assert!(run.status.success());
+    assert!(String::from_utf8_lossy(&run.stdout).contains("terminated by SIGTERM"));
+
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!(target["aborted_by_user"], "false");
+    assert_eq!(target["terminated"], "true");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn count_stops_after_n_target_probes() {
+    let dir = scratch("count");
+    let run = vasili(&dir, "10", "1", &["-t", "192.0.2.10", "--daemon", "--count", "25", "--interval", "20ms", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(String::from_utf8_lossy(&run.stdout).contains("Probe count reached"));
+
+    let log = fs::read_to_string(output_file(&dir.join("out"), "_192.0.2.10.csv")).unwrap();
+    assert_eq!(log.lines().filter(|l| l.contains(",Target,")).count(), 25);
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!(target["samples"], "25");
+    assert_eq!(target["aborted_by_user"], "false");
+    // 25 samples are below --min-samples: a grade all the same, flagged as unsettled.
+    assert_eq!(target["grade"], "S");
+    assert_eq!(target["warming_up"], "true");
+    assert!(String::from_utf8_lossy(&run.stdout).contains("Grade warming up (n=25)"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rotated_segments_are_stitched_back_together() {
+    let dir = scratch("rotate");
+    let run = vasili(&dir, "10", "1", &["-t", "192.0.2.10", "--daemon", "--count", "150", "--interval", "20ms", "--rotate-size", "2k", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    let first = output_file(&dir.join("out"), "_192.0.2.10.csv");
+    output_file(&dir.join("out"), "_192.0.2.10_part002.csv");
+    let analyze = vasili(&dir, "10", "1", &["analyze", first.to_str().unwrap(), "--json"]);
+    assert!(analyze.status.success(), "{}", String::from_utf8_lossy(&analyze.stderr));
+    let hosts: serde_json::Value = serde_json::from_slice(&analyze.stdout).unwrap();
+    let target = hosts.as_array().unwrap().iter().find(|h| h["target_type"] == "Target").unwrap();
+    assert_eq!(target["samples"], 150);
+
+    // The summary is in the last segment, the first one finds it.
+    let report = vasili(&dir, "10", "1", &["report", first.to_str().unwrap()]);
+    assert!(report.status.success(), "{}", String::from_utf8_lossy(&report.stderr));
+    assert!(String::from_utf8_lossy(&report.stdout).contains("rotated segments"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn compress_writes_gzipped_logs() {
+    let dir = scratch("compress");
+    let run = vasili(&dir, "10", "1", &["-t", "192.0.2.10", "--daemon", "--count", "20", "--interval", "20ms", "--compress", "--format", "csv,jsonl", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    output_file(&dir.join("out"), "_192.0.2.10.jsonl.gz");
+    let log = output_file(&dir.join("out"), "_192.0.2.10.csv.gz");
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_192.0.2.10_summary.csv")).unwrap();
+    assert_eq!(summary_row(&summary, "Target")["samples"], "20");
+    let analyze = vasili(&dir, "10", "1", &["analyze", log.to_str().unwrap(), "--json"]);
+    assert!(analyze.status.success(), "{}", String::from_utf8_lossy(&analyze.stderr));
+    let hosts: serde_json::Value = serde_json::from_slice(&analyze.stdout).unwrap();
+    assert!(hosts.as_array().unwrap().iter().any(|h| h["target_type"] == "Target" && h["samples"] == 20));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn once_prints_one_probe_and_sets_the_exit_code() {
+    let dir = scratch("once");
+    let replied = vasili(&dir, "12", "3", &["-t", "192.0.2.10", "--once"]);
+    assert_eq!(replied.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&replied.stdout);
+    assert!(stdout.contains("Target 192.0.2.10: 12.0ms"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("Gateway ") && l.ends_with(": 3.0ms")), "{}", stdout);
+
+    let lost = vasili(&dir, "timeout", "3", &["-t", "192.0.2.10", "--once", "--no-gateway"]);
+    assert_eq!(lost.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&lost.stdout).trim(), "Target 192.0.2.10: timeout");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn replies_slower_than_the_timeout_are_lost() {
+    let dir = scratch("timeout");
+    let run = vasili(&dir, "50*5,300", "1", &["-t", "192.0.2.10", "--daemon", "--count", "10", "--interval", "150ms", "--timeout", "100ms", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(String::from_utf8_lossy(&run.stdout).contains("Timeout: 100ms"));
+
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!(target["lost"], "5");
+    assert_eq!(target["timeout_ms"], "100");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn dscp_is_recorded_in_the_summary() {
+    let dir = scratch("dscp");
+    let run = vasili(&dir, "10", "1", &["-t", "192.0.2.10", "--daemon", "--count", "5", "--interval", "50ms", "--dscp", "ef", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(stdout.contains("Probe: ICMP echo, DSCP EF (46)"), "{}", stdout);
+
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    assert_eq!(summary_row(&summary, "Target")["dscp"], "46");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn running_stats_are_written_to_the_summary() {
+    let dir = scratch("welford");
+    let run = vasili(&dir, "10*1,20*1,30", "1", &["-t", "192.0.2.10", "--daemon", "--count", "3", "--interval", "50ms", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!((&*target["min"], &*target["mean"], &*target["stddev"]), ("10.0", "20.0", "10.0"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn slow_replies_do_not_hold_back_the_next_probes() {
+    let dir = scratch("concurrent");
+    // Waiting for every 400ms reply before the next probe would take 8s.
+    let started = std::time::Instant::now();
+    let run = vasili(&dir, "400", "1", &["-t", "192.0.2.10", "--daemon", "--count", "20", "--interval", "50ms", "--timeout", "1s", "--no-gateway", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(started.elapsed().as_secs_f64() < 5.0, "took {:?}", started.elapsed());
+
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    let target = summary_row(&summary, "Target");
+    assert_eq!(target["samples"], "20");
+    assert_eq!(target["lost"], "0");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn unreachable_is_logged_apart_from_timeouts() {
+    let dir = scratch("unreachable");
+    let run = vasili(&dir, "10*5,unreachable*5,timeout*2,10", "1", &["-t", "192.0.2.10", "--daemon", "--count", "20", "--interval", "20ms", "--no-gateway", "--output-dir", "out"]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+    let log_path = output_file(&dir.join("out"), "_192.0.2.10.csv");
+    let log = fs::read_to_string(&log_path).unwrap();
+    assert_eq!(log.lines().filter(|l| l.contains(",Target,") && l.ends_with(",UNREACHABLE,")).count(), 5);
+    assert_eq!(log.lines().filter(|l| l.contains(",Target,") && l.ends_with(",TIMEOUT,")).count(), 2);
+    let summary = fs::read_to_string(output_file(&dir.join("out"), "_summary.csv")).unwrap();
+    assert_eq!(summary_row(&summary, "Target")["lost"], "7");
+
+    let validate = Command::new(env!("CARGO_BIN_EXE_vasili")).arg("validate").arg(&log_path).env("HOME", &dir).output().unwrap();
+    assert!(validate.status.success(), "{}", String::from_utf8_lossy(&validate.stdout));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reboot_test_finds_the_outage_and_compares_before_and_after() {
+    let dir = scratch("reboot");
+    // The router is gone from 2s to 3s, the Target from 2s to 5s.
+    let run = vasili(&dir, "20*40,timeout*60,30", "1*80,timeout*40,1", &["-t", "192.0.2.10", "--reboot-test", "1s", "--interval", "50ms"]);
+    assert_eq!(run.status.code(), Some(0), "{}", String::from_utf8_lossy(&run.stderr));
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(stdout.contains("Reboot your router now"), "{}", stdout);
+    assert!(stdout.contains("Router answers again after 1s"), "{}", stdout);
+    assert!(stdout.contains("Internet back after 3s"), "{}", stdout);
+    assert!(stdout.contains("before  loss 0.0%, P50 20.0ms"), "{}", stdout);
+    assert!(stdout.contains("after   loss 0.0%, P50 30.0ms"), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn gaps_are_left_out_of_outages_and_measured_time() {
+    let dir = scratch("gaps");
+    // 20 replies a second apart, 3 timeouts, a minute without samples, then
+    // 2 more timeouts and 10 replies.
+    let mut csv = String::from("Timestamp,Target Type,Target IP,Latency (ms),Status,Reply TTL\n");
+    let mut second = 0;
+    let push = |csv: &mut String, second: &mut u32, latency: Option<f64>| {
+        let (latency, status) = latency.map(|ms| (ms.to_string(), "OK")).unwrap_or((String::new(), "TIMEOUT"));
+        csv.push_str(&format!("2026-10-17 10:{:02}:{:02}.000,Target,192.0.2.10,{},{},\n", *second / 60, *second % 60, latency, status));
+        *second += 1;
+    };
+    (0..20).for_each(|_| push(&mut csv, &mut second, Some(20.0)));
+    (0..3).for_each(|_| push(&mut csv, &mut second, None));
+    second += 60;
+    (0..2).for_each(|_| push(&mut csv, &mut second, None));
+    (0..10).for_each(|_| push(&mut csv, &mut second, Some(20.0)));
+    let log = dir.join("gaps.csv");
+    fs::write(&log, csv).unwrap();
+
+    let analyze = Command::new(env!("CARGO_BIN_EXE_vasili")).args(["analyze", "--json"]).arg(&log).env("HOME", &dir).output().unwrap();
+    assert!(analyze.status.success(), "{}", String::from_utf8_lossy(&analyze.stderr));
+    let hosts: serde_json::Value = serde_json::from_slice(&analyze.stdout).unwrap();
+    let target = &hosts[0];
+    assert_eq!(target["gaps"], 1);
+    assert_eq!(target["measured_secs"], 33.0);
+    assert_eq!(target["lost"], 5);
+    assert_eq!(target["longest_outage"]["lost"], 3);
+
+    let _ = fs::remove_dir_all(&dir);
+}